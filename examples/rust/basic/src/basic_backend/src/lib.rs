@@ -39,6 +39,10 @@ async fn http_request_via_proxy(
             request: req,
             timeout_ms,
             callback_method_name: with_callback.then_some("http_response_callback".to_string()),
+            cache: None,
+            idempotency_key: None,
+            coalesce: None,
+            signing_profile: None,
         },),
     )
     .await;
@@ -1,5 +1,10 @@
 import IcWebSocket, { createWsConfig, generateRandomIdentity } from "ic-websocket-js";
 import { proxy_canister, canisterId } from "./src/canister/declarations/proxy_canister";
+import type { HttpMethod, HttpResponse } from "./src/canister/declarations/proxy_canister/proxy_canister.did";
+import { loadAttestationKey, signResponse } from "./src/attestation";
+import { httpMethod, httpMethodName, STANDARD_HTTP_METHODS } from "./src/methods";
+import { BodyReadError, toProxyError } from "./src/proxyErrors";
+import { loadSecrets, substituteSecrets } from "./src/secrets";
 import { printVersion } from "./src/utils";
 
 /**
@@ -8,13 +13,63 @@ import { printVersion } from "./src/utils";
  */
 const RECONNECT_AFTER_SECONDS = Number(process.env.RECONNECT_AFTER_SECONDS) || 45;
 
+/**
+ * The port on which the inbound webhook requests are received and forwarded to the canister.
+ * If not set, no webhook server is started.
+ */
+const WEBHOOKS_PORT = Number(process.env.WEBHOOKS_PORT) || 0;
+
+/**
+ * How many seconds to wait for the canister to answer a webhook request
+ */
+const WEBHOOK_TIMEOUT_SECONDS = Number(process.env.WEBHOOK_TIMEOUT_SECONDS) || 30;
+
+/**
+ * How many seconds to wait for the response of an outgoing HTTP request.
+ * If not set, the request waits until the canister gives up on it.
+ */
+const HTTP_REQUEST_TIMEOUT_SECONDS = Number(process.env.HTTP_REQUEST_TIMEOUT_SECONDS) || 0;
+
+/**
+ * The custom HTTP methods that the proxy executes, comma separated, in addition to the standard ones
+ */
+const CUSTOM_HTTP_METHODS = (process.env.CUSTOM_HTTP_METHODS || "")
+  .split(",")
+  .map((method) => method.trim())
+  .filter((method) => method.length > 0);
+
+/**
+ * The secrets that the proxy substitutes for their placeholders, as a JSON object of names to values
+ */
+const PROXY_SECRETS = loadSecrets(process.env.PROXY_SECRETS);
+
+/**
+ * The Ed25519 key with which the responses are attested, as a hex encoded 32 bytes seed.
+ * Its public key must be registered for the proxy principal on the canister.
+ */
+const ATTESTATION_KEY = process.env.ATTESTATION_PRIVATE_KEY
+  ? loadAttestationKey(process.env.ATTESTATION_PRIVATE_KEY)
+  : null;
+
+/**
+ * ic-websocket-js encodes the messages with the HttpOverWsMessage type of the canister's `ws_message` method,
+ * so the proxy can only speak the version of the protocol whose messages aren't wrapped in an envelope
+ */
+const HTTP_OVER_WS_PROTOCOL_V1 = 1;
+
 const IC_NETWORK_URL = process.env.IC_NETWORK_URL as string;
 const IC_WS_GATEWAY_URL = process.env.IC_WS_GATEWAY_URL as string;
 
 console.log(`Config:
   IC_NETWORK_URL=${IC_NETWORK_URL},
   IC_WS_GATEWAY_URL=${IC_WS_GATEWAY_URL},
-  RECONNECT_AFTER_SECONDS=${RECONNECT_AFTER_SECONDS}`
+  RECONNECT_AFTER_SECONDS=${RECONNECT_AFTER_SECONDS},
+  WEBHOOKS_PORT=${WEBHOOKS_PORT},
+  WEBHOOK_TIMEOUT_SECONDS=${WEBHOOK_TIMEOUT_SECONDS},
+  HTTP_REQUEST_TIMEOUT_SECONDS=${HTTP_REQUEST_TIMEOUT_SECONDS},
+  CUSTOM_HTTP_METHODS=${CUSTOM_HTTP_METHODS.join(",")},
+  PROXY_SECRETS=${Array.from(PROXY_SECRETS.keys()).join(",")} (names only),
+  ATTESTATION_PRIVATE_KEY=${ATTESTATION_KEY ? "set" : "not set"}`
 );
 printVersion();
 
@@ -27,6 +82,21 @@ const wsConfig = createWsConfig({
 
 console.log("Canister ID:", canisterId);
 
+/**
+ * The HTTP methods announced to the canister, which only assigns to the proxy the requests it can execute
+ */
+const HTTP_METHODS: Array<HttpMethod> = [...STANDARD_HTTP_METHODS, ...CUSTOM_HTTP_METHODS].map(httpMethod);
+
+/**
+ * The WebSocket connection set up with the canister, used to forward the webhook requests
+ */
+let proxyWs: InstanceType<typeof IcWebSocket> | null = null;
+let nextWebhookId = BigInt(0);
+/**
+ * The webhook requests waiting for the canister's response, by id
+ */
+const pendingWebhooks = new Map<bigint, (response: Response) => void>();
+
 const openWsConnection = () => {
   const ws = new IcWebSocket(IC_WS_GATEWAY_URL, {}, wsConfig);
   const principal = ws.getPrincipal().toString();
@@ -35,12 +105,23 @@ const openWsConnection = () => {
   ws.onopen = () => {
     console.log("WebSocket connected with principal", principal);
 
-    // send the setup message
+    // send the setup message, announcing what the proxy can do.
+    // The payload encryption is not supported, so no encryption key is announced
+    // and the canister never assigns encrypted requests to this proxy
     ws.send({
-      SetupProxyClient: null,
+      SetupVersionedProxyClient: {
+        supported_protocol_versions: [HTTP_OVER_WS_PROTOCOL_V1],
+        capabilities: [{
+          http_methods: HTTP_METHODS,
+          secret_names: [Array.from(PROXY_SECRETS.keys())],
+        }],
+        encryption_key: [],
+      },
     });
 
     console.log("Setup message sent");
+
+    proxyWs = ws;
   };
 
   ws.onmessage = async (ev) => {
@@ -53,10 +134,10 @@ const openWsConnection = () => {
         const request = incomingMessage.HttpRequest[1];
 
         try {
-          const url = new URL(request.url);
-          const method = Object.keys(request.method)[0]; // workaround to get the candid enum
+          const url = new URL(substituteSecrets(request.url, PROXY_SECRETS));
+          const method = httpMethodName(request.method);
           const headers = new Headers(
-            request.headers.map(({ name, value }) => [name, value] as [string, string])
+            request.headers.map(({ name, value }) => [name, substituteSecrets(value, PROXY_SECRETS)] as [string, string])
           );
           const body = (request.body.length > 0 && method !== "GET")
             ? new Uint8Array(request.body[0]!)
@@ -64,7 +145,7 @@ const openWsConnection = () => {
 
           console.log(
             "\nExecuting HTTP request:",
-            "\nurl:", url.toString(),
+            "\nurl:", request.url,
             "\nmethod:", method,
             "\nheaders count:", headers.count,
             "\nbody bytes:", body?.length || 0,
@@ -72,13 +153,27 @@ const openWsConnection = () => {
             // "\nbody:", body ? new TextDecoder().decode(body) : null
           );
 
+          const startedAt = performance.now();
           const response = await fetch(url, {
             method,
             headers,
             body,
+            // the canister follows the redirects itself when the request has a redirect policy,
+            // validating each hop, so the proxy must return the redirect responses as they are
+            redirect: request.redirect.length > 0 ? "manual" : "follow",
+            signal: HTTP_REQUEST_TIMEOUT_SECONDS > 0
+              ? AbortSignal.timeout(HTTP_REQUEST_TIMEOUT_SECONDS * 1000)
+              : undefined,
           });
+          const timeToFirstByteMs = performance.now() - startedAt;
 
-          const responseBody = new Uint8Array(await response.arrayBuffer());
+          let responseBody: Uint8Array;
+          try {
+            responseBody = new Uint8Array(await response.arrayBuffer());
+          } catch (e) {
+            throw new BodyReadError(String(e));
+          }
+          const totalMs = performance.now() - startedAt;
 
           console.log(
             "HTTP response:",
@@ -88,34 +183,76 @@ const openWsConnection = () => {
             // "\nbody:", new TextDecoder().decode(responseBody),
           );
 
+          // the stats must be received before the response, which completes the request
           ws.send({
-            HttpResponse: [
+            HttpResponseStats: [
               requestId,
               {
-                status: BigInt(response.status),
-                headers: Array.from(response.headers.entries()).map(([key, value]) => ({
-                  name: key,
-                  value,
-                })),
-                body: responseBody,
+                timing: {
+                  dns_lookup_ms: [],
+                  connect_ms: [],
+                  time_to_first_byte_ms: [BigInt(Math.round(timeToFirstByteMs))],
+                  total_ms: BigInt(Math.round(totalMs)),
+                },
+                body_size_bytes: BigInt(responseBody.byteLength),
               },
             ],
           });
 
+          const httpResponse: HttpResponse = {
+            status: BigInt(response.status),
+            headers: Array.from(response.headers.entries()).map(([key, value]) => ({
+              name: key,
+              value,
+            })),
+            body: responseBody,
+          };
+          if (ATTESTATION_KEY) {
+            ws.send({
+              AttestedHttpResponse: [
+                requestId,
+                httpResponse,
+                signResponse(ATTESTATION_KEY, requestId, request, httpResponse),
+              ],
+            });
+          } else {
+            ws.send({
+              HttpResponse: [requestId, httpResponse],
+            });
+          }
+
           console.log("Sent response over WebSocket.");
         } catch (e) {
           console.error("http-over-ws: error for request id:", requestId, e);
           ws.send({
-            Error: [[requestId], String(e)],
+            Error: [[requestId], String(e), [toProxyError(e)]],
           });
         }
+      } else if ("HttpResponse" in incomingMessage) {
+        // the response to an inbound webhook request
+        const webhookId = incomingMessage.HttpResponse[0];
+        const response = incomingMessage.HttpResponse[1];
+
+        const resolve = pendingWebhooks.get(webhookId);
+        if (!resolve) {
+          console.warn("http-over-ws: response for unknown webhook id:", webhookId);
+          return;
+        }
+        pendingWebhooks.delete(webhookId);
+
+        resolve(new Response(new Uint8Array(response.body), {
+          status: Number(response.status),
+          headers: response.headers.map(({ name, value }) => [name, value] as [string, string]),
+        }));
+      } else if ("ProtocolVersionNegotiated" in incomingMessage) {
+        console.log("Protocol version negotiated:", incomingMessage.ProtocolVersionNegotiated);
       } else if ("Error" in incomingMessage) {
         console.error("http-over-ws: incoming error:", incomingMessage.Error);
       }
     } catch (e) {
       console.error("http-over-ws: error", e);
       ws.send({
-        Error: [[], String(e)],
+        Error: [[], String(e), []],
       });
     }
   };
@@ -123,6 +260,10 @@ const openWsConnection = () => {
   ws.onclose = (ev) => {
     console.warn("WebSocket disconnected. Reason:", ev.reason);
 
+    if (proxyWs === ws) {
+      proxyWs = null;
+    }
+
     // if there are problems with the WebSocket itself, don't reconnect
     // this may occur also when connecting to a non-existing canister,
     // since the ws gateway can't relay the open message and closes the connection
@@ -150,4 +291,56 @@ const openWsConnection = () => {
   };
 };
 
+const forwardWebhook = async (req: Request): Promise<Response> => {
+  if (!proxyWs) {
+    return new Response("Service Unavailable", { status: 503 });
+  }
+
+  const webhookId = nextWebhookId++;
+  const body = new Uint8Array(await req.arrayBuffer());
+
+  console.log(
+    "\nForwarding webhook request:",
+    "\nid:", webhookId,
+    "\nurl:", req.url,
+    "\nmethod:", req.method,
+    "\nbody bytes:", body.byteLength,
+  );
+
+  const response = new Promise<Response>((resolve) => {
+    pendingWebhooks.set(webhookId, resolve);
+
+    setTimeout(() => {
+      if (pendingWebhooks.delete(webhookId)) {
+        console.error("http-over-ws: timeout for webhook id:", webhookId);
+        resolve(new Response("Gateway Timeout", { status: 504 }));
+      }
+    }, WEBHOOK_TIMEOUT_SECONDS * 1000);
+  });
+
+  proxyWs.send({
+    HttpRequest: [
+      webhookId,
+      {
+        url: req.url,
+        method: httpMethod(req.method),
+        headers: Array.from(req.headers.entries()).map(([name, value]) => ({ name, value })),
+        body: body.byteLength > 0 ? [body] : [],
+        redirect: [],
+      },
+    ],
+  });
+
+  return response;
+};
+
+if (WEBHOOKS_PORT > 0) {
+  Bun.serve({
+    port: WEBHOOKS_PORT,
+    fetch: forwardWebhook,
+  });
+
+  console.log("Webhooks server listening on port", WEBHOOKS_PORT);
+}
+
 openWsConnection();
@@ -0,0 +1,109 @@
+import { createHash, createPrivateKey, sign, type KeyObject } from "node:crypto";
+import type {
+  HttpHeader,
+  HttpRequest,
+  HttpResponse,
+  ResponseSignature,
+} from "./canister/declarations/proxy_canister/proxy_canister.did";
+import { httpMethodName } from "./methods";
+
+/**
+ * Prefixes the signed digests, must match the one of the http_over_ws library
+ */
+const RESPONSE_ATTESTATION_DOMAIN_SEPARATOR = new TextEncoder().encode("ic-http-proxy-response-attestation");
+
+/**
+ * The DER prefix of a PKCS#8 Ed25519 private key, followed by the 32 bytes seed
+ */
+const ED25519_PKCS8_PREFIX = Buffer.from("302e020100300506032b657004220420", "hex");
+
+/**
+ * Loads the Ed25519 private key from its 32 bytes seed, hex encoded
+ */
+export const loadAttestationKey = (seedHex: string): KeyObject => {
+  const seed = Buffer.from(seedHex, "hex");
+  if (seed.length !== 32) {
+    throw new Error("the attestation key must be a 32 bytes Ed25519 seed, hex encoded");
+  }
+
+  return createPrivateKey({
+    key: Buffer.concat([ED25519_PKCS8_PREFIX, seed]),
+    format: "der",
+    type: "pkcs8",
+  });
+};
+
+/**
+ * Signs the response as expected by the canister to accept an AttestedHttpResponse
+ * @param request the request as received from the canister, before the secrets are substituted
+ */
+export const signResponse = (
+  key: KeyObject,
+  requestId: bigint,
+  request: HttpRequest,
+  response: HttpResponse,
+): ResponseSignature => {
+  const timestampNs = BigInt(Date.now()) * BigInt(1_000_000);
+  const digest = responseAttestationDigest(requestId, request, response, timestampNs);
+
+  return {
+    timestamp_ns: timestampNs,
+    signature: new Uint8Array(sign(null, digest, key)),
+  };
+};
+
+/**
+ * Same as `response_attestation_digest` in the http_over_ws library
+ */
+const responseAttestationDigest = (
+  requestId: bigint,
+  request: HttpRequest,
+  response: HttpResponse,
+  timestampNs: bigint,
+): Buffer => {
+  const hash = createHash("sha256");
+  hash.update(RESPONSE_ATTESTATION_DOMAIN_SEPARATOR);
+  hash.update(u64(requestId));
+  hash.update(requestHash(request));
+  updateWithBytes(hash, new TextEncoder().encode(BigInt(response.status).toString()));
+  updateWithHeaders(hash, response.headers);
+  hash.update(createHash("sha256").update(new Uint8Array(response.body)).digest());
+  hash.update(u64(timestampNs));
+  return hash.digest();
+};
+
+const requestHash = (request: HttpRequest): Buffer => {
+  const hash = createHash("sha256");
+  updateWithBytes(hash, new TextEncoder().encode(httpMethodName(request.method)));
+  updateWithBytes(hash, new TextEncoder().encode(request.url));
+  updateWithHeaders(hash, request.headers);
+  if (request.body.length === 0) {
+    hash.update(new Uint8Array([0]));
+  } else {
+    hash.update(new Uint8Array([1]));
+    updateWithBytes(hash, new Uint8Array(request.body[0]!));
+  }
+  return hash.digest();
+};
+
+const updateWithBytes = (hash: ReturnType<typeof createHash>, bytes: Uint8Array) => {
+  hash.update(u64(BigInt(bytes.length)));
+  hash.update(bytes);
+};
+
+const updateWithHeaders = (hash: ReturnType<typeof createHash>, headers: Array<HttpHeader>) => {
+  hash.update(u64(BigInt(headers.length)));
+  for (const { name, value } of headers) {
+    updateWithBytes(hash, new TextEncoder().encode(name));
+    updateWithBytes(hash, new TextEncoder().encode(value));
+  }
+};
+
+/**
+ * Encodes the integer as 8 bytes big endian
+ */
+const u64 = (value: bigint): Uint8Array => {
+  const bytes = new Uint8Array(8);
+  new DataView(bytes.buffer).setBigUint64(0, value);
+  return bytes;
+};
@@ -1,6 +1,17 @@
 import type { Principal } from '@dfinity/principal';
 import type { ActorMethod } from '@dfinity/agent';
 
+export interface CacheStats {
+  'hits' : bigint,
+  'size_bytes' : bigint,
+  'misses' : bigint,
+  'entries' : bigint,
+}
+export type CacheTtlMs = bigint;
+export interface CallbackAttempt {
+  'timestamp_ns' : bigint,
+  'error' : [] | [string],
+}
 export type CanisterCallbackMethodName = string;
 export type CanisterId = Principal;
 export interface CanisterOutputCertifiedMessages {
@@ -15,8 +26,12 @@ export interface CanisterOutputMessage {
   'client_key' : ClientKey,
 }
 export interface CanisterRequest {
+  'response_attestation' : [] | [ResponseAttestation],
   'canister_id' : CanisterId,
   'state' : RequestState,
+  'callback_attempts' : Array<CallbackAttempt>,
+  'http_result' : [] | [HttpResult],
+  'response_metadata' : [] | [HttpResponseMetadata],
 }
 export interface CanisterWsCloseArguments { 'client_key' : ClientKey }
 export type CanisterWsCloseResult = { 'Ok' : null } |
@@ -40,55 +55,422 @@ export interface ClientKey {
   'client_nonce' : bigint,
 }
 export type ClientPrincipal = Principal;
+export interface DeadLetter {
+  'request_id' : HttpRequestId,
+  'canister_id' : CanisterId,
+  'callback_attempts' : Array<CallbackAttempt>,
+  'http_result' : HttpResult,
+  'callback_method_name' : CanisterCallbackMethodName,
+}
+export interface EncryptedHttpRequest {
+  'hop' : number,
+  'ciphertext' : Uint8Array | number[],
+  'ephemeral_public_key' : Uint8Array | number[],
+}
+export interface ExportLogsArgs {
+  'cursor' : [] | [LogsCursor],
+  'from_timestamp_ns' : [] | [bigint],
+  'limit' : [] | [number],
+}
+export interface ExportLogsResult {
+  'missed_count' : bigint,
+  'logs' : Array<LogEntry>,
+  'next_cursor' : [] | [LogsCursor],
+  'has_more' : boolean,
+}
 export type GatewayPrincipal = Principal;
-export type HttpFailureReason = { 'ProxyError' : string } |
+export interface GetLogsArgs {
+  'request_id' : [] | [HttpRequestId],
+  'after_id' : [] | [LogEntryId],
+  'proxy_principal' : [] | [Principal],
+  'canister_id' : [] | [CanisterId],
+  'limit' : [] | [number],
+  'min_level' : [] | [LogLevel],
+}
+export interface GetLogsResult {
+  'logs' : Array<LogEntry>,
+  'next_after_id' : [] | [LogEntryId],
+}
+export interface GetMetricsResult {
+  'prometheus' : string,
+  'metrics' : ProxyCanisterMetrics,
+}
+export interface Histogram {
+  'sum' : bigint,
+  'count' : bigint,
+  'buckets' : Array<[bigint, bigint]>,
+}
+export type HttpFailureReason = { 'InvalidEncryptedResponse' : string } |
+  { 'InvalidRedirect' : string } |
+  { 'RedirectNotAllowed' : string } |
+  { 'InvalidResponseAttestation' : string } |
+  { 'ProxyError' : ProxyError } |
+  { 'TooManyRedirects' : null } |
   { 'RequestTimeout' : null };
 export interface HttpHeader { 'value' : string, 'name' : string }
 export type HttpMethod = { 'GET' : null } |
   { 'PUT' : null } |
   { 'DELETE' : null } |
   { 'HEAD' : null } |
-  { 'POST' : null };
+  { 'POST' : null } |
+  { 'PATCH' : null } |
+  { 'OPTIONS' : null } |
+  { 'Other' : string };
+export interface HttpOverWsEnvelope {
+  'version' : HttpOverWsProtocolVersion,
+  'message' : HttpOverWsMessage,
+}
 export type HttpOverWsError = { 'NotHttpOverWsType' : string } |
   { 'ProxyNotFound' : null } |
+  { 'InvalidSecretPlaceholder' : string } |
   { 'NotYetReceived' : null } |
   { 'ConnectionNotAssignedToProxy' : null } |
   { 'RequestIdNotFound' : null } |
   { 'NoProxiesConnected' : null } |
   { 'InvalidHttpMessage' : null } |
-  { 'RequestFailed' : HttpFailureReason };
-export type HttpOverWsMessage = { 'Error' : [[] | [HttpRequestId], string] } |
+  { 'PayloadEncryptionNotAvailable' : null } |
+  { 'SecretsNotAvailable' : Array<SecretName> } |
+  { 'UnsupportedHttpMethod' : HttpMethod } |
+  { 'UnsupportedProtocolVersion' : HttpOverWsProtocolVersion } |
+  { 'RequestFailed' : HttpFailureReason } |
+  { 'InvalidAttestationKey' : null };
+export type HttpOverWsMessage = {
+    'Error' : [[] | [HttpRequestId], string, [] | [ProxyError]]
+  } |
   { 'HttpRequest' : [HttpRequestId, HttpRequest] } |
+  {
+    'AttestedHttpResponse' : [HttpRequestId, HttpResponse, ResponseSignature]
+  } |
+  { 'HttpResponseStats' : [HttpRequestId, ProxyResponseStats] } |
   { 'SetupProxyClient' : null } |
-  { 'HttpResponse' : [HttpRequestId, HttpResponse] };
+  { 'SetupVersionedProxyClient' : ProxyClientSetup } |
+  { 'HttpResponse' : [HttpRequestId, HttpResponse] } |
+  {
+    'EncryptedHttpResponse' : [
+      HttpRequestId,
+      Uint8Array | number[],
+      [] | [ResponseSignature],
+    ]
+  } |
+  { 'ProtocolVersionNegotiated' : HttpOverWsProtocolVersion } |
+  { 'EncryptedHttpRequest' : [HttpRequestId, EncryptedHttpRequest] };
+export interface HttpOverWsMetrics {
+  'requests_succeeded' : bigint,
+  'proxy_errors' : Array<ProxyErrorCount>,
+  'requests_failed_redirect' : bigint,
+  'requests_retried' : bigint,
+  'request_bytes' : Histogram,
+  'requests_started' : bigint,
+  'requests_failed_invalid_encryption' : bigint,
+  'requests_rejected_no_proxies' : bigint,
+  'latency_ms' : Histogram,
+  'requests_failed_timeout' : bigint,
+  'requests_failed_invalid_attestation' : bigint,
+  'requests_failed_proxy_error' : bigint,
+  'response_bytes' : Histogram,
+  'proxies' : Array<ProxyMetrics>,
+}
+export type HttpOverWsProtocolVersion = number;
+export interface HttpRedirect { 'url' : string, 'status' : number }
 export interface HttpRequest {
   'url' : string,
   'method' : HttpMethod,
   'body' : [] | [Uint8Array | number[]],
   'headers' : Array<HttpHeader>,
+  'redirect' : [] | [RedirectPolicy],
+}
+export type HttpRequestBatchEndpointResult = {
+    'Ok' : Array<HttpRequestEndpointResult>
+  } |
+  { 'Err' : ProxyCanisterError };
+export interface HttpRequestCacheOptions {
+  'key_headers' : Array<string>,
+  'ttl_ms' : [] | [CacheTtlMs],
 }
 export interface HttpRequestEndpointArgs {
+  'cache' : [] | [HttpRequestCacheOptions],
   'request' : HttpRequest,
+  'coalesce' : [] | [boolean],
   'timeout_ms' : [] | [HttpRequestTimeoutMs],
   'callback_method_name' : [] | [CanisterCallbackMethodName],
+  'signing_profile' : [] | [SigningProfileName],
+  'idempotency_key' : [] | [IdempotencyKey],
 }
 export type HttpRequestEndpointResult = { 'Ok' : HttpRequestId } |
   { 'Err' : ProxyCanisterError };
 export type HttpRequestId = bigint;
+export type HttpRequestSchedule = { 'Interval' : { 'interval_ms' : bigint } } |
+  { 'Cron' : string };
 export type HttpRequestTimeoutMs = bigint;
 export interface HttpResponse {
   'status' : bigint,
   'body' : Uint8Array | number[],
   'headers' : Array<HttpHeader>,
 }
-export type InvalidRequest = { 'TooManyHeaders' : null } |
+export interface HttpResponseEnvelope {
+  'metadata' : HttpResponseMetadata,
+  'response' : HttpResponse,
+}
+export interface HttpResponseMetadata {
+  'timing' : [] | [HttpResponseTiming],
+  'proxy_principal' : Principal,
+  'final_url' : string,
+  'redirects' : Array<HttpRedirect>,
+  'proxy_attempts' : number,
+  'body_size_bytes' : [] | [bigint],
+}
+export interface HttpResponseTiming {
+  'dns_lookup_ms' : [] | [bigint],
+  'time_to_first_byte_ms' : [] | [bigint],
+  'total_ms' : bigint,
+  'connect_ms' : [] | [bigint],
+}
+export type HttpResult = { 'Success' : HttpResponse } |
+  { 'Failure' : HttpFailureReason };
+export type IdempotencyKey = string;
+export type InvalidProxyConfig = {
+    'InvalidMaxCallbackDeliveryAttempts' : null
+  } |
+  { 'InvalidTimeouts' : null } |
+  { 'InvalidMaxHttpHeadersCount' : null };
+export type InvalidRequest = { 'InvalidRedirectPolicy' : null } |
+  { 'TooManyRequests' : null } |
+  { 'InvalidWebhookPath' : null } |
+  { 'TooManyHeaders' : null } |
+  { 'InvalidHttpMethod' : null } |
+  { 'TooManyScheduledJobs' : null } |
+  { 'SigningProfileNotFound' : null } |
+  { 'SecretNotAllowed' : SecretName } |
+  { 'InvalidSecretPlaceholder' : string } |
   { 'InvalidTimeout' : null } |
-  { 'InvalidUrl' : string };
+  { 'InvalidCacheTtl' : null } |
+  { 'InvalidIdempotencyKey' : null } |
+  { 'InvalidSigningProfile' : string } |
+  { 'InvalidSchedule' : string } |
+  { 'InvalidUrl' : string } |
+  { 'TooManyWebhooks' : null } |
+  { 'TooManySigningProfiles' : null } |
+  { 'NotCacheable' : null };
+export interface ListDeadLettersResult {
+  'dead_letters' : Array<DeadLetter>,
+  'next_after_request_id' : [] | [HttpRequestId],
+}
+export interface ListRequestsArgs {
+  'limit' : [] | [number],
+  'after_request_id' : [] | [HttpRequestId],
+}
+export interface ListRequestsResult {
+  'requests' : Array<[HttpRequestId, CanisterRequest]>,
+  'next_after_request_id' : [] | [HttpRequestId],
+}
+export interface LogEntry {
+  'id' : LogEntryId,
+  'timestamp_ns' : bigint,
+  'level' : LogLevel,
+  'fields' : LogFields,
+  'message' : string,
+}
+export type LogEntryId = bigint;
+export interface LogFields {
+  'request_id' : [] | [HttpRequestId],
+  'proxy_principal' : [] | [Principal],
+  'canister_id' : [] | [CanisterId],
+}
+export type LogLevel = { 'Error' : null } |
+  { 'Info' : null } |
+  { 'Warn' : null } |
+  { 'Debug' : null };
+export interface LoggerConfig {
+  'max_size_bytes' : bigint,
+  'min_level' : LogLevel,
+}
+export interface LogsCursor { 'id' : LogEntryId, 'timestamp_ns' : bigint }
+export type ManageProxyResult = { 'Ok' : null } |
+  { 'Err' : HttpOverWsError };
+export type ManageScheduledJobResult = { 'Ok' : null } |
+  { 'Err' : ScheduledJobError };
 export type ProxyCanisterError = { 'HttpOverWs' : HttpOverWsError } |
   { 'InvalidRequest' : InvalidRequest };
+export interface ProxyCanisterMetrics {
+  'cycles_balance' : bigint,
+  'stable_memory_bytes' : bigint,
+  'cache' : CacheStats,
+  'heap_memory_bytes' : bigint,
+  'http_over_ws' : HttpOverWsMetrics,
+  'callbacks_failed' : bigint,
+  'callbacks_succeeded' : bigint,
+}
+export interface ProxyCapabilities {
+  'secret_names' : [] | [Array<SecretName>],
+  'http_methods' : Array<HttpMethod>,
+}
+export interface ProxyClientSetup {
+  'capabilities' : [] | [ProxyCapabilities],
+  'supported_protocol_versions' : Uint32Array | number[],
+  'encryption_key' : [] | [ProxyEncryptionKey],
+}
+export interface ProxyConfig {
+  'max_http_headers_count' : number,
+  'max_http_request_timeout_ms' : HttpRequestTimeoutMs,
+  'default_http_request_timeout_ms' : HttpRequestTimeoutMs,
+  'min_http_request_timeout_ms' : HttpRequestTimeoutMs,
+  'max_callback_delivery_attempts' : number,
+}
+export interface ProxyEncryptionKey {
+  'signature' : Uint8Array | number[],
+  'public_key' : Uint8Array | number[],
+}
+export type ProxyError = { 'ProxyOverloaded' : [] | [string] } |
+  { 'ConnectionRefused' : [] | [string] } |
+  { 'TlsError' : [] | [string] } |
+  { 'RejectedByPolicy' : [] | [string] } |
+  { 'DnsResolutionFailed' : [] | [string] } |
+  { 'BodyReadError' : [] | [string] } |
+  { 'Unknown' : [] | [string] } |
+  { 'UpstreamTimeout' : [] | [string] };
+export interface ProxyErrorCount { 'kind' : string, 'count' : bigint }
+export type ProxyHealth = { 'Unhealthy' : null } |
+  { 'Healthy' : null } |
+  { 'Unknown' : null };
+export interface ProxyInfo {
+  'capabilities' : ProxyCapabilities,
+  'completed_requests' : bigint,
+  'proxy_principal' : Principal,
+  'draining' : boolean,
+  'in_flight_requests' : bigint,
+  'protocol_version' : HttpOverWsProtocolVersion,
+  'connected_at_ns' : bigint,
+  'health' : ProxyHealth,
+}
+export interface ProxyMetrics {
+  'proxy_principal' : Principal,
+  'failed_requests' : bigint,
+  'success_rate' : [] | [number],
+  'latency_ms' : Histogram,
+  'succeeded_requests' : bigint,
+  'in_flight_requests' : bigint,
+}
+export interface ProxyResponseStats {
+  'timing' : HttpResponseTiming,
+  'body_size_bytes' : bigint,
+}
+export type RedirectPolicy = { 'Error' : null } |
+  { 'Follow' : number } |
+  { 'Manual' : null };
+export interface RegisterSigningProfileArgs {
+  'scheme' : SigningScheme,
+  'name' : SigningProfileName,
+  'canister_id' : [] | [CanisterId],
+  'secret' : Uint8Array | number[],
+}
+export type RegisterSigningProfileResult = { 'Ok' : null } |
+  { 'Err' : ProxyCanisterError };
+export type RegisterWebhookResult = { 'Ok' : null } |
+  { 'Err' : ProxyCanisterError };
 export type RequestState = { 'Executing' : [] | [CanisterCallbackMethodName] } |
   { 'Executed' : null } |
   { 'CallbackFailed' : string };
+export interface RequestTrace {
+  'request_id' : HttpRequestId,
+  'proxy_principal' : [] | [Principal],
+  'canister_id' : CanisterId,
+  'events' : Array<RequestTraceEvent>,
+  'duration_ns' : bigint,
+}
+export interface RequestTraceEvent {
+  'timestamp_ns' : bigint,
+  'kind' : RequestTraceEventKind,
+  'duration_ns' : bigint,
+}
+export type RequestTraceEventKind = { 'AssignedToProxy' : Principal } |
+  { 'ServedFromCache' : null } |
+  { 'CallbackFinished' : { 'attempt' : number } } |
+  { 'Sent' : null } |
+  { 'MovedToDeadLetters' : null } |
+  { 'CallbackStarted' : { 'attempt' : number } } |
+  { 'CallbackFailed' : { 'attempt' : number, 'error' : string } } |
+  { 'ResponseReceived' : null } |
+  { 'Validated' : null } |
+  { 'CoalescedWith' : HttpRequestId } |
+  { 'RequestFailed' : HttpFailureReason };
+export interface ResponseAttestation {
+  'signature' : Uint8Array | number[],
+  'timestamp_ns' : bigint,
+  'public_key' : Uint8Array | number[],
+  'proxy_principal' : Principal,
+}
+export interface ResponseAttestationConfig {
+  'required' : boolean,
+  'proxy_public_keys' : Array<[Principal, Uint8Array | number[]]>,
+}
+export interface ResponseSignature {
+  'signature' : Uint8Array | number[],
+  'timestamp_ns' : bigint,
+}
+export interface ScheduleHttpRequestArgs {
+  'request' : HttpRequest,
+  'timeout_ms' : [] | [HttpRequestTimeoutMs],
+  'callback_method_name' : CanisterCallbackMethodName,
+  'schedule' : HttpRequestSchedule,
+  'end_time_ns' : [] | [bigint],
+}
+export type ScheduleHttpRequestResult = { 'Ok' : ScheduledJobId } |
+  { 'Err' : ProxyCanisterError };
+export interface ScheduledJob {
+  'executions' : Array<ScheduledJobExecution>,
+  'request' : HttpRequest,
+  'canister_id' : CanisterId,
+  'timeout_ms' : [] | [HttpRequestTimeoutMs],
+  'state' : ScheduledJobState,
+  'job_id' : ScheduledJobId,
+  'callback_method_name' : CanisterCallbackMethodName,
+  'next_execution_ns' : [] | [bigint],
+  'schedule' : HttpRequestSchedule,
+  'end_time_ns' : [] | [bigint],
+}
+export type ScheduledJobError = { 'NotFound' : null } |
+  { 'Finished' : null };
+export interface ScheduledJobExecution {
+  'request_id' : [] | [HttpRequestId],
+  'timestamp_ns' : bigint,
+  'error' : [] | [string],
+}
+export type ScheduledJobId = bigint;
+export type ScheduledJobState = { 'Paused' : null } |
+  { 'Active' : null } |
+  { 'Finished' : null };
+export type SecretName = string;
+export type SetAllowedSecretsResult = { 'Ok' : null } |
+  { 'Err' : ProxyCanisterError };
+export interface SigningProfileInfo {
+  'registered_at_ns' : bigint,
+  'scheme' : SigningScheme,
+  'name' : SigningProfileName,
+}
+export type SigningProfileName = string;
+export type SigningScheme = {
+    'EcdsaSecp256k1' : {
+      'timestamp_header' : string,
+      'signature_header' : string,
+    }
+  } |
+  {
+    'AwsSigV4' : {
+      'region' : string,
+      'service' : string,
+      'access_key_id' : string,
+    }
+  } |
+  {
+    'HmacSha256' : { 'timestamp_header' : string, 'signature_header' : string }
+  };
+export type UpdateConfigResult = { 'Ok' : null } |
+  { 'Err' : InvalidProxyConfig };
+export interface Webhook {
+  'path' : WebhookPath,
+  'callback_method_name' : CanisterCallbackMethodName,
+}
+export type WebhookPath = string;
 export interface WebsocketMessage {
   'sequence_num' : bigint,
   'content' : Uint8Array | number[],
@@ -97,13 +479,89 @@ export interface WebsocketMessage {
   'is_service_message' : boolean,
 }
 export interface _SERVICE {
+  'cancel_scheduled_job' : ActorMethod<
+    [ScheduledJobId],
+    ManageScheduledJobResult
+  >,
+  'delete_my_dead_letters' : ActorMethod<
+    [BigUint64Array | bigint[]],
+    undefined
+  >,
   'disconnect_all_proxies' : ActorMethod<[], undefined>,
-  'get_logs' : ActorMethod<[], Array<[string, string]>>,
+  'disconnect_proxy' : ActorMethod<[Principal], ManageProxyResult>,
+  'drain_proxy' : ActorMethod<[Principal], ManageProxyResult>,
+  'export_logs' : ActorMethod<[ExportLogsArgs], ExportLogsResult>,
+  'get_cache_stats' : ActorMethod<[], CacheStats>,
+  'get_config' : ActorMethod<[], ProxyConfig>,
+  'get_logger_config' : ActorMethod<[], LoggerConfig>,
+  'get_logs' : ActorMethod<[GetLogsArgs], GetLogsResult>,
+  'get_metrics' : ActorMethod<[], GetMetricsResult>,
+  'get_my_http_response' : ActorMethod<
+    [HttpRequestId],
+    [] | [HttpResponseEnvelope]
+  >,
+  'get_my_request' : ActorMethod<[HttpRequestId], [] | [CanisterRequest]>,
   'get_request_by_id' : ActorMethod<[HttpRequestId], [] | [CanisterRequest]>,
+  'get_request_trace' : ActorMethod<[HttpRequestId], [] | [RequestTrace]>,
+  'get_response_attestation_config' : ActorMethod<
+    [],
+    ResponseAttestationConfig
+  >,
   'http_request' : ActorMethod<
     [HttpRequestEndpointArgs],
     HttpRequestEndpointResult
   >,
+  'http_request_batch' : ActorMethod<
+    [Array<HttpRequestEndpointArgs>, [] | [CanisterCallbackMethodName]],
+    HttpRequestBatchEndpointResult
+  >,
+  'is_payload_encryption_required' : ActorMethod<[], boolean>,
+  'list_dead_letters' : ActorMethod<[ListRequestsArgs], ListDeadLettersResult>,
+  'list_my_allowed_secrets' : ActorMethod<[], Array<SecretName>>,
+  'list_my_dead_letters' : ActorMethod<
+    [ListRequestsArgs],
+    ListDeadLettersResult
+  >,
+  'list_my_requests' : ActorMethod<[ListRequestsArgs], ListRequestsResult>,
+  'list_my_scheduled_jobs' : ActorMethod<[], Array<ScheduledJob>>,
+  'list_my_signing_profiles' : ActorMethod<[], Array<SigningProfileInfo>>,
+  'list_my_webhooks' : ActorMethod<[], Array<Webhook>>,
+  'list_proxies' : ActorMethod<[], Array<ProxyInfo>>,
+  'pause_scheduled_job' : ActorMethod<
+    [ScheduledJobId],
+    ManageScheduledJobResult
+  >,
+  'register_signing_profile' : ActorMethod<
+    [RegisterSigningProfileArgs],
+    RegisterSigningProfileResult
+  >,
+  'register_webhook' : ActorMethod<[Webhook], RegisterWebhookResult>,
+  'replay_dead_letters' : ActorMethod<[BigUint64Array | bigint[]], undefined>,
+  'resume_scheduled_job' : ActorMethod<
+    [ScheduledJobId],
+    ManageScheduledJobResult
+  >,
+  'schedule_http_request' : ActorMethod<
+    [ScheduleHttpRequestArgs],
+    ScheduleHttpRequestResult
+  >,
+  'set_allowed_secrets' : ActorMethod<
+    [CanisterId, Array<SecretName>],
+    SetAllowedSecretsResult
+  >,
+  'set_logger_config' : ActorMethod<[LoggerConfig], undefined>,
+  'set_payload_encryption_required' : ActorMethod<[boolean], undefined>,
+  'set_proxy_attestation_key' : ActorMethod<
+    [Principal, [] | [Uint8Array | number[]]],
+    ManageProxyResult
+  >,
+  'set_response_attestations_required' : ActorMethod<[boolean], undefined>,
+  'unregister_signing_profile' : ActorMethod<
+    [[] | [CanisterId], SigningProfileName],
+    undefined
+  >,
+  'unregister_webhook' : ActorMethod<[WebhookPath], undefined>,
+  'update_config' : ActorMethod<[ProxyConfig], UpdateConfigResult>,
   'ws_close' : ActorMethod<[CanisterWsCloseArguments], CanisterWsCloseResult>,
   'ws_get_messages' : ActorMethod<
     [CanisterWsGetMessagesArguments],
@@ -1,54 +1,308 @@
 export const idlFactory = ({ IDL }) => {
+  const HttpRequestTimeoutMs = IDL.Nat64;
+  const ProxyConfig = IDL.Record({
+    'max_http_headers_count' : IDL.Nat32,
+    'max_http_request_timeout_ms' : HttpRequestTimeoutMs,
+    'default_http_request_timeout_ms' : HttpRequestTimeoutMs,
+    'min_http_request_timeout_ms' : HttpRequestTimeoutMs,
+    'max_callback_delivery_attempts' : IDL.Nat32,
+  });
+  const ScheduledJobId = IDL.Nat64;
+  const ScheduledJobError = IDL.Variant({
+    'NotFound' : IDL.Null,
+    'Finished' : IDL.Null,
+  });
+  const ManageScheduledJobResult = IDL.Variant({
+    'Ok' : IDL.Null,
+    'Err' : ScheduledJobError,
+  });
   const HttpRequestId = IDL.Nat64;
+  const SecretName = IDL.Text;
+  const HttpMethod = IDL.Variant({
+    'GET' : IDL.Null,
+    'PUT' : IDL.Null,
+    'DELETE' : IDL.Null,
+    'HEAD' : IDL.Null,
+    'POST' : IDL.Null,
+    'PATCH' : IDL.Null,
+    'OPTIONS' : IDL.Null,
+    'Other' : IDL.Text,
+  });
+  const HttpOverWsProtocolVersion = IDL.Nat32;
+  const ProxyError = IDL.Variant({
+    'ProxyOverloaded' : IDL.Opt(IDL.Text),
+    'ConnectionRefused' : IDL.Opt(IDL.Text),
+    'TlsError' : IDL.Opt(IDL.Text),
+    'RejectedByPolicy' : IDL.Opt(IDL.Text),
+    'DnsResolutionFailed' : IDL.Opt(IDL.Text),
+    'BodyReadError' : IDL.Opt(IDL.Text),
+    'Unknown' : IDL.Opt(IDL.Text),
+    'UpstreamTimeout' : IDL.Opt(IDL.Text),
+  });
+  const HttpFailureReason = IDL.Variant({
+    'InvalidEncryptedResponse' : IDL.Text,
+    'InvalidRedirect' : IDL.Text,
+    'RedirectNotAllowed' : IDL.Text,
+    'InvalidResponseAttestation' : IDL.Text,
+    'ProxyError' : ProxyError,
+    'TooManyRedirects' : IDL.Null,
+    'RequestTimeout' : IDL.Null,
+  });
+  const HttpOverWsError = IDL.Variant({
+    'NotHttpOverWsType' : IDL.Text,
+    'ProxyNotFound' : IDL.Null,
+    'InvalidSecretPlaceholder' : IDL.Text,
+    'NotYetReceived' : IDL.Null,
+    'ConnectionNotAssignedToProxy' : IDL.Null,
+    'RequestIdNotFound' : IDL.Null,
+    'NoProxiesConnected' : IDL.Null,
+    'InvalidHttpMessage' : IDL.Null,
+    'PayloadEncryptionNotAvailable' : IDL.Null,
+    'SecretsNotAvailable' : IDL.Vec(SecretName),
+    'UnsupportedHttpMethod' : HttpMethod,
+    'UnsupportedProtocolVersion' : HttpOverWsProtocolVersion,
+    'RequestFailed' : HttpFailureReason,
+    'InvalidAttestationKey' : IDL.Null,
+  });
+  const ManageProxyResult = IDL.Variant({
+    'Ok' : IDL.Null,
+    'Err' : HttpOverWsError,
+  });
+  const LogEntryId = IDL.Nat64;
+  const LogsCursor = IDL.Record({
+    'id' : LogEntryId,
+    'timestamp_ns' : IDL.Nat64,
+  });
+  const ExportLogsArgs = IDL.Record({
+    'cursor' : IDL.Opt(LogsCursor),
+    'from_timestamp_ns' : IDL.Opt(IDL.Nat64),
+    'limit' : IDL.Opt(IDL.Nat32),
+  });
+  const LogLevel = IDL.Variant({
+    'Error' : IDL.Null,
+    'Info' : IDL.Null,
+    'Warn' : IDL.Null,
+    'Debug' : IDL.Null,
+  });
   const CanisterId = IDL.Principal;
+  const LogFields = IDL.Record({
+    'request_id' : IDL.Opt(HttpRequestId),
+    'proxy_principal' : IDL.Opt(IDL.Principal),
+    'canister_id' : IDL.Opt(CanisterId),
+  });
+  const LogEntry = IDL.Record({
+    'id' : LogEntryId,
+    'timestamp_ns' : IDL.Nat64,
+    'level' : LogLevel,
+    'fields' : LogFields,
+    'message' : IDL.Text,
+  });
+  const ExportLogsResult = IDL.Record({
+    'missed_count' : IDL.Nat64,
+    'logs' : IDL.Vec(LogEntry),
+    'next_cursor' : IDL.Opt(LogsCursor),
+    'has_more' : IDL.Bool,
+  });
+  const CacheStats = IDL.Record({
+    'hits' : IDL.Nat64,
+    'size_bytes' : IDL.Nat64,
+    'misses' : IDL.Nat64,
+    'entries' : IDL.Nat64,
+  });
+  const LoggerConfig = IDL.Record({
+    'max_size_bytes' : IDL.Nat64,
+    'min_level' : LogLevel,
+  });
+  const GetLogsArgs = IDL.Record({
+    'request_id' : IDL.Opt(HttpRequestId),
+    'after_id' : IDL.Opt(LogEntryId),
+    'proxy_principal' : IDL.Opt(IDL.Principal),
+    'canister_id' : IDL.Opt(CanisterId),
+    'limit' : IDL.Opt(IDL.Nat32),
+    'min_level' : IDL.Opt(LogLevel),
+  });
+  const GetLogsResult = IDL.Record({
+    'logs' : IDL.Vec(LogEntry),
+    'next_after_id' : IDL.Opt(LogEntryId),
+  });
+  const ProxyErrorCount = IDL.Record({
+    'kind' : IDL.Text,
+    'count' : IDL.Nat64,
+  });
+  const Histogram = IDL.Record({
+    'sum' : IDL.Nat64,
+    'count' : IDL.Nat64,
+    'buckets' : IDL.Vec(IDL.Tuple(IDL.Nat64, IDL.Nat64)),
+  });
+  const ProxyMetrics = IDL.Record({
+    'proxy_principal' : IDL.Principal,
+    'failed_requests' : IDL.Nat64,
+    'success_rate' : IDL.Opt(IDL.Float64),
+    'latency_ms' : Histogram,
+    'succeeded_requests' : IDL.Nat64,
+    'in_flight_requests' : IDL.Nat64,
+  });
+  const HttpOverWsMetrics = IDL.Record({
+    'requests_succeeded' : IDL.Nat64,
+    'proxy_errors' : IDL.Vec(ProxyErrorCount),
+    'requests_failed_redirect' : IDL.Nat64,
+    'requests_retried' : IDL.Nat64,
+    'request_bytes' : Histogram,
+    'requests_started' : IDL.Nat64,
+    'requests_failed_invalid_encryption' : IDL.Nat64,
+    'requests_rejected_no_proxies' : IDL.Nat64,
+    'latency_ms' : Histogram,
+    'requests_failed_timeout' : IDL.Nat64,
+    'requests_failed_invalid_attestation' : IDL.Nat64,
+    'requests_failed_proxy_error' : IDL.Nat64,
+    'response_bytes' : Histogram,
+    'proxies' : IDL.Vec(ProxyMetrics),
+  });
+  const ProxyCanisterMetrics = IDL.Record({
+    'cycles_balance' : IDL.Nat,
+    'stable_memory_bytes' : IDL.Nat64,
+    'cache' : CacheStats,
+    'heap_memory_bytes' : IDL.Nat64,
+    'http_over_ws' : HttpOverWsMetrics,
+    'callbacks_failed' : IDL.Nat64,
+    'callbacks_succeeded' : IDL.Nat64,
+  });
+  const GetMetricsResult = IDL.Record({
+    'prometheus' : IDL.Text,
+    'metrics' : ProxyCanisterMetrics,
+  });
+  const HttpResponseTiming = IDL.Record({
+    'dns_lookup_ms' : IDL.Opt(IDL.Nat64),
+    'time_to_first_byte_ms' : IDL.Opt(IDL.Nat64),
+    'total_ms' : IDL.Nat64,
+    'connect_ms' : IDL.Opt(IDL.Nat64),
+  });
+  const HttpRedirect = IDL.Record({ 'url' : IDL.Text, 'status' : IDL.Nat16 });
+  const HttpResponseMetadata = IDL.Record({
+    'timing' : IDL.Opt(HttpResponseTiming),
+    'proxy_principal' : IDL.Principal,
+    'final_url' : IDL.Text,
+    'redirects' : IDL.Vec(HttpRedirect),
+    'proxy_attempts' : IDL.Nat32,
+    'body_size_bytes' : IDL.Opt(IDL.Nat64),
+  });
+  const HttpHeader = IDL.Record({ 'value' : IDL.Text, 'name' : IDL.Text });
+  const HttpResponse = IDL.Record({
+    'status' : IDL.Nat,
+    'body' : IDL.Vec(IDL.Nat8),
+    'headers' : IDL.Vec(HttpHeader),
+  });
+  const HttpResponseEnvelope = IDL.Record({
+    'metadata' : HttpResponseMetadata,
+    'response' : HttpResponse,
+  });
+  const ResponseAttestation = IDL.Record({
+    'signature' : IDL.Vec(IDL.Nat8),
+    'timestamp_ns' : IDL.Nat64,
+    'public_key' : IDL.Vec(IDL.Nat8),
+    'proxy_principal' : IDL.Principal,
+  });
   const CanisterCallbackMethodName = IDL.Text;
   const RequestState = IDL.Variant({
     'Executing' : IDL.Opt(CanisterCallbackMethodName),
     'Executed' : IDL.Null,
     'CallbackFailed' : IDL.Text,
   });
+  const CallbackAttempt = IDL.Record({
+    'timestamp_ns' : IDL.Nat64,
+    'error' : IDL.Opt(IDL.Text),
+  });
+  const HttpResult = IDL.Variant({
+    'Success' : HttpResponse,
+    'Failure' : HttpFailureReason,
+  });
   const CanisterRequest = IDL.Record({
+    'response_attestation' : IDL.Opt(ResponseAttestation),
     'canister_id' : CanisterId,
     'state' : RequestState,
+    'callback_attempts' : IDL.Vec(CallbackAttempt),
+    'http_result' : IDL.Opt(HttpResult),
+    'response_metadata' : IDL.Opt(HttpResponseMetadata),
   });
-  const HttpMethod = IDL.Variant({
-    'GET' : IDL.Null,
-    'PUT' : IDL.Null,
-    'DELETE' : IDL.Null,
-    'HEAD' : IDL.Null,
-    'POST' : IDL.Null,
+  const RequestTraceEventKind = IDL.Variant({
+    'AssignedToProxy' : IDL.Principal,
+    'ServedFromCache' : IDL.Null,
+    'CallbackFinished' : IDL.Record({ 'attempt' : IDL.Nat32 }),
+    'Sent' : IDL.Null,
+    'MovedToDeadLetters' : IDL.Null,
+    'CallbackStarted' : IDL.Record({ 'attempt' : IDL.Nat32 }),
+    'CallbackFailed' : IDL.Record({
+      'attempt' : IDL.Nat32,
+      'error' : IDL.Text,
+    }),
+    'ResponseReceived' : IDL.Null,
+    'Validated' : IDL.Null,
+    'CoalescedWith' : HttpRequestId,
+    'RequestFailed' : HttpFailureReason,
+  });
+  const RequestTraceEvent = IDL.Record({
+    'timestamp_ns' : IDL.Nat64,
+    'kind' : RequestTraceEventKind,
+    'duration_ns' : IDL.Nat64,
+  });
+  const RequestTrace = IDL.Record({
+    'request_id' : HttpRequestId,
+    'proxy_principal' : IDL.Opt(IDL.Principal),
+    'canister_id' : CanisterId,
+    'events' : IDL.Vec(RequestTraceEvent),
+    'duration_ns' : IDL.Nat64,
+  });
+  const ResponseAttestationConfig = IDL.Record({
+    'required' : IDL.Bool,
+    'proxy_public_keys' : IDL.Vec(IDL.Tuple(IDL.Principal, IDL.Vec(IDL.Nat8))),
+  });
+  const CacheTtlMs = IDL.Nat64;
+  const HttpRequestCacheOptions = IDL.Record({
+    'key_headers' : IDL.Vec(IDL.Text),
+    'ttl_ms' : IDL.Opt(CacheTtlMs),
+  });
+  const RedirectPolicy = IDL.Variant({
+    'Error' : IDL.Null,
+    'Follow' : IDL.Nat32,
+    'Manual' : IDL.Null,
   });
-  const HttpHeader = IDL.Record({ 'value' : IDL.Text, 'name' : IDL.Text });
   const HttpRequest = IDL.Record({
     'url' : IDL.Text,
     'method' : HttpMethod,
     'body' : IDL.Opt(IDL.Vec(IDL.Nat8)),
     'headers' : IDL.Vec(HttpHeader),
+    'redirect' : IDL.Opt(RedirectPolicy),
   });
-  const HttpRequestTimeoutMs = IDL.Nat64;
+  const SigningProfileName = IDL.Text;
+  const IdempotencyKey = IDL.Text;
   const HttpRequestEndpointArgs = IDL.Record({
+    'cache' : IDL.Opt(HttpRequestCacheOptions),
     'request' : HttpRequest,
+    'coalesce' : IDL.Opt(IDL.Bool),
     'timeout_ms' : IDL.Opt(HttpRequestTimeoutMs),
     'callback_method_name' : IDL.Opt(CanisterCallbackMethodName),
-  });
-  const HttpFailureReason = IDL.Variant({
-    'ProxyError' : IDL.Text,
-    'RequestTimeout' : IDL.Null,
-  });
-  const HttpOverWsError = IDL.Variant({
-    'NotHttpOverWsType' : IDL.Text,
-    'ProxyNotFound' : IDL.Null,
-    'NotYetReceived' : IDL.Null,
-    'ConnectionNotAssignedToProxy' : IDL.Null,
-    'RequestIdNotFound' : IDL.Null,
-    'NoProxiesConnected' : IDL.Null,
-    'InvalidHttpMessage' : IDL.Null,
-    'RequestFailed' : HttpFailureReason,
+    'signing_profile' : IDL.Opt(SigningProfileName),
+    'idempotency_key' : IDL.Opt(IdempotencyKey),
   });
   const InvalidRequest = IDL.Variant({
+    'InvalidRedirectPolicy' : IDL.Null,
+    'TooManyRequests' : IDL.Null,
+    'InvalidWebhookPath' : IDL.Null,
     'TooManyHeaders' : IDL.Null,
+    'InvalidHttpMethod' : IDL.Null,
+    'TooManyScheduledJobs' : IDL.Null,
+    'SigningProfileNotFound' : IDL.Null,
+    'SecretNotAllowed' : SecretName,
+    'InvalidSecretPlaceholder' : IDL.Text,
     'InvalidTimeout' : IDL.Null,
+    'InvalidCacheTtl' : IDL.Null,
+    'InvalidIdempotencyKey' : IDL.Null,
+    'InvalidSigningProfile' : IDL.Text,
+    'InvalidSchedule' : IDL.Text,
     'InvalidUrl' : IDL.Text,
+    'TooManyWebhooks' : IDL.Null,
+    'TooManySigningProfiles' : IDL.Null,
+    'NotCacheable' : IDL.Null,
   });
   const ProxyCanisterError = IDL.Variant({
     'HttpOverWs' : HttpOverWsError,
@@ -58,6 +312,137 @@ export const idlFactory = ({ IDL }) => {
     'Ok' : HttpRequestId,
     'Err' : ProxyCanisterError,
   });
+  const HttpRequestBatchEndpointResult = IDL.Variant({
+    'Ok' : IDL.Vec(HttpRequestEndpointResult),
+    'Err' : ProxyCanisterError,
+  });
+  const ListRequestsArgs = IDL.Record({
+    'limit' : IDL.Opt(IDL.Nat32),
+    'after_request_id' : IDL.Opt(HttpRequestId),
+  });
+  const DeadLetter = IDL.Record({
+    'request_id' : HttpRequestId,
+    'canister_id' : CanisterId,
+    'callback_attempts' : IDL.Vec(CallbackAttempt),
+    'http_result' : HttpResult,
+    'callback_method_name' : CanisterCallbackMethodName,
+  });
+  const ListDeadLettersResult = IDL.Record({
+    'dead_letters' : IDL.Vec(DeadLetter),
+    'next_after_request_id' : IDL.Opt(HttpRequestId),
+  });
+  const ListRequestsResult = IDL.Record({
+    'requests' : IDL.Vec(IDL.Tuple(HttpRequestId, CanisterRequest)),
+    'next_after_request_id' : IDL.Opt(HttpRequestId),
+  });
+  const ScheduledJobExecution = IDL.Record({
+    'request_id' : IDL.Opt(HttpRequestId),
+    'timestamp_ns' : IDL.Nat64,
+    'error' : IDL.Opt(IDL.Text),
+  });
+  const ScheduledJobState = IDL.Variant({
+    'Paused' : IDL.Null,
+    'Active' : IDL.Null,
+    'Finished' : IDL.Null,
+  });
+  const HttpRequestSchedule = IDL.Variant({
+    'Interval' : IDL.Record({ 'interval_ms' : IDL.Nat64 }),
+    'Cron' : IDL.Text,
+  });
+  const ScheduledJob = IDL.Record({
+    'executions' : IDL.Vec(ScheduledJobExecution),
+    'request' : HttpRequest,
+    'canister_id' : CanisterId,
+    'timeout_ms' : IDL.Opt(HttpRequestTimeoutMs),
+    'state' : ScheduledJobState,
+    'job_id' : ScheduledJobId,
+    'callback_method_name' : CanisterCallbackMethodName,
+    'next_execution_ns' : IDL.Opt(IDL.Nat64),
+    'schedule' : HttpRequestSchedule,
+    'end_time_ns' : IDL.Opt(IDL.Nat64),
+  });
+  const SigningScheme = IDL.Variant({
+    'EcdsaSecp256k1' : IDL.Record({
+      'timestamp_header' : IDL.Text,
+      'signature_header' : IDL.Text,
+    }),
+    'AwsSigV4' : IDL.Record({
+      'region' : IDL.Text,
+      'service' : IDL.Text,
+      'access_key_id' : IDL.Text,
+    }),
+    'HmacSha256' : IDL.Record({
+      'timestamp_header' : IDL.Text,
+      'signature_header' : IDL.Text,
+    }),
+  });
+  const SigningProfileInfo = IDL.Record({
+    'registered_at_ns' : IDL.Nat64,
+    'scheme' : SigningScheme,
+    'name' : SigningProfileName,
+  });
+  const WebhookPath = IDL.Text;
+  const Webhook = IDL.Record({
+    'path' : WebhookPath,
+    'callback_method_name' : CanisterCallbackMethodName,
+  });
+  const ProxyCapabilities = IDL.Record({
+    'secret_names' : IDL.Opt(IDL.Vec(SecretName)),
+    'http_methods' : IDL.Vec(HttpMethod),
+  });
+  const ProxyHealth = IDL.Variant({
+    'Unhealthy' : IDL.Null,
+    'Healthy' : IDL.Null,
+    'Unknown' : IDL.Null,
+  });
+  const ProxyInfo = IDL.Record({
+    'capabilities' : ProxyCapabilities,
+    'completed_requests' : IDL.Nat64,
+    'proxy_principal' : IDL.Principal,
+    'draining' : IDL.Bool,
+    'in_flight_requests' : IDL.Nat64,
+    'protocol_version' : HttpOverWsProtocolVersion,
+    'connected_at_ns' : IDL.Nat64,
+    'health' : ProxyHealth,
+  });
+  const RegisterSigningProfileArgs = IDL.Record({
+    'scheme' : SigningScheme,
+    'name' : SigningProfileName,
+    'canister_id' : IDL.Opt(CanisterId),
+    'secret' : IDL.Vec(IDL.Nat8),
+  });
+  const RegisterSigningProfileResult = IDL.Variant({
+    'Ok' : IDL.Null,
+    'Err' : ProxyCanisterError,
+  });
+  const RegisterWebhookResult = IDL.Variant({
+    'Ok' : IDL.Null,
+    'Err' : ProxyCanisterError,
+  });
+  const ScheduleHttpRequestArgs = IDL.Record({
+    'request' : HttpRequest,
+    'timeout_ms' : IDL.Opt(HttpRequestTimeoutMs),
+    'callback_method_name' : CanisterCallbackMethodName,
+    'schedule' : HttpRequestSchedule,
+    'end_time_ns' : IDL.Opt(IDL.Nat64),
+  });
+  const ScheduleHttpRequestResult = IDL.Variant({
+    'Ok' : ScheduledJobId,
+    'Err' : ProxyCanisterError,
+  });
+  const SetAllowedSecretsResult = IDL.Variant({
+    'Ok' : IDL.Null,
+    'Err' : ProxyCanisterError,
+  });
+  const InvalidProxyConfig = IDL.Variant({
+    'InvalidMaxCallbackDeliveryAttempts' : IDL.Null,
+    'InvalidTimeouts' : IDL.Null,
+    'InvalidMaxHttpHeadersCount' : IDL.Null,
+  });
+  const UpdateConfigResult = IDL.Variant({
+    'Ok' : IDL.Null,
+    'Err' : InvalidProxyConfig,
+  });
   const ClientPrincipal = IDL.Principal;
   const ClientKey = IDL.Record({
     'client_principal' : ClientPrincipal,
@@ -92,16 +477,47 @@ export const idlFactory = ({ IDL }) => {
     'is_service_message' : IDL.Bool,
   });
   const CanisterWsMessageArguments = IDL.Record({ 'msg' : WebsocketMessage });
-  const HttpResponse = IDL.Record({
-    'status' : IDL.Nat,
-    'body' : IDL.Vec(IDL.Nat8),
-    'headers' : IDL.Vec(HttpHeader),
+  const ResponseSignature = IDL.Record({
+    'signature' : IDL.Vec(IDL.Nat8),
+    'timestamp_ns' : IDL.Nat64,
+  });
+  const ProxyResponseStats = IDL.Record({
+    'timing' : HttpResponseTiming,
+    'body_size_bytes' : IDL.Nat64,
+  });
+  const ProxyEncryptionKey = IDL.Record({
+    'signature' : IDL.Vec(IDL.Nat8),
+    'public_key' : IDL.Vec(IDL.Nat8),
+  });
+  const ProxyClientSetup = IDL.Record({
+    'capabilities' : IDL.Opt(ProxyCapabilities),
+    'supported_protocol_versions' : IDL.Vec(HttpOverWsProtocolVersion),
+    'encryption_key' : IDL.Opt(ProxyEncryptionKey),
+  });
+  const EncryptedHttpRequest = IDL.Record({
+    'hop' : IDL.Nat32,
+    'ciphertext' : IDL.Vec(IDL.Nat8),
+    'ephemeral_public_key' : IDL.Vec(IDL.Nat8),
   });
   const HttpOverWsMessage = IDL.Variant({
-    'Error' : IDL.Tuple(IDL.Opt(HttpRequestId), IDL.Text),
+    'Error' : IDL.Tuple(IDL.Opt(HttpRequestId), IDL.Text, IDL.Opt(ProxyError)),
     'HttpRequest' : IDL.Tuple(HttpRequestId, HttpRequest),
+    'AttestedHttpResponse' : IDL.Tuple(
+      HttpRequestId,
+      HttpResponse,
+      ResponseSignature,
+    ),
+    'HttpResponseStats' : IDL.Tuple(HttpRequestId, ProxyResponseStats),
     'SetupProxyClient' : IDL.Null,
+    'SetupVersionedProxyClient' : ProxyClientSetup,
     'HttpResponse' : IDL.Tuple(HttpRequestId, HttpResponse),
+    'EncryptedHttpResponse' : IDL.Tuple(
+      HttpRequestId,
+      IDL.Vec(IDL.Nat8),
+      IDL.Opt(ResponseSignature),
+    ),
+    'ProtocolVersionNegotiated' : HttpOverWsProtocolVersion,
+    'EncryptedHttpRequest' : IDL.Tuple(HttpRequestId, EncryptedHttpRequest),
   });
   const CanisterWsMessageResult = IDL.Variant({
     'Ok' : IDL.Null,
@@ -117,10 +533,29 @@ export const idlFactory = ({ IDL }) => {
     'Err' : IDL.Text,
   });
   return IDL.Service({
-    'disconnect_all_proxies' : IDL.Func([], [], []),
-    'get_logs' : IDL.Func(
+    'cancel_scheduled_job' : IDL.Func(
+        [ScheduledJobId],
+        [ManageScheduledJobResult],
         [],
-        [IDL.Vec(IDL.Tuple(IDL.Text, IDL.Text))],
+      ),
+    'delete_my_dead_letters' : IDL.Func([IDL.Vec(HttpRequestId)], [], []),
+    'disconnect_all_proxies' : IDL.Func([], [], []),
+    'disconnect_proxy' : IDL.Func([IDL.Principal], [ManageProxyResult], []),
+    'drain_proxy' : IDL.Func([IDL.Principal], [ManageProxyResult], []),
+    'export_logs' : IDL.Func([ExportLogsArgs], [ExportLogsResult], ['query']),
+    'get_cache_stats' : IDL.Func([], [CacheStats], ['query']),
+    'get_config' : IDL.Func([], [ProxyConfig], ['query']),
+    'get_logger_config' : IDL.Func([], [LoggerConfig], ['query']),
+    'get_logs' : IDL.Func([GetLogsArgs], [GetLogsResult], ['query']),
+    'get_metrics' : IDL.Func([], [GetMetricsResult], ['query']),
+    'get_my_http_response' : IDL.Func(
+        [HttpRequestId],
+        [IDL.Opt(HttpResponseEnvelope)],
+        ['query'],
+      ),
+    'get_my_request' : IDL.Func(
+        [HttpRequestId],
+        [IDL.Opt(CanisterRequest)],
         ['query'],
       ),
     'get_request_by_id' : IDL.Func(
@@ -128,11 +563,93 @@ export const idlFactory = ({ IDL }) => {
         [IDL.Opt(CanisterRequest)],
         ['query'],
       ),
+    'get_request_trace' : IDL.Func(
+        [HttpRequestId],
+        [IDL.Opt(RequestTrace)],
+        ['query'],
+      ),
+    'get_response_attestation_config' : IDL.Func(
+        [],
+        [ResponseAttestationConfig],
+        ['query'],
+      ),
     'http_request' : IDL.Func(
         [HttpRequestEndpointArgs],
         [HttpRequestEndpointResult],
         [],
       ),
+    'http_request_batch' : IDL.Func(
+        [IDL.Vec(HttpRequestEndpointArgs), IDL.Opt(CanisterCallbackMethodName)],
+        [HttpRequestBatchEndpointResult],
+        [],
+      ),
+    'is_payload_encryption_required' : IDL.Func([], [IDL.Bool], ['query']),
+    'list_dead_letters' : IDL.Func(
+        [ListRequestsArgs],
+        [ListDeadLettersResult],
+        ['query'],
+      ),
+    'list_my_allowed_secrets' : IDL.Func([], [IDL.Vec(SecretName)], ['query']),
+    'list_my_dead_letters' : IDL.Func(
+        [ListRequestsArgs],
+        [ListDeadLettersResult],
+        ['query'],
+      ),
+    'list_my_requests' : IDL.Func(
+        [ListRequestsArgs],
+        [ListRequestsResult],
+        ['query'],
+      ),
+    'list_my_scheduled_jobs' : IDL.Func([], [IDL.Vec(ScheduledJob)], ['query']),
+    'list_my_signing_profiles' : IDL.Func(
+        [],
+        [IDL.Vec(SigningProfileInfo)],
+        ['query'],
+      ),
+    'list_my_webhooks' : IDL.Func([], [IDL.Vec(Webhook)], ['query']),
+    'list_proxies' : IDL.Func([], [IDL.Vec(ProxyInfo)], ['query']),
+    'pause_scheduled_job' : IDL.Func(
+        [ScheduledJobId],
+        [ManageScheduledJobResult],
+        [],
+      ),
+    'register_signing_profile' : IDL.Func(
+        [RegisterSigningProfileArgs],
+        [RegisterSigningProfileResult],
+        [],
+      ),
+    'register_webhook' : IDL.Func([Webhook], [RegisterWebhookResult], []),
+    'replay_dead_letters' : IDL.Func([IDL.Vec(HttpRequestId)], [], []),
+    'resume_scheduled_job' : IDL.Func(
+        [ScheduledJobId],
+        [ManageScheduledJobResult],
+        [],
+      ),
+    'schedule_http_request' : IDL.Func(
+        [ScheduleHttpRequestArgs],
+        [ScheduleHttpRequestResult],
+        [],
+      ),
+    'set_allowed_secrets' : IDL.Func(
+        [CanisterId, IDL.Vec(SecretName)],
+        [SetAllowedSecretsResult],
+        [],
+      ),
+    'set_logger_config' : IDL.Func([LoggerConfig], [], []),
+    'set_payload_encryption_required' : IDL.Func([IDL.Bool], [], []),
+    'set_proxy_attestation_key' : IDL.Func(
+        [IDL.Principal, IDL.Opt(IDL.Vec(IDL.Nat8))],
+        [ManageProxyResult],
+        [],
+      ),
+    'set_response_attestations_required' : IDL.Func([IDL.Bool], [], []),
+    'unregister_signing_profile' : IDL.Func(
+        [IDL.Opt(CanisterId), SigningProfileName],
+        [],
+        [],
+      ),
+    'unregister_webhook' : IDL.Func([WebhookPath], [], []),
+    'update_config' : IDL.Func([ProxyConfig], [UpdateConfigResult], []),
     'ws_close' : IDL.Func(
         [CanisterWsCloseArguments],
         [CanisterWsCloseResult],
@@ -151,4 +668,14 @@ export const idlFactory = ({ IDL }) => {
     'ws_open' : IDL.Func([CanisterWsOpenArguments], [CanisterWsOpenResult], []),
   });
 };
-export const init = ({ IDL }) => { return []; };
+export const init = ({ IDL }) => {
+  const HttpRequestTimeoutMs = IDL.Nat64;
+  const ProxyConfig = IDL.Record({
+    'max_http_headers_count' : IDL.Nat32,
+    'max_http_request_timeout_ms' : HttpRequestTimeoutMs,
+    'default_http_request_timeout_ms' : HttpRequestTimeoutMs,
+    'min_http_request_timeout_ms' : HttpRequestTimeoutMs,
+    'max_callback_delivery_attempts' : IDL.Nat32,
+  });
+  return [IDL.Opt(ProxyConfig)];
+};
@@ -0,0 +1,28 @@
+import type { HttpMethod } from "./canister/declarations/proxy_canister/proxy_canister.did";
+
+/**
+ * The methods that have their own variant in the candid HttpMethod
+ */
+export const STANDARD_HTTP_METHODS = ["GET", "POST", "PUT", "HEAD", "DELETE", "PATCH", "OPTIONS"];
+
+/**
+ * Returns the name of the method, e.g. `GET` or the name of a custom method
+ */
+export const httpMethodName = (method: HttpMethod): string => {
+  if ("Other" in method) {
+    return method.Other;
+  }
+
+  return Object.keys(method)[0]; // workaround to get the candid enum
+};
+
+/**
+ * Returns the candid HttpMethod for the method name
+ */
+export const httpMethod = (name: string): HttpMethod => {
+  if (STANDARD_HTTP_METHODS.includes(name)) {
+    return { [name]: null } as HttpMethod;
+  }
+
+  return { Other: name };
+};
@@ -0,0 +1,51 @@
+import type { ProxyError } from "./canister/declarations/proxy_canister/proxy_canister.did";
+
+const DNS_ERROR_CODES = ["ENOTFOUND", "EAI_AGAIN", "DNSException"];
+const CONNECTION_REFUSED_ERROR_CODES = ["ConnectionRefused", "ECONNREFUSED", "FailedToOpenSocket", "ECONNRESET"];
+const TIMEOUT_ERROR_CODES = ["ETIMEDOUT", "UND_ERR_CONNECT_TIMEOUT", "UND_ERR_HEADERS_TIMEOUT"];
+
+/**
+ * Raised when the request can't be executed by this proxy, e.g. because it references an unknown secret
+ */
+export class RejectedByPolicyError extends Error { }
+
+/**
+ * Raised when the response body can't be read
+ */
+export class BodyReadError extends Error { }
+
+/**
+ * Maps an error raised while executing a request to the ProxyError reported to the canister,
+ * so that it can decide whether to retry the request with another proxy
+ */
+export const toProxyError = (e: unknown): ProxyError => {
+  const message: [] | [string] = [String(e)];
+
+  if (e instanceof RejectedByPolicyError) {
+    return { RejectedByPolicy: message };
+  }
+  if (e instanceof BodyReadError) {
+    return { BodyReadError: message };
+  }
+
+  const error = e as { name?: string, code?: string, cause?: { code?: string } } | null;
+  if (error?.name === "TimeoutError" || error?.name === "AbortError") {
+    return { UpstreamTimeout: message };
+  }
+
+  const code = error?.code || error?.cause?.code || "";
+  if (DNS_ERROR_CODES.includes(code)) {
+    return { DnsResolutionFailed: message };
+  }
+  if (CONNECTION_REFUSED_ERROR_CODES.includes(code)) {
+    return { ConnectionRefused: message };
+  }
+  if (TIMEOUT_ERROR_CODES.includes(code)) {
+    return { UpstreamTimeout: message };
+  }
+  if (code.startsWith("ERR_TLS") || code.includes("CERT") || code.includes("SSL")) {
+    return { TlsError: message };
+  }
+
+  return { Unknown: message };
+};
@@ -0,0 +1,38 @@
+import { RejectedByPolicyError } from "./proxyErrors";
+
+/**
+ * Same as the placeholders of the http_over_ws library: `{{secret:<name>}}`
+ */
+const SECRET_PLACEHOLDER_REGEX = /\{\{secret:([A-Za-z0-9_.-]+)\}\}/g;
+
+/**
+ * Parses the secrets configured on the proxy, as a JSON object of names to values
+ */
+export const loadSecrets = (json: string | undefined): Map<string, string> => {
+  if (!json) {
+    return new Map();
+  }
+
+  const secrets = JSON.parse(json);
+  if (typeof secrets !== "object" || secrets === null || Array.isArray(secrets)) {
+    throw new Error("the secrets must be a JSON object of names to values");
+  }
+
+  return new Map(
+    Object.entries(secrets).map(([name, value]) => [name, String(value)] as [string, string])
+  );
+};
+
+/**
+ * Replaces the secret placeholders with the values of the secrets.
+ * The canister only assigns to this proxy the requests referencing the secrets it announced.
+ */
+export const substituteSecrets = (value: string, secrets: Map<string, string>): string => {
+  return value.replace(SECRET_PLACEHOLDER_REGEX, (_, name: string) => {
+    const secret = secrets.get(name);
+    if (secret === undefined) {
+      throw new RejectedByPolicyError(`unknown secret: ${name}`);
+    }
+    return secret;
+  });
+};
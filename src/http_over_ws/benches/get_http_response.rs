@@ -0,0 +1,63 @@
+//! Reports the instructions taken by `get_http_response` as the stored connections grow.
+//!
+//! Run it with `./scripts/bench.sh`.
+
+use std::path::PathBuf;
+
+use http_over_ws::{GetHttpResponseResult, HttpMethod, HttpRequest, HttpRequestId};
+use test_utils::{
+    ic_env::{get_test_env, load_canister_wasm_from_path, CanisterData},
+    identity::generate_random_principal,
+    proxy_client::ProxyClient,
+};
+
+/// The connections are stored in batches to stay within the instructions limit of a single call.
+const STORE_CONNECTIONS_BATCH_SIZE: u32 = 10_000;
+
+const STORED_CONNECTIONS_COUNTS: [u32; 4] = [1, 1_000, 10_000, 100_000];
+
+fn main() {
+    let wasm_module = load_canister_wasm_from_path(&PathBuf::from(
+        std::env::var("TEST_CANISTER_WASM_PATH").expect("TEST_CANISTER_WASM_PATH must be set"),
+    ));
+
+    let mut test_env = get_test_env();
+    let canister_id = test_env.add_canister(CanisterData {
+        wasm_module,
+        args: vec![],
+        controller: None,
+    });
+    let caller = generate_random_principal();
+
+    let mut proxy_client = ProxyClient::new(&test_env, canister_id);
+    proxy_client.setup_proxy();
+
+    let request = HttpRequest::new("https://example.com/", HttpMethod::GET, vec![], None);
+
+    println!("{:>12} {:>14}", "connections", "instructions");
+
+    let mut stored_connections = 0;
+    for count in STORED_CONNECTIONS_COUNTS {
+        let mut last_request_id: HttpRequestId = 0;
+        while stored_connections < count {
+            let batch_size = STORE_CONNECTIONS_BATCH_SIZE.min(count - stored_connections);
+            last_request_id = test_env.call_canister_method_with_panic(
+                canister_id,
+                caller,
+                "store_http_connections",
+                (request.clone(), batch_size),
+            );
+            stored_connections += batch_size;
+        }
+
+        let (_, instructions): (GetHttpResponseResult, u64) = test_env
+            .query_canister_method_with_panic(
+                canister_id,
+                caller,
+                "get_http_response_with_instructions",
+                (last_request_id,),
+            );
+
+        println!("{:>12} {:>14}", stored_connections, instructions);
+    }
+}
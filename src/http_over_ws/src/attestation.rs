@@ -0,0 +1,119 @@
+use candid::{CandidType, Deserialize, Principal};
+use ed25519_consensus::{Signature, VerificationKey};
+use sha2::{Digest, Sha256};
+
+use crate::{HttpHeader, HttpRequest, HttpRequestId, HttpResponse};
+
+/// Prefixes the signed digests, so that the signatures can't be confused
+/// with the ones made by the same key for other purposes.
+pub const RESPONSE_ATTESTATION_DOMAIN_SEPARATOR: &[u8] = b"ic-http-proxy-response-attestation";
+
+/// How far the timestamp of an attestation can be from the canister time.
+pub const MAX_RESPONSE_ATTESTATION_CLOCK_SKEW_NS: u64 = 5 * 60 * 1_000_000_000;
+
+/// Sent by a proxy along with a response, see [HttpOverWsMessage::AttestedHttpResponse](crate::HttpOverWsMessage::AttestedHttpResponse).
+#[derive(CandidType, Clone, Debug, Deserialize, PartialEq, Eq)]
+pub struct ResponseSignature {
+    /// The time at which the proxy received the response, in nanoseconds since the epoch.
+    pub timestamp_ns: u64,
+    /// The Ed25519 signature of the [response_attestation_digest].
+    pub signature: Vec<u8>,
+}
+
+/// Proves which proxy vouched for a response. Stored along with the response once verified.
+#[derive(CandidType, Clone, Debug, Deserialize, PartialEq, Eq)]
+pub struct ResponseAttestation {
+    pub proxy_principal: Principal,
+    /// The Ed25519 public key registered for the proxy when the response was received.
+    pub public_key: Vec<u8>,
+    pub timestamp_ns: u64,
+    pub signature: Vec<u8>,
+}
+
+impl ResponseAttestation {
+    /// Checks that the attestation has been made for the given request and response,
+    /// so that it can be verified again outside of the canister.
+    pub fn verify(
+        &self,
+        request_id: HttpRequestId,
+        request: &HttpRequest,
+        response: &HttpResponse,
+    ) -> Result<(), String> {
+        let public_key =
+            VerificationKey::try_from(self.public_key.as_slice()).map_err(|e| e.to_string())?;
+        let signature =
+            Signature::try_from(self.signature.as_slice()).map_err(|e| e.to_string())?;
+        let digest = response_attestation_digest(request_id, request, response, self.timestamp_ns);
+
+        public_key
+            .verify(&signature, &digest)
+            .map_err(|e| e.to_string())
+    }
+}
+
+/// The keys with which the proxies attest the responses.
+#[derive(CandidType, Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+pub struct ResponseAttestationConfig {
+    /// Whether the responses of the proxies without a registered key are rejected.
+    pub required: bool,
+    /// The Ed25519 public key registered for each proxy.
+    /// The responses of these proxies are rejected if not attested.
+    pub proxy_public_keys: Vec<(Principal, Vec<u8>)>,
+}
+
+pub(crate) fn is_valid_public_key(public_key: &[u8]) -> bool {
+    VerificationKey::try_from(public_key).is_ok()
+}
+
+/// The SHA-256 digest that a proxy signs to attest a response.
+///
+/// It is computed over [RESPONSE_ATTESTATION_DOMAIN_SEPARATOR], the request id, the [request_hash],
+/// the status, the headers, the SHA-256 hash of the body and the timestamp.
+/// Integers are encoded as 8 bytes big endian and the variable length fields are prefixed by their length,
+/// so that proxies written in any language can compute it.
+pub fn response_attestation_digest(
+    request_id: HttpRequestId,
+    request: &HttpRequest,
+    response: &HttpResponse,
+    timestamp_ns: u64,
+) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(RESPONSE_ATTESTATION_DOMAIN_SEPARATOR);
+    hasher.update(request_id.to_be_bytes());
+    hasher.update(request_hash(request));
+    update_with_bytes(&mut hasher, response.status.0.to_string().as_bytes());
+    update_with_headers(&mut hasher, &response.headers);
+    hasher.update(Sha256::digest(&response.body));
+    hasher.update(timestamp_ns.to_be_bytes());
+    hasher.finalize().into()
+}
+
+/// The SHA-256 hash of the method, url, headers and body of the request.
+/// A missing body is encoded as a single 0 byte, a present one as a 1 byte followed by the body.
+pub fn request_hash(request: &HttpRequest) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    update_with_bytes(&mut hasher, request.method.as_str().as_bytes());
+    update_with_bytes(&mut hasher, request.url.as_bytes());
+    update_with_headers(&mut hasher, &request.headers);
+    match &request.body {
+        None => hasher.update([0]),
+        Some(body) => {
+            hasher.update([1]);
+            update_with_bytes(&mut hasher, body);
+        }
+    }
+    hasher.finalize().into()
+}
+
+fn update_with_bytes(hasher: &mut Sha256, bytes: &[u8]) {
+    hasher.update((bytes.len() as u64).to_be_bytes());
+    hasher.update(bytes);
+}
+
+fn update_with_headers(hasher: &mut Sha256, headers: &[HttpHeader]) {
+    hasher.update((headers.len() as u64).to_be_bytes());
+    for header in headers {
+        update_with_bytes(hasher, header.name.as_bytes());
+        update_with_bytes(hasher, header.value.as_bytes());
+    }
+}
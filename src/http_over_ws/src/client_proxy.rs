@@ -1,20 +1,144 @@
 use crate::{
     http_connection::{HttpConnection, HttpRequestId},
-    HttpOverWsError,
+    metrics::{ClientProxyMetrics, ProxyMetrics},
+    protocol::{HttpOverWsProtocolVersion, ProxyCapabilities},
+    secrets::SecretName,
+    HttpMethod, HttpOverWsError, HttpResult,
 };
-use std::collections::BTreeMap;
+use candid::{CandidType, Deserialize, Principal};
+use ic_cdk::api::time;
+use std::collections::{BTreeSet, HashMap};
+
+/// The amount of consecutive failed requests after which a proxy is considered unhealthy.
+pub const UNHEALTHY_PROXY_CONSECUTIVE_FAILURES: u64 = 3;
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum ProxyHealth {
+    /// No request has completed yet.
+    Unknown,
+    Healthy,
+    /// The last [UNHEALTHY_PROXY_CONSECUTIVE_FAILURES] requests failed.
+    Unhealthy,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ProxyInfo {
+    pub proxy_principal: Principal,
+    pub connected_at_ns: u64,
+    /// Whether the proxy is being drained, in which case no new request is assigned to it.
+    pub draining: bool,
+    pub in_flight_requests: u64,
+    pub completed_requests: u64,
+    pub health: ProxyHealth,
+    pub protocol_version: HttpOverWsProtocolVersion,
+    pub capabilities: ProxyCapabilities,
+}
 
 pub(crate) struct ClientProxy {
-    connections: BTreeMap<HttpRequestId, HttpConnection>,
+    connections: HashMap<HttpRequestId, HttpConnection>,
+    metrics: ClientProxyMetrics,
+    connected_at_ns: u64,
+    draining: bool,
+    consecutive_failures: u64,
+    protocol_version: HttpOverWsProtocolVersion,
+    capabilities: ProxyCapabilities,
+    /// The verified X25519 public key announced by the proxy, if any.
+    encryption_public_key: Option<[u8; 32]>,
 }
 
 impl ClientProxy {
-    pub(crate) fn new() -> Self {
+    pub(crate) fn new(
+        protocol_version: HttpOverWsProtocolVersion,
+        capabilities: ProxyCapabilities,
+        encryption_public_key: Option<[u8; 32]>,
+    ) -> Self {
         ClientProxy {
-            connections: BTreeMap::new(),
+            connections: HashMap::new(),
+            metrics: ClientProxyMetrics::new(),
+            connected_at_ns: time(),
+            draining: false,
+            consecutive_failures: 0,
+            protocol_version,
+            capabilities,
+            encryption_public_key,
         }
     }
 
+    pub(crate) fn supports_method(&self, method: &HttpMethod) -> bool {
+        self.capabilities.http_methods.contains(method)
+    }
+
+    pub(crate) fn holds_secrets(&self, secret_names: &BTreeSet<SecretName>) -> bool {
+        let held_secret_names = self
+            .capabilities
+            .secret_names
+            .as_deref()
+            .unwrap_or_default();
+
+        secret_names
+            .iter()
+            .all(|secret_name| held_secret_names.contains(secret_name))
+    }
+
+    pub(crate) fn encryption_public_key(&self) -> Option<&[u8; 32]> {
+        self.encryption_public_key.as_ref()
+    }
+
+    pub(crate) fn protocol_version(&self) -> HttpOverWsProtocolVersion {
+        self.protocol_version
+    }
+
+    pub(crate) fn is_draining(&self) -> bool {
+        self.draining
+    }
+
+    pub(crate) fn start_draining(&mut self) {
+        self.draining = true;
+    }
+
+    pub(crate) fn record_request_completed(&mut self, http_result: &HttpResult, latency_ms: u64) {
+        match http_result {
+            HttpResult::Success(_) => self.consecutive_failures = 0,
+            HttpResult::Failure(_) => self.consecutive_failures += 1,
+        }
+        self.metrics
+            .record_request_completed(http_result, latency_ms);
+    }
+
+    pub(crate) fn get_info(&self, proxy_principal: Principal) -> ProxyInfo {
+        let metrics = self.get_metrics(proxy_principal);
+        let completed_requests = metrics.succeeded_requests + metrics.failed_requests;
+        let health = if completed_requests == 0 {
+            ProxyHealth::Unknown
+        } else if self.consecutive_failures >= UNHEALTHY_PROXY_CONSECUTIVE_FAILURES {
+            ProxyHealth::Unhealthy
+        } else {
+            ProxyHealth::Healthy
+        };
+
+        ProxyInfo {
+            proxy_principal,
+            connected_at_ns: self.connected_at_ns,
+            draining: self.draining,
+            in_flight_requests: metrics.in_flight_requests,
+            completed_requests,
+            health,
+            protocol_version: self.protocol_version,
+            capabilities: self.capabilities.clone(),
+        }
+    }
+
+    pub(crate) fn get_metrics(&self, proxy_principal: Principal) -> ProxyMetrics {
+        let in_flight_requests = self
+            .connections
+            .values()
+            .filter(|connection| connection.is_waiting_for_response())
+            .count();
+
+        self.metrics
+            .to_proxy_metrics(proxy_principal, in_flight_requests as u64)
+    }
+
     pub(crate) fn assign_connection(
         &mut self,
         request_id: HttpRequestId,
@@ -32,10 +156,18 @@ impl ClientProxy {
             .ok_or(HttpOverWsError::RequestIdNotFound)
     }
 
-    pub(crate) fn get_connections(&self) -> &BTreeMap<HttpRequestId, HttpConnection> {
+    pub(crate) fn get_connection(&self, request_id: HttpRequestId) -> Option<&HttpConnection> {
+        self.connections.get(&request_id)
+    }
+
+    pub(crate) fn get_connections(&self) -> &HashMap<HttpRequestId, HttpConnection> {
         &self.connections
     }
 
+    pub(crate) fn into_connections(self) -> HashMap<HttpRequestId, HttpConnection> {
+        self.connections
+    }
+
     pub(crate) fn remove_connection(
         &mut self,
         request_id: HttpRequestId,
@@ -0,0 +1,297 @@
+use candid::{decode_one, encode_one, CandidType, Deserialize, Principal};
+use chacha20poly1305::{
+    aead::{Aead, KeyInit, Payload},
+    ChaCha20Poly1305, Nonce,
+};
+use ed25519_consensus::{Signature, VerificationKey};
+use hkdf::Hkdf;
+use sha2::{Digest, Sha256};
+use x25519_dalek::{PublicKey, StaticSecret};
+
+use crate::{HttpRequest, HttpRequestId, HttpResponse};
+
+/// Used to derive the keys that encrypt the payloads of the requests and of the responses.
+pub const PAYLOAD_ENCRYPTION_DOMAIN_SEPARATOR: &[u8] = b"ic-http-proxy-payload-encryption";
+
+/// Prefixes the digests signed by the proxies to announce their encryption keys.
+pub const PROXY_ENCRYPTION_KEY_DOMAIN_SEPARATOR: &[u8] = b"ic-http-proxy-encryption-key";
+
+// each key encrypts exactly one request and one response
+const REQUEST_NONCE_DIRECTION: u8 = 0;
+const RESPONSE_NONCE_DIRECTION: u8 = 1;
+
+/// The X25519 key with which a proxy decrypts the requests and encrypts the responses,
+/// announced in its [ProxyClientSetup](crate::ProxyClientSetup).
+#[derive(CandidType, Clone, Debug, Deserialize, PartialEq, Eq)]
+pub struct ProxyEncryptionKey {
+    /// The X25519 public key.
+    pub public_key: Vec<u8>,
+    /// The Ed25519 signature of the [proxy_encryption_key_digest],
+    /// made with the attestation key registered for the proxy.
+    pub signature: Vec<u8>,
+}
+
+impl ProxyEncryptionKey {
+    /// Checks that the key has been announced by the proxy owning the given attestation key,
+    /// so that the WS gateway can't substitute its own key.
+    pub(crate) fn verify(
+        &self,
+        proxy_principal: &Principal,
+        attestation_key: &[u8],
+    ) -> Result<[u8; 32], String> {
+        let public_key: [u8; 32] = self
+            .public_key
+            .as_slice()
+            .try_into()
+            .map_err(|_| "the X25519 public key must be 32 bytes long".to_string())?;
+        let attestation_key =
+            VerificationKey::try_from(attestation_key).map_err(|e| e.to_string())?;
+        let signature =
+            Signature::try_from(self.signature.as_slice()).map_err(|e| e.to_string())?;
+
+        attestation_key
+            .verify(
+                &signature,
+                &proxy_encryption_key_digest(proxy_principal, &public_key),
+            )
+            .map_err(|e| e.to_string())?;
+
+        Ok(public_key)
+    }
+}
+
+/// The request encrypted for a proxy, see [HttpOverWsMessage::EncryptedHttpRequest](crate::HttpOverWsMessage::EncryptedHttpRequest).
+#[derive(CandidType, Clone, Debug, Deserialize, PartialEq, Eq)]
+pub struct EncryptedHttpRequest {
+    /// The X25519 public key generated by the canister for this request and hop only.
+    pub ephemeral_public_key: Vec<u8>,
+    /// The index of the hop when the canister follows redirects, 0 for the first request.
+    /// It is mixed into the key and the nonce, so that no two hops share them.
+    pub hop: u32,
+    /// The Candid encoded [HttpRequest], encrypted with ChaCha20-Poly1305.
+    pub ciphertext: Vec<u8>,
+}
+
+/// The SHA-256 digest that a proxy signs to announce its encryption key.
+///
+/// It is computed over [PROXY_ENCRYPTION_KEY_DOMAIN_SEPARATOR], the principal of the proxy and the X25519 public key,
+/// each prefixed by its length encoded as 8 bytes big endian.
+pub fn proxy_encryption_key_digest(proxy_principal: &Principal, public_key: &[u8]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(PROXY_ENCRYPTION_KEY_DOMAIN_SEPARATOR);
+    for bytes in [proxy_principal.as_slice(), public_key] {
+        hasher.update((bytes.len() as u64).to_be_bytes());
+        hasher.update(bytes);
+    }
+    hasher.finalize().into()
+}
+
+/// Returns the X25519 public key of the given secret key.
+pub fn encryption_public_key(secret_key: &[u8; 32]) -> [u8; 32] {
+    PublicKey::from(&StaticSecret::from(*secret_key)).to_bytes()
+}
+
+/// Encrypts the request for the proxy with a key that only the canister and the proxy can derive.
+pub(crate) fn encrypt_http_request(
+    seed: &[u8; 32],
+    request_id: HttpRequestId,
+    hop: u32,
+    proxy_public_key: &[u8; 32],
+    request: &HttpRequest,
+) -> EncryptedHttpRequest {
+    let (ephemeral_public_key, cipher) =
+        canister_payload_cipher(seed, request_id, hop, proxy_public_key);
+
+    EncryptedHttpRequest {
+        ephemeral_public_key: ephemeral_public_key.to_vec(),
+        hop,
+        ciphertext: encrypt(
+            &cipher,
+            &nonce(hop, REQUEST_NONCE_DIRECTION),
+            request_id,
+            request,
+        ),
+    }
+}
+
+/// Decrypts the response sent by the proxy for a request encrypted with [encrypt_http_request].
+pub(crate) fn decrypt_http_response(
+    seed: &[u8; 32],
+    request_id: HttpRequestId,
+    hop: u32,
+    proxy_public_key: &[u8; 32],
+    ciphertext: &[u8],
+) -> Result<HttpResponse, String> {
+    let (_, cipher) = canister_payload_cipher(seed, request_id, hop, proxy_public_key);
+
+    decrypt(
+        &cipher,
+        &nonce(hop, RESPONSE_NONCE_DIRECTION),
+        request_id,
+        ciphertext,
+    )
+}
+
+/// Decrypts a request received by a proxy, given the X25519 secret key of the proxy.
+pub fn decrypt_http_request(
+    proxy_secret_key: &[u8; 32],
+    request_id: HttpRequestId,
+    encrypted_request: &EncryptedHttpRequest,
+) -> Result<HttpRequest, String> {
+    let cipher = proxy_payload_cipher(proxy_secret_key, encrypted_request, request_id)?;
+
+    decrypt(
+        &cipher,
+        &nonce(encrypted_request.hop, REQUEST_NONCE_DIRECTION),
+        request_id,
+        &encrypted_request.ciphertext,
+    )
+}
+
+/// Encrypts the response of a request decrypted with [decrypt_http_request],
+/// to be sent in an [HttpOverWsMessage::EncryptedHttpResponse](crate::HttpOverWsMessage::EncryptedHttpResponse).
+pub fn encrypt_http_response(
+    proxy_secret_key: &[u8; 32],
+    request_id: HttpRequestId,
+    encrypted_request: &EncryptedHttpRequest,
+    response: &HttpResponse,
+) -> Result<Vec<u8>, String> {
+    let cipher = proxy_payload_cipher(proxy_secret_key, encrypted_request, request_id)?;
+
+    Ok(encrypt(
+        &cipher,
+        &nonce(encrypted_request.hop, RESPONSE_NONCE_DIRECTION),
+        request_id,
+        response,
+    ))
+}
+
+fn canister_payload_cipher(
+    seed: &[u8; 32],
+    request_id: HttpRequestId,
+    hop: u32,
+    proxy_public_key: &[u8; 32],
+) -> ([u8; 32], ChaCha20Poly1305) {
+    let ephemeral_secret = ephemeral_secret(seed, request_id, hop);
+    let ephemeral_public_key = PublicKey::from(&ephemeral_secret).to_bytes();
+    let shared_secret = ephemeral_secret.diffie_hellman(&PublicKey::from(*proxy_public_key));
+
+    (
+        ephemeral_public_key,
+        payload_cipher(
+            shared_secret.as_bytes(),
+            &ephemeral_public_key,
+            proxy_public_key,
+            request_id,
+            hop,
+        ),
+    )
+}
+
+/// The secret key of the canister for the hop of the request, derived from the seed,
+/// the request id and the hop, which is never reused thanks to the request ids being unique.
+fn ephemeral_secret(seed: &[u8; 32], request_id: HttpRequestId, hop: u32) -> StaticSecret {
+    let mut secret = [0; 32];
+    Hkdf::<Sha256>::new(Some(PAYLOAD_ENCRYPTION_DOMAIN_SEPARATOR), seed)
+        .expand(
+            &[request_id.to_be_bytes().as_slice(), &hop.to_be_bytes()].concat(),
+            &mut secret,
+        )
+        .expect("32 bytes is a valid HKDF-SHA256 output length");
+    StaticSecret::from(secret)
+}
+
+fn proxy_payload_cipher(
+    proxy_secret_key: &[u8; 32],
+    encrypted_request: &EncryptedHttpRequest,
+    request_id: HttpRequestId,
+) -> Result<ChaCha20Poly1305, String> {
+    let ephemeral_public_key: [u8; 32] = encrypted_request
+        .ephemeral_public_key
+        .as_slice()
+        .try_into()
+        .map_err(|_| "the ephemeral public key must be 32 bytes long".to_string())?;
+
+    let shared_secret = StaticSecret::from(*proxy_secret_key)
+        .diffie_hellman(&PublicKey::from(ephemeral_public_key));
+
+    Ok(payload_cipher(
+        shared_secret.as_bytes(),
+        &ephemeral_public_key,
+        &encryption_public_key(proxy_secret_key),
+        request_id,
+        encrypted_request.hop,
+    ))
+}
+
+/// Derives the key with HKDF-SHA256 from the X25519 shared secret,
+/// salted with the ephemeral public key followed by the public key of the proxy.
+fn payload_cipher(
+    shared_secret: &[u8; 32],
+    ephemeral_public_key: &[u8; 32],
+    proxy_public_key: &[u8; 32],
+    request_id: HttpRequestId,
+    hop: u32,
+) -> ChaCha20Poly1305 {
+    let salt = [ephemeral_public_key.as_slice(), proxy_public_key.as_slice()].concat();
+
+    let mut key = [0; 32];
+    Hkdf::<Sha256>::new(Some(&salt), shared_secret)
+        .expand(
+            &[
+                PAYLOAD_ENCRYPTION_DOMAIN_SEPARATOR,
+                &request_id.to_be_bytes(),
+                &hop.to_be_bytes(),
+            ]
+            .concat(),
+            &mut key,
+        )
+        .expect("32 bytes is a valid HKDF-SHA256 output length");
+    ChaCha20Poly1305::new(&key.into())
+}
+
+/// The hop encoded as 4 bytes big endian, followed by zeros and the direction of the payload.
+fn nonce(hop: u32, direction: u8) -> [u8; 12] {
+    let mut nonce = [0; 12];
+    nonce[..4].copy_from_slice(&hop.to_be_bytes());
+    nonce[11] = direction;
+    nonce
+}
+
+fn encrypt<T: CandidType>(
+    cipher: &ChaCha20Poly1305,
+    nonce: &[u8; 12],
+    request_id: HttpRequestId,
+    value: &T,
+) -> Vec<u8> {
+    let plaintext = encode_one(value).expect("payloads can be encoded");
+
+    cipher
+        .encrypt(
+            Nonce::from_slice(nonce),
+            Payload {
+                msg: &plaintext,
+                aad: &request_id.to_be_bytes(),
+            },
+        )
+        .expect("payloads can be encrypted")
+}
+
+fn decrypt<T: CandidType + for<'de> Deserialize<'de>>(
+    cipher: &ChaCha20Poly1305,
+    nonce: &[u8; 12],
+    request_id: HttpRequestId,
+    ciphertext: &[u8],
+) -> Result<T, String> {
+    let plaintext = cipher
+        .decrypt(
+            Nonce::from_slice(nonce),
+            Payload {
+                msg: ciphertext,
+                aad: &request_id.to_be_bytes(),
+            },
+        )
+        .map_err(|_| "the payload can't be decrypted".to_string())?;
+
+    decode_one(&plaintext).map_err(|e| e.to_string())
+}
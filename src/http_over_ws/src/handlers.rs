@@ -1,6 +1,21 @@
-use crate::{http_connection::*, state::STATE};
-use candid::Principal;
-use logger::log;
+use crate::{
+    attestation::{
+        is_valid_public_key, ResponseAttestation, ResponseAttestationConfig, ResponseSignature,
+    },
+    client_proxy::ProxyInfo,
+    encryption::ProxyEncryptionKey,
+    http_connection::*,
+    metrics::HttpOverWsMetrics,
+    protocol::{
+        decode_message, encode_message, negotiate_protocol_version, HttpOverWsProtocolVersion,
+        ProxyCapabilities, HTTP_OVER_WS_PROTOCOL_V1, SUPPORTED_PROTOCOL_VERSIONS,
+    },
+    redirects::{proxy_request, RedirectValidator},
+    state::STATE,
+};
+use candid::{Nat, Principal};
+use ic_cdk::api::time;
+use logger::{debug, error, log, warn};
 
 /// Called by the callback passed to the IC WS cdk when a new message is received.
 /// Checks if the message is an [HttpOverWsMessage], and if so it handles it.
@@ -8,60 +23,389 @@ use logger::log;
 pub fn try_handle_http_over_ws_message(
     proxy_principal: Principal,
     serialized_message: Vec<u8>,
+    ws_send: fn(Principal, Vec<u8>) -> Result<(), String>,
 ) -> Result<(), HttpOverWsError> {
-    let incoming_msg = HttpOverWsMessage::from_bytes(&serialized_message)
-        .map_err(|e| HttpOverWsError::NotHttpOverWsType(e))?;
+    let (incoming_msg_version, incoming_msg) =
+        decode_message(&serialized_message).map_err(|e| HttpOverWsError::NotHttpOverWsType(e))?;
 
     match incoming_msg {
         HttpOverWsMessage::SetupProxyClient => {
-            STATE.with(|state| {
-                state.borrow_mut().add_proxy(proxy_principal);
-            });
-            log!("http_over_ws: client proxy {} connected", proxy_principal);
+            setup_proxy_client(
+                proxy_principal,
+                &[HTTP_OVER_WS_PROTOCOL_V1],
+                ProxyCapabilities::default(),
+                None,
+                None,
+                ws_send,
+            );
+        }
+        HttpOverWsMessage::SetupVersionedProxyClient(setup) => {
+            setup_proxy_client(
+                proxy_principal,
+                &setup.supported_protocol_versions,
+                setup.capabilities.unwrap_or_default(),
+                setup.encryption_key,
+                Some(incoming_msg_version),
+                ws_send,
+            );
+        }
+        HttpOverWsMessage::ProtocolVersionNegotiated(_) => {
+            warn!(
+                { proxy_principal: proxy_principal },
+                "http_over_ws: unexpected protocol version from client proxy {}",
+                proxy_principal
+            );
         }
         HttpOverWsMessage::HttpResponse(request_id, response) => {
-            handle_http_result(proxy_principal, request_id, HttpResult::Success(response));
+            handle_plaintext_http_response(proxy_principal, request_id, response, None, ws_send);
+        }
+        HttpOverWsMessage::AttestedHttpResponse(request_id, response, signature) => {
+            handle_plaintext_http_response(
+                proxy_principal,
+                request_id,
+                response,
+                Some(signature),
+                ws_send,
+            );
+        }
+        HttpOverWsMessage::EncryptedHttpResponse(request_id, ciphertext, signature) => {
+            let response = STATE.with(|state| {
+                state
+                    .borrow()
+                    .decrypt_http_response(&proxy_principal, request_id, &ciphertext)
+            });
+
+            match response {
+                Ok(response) => {
+                    handle_http_response(proxy_principal, request_id, response, signature, ws_send)
+                }
+                Err(e) => reject_encrypted_http_response(proxy_principal, request_id, e, ws_send),
+            }
         }
-        HttpOverWsMessage::Error(request_id, err) => {
+        HttpOverWsMessage::HttpResponseStats(request_id, stats) => {
+            if let Err(e) = STATE.with(|state| {
+                state
+                    .borrow_mut()
+                    .set_proxy_response_stats(proxy_principal, request_id, stats)
+            }) {
+                warn!(
+                    { request_id: request_id, proxy_principal: proxy_principal },
+                    "http_over_ws: error {:?} while storing stats for request with id: {} from client proxy {}",
+                    e,
+                    request_id,
+                    proxy_principal
+                );
+            }
+        }
+        HttpOverWsMessage::EncryptedHttpRequest(request_id, _) => {
+            warn!(
+                { proxy_principal: proxy_principal },
+                "http_over_ws: unexpected encrypted request with id: {} from client proxy {}",
+                request_id,
+                proxy_principal
+            );
+        }
+        HttpOverWsMessage::Error(request_id, err, proxy_error) => {
             if let Some(request_id) = request_id {
+                let proxy_error = proxy_error.unwrap_or(ProxyError::Unknown(Some(err)));
                 handle_http_result(
                     proxy_principal,
                     request_id,
-                    HttpResult::Failure(HttpFailureReason::ProxyError(err)),
+                    HttpResult::Failure(HttpFailureReason::ProxyError(proxy_error)),
+                    None,
+                    ws_send,
                 );
             } else {
-                log!("http_over_ws: incoming error: {}", err);
+                warn!({ proxy_principal: proxy_principal }, "http_over_ws: incoming error: {}", err);
             }
         }
-        HttpOverWsMessage::HttpRequest(_, _) => {
-            log!("http_over_ws: client proxy is not allowed to send HTTP connections over WS");
+        HttpOverWsMessage::HttpRequest(request_id, request) => {
+            handle_webhook_request(proxy_principal, request_id, request, ws_send);
         }
     };
     Ok(())
 }
 
+/// Sets up the proxy with the newest protocol version both sides can speak.
+///
+/// The proxies that negotiate the version get the result of the negotiation,
+/// encoded in the format of their setup message since they can't know the negotiated version yet.
+/// The proxies that don't only get an error in case none of their versions is supported anymore.
+fn setup_proxy_client(
+    proxy_principal: Principal,
+    proxy_supported_versions: &[HttpOverWsProtocolVersion],
+    capabilities: ProxyCapabilities,
+    encryption_key: Option<ProxyEncryptionKey>,
+    setup_msg_version: Option<HttpOverWsProtocolVersion>,
+    ws_send: fn(Principal, Vec<u8>) -> Result<(), String>,
+) {
+    let min_version = STATE.with(|state| state.borrow().min_protocol_version());
+    let encryption_public_key = encryption_key
+        .map(|encryption_key| {
+            STATE.with(|state| {
+                state
+                    .borrow()
+                    .verify_proxy_encryption_key(proxy_principal, &encryption_key)
+            })
+        })
+        .transpose();
+
+    let reply = match (
+        negotiate_protocol_version(proxy_supported_versions, min_version),
+        encryption_public_key,
+    ) {
+        (Some(_), Err(e)) => {
+            warn!(
+                { proxy_principal: proxy_principal },
+                "http_over_ws: client proxy {} announced an invalid encryption key: {}",
+                proxy_principal,
+                e
+            );
+
+            Some(HttpOverWsMessage::Error(
+                None,
+                format!("invalid encryption key: {}", e),
+                None,
+            ))
+        }
+        (Some(version), Ok(encryption_public_key)) => {
+            STATE.with(|state| {
+                state.borrow_mut().add_proxy(
+                    proxy_principal,
+                    version,
+                    capabilities,
+                    encryption_public_key,
+                )
+            });
+            log!(
+                { proxy_principal: proxy_principal },
+                "http_over_ws: client proxy {} connected with protocol version {}",
+                proxy_principal,
+                version
+            );
+
+            setup_msg_version.map(|_| HttpOverWsMessage::ProtocolVersionNegotiated(version))
+        }
+        (None, _) => {
+            let canister_supported_versions: Vec<_> = SUPPORTED_PROTOCOL_VERSIONS
+                .into_iter()
+                .filter(|version| *version >= min_version)
+                .collect();
+            warn!(
+                { proxy_principal: proxy_principal },
+                "http_over_ws: client proxy {} with protocol versions {:?} is incompatible",
+                proxy_principal,
+                proxy_supported_versions
+            );
+
+            Some(HttpOverWsMessage::Error(
+                None,
+                format!(
+                    "incompatible protocol: the proxy supports the versions {:?}, while the canister supports the versions {:?}. Please update the proxy",
+                    proxy_supported_versions, canister_supported_versions
+                ),
+                None,
+            ))
+        }
+    };
+
+    if let Some(reply) = reply {
+        let reply_version = setup_msg_version.unwrap_or(HTTP_OVER_WS_PROTOCOL_V1);
+
+        if let Err(e) = ws_send(proxy_principal, encode_message(reply, reply_version)) {
+            error!(
+                { proxy_principal: proxy_principal },
+                "http_over_ws: error while replying to the setup of client proxy {}: {}",
+                proxy_principal,
+                e
+            );
+        }
+    }
+}
+
+/// Removes the proxy from the connected proxies.
+/// The requests still waiting for its responses fail with a [ProxyError::Unknown].
 pub fn try_disconnect_http_proxy(proxy_principal: Principal) -> Result<(), HttpOverWsError> {
-    STATE.with(|state| state.borrow_mut().remove_proxy(&proxy_principal))?;
+    let callbacks_with_results =
+        STATE.with(|state| state.borrow_mut().remove_proxy(&proxy_principal))?;
+
+    log!({ proxy_principal: proxy_principal }, "http_over_ws: Client {} disconnected", proxy_principal);
 
-    log!("http_over_ws: Client {} disconnected", proxy_principal);
+    for (request_id, callback_with_result) in callbacks_with_results {
+        trigger_callback_with_result(request_id, callback_with_result);
+    }
     Ok(())
 }
 
+/// Handles a response that is not encrypted, which is rejected if the request has been encrypted.
+fn handle_plaintext_http_response(
+    proxy_principal: Principal,
+    request_id: HttpRequestId,
+    response: HttpResponse,
+    signature: Option<ResponseSignature>,
+    ws_send: fn(Principal, Vec<u8>) -> Result<(), String>,
+) {
+    if STATE.with(|state| state.borrow().is_request_encrypted(request_id)) {
+        reject_encrypted_http_response(
+            proxy_principal,
+            request_id,
+            "the response is not encrypted".to_string(),
+            ws_send,
+        );
+        return;
+    }
+
+    handle_http_response(proxy_principal, request_id, response, signature, ws_send);
+}
+
+fn reject_encrypted_http_response(
+    proxy_principal: Principal,
+    request_id: HttpRequestId,
+    reason: String,
+    ws_send: fn(Principal, Vec<u8>) -> Result<(), String>,
+) {
+    warn!(
+        { request_id: request_id, proxy_principal: proxy_principal },
+        "http_over_ws: rejected response for request with id: {} from client proxy {}: {}",
+        request_id,
+        proxy_principal,
+        reason
+    );
+
+    handle_http_result(
+        proxy_principal,
+        request_id,
+        HttpResult::Failure(HttpFailureReason::InvalidEncryptedResponse(reason)),
+        None,
+        ws_send,
+    );
+}
+
+fn handle_http_response(
+    proxy_principal: Principal,
+    request_id: HttpRequestId,
+    response: HttpResponse,
+    signature: Option<ResponseSignature>,
+    ws_send: fn(Principal, Vec<u8>) -> Result<(), String>,
+) {
+    let attestation = STATE.with(|state| {
+        state
+            .borrow()
+            .attest_response(proxy_principal, request_id, &response, signature, time())
+    });
+
+    match attestation {
+        Ok(response_attestation) => {
+            let redirect = STATE.with(|state| {
+                state
+                    .borrow_mut()
+                    .follow_redirect(proxy_principal, request_id, &response)
+            });
+
+            match redirect {
+                Ok(None) => handle_http_result(
+                    proxy_principal,
+                    request_id,
+                    HttpResult::Success(response),
+                    response_attestation,
+                    ws_send,
+                ),
+                Ok(Some((protocol_version, message))) => {
+                    if let Err(e) =
+                        ws_send(proxy_principal, encode_message(message, protocol_version))
+                    {
+                        error!(
+                            { request_id: request_id, proxy_principal: proxy_principal },
+                            "http_over_ws: error while following redirect for request with id: {}: {}",
+                            request_id,
+                            e
+                        );
+                    }
+                }
+                Err(reason) => handle_http_result(
+                    proxy_principal,
+                    request_id,
+                    HttpResult::Failure(reason),
+                    None,
+                    ws_send,
+                ),
+            }
+        }
+        Err(e) => {
+            warn!(
+                { request_id: request_id, proxy_principal: proxy_principal },
+                "http_over_ws: rejected response for request with id: {} from client proxy {}: {}",
+                request_id,
+                proxy_principal,
+                e
+            );
+
+            handle_http_result(
+                proxy_principal,
+                request_id,
+                HttpResult::Failure(HttpFailureReason::InvalidResponseAttestation(e)),
+                None,
+                ws_send,
+            );
+        }
+    }
+}
+
 fn handle_http_result(
     proxy_principal: Principal,
     request_id: HttpRequestId,
     http_result: HttpResult,
+    response_attestation: Option<ResponseAttestation>,
+    ws_send: fn(Principal, Vec<u8>) -> Result<(), String>,
 ) {
+    if let HttpResult::Failure(HttpFailureReason::ProxyError(proxy_error)) = &http_result {
+        if proxy_error.is_retryable() {
+            let retry = STATE.with(|state| {
+                state
+                    .borrow_mut()
+                    .retry_connection(proxy_principal, request_id, proxy_error)
+            });
+
+            if let Some((retry_proxy_principal, protocol_version, message)) = retry {
+                log!(
+                    { request_id: request_id, proxy_principal: retry_proxy_principal },
+                    "http_over_ws: request with id: {} failed on client proxy {} with error {}, retrying on client proxy {}",
+                    request_id,
+                    proxy_principal,
+                    proxy_error.kind(),
+                    retry_proxy_principal
+                );
+
+                if let Err(e) = ws_send(
+                    retry_proxy_principal,
+                    encode_message(message, protocol_version),
+                ) {
+                    error!(
+                        { request_id: request_id, proxy_principal: retry_proxy_principal },
+                        "http_over_ws: error while retrying request with id: {}: {}",
+                        request_id,
+                        e
+                    );
+                }
+                return;
+            }
+        }
+    }
+
     match STATE.with(|state| {
-        state
-            .borrow_mut()
-            .update_connection_state(proxy_principal, request_id, http_result)
+        state.borrow_mut().update_connection_state(
+            proxy_principal,
+            request_id,
+            http_result,
+            response_attestation,
+        )
     }) {
         Ok(callback_with_result) => {
             trigger_callback_with_result(request_id, callback_with_result);
         }
         Err(e) => {
-            log!(
+            error!(
+                { request_id: request_id, proxy_principal: proxy_principal },
                 "http_over_ws: error {:?} while updating state for request with id: {}",
                 e,
                 request_id
@@ -70,6 +414,95 @@ fn handle_http_result(
     }
 }
 
+fn handle_webhook_request(
+    proxy_principal: Principal,
+    request_id: HttpRequestId,
+    request: HttpRequest,
+    ws_send: fn(Principal, Vec<u8>) -> Result<(), String>,
+) {
+    let handler = STATE.with(|state| {
+        let state = state.borrow();
+
+        state
+            .is_proxy_connected(&proxy_principal)
+            .then(|| state.get_webhook_handler(webhook_path(&request.url)))
+    });
+
+    match handler {
+        Some(Some(handler)) => {
+            log!(
+                { proxy_principal: proxy_principal },
+                "http_over_ws: handling webhook request with id: {} from client proxy {}",
+                request_id,
+                proxy_principal
+            );
+
+            ic_cdk::spawn(async move {
+                let response = handler(request).await;
+                send_webhook_response(proxy_principal, request_id, response, ws_send);
+            });
+        }
+        Some(None) => {
+            warn!(
+                { proxy_principal: proxy_principal },
+                "http_over_ws: no webhook handler for request with id: {}, url: {}",
+                request_id,
+                request.url
+            );
+
+            let response = HttpResponse {
+                status: Nat::from(404),
+                headers: vec![],
+                body: b"Not Found".to_vec(),
+            };
+            send_webhook_response(proxy_principal, request_id, response, ws_send);
+        }
+        None => {
+            warn!(
+                { proxy_principal: proxy_principal },
+                "http_over_ws: webhook request with id: {} from client proxy {} which is not set up",
+                request_id,
+                proxy_principal
+            );
+        }
+    }
+}
+
+fn send_webhook_response(
+    proxy_principal: Principal,
+    request_id: HttpRequestId,
+    response: HttpResponse,
+    ws_send: fn(Principal, Vec<u8>) -> Result<(), String>,
+) {
+    let protocol_version =
+        STATE.with(|state| state.borrow().get_proxy_protocol_version(&proxy_principal));
+
+    if let Err(e) = ws_send(
+        proxy_principal,
+        encode_message(
+            HttpOverWsMessage::HttpResponse(request_id, response),
+            protocol_version,
+        ),
+    ) {
+        error!(
+            { proxy_principal: proxy_principal },
+            "http_over_ws: error while sending webhook response with id: {}: {}",
+            request_id,
+            e
+        );
+    }
+}
+
+/// Returns the path of the URL of a webhook request, which may be either absolute or just a path.
+fn webhook_path(url: &str) -> &str {
+    let path = match url.split_once("://") {
+        Some((_, rest)) => rest.find('/').map_or("/", |i| &rest[i..]),
+        None => url,
+    };
+
+    path.split(['?', '#']).next().unwrap_or(path)
+}
+
 pub(crate) fn trigger_callback_with_result(
     request_id: HttpRequestId,
     callback_with_result: Option<HttpCallbackWithResult>,
@@ -77,12 +510,14 @@ pub(crate) fn trigger_callback_with_result(
     if let Some((callback, http_result)) = callback_with_result {
         ic_cdk::spawn(async move { callback(request_id, http_result).await });
 
-        log!(
+        debug!(
+            { request_id: request_id },
             "http_over_ws: triggered callback with result for request with id: {}",
             request_id
         );
     } else {
-        log!(
+        debug!(
+            { request_id: request_id },
             "http_over_ws: request with id: {} completed without callback",
             request_id
         );
@@ -92,24 +527,161 @@ pub(crate) fn trigger_callback_with_result(
 pub fn execute_http_request(
     req: HttpRequest,
     callback: Option<HttpCallback>,
-    timeout_ms: Option<HttpRequestTimeoutMs>,
+    deadline: Option<HttpRequestDeadline>,
     ws_send: fn(Principal, Vec<u8>) -> Result<(), String>,
 ) -> ExecuteHttpRequestResult {
-    let (assigned_proxy_principal, request_id) = STATE.with(|state| {
-        state
-            .borrow_mut()
-            .assign_connection(req.clone(), callback, timeout_ms)
-    })?;
+    let (assigned_proxy_principal, request_id, protocol_version, message) =
+        STATE.with(|state| {
+            let mut state = state.borrow_mut();
+
+            let (assigned_proxy_principal, request_id) =
+                state.assign_connection(req.clone(), callback, deadline)?;
+            let protocol_version = state.get_proxy_protocol_version(&assigned_proxy_principal);
+            let message = state.http_request_message(
+                &assigned_proxy_principal,
+                request_id,
+                proxy_request(&req),
+            );
+
+            Ok::<_, HttpOverWsError>((
+                assigned_proxy_principal,
+                request_id,
+                protocol_version,
+                message,
+            ))
+        })?;
 
     ws_send(
         assigned_proxy_principal,
-        HttpOverWsMessage::HttpRequest(request_id, req).to_bytes(),
+        encode_message(message, protocol_version),
     )
     .unwrap();
 
     Ok(request_id)
 }
 
+/// Sets the timeout of the requests executed without a deadline,
+/// which is [DEFAULT_HTTP_REQUEST_TIMEOUT_MS] if never set.
+pub fn set_default_http_request_timeout_ms(timeout_ms: HttpRequestTimeoutMs) {
+    STATE.with(|state| state.borrow_mut().set_default_timeout_ms(timeout_ms));
+}
+
+/// Sets the oldest protocol version the proxies can set up with.
+/// The proxies already connected keep the version they negotiated.
+pub fn set_min_protocol_version(version: HttpOverWsProtocolVersion) -> Result<(), HttpOverWsError> {
+    if !SUPPORTED_PROTOCOL_VERSIONS.contains(&version) {
+        return Err(HttpOverWsError::UnsupportedProtocolVersion(version));
+    }
+
+    STATE.with(|state| state.borrow_mut().set_min_protocol_version(version));
+    Ok(())
+}
+
+/// Registers the Ed25519 public key with which the proxy attests its responses, or removes it if [None].
+/// Once a key is registered, the responses of the proxy are accepted only if attested with it.
+///
+/// The keys are kept in the heap, so they should be saved in the `pre_upgrade` hook
+/// with [get_response_attestation_config] and registered again in the `post_upgrade` hook.
+pub fn set_proxy_attestation_key(
+    proxy_principal: Principal,
+    public_key: Option<Vec<u8>>,
+) -> Result<(), HttpOverWsError> {
+    if let Some(public_key) = &public_key {
+        if !is_valid_public_key(public_key) {
+            return Err(HttpOverWsError::InvalidAttestationKey);
+        }
+    }
+
+    STATE.with(|state| {
+        state
+            .borrow_mut()
+            .set_proxy_attestation_key(proxy_principal, public_key)
+    });
+    Ok(())
+}
+
+/// Sets whether the responses of the proxies without a registered key are rejected,
+/// which is not the case by default.
+pub fn set_response_attestations_required(required: bool) {
+    STATE.with(|state| {
+        state
+            .borrow_mut()
+            .set_response_attestations_required(required)
+    });
+}
+
+pub fn get_response_attestation_config() -> ResponseAttestationConfig {
+    STATE.with(|state| state.borrow().get_response_attestation_config())
+}
+
+/// Enables the encryption of the requests and of the responses exchanged with the proxies
+/// that announce an encryption key signed with their attestation key, see [set_proxy_attestation_key].
+/// The WS gateway then only sees the ciphertexts.
+///
+/// The seed must be secret and random, e.g. obtained from the management canister's `raw_rand`.
+/// It is kept in the heap only, so it must be set again after each upgrade.
+pub fn init_payload_encryption(seed: [u8; 32]) {
+    STATE.with(|state| state.borrow_mut().set_payload_encryption_seed(seed));
+}
+
+/// Sets whether the requests are only assigned to the proxies with which the payloads are encrypted,
+/// which is not the case by default.
+pub fn set_payload_encryption_required(required: bool) {
+    STATE.with(|state| state.borrow_mut().set_payload_encryption_required(required));
+}
+
+pub fn is_payload_encryption_required() -> bool {
+    STATE.with(|state| state.borrow().is_payload_encryption_required())
+}
+
+/// Reserves a new [HttpRequestId] without sending any request to the proxies.
+/// Useful when the result of a request is already known, e.g. when it is served from a cache,
+/// but the caller still needs an id to refer to it.
+pub fn reserve_request_id() -> HttpRequestId {
+    STATE.with(|state| state.borrow_mut().next_request_id())
+}
+
+/// Returns the last [HttpRequestId] that has been assigned.
+///
+/// The ids restart from 1 when the canister's heap is cleared, so the value returned
+/// should be saved in the `pre_upgrade` hook and restored with [restore_last_request_id]
+/// in the `post_upgrade` hook, in order to never assign the same id twice.
+pub fn get_last_request_id() -> HttpRequestId {
+    STATE.with(|state| state.borrow().last_request_id())
+}
+
+/// Makes the next assigned ids greater than the given one.
+/// Has no effect if an id greater than or equal to the given one has already been assigned.
+pub fn restore_last_request_id(last_request_id: HttpRequestId) {
+    STATE.with(|state| state.borrow_mut().restore_last_request_id(last_request_id));
+}
+
+/// Registers the handler of the inbound webhook requests that the proxies receive on the given path,
+/// e.g. `/github/push`.
+pub fn register_webhook_handler(path: &str, handler: WebhookHandler) {
+    STATE.with(|state| {
+        state
+            .borrow_mut()
+            .set_webhook_handler(path.to_string(), handler)
+    });
+}
+
+pub fn unregister_webhook_handler(path: &str) {
+    STATE.with(|state| state.borrow_mut().remove_webhook_handler(path));
+}
+
+/// Sets the handler of the inbound webhook requests whose path has no registered handler.
+/// Without a default handler, these requests get a `404 Not Found` response.
+pub fn set_default_webhook_handler(handler: Option<WebhookHandler>) {
+    STATE.with(|state| state.borrow_mut().set_default_webhook_handler(handler));
+}
+
+/// Sets the function that validates the request for each hop of the redirects followed by the canister,
+/// failing the request with [HttpFailureReason::InvalidRedirect] if it returns an error.
+pub fn set_redirect_validator(validator: Option<RedirectValidator>) {
+    STATE.with(|state| state.borrow_mut().set_redirect_validator(validator));
+}
+
 pub fn disconnect_all_connected_proxies(ws_close: fn(Principal) -> Result<(), String>) {
     let proxies = STATE.with(|state| state.borrow().get_connected_proxies());
 
@@ -117,7 +689,8 @@ pub fn disconnect_all_connected_proxies(ws_close: fn(Principal) -> Result<(), St
         let res = ws_close(proxy);
 
         if let Err(e) = res {
-            log!(
+            warn!(
+                { proxy_principal: proxy },
                 "http_over_ws: error while disconnecting proxy {}: {}",
                 proxy,
                 e
@@ -125,13 +698,90 @@ pub fn disconnect_all_connected_proxies(ws_close: fn(Principal) -> Result<(), St
         }
     }
 
-    STATE.with(|state| state.borrow_mut().remove_all_proxies());
+    let callbacks_with_results = STATE.with(|state| state.borrow_mut().remove_all_proxies());
+    for (request_id, callback_with_result) in callbacks_with_results {
+        trigger_callback_with_result(request_id, callback_with_result);
+    }
+}
+
+/// Closes the WebSocket connection of the proxy and removes it from the connected proxies,
+/// together with the requests still assigned to it. The ones still waiting for a response fail,
+/// see [try_disconnect_http_proxy].
+pub fn disconnect_proxy(
+    proxy_principal: Principal,
+    ws_close: fn(Principal) -> Result<(), String>,
+) -> Result<(), HttpOverWsError> {
+    if !STATE.with(|state| state.borrow().is_proxy_connected(&proxy_principal)) {
+        return Err(HttpOverWsError::ProxyNotFound);
+    }
+
+    if let Err(e) = ws_close(proxy_principal) {
+        warn!(
+            { proxy_principal: proxy_principal },
+            "http_over_ws: error while disconnecting proxy {}: {}",
+            proxy_principal,
+            e
+        );
+    }
+
+    // the proxy may have already been removed by the on_close callback
+    if STATE.with(|state| state.borrow().is_proxy_connected(&proxy_principal)) {
+        try_disconnect_http_proxy(proxy_principal)?;
+    }
+
+    Ok(())
+}
+
+/// Stops assigning new requests to the proxy, while the ones already assigned to it can still complete.
+/// The proxy accepts new requests again only after reconnecting.
+pub fn drain_proxy(proxy_principal: Principal) -> Result<(), HttpOverWsError> {
+    STATE.with(|state| state.borrow_mut().drain_proxy(&proxy_principal))?;
+
+    log!({ proxy_principal: proxy_principal }, "http_over_ws: draining proxy {}", proxy_principal);
+    Ok(())
+}
+
+/// Returns the connected proxies, ordered by principal.
+pub fn get_proxies() -> Vec<ProxyInfo> {
+    STATE.with(|state| state.borrow().get_proxies())
 }
 
 pub fn get_http_connection(request_id: HttpRequestId) -> Option<HttpRequest> {
     STATE.with(|state| state.borrow().get_http_connection(request_id))
 }
 
+pub fn get_metrics() -> HttpOverWsMetrics {
+    STATE.with(|state| state.borrow().get_metrics())
+}
+
+/// Returns the principal of the proxy the request has been assigned to.
+pub fn get_http_connection_proxy(request_id: HttpRequestId) -> Option<Principal> {
+    STATE.with(|state| state.borrow().get_http_connection_proxy(request_id))
+}
+
 pub fn get_http_response(request_id: HttpRequestId) -> GetHttpResponseResult {
     STATE.with(|state| state.borrow().get_http_response(request_id))
 }
+
+/// Returns the attestation stored along with the response of the request,
+/// [None] if the response has not been attested or not received yet.
+pub fn get_http_response_attestation(
+    request_id: HttpRequestId,
+) -> Result<Option<ResponseAttestation>, HttpOverWsError> {
+    STATE.with(|state| state.borrow().get_http_response_attestation(request_id))
+}
+
+/// Returns the metadata stored along with the response of the request,
+/// [None] if the response has not been received yet.
+///
+/// See [get_http_response_envelope] to get both the response and its metadata.
+pub fn get_http_response_metadata(
+    request_id: HttpRequestId,
+) -> Result<Option<HttpResponseMetadata>, HttpOverWsError> {
+    STATE.with(|state| state.borrow().get_http_response_metadata(request_id))
+}
+
+/// Like [get_http_response], but returns the response along with its [HttpResponseMetadata].
+pub fn get_http_response_envelope(request_id: HttpRequestId) -> GetHttpResponseEnvelopeResult {
+    STATE.with(|state| state.borrow().get_http_response_envelope(request_id))
+}
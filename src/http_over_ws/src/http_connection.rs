@@ -1,9 +1,21 @@
-use candid::{decode_one, encode_one, CandidType, Deserialize};
-use ic_cdk::api::management_canister::http_request::{
-    HttpHeader as ApiHttpHeader, HttpResponse as ApiHttpResponse,
+use crate::{
+    attestation::{ResponseAttestation, ResponseSignature},
+    encryption::EncryptedHttpRequest,
+    protocol::{HttpOverWsProtocolVersion, ProxyClientSetup},
+    redirects::{
+        proxy_request, redirect_location, redirect_request, HttpRedirect, RedirectPolicy,
+        RedirectValidator, MAX_FOLLOWED_REDIRECTS,
+    },
+    secrets::SecretName,
 };
-use ic_cdk_timers::TimerId;
-use logger::log;
+use candid::{CandidType, Deserialize, Principal};
+use ic_cdk::api::{
+    management_canister::http_request::{
+        HttpHeader as ApiHttpHeader, HttpResponse as ApiHttpResponse,
+    },
+    time,
+};
+use logger::{log, warn};
 use std::{future::Future, pin::Pin};
 
 pub type HttpRequestId = u64;
@@ -18,6 +30,48 @@ pub enum HttpMethod {
     PUT,
     HEAD,
     DELETE,
+    PATCH,
+    OPTIONS,
+    /// A custom method, see [HttpMethod::is_valid].
+    Other(String),
+}
+
+/// The maximum length of the name of a custom method.
+pub const MAX_CUSTOM_HTTP_METHOD_LENGTH: usize = 32;
+
+impl HttpMethod {
+    pub fn as_str(&self) -> &str {
+        match self {
+            HttpMethod::GET => "GET",
+            HttpMethod::POST => "POST",
+            HttpMethod::PUT => "PUT",
+            HttpMethod::HEAD => "HEAD",
+            HttpMethod::DELETE => "DELETE",
+            HttpMethod::PATCH => "PATCH",
+            HttpMethod::OPTIONS => "OPTIONS",
+            HttpMethod::Other(name) => name,
+        }
+    }
+
+    /// Whether the method can be sent to the proxies.
+    ///
+    /// A custom method must be a valid HTTP token (RFC 9110) of at most [MAX_CUSTOM_HTTP_METHOD_LENGTH] characters,
+    /// and must not be the name of one of the other variants.
+    pub fn is_valid(&self) -> bool {
+        let HttpMethod::Other(name) = self else {
+            return true;
+        };
+
+        let is_tchar = |c: char| c.is_ascii_alphanumeric() || "!#$%&'*+-.^_`|~".contains(c);
+
+        !name.is_empty()
+            && name.len() <= MAX_CUSTOM_HTTP_METHOD_LENGTH
+            && name.chars().all(is_tchar)
+            && !matches!(
+                name.as_str(),
+                "GET" | "POST" | "PUT" | "HEAD" | "DELETE" | "PATCH" | "OPTIONS"
+            )
+    }
 }
 
 pub type HttpHeader = ApiHttpHeader;
@@ -28,6 +82,8 @@ pub struct HttpRequest {
     pub method: HttpMethod,
     pub headers: Vec<HttpHeader>,
     pub body: Option<Vec<u8>>,
+    /// Left to the defaults of the proxy if not set.
+    pub redirect: Option<RedirectPolicy>,
 }
 
 impl HttpRequest {
@@ -42,6 +98,7 @@ impl HttpRequest {
             method,
             headers,
             body,
+            redirect: None,
         }
     }
 }
@@ -54,27 +111,110 @@ pub enum HttpResult {
     Failure(HttpFailureReason),
 }
 
+/// How long the phases of the upstream request took, as measured by the proxy.
+/// The phases the proxy can't measure, e.g. the DNS lookup of a reused connection, are [None].
+#[derive(CandidType, Clone, Debug, Deserialize, PartialEq, Eq)]
+pub struct HttpResponseTiming {
+    pub dns_lookup_ms: Option<u64>,
+    pub connect_ms: Option<u64>,
+    pub time_to_first_byte_ms: Option<u64>,
+    pub total_ms: u64,
+}
+
+/// Sent by a proxy in an [HttpOverWsMessage::HttpResponseStats] before the response it refers to.
+#[derive(CandidType, Clone, Debug, Deserialize, PartialEq, Eq)]
+pub struct ProxyResponseStats {
+    pub timing: HttpResponseTiming,
+    /// The size of the body received from the upstream server, before the proxy truncated it if it did.
+    pub body_size_bytes: u64,
+}
+
+/// What the canister knows about how the response has been obtained, stored along with the response.
+#[derive(CandidType, Clone, Debug, Deserialize, PartialEq, Eq)]
+pub struct HttpResponseMetadata {
+    /// The proxy that got the response.
+    pub proxy_principal: Principal,
+    /// The URL of the request that got the response, after following the redirects.
+    pub final_url: String,
+    /// The redirects followed by the canister, in order.
+    pub redirects: Vec<HttpRedirect>,
+    /// [None] if the proxy didn't report the timing of the request that got the response.
+    pub timing: Option<HttpResponseTiming>,
+    /// The size of the body before any truncation, [None] if the proxy didn't report it.
+    pub body_size_bytes: Option<u64>,
+    /// How many proxies the request has been sent to, see [MAX_HTTP_REQUEST_PROXY_ATTEMPTS].
+    pub proxy_attempts: u32,
+}
+
+/// A response along with its [HttpResponseMetadata].
+#[derive(CandidType, Clone, Debug, Deserialize, PartialEq, Eq)]
+pub struct HttpResponseEnvelope {
+    pub response: HttpResponse,
+    pub metadata: HttpResponseMetadata,
+}
+
+pub type GetHttpResponseEnvelopeResult = Result<HttpResponseEnvelope, HttpOverWsError>;
+
 pub(crate) type HttpCallbackWithResult = (HttpCallback, HttpResult);
 pub(crate) type HttpCallback = fn(HttpRequestId, HttpResult) -> Pin<Box<dyn Future<Output = ()>>>;
 
+/// Handles an inbound webhook request forwarded by a proxy, returning the response
+/// that the proxy sends back to the webhook caller.
+pub type WebhookHandler = fn(HttpRequest) -> Pin<Box<dyn Future<Output = HttpResponse>>>;
+
 pub type HttpRequestTimeoutMs = u64;
 
+/// The timeout of the requests executed without a deadline, unless changed with
+/// [set_default_http_request_timeout_ms](crate::set_default_http_request_timeout_ms).
+pub const DEFAULT_HTTP_REQUEST_TIMEOUT_MS: HttpRequestTimeoutMs = 30_000;
+
+/// How often the requests whose deadline has passed are failed with [HttpFailureReason::RequestTimeout].
+/// A request may therefore time out up to this interval after its deadline.
+pub const DEADLINES_SWEEP_INTERVAL_MS: u64 = 1_000;
+
+/// The time after which a request fails with [HttpFailureReason::RequestTimeout]
+/// if no response has been received.
+#[derive(CandidType, Clone, Debug, Deserialize, PartialEq, Eq)]
+pub enum HttpRequestDeadline {
+    /// Relative to the time at which the request is sent to the proxy.
+    TimeoutMs(HttpRequestTimeoutMs),
+    /// The absolute canister time, in nanoseconds.
+    AtNs(u64),
+}
+
+/// Requests sent by the canister are answered by the proxy with an [HttpOverWsMessage::HttpResponse],
+/// an [HttpOverWsMessage::AttestedHttpResponse] or an [HttpOverWsMessage::Error] with the same id.
+///
+/// Requests sent by a proxy are inbound webhook requests, answered by the canister
+/// with an [HttpOverWsMessage::HttpResponse] with the same id. These ids are assigned by the proxy
+/// and are unrelated to the ids of the requests sent by the canister.
+///
+/// Only the variants known by the protocol version negotiated with a proxy are sent to it.
 #[derive(CandidType, Debug, Deserialize, PartialEq, Eq)]
 pub enum HttpOverWsMessage {
+    /// Sent by the proxies that only speak [HTTP_OVER_WS_PROTOCOL_V1](crate::HTTP_OVER_WS_PROTOCOL_V1).
     SetupProxyClient,
     HttpRequest(HttpRequestId, HttpRequest),
     HttpResponse(HttpRequestId, HttpResponse),
-    Error(Option<HttpRequestId>, String),
-}
-
-impl HttpOverWsMessage {
-    pub(crate) fn to_bytes(&self) -> Vec<u8> {
-        encode_one(self).unwrap()
-    }
-
-    pub(crate) fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
-        decode_one(bytes).map_err(|e| e.to_string())
-    }
+    /// The message describes the error for the proxies speaking [HTTP_OVER_WS_PROTOCOL_V1](crate::HTTP_OVER_WS_PROTOCOL_V1),
+    /// which don't send the structured [ProxyError].
+    Error(Option<HttpRequestId>, String, Option<ProxyError>),
+    /// Sent by the proxies to negotiate the protocol version,
+    /// answered by the canister with a [HttpOverWsMessage::ProtocolVersionNegotiated] or an [HttpOverWsMessage::Error].
+    SetupVersionedProxyClient(ProxyClientSetup),
+    /// The protocol version used for all the following messages.
+    ProtocolVersionNegotiated(HttpOverWsProtocolVersion),
+    /// A response signed by the proxy with the key registered for it,
+    /// see [set_proxy_attestation_key](crate::set_proxy_attestation_key).
+    AttestedHttpResponse(HttpRequestId, HttpResponse, ResponseSignature),
+    /// Sent instead of [HttpOverWsMessage::HttpRequest] to the proxies that announced an encryption key,
+    /// see [init_payload_encryption](crate::init_payload_encryption).
+    EncryptedHttpRequest(HttpRequestId, EncryptedHttpRequest),
+    /// The response to an [HttpOverWsMessage::EncryptedHttpRequest], encrypted with [encrypt_http_response](crate::encrypt_http_response)
+    /// and optionally signed like an [HttpOverWsMessage::AttestedHttpResponse].
+    EncryptedHttpResponse(HttpRequestId, Vec<u8>, Option<ResponseSignature>),
+    /// Optionally sent by a proxy right before the response to a request, in any of its forms.
+    HttpResponseStats(HttpRequestId, ProxyResponseStats),
 }
 
 #[derive(CandidType, Debug, Deserialize, PartialEq, Eq)]
@@ -90,18 +230,110 @@ pub enum HttpOverWsError {
     NoProxiesConnected,
     ConnectionNotAssignedToProxy,
     RequestFailed(HttpFailureReason),
+    UnsupportedProtocolVersion(HttpOverWsProtocolVersion),
+    /// None of the connected proxies advertised the method in its capabilities.
+    UnsupportedHttpMethod(HttpMethod),
+    /// The key is not a valid Ed25519 public key.
+    InvalidAttestationKey,
+    /// A secret placeholder of the request is not terminated or names an invalid secret.
+    InvalidSecretPlaceholder(String),
+    /// None of the connected proxies advertised all the secrets referenced by the request.
+    SecretsNotAvailable(Vec<SecretName>),
+    /// The payloads must be encrypted, but the encryption is not initialized
+    /// or none of the connected proxies announced an encryption key.
+    PayloadEncryptionNotAvailable,
 }
 
 #[derive(CandidType, Clone, Debug, Deserialize, PartialEq, Eq)]
 pub enum HttpFailureReason {
     RequestTimeout,
-    ProxyError(String),
+    ProxyError(ProxyError),
+    /// The response is not attested as required, or its attestation is not valid.
+    InvalidResponseAttestation(String),
+    /// The response to an encrypted request is not encrypted, or can't be decrypted.
+    InvalidEncryptedResponse(String),
+    /// The response is a redirect to the given location, but the [RedirectPolicy] is [RedirectPolicy::Error].
+    RedirectNotAllowed(String),
+    /// The response is a redirect, but the maximum number of redirects has already been followed.
+    TooManyRedirects,
+    /// The redirect can't be followed, or has been rejected by the [RedirectValidator].
+    InvalidRedirect(String),
+}
+
+/// The reason why a proxy couldn't get a response for a request, with an optional detail.
+#[derive(CandidType, Clone, Debug, Deserialize, PartialEq, Eq)]
+pub enum ProxyError {
+    DnsResolutionFailed(Option<String>),
+    ConnectionRefused(Option<String>),
+    TlsError(Option<String>),
+    UpstreamTimeout(Option<String>),
+    BodyReadError(Option<String>),
+    /// The request is not allowed by the policy of the proxy.
+    RejectedByPolicy(Option<String>),
+    /// The proxy has too many requests in flight.
+    ProxyOverloaded(Option<String>),
+    /// The error has not been classified by the proxy.
+    Unknown(Option<String>),
+}
+
+impl ProxyError {
+    /// The name of the kind of error, in snake case.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ProxyError::DnsResolutionFailed(_) => "dns_resolution_failed",
+            ProxyError::ConnectionRefused(_) => "connection_refused",
+            ProxyError::TlsError(_) => "tls_error",
+            ProxyError::UpstreamTimeout(_) => "upstream_timeout",
+            ProxyError::BodyReadError(_) => "body_read_error",
+            ProxyError::RejectedByPolicy(_) => "rejected_by_policy",
+            ProxyError::ProxyOverloaded(_) => "proxy_overloaded",
+            ProxyError::Unknown(_) => "unknown",
+        }
+    }
+
+    pub fn detail(&self) -> Option<&str> {
+        match self {
+            ProxyError::DnsResolutionFailed(detail)
+            | ProxyError::ConnectionRefused(detail)
+            | ProxyError::TlsError(detail)
+            | ProxyError::UpstreamTimeout(detail)
+            | ProxyError::BodyReadError(detail)
+            | ProxyError::RejectedByPolicy(detail)
+            | ProxyError::ProxyOverloaded(detail)
+            | ProxyError::Unknown(detail) => detail.as_deref(),
+        }
+    }
+
+    /// Whether the request can be sent to another proxy,
+    /// which is the case only if it surely didn't reach the upstream server.
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            ProxyError::DnsResolutionFailed(_)
+                | ProxyError::ConnectionRefused(_)
+                | ProxyError::ProxyOverloaded(_)
+        )
+    }
 }
 
+/// How many proxies a request is sent to at most, in case of [retryable](ProxyError::is_retryable) errors.
+pub const MAX_HTTP_REQUEST_PROXY_ATTEMPTS: u32 = 2;
+
 pub(crate) struct HttpConnection {
     id: HttpRequestId,
     request: HttpRequest,
     state: HttpConnectionState,
+    started_at_ns: u64,
+    deadline_ns: u64,
+    proxy_attempts: u32,
+    response_attestation: Option<ResponseAttestation>,
+    /// The request sent to the proxy for the current hop.
+    hop_request: HttpRequest,
+    redirects: Vec<HttpRedirect>,
+    /// The stats reported by the proxy for the current hop.
+    proxy_stats: Option<ProxyResponseStats>,
+    /// Whether the request for the current hop has been sent encrypted.
+    encrypted: bool,
 }
 
 impl HttpConnection {
@@ -109,19 +341,122 @@ impl HttpConnection {
         id: HttpRequestId,
         request: HttpRequest,
         callback: Option<HttpCallback>,
-        timer_id: Option<TimerId>,
+        deadline_ns: u64,
     ) -> Self {
         HttpConnection {
             id,
+            hop_request: proxy_request(&request),
             request,
-            state: HttpConnectionState::new(timer_id, callback),
+            state: HttpConnectionState::new(callback),
+            started_at_ns: time(),
+            deadline_ns,
+            proxy_attempts: 1,
+            response_attestation: None,
+            redirects: Vec::new(),
+            proxy_stats: None,
+            encrypted: false,
         }
     }
 
+    pub(crate) fn started_at_ns(&self) -> u64 {
+        self.started_at_ns
+    }
+
+    pub(crate) fn deadline_ns(&self) -> u64 {
+        self.deadline_ns
+    }
+
+    /// Whether the request can still be sent to another proxy.
+    pub(crate) fn can_retry(&self) -> bool {
+        self.is_waiting_for_response() && self.proxy_attempts < MAX_HTTP_REQUEST_PROXY_ATTEMPTS
+    }
+
+    pub(crate) fn record_retry(&mut self) {
+        self.proxy_attempts += 1;
+        self.proxy_stats = None;
+    }
+
+    pub(crate) fn set_proxy_stats(&mut self, stats: ProxyResponseStats) {
+        self.proxy_stats = Some(stats);
+    }
+
+    /// The index of the current hop, 0 until the first redirect is followed.
+    pub(crate) fn hop(&self) -> u32 {
+        self.redirects.len() as u32
+    }
+
+    pub(crate) fn set_encrypted(&mut self, encrypted: bool) {
+        self.encrypted = encrypted;
+    }
+
+    pub(crate) fn is_encrypted(&self) -> bool {
+        self.encrypted
+    }
+
+    pub(crate) fn is_waiting_for_response(&self) -> bool {
+        matches!(self.state, HttpConnectionState::WaitingForResponse(_))
+    }
+
+    /// The milliseconds elapsed since the connection has been assigned to a proxy.
+    pub(crate) fn elapsed_ms(&self) -> u64 {
+        time().saturating_sub(self.started_at_ns) / 1_000_000
+    }
+
     pub(crate) fn get_request(&self) -> HttpRequest {
         self.request.clone()
     }
 
+    pub(crate) fn get_hop_request(&self) -> HttpRequest {
+        self.hop_request.clone()
+    }
+
+    /// If the response is a redirect that the canister has to follow,
+    /// returns the request for the next hop, which becomes the current one.
+    pub(crate) fn follow_redirect(
+        &mut self,
+        response: &HttpResponse,
+        redirect_validator: Option<RedirectValidator>,
+    ) -> Result<Option<HttpRequest>, HttpFailureReason> {
+        let max_redirects = match self.request.redirect {
+            Some(RedirectPolicy::Follow(max_redirects)) => Some(max_redirects),
+            Some(RedirectPolicy::Error) => None,
+            Some(RedirectPolicy::Manual) | None => return Ok(None),
+        };
+        let Some((status, location)) = redirect_location(response) else {
+            return Ok(None);
+        };
+        let Some(max_redirects) = max_redirects else {
+            return Err(HttpFailureReason::RedirectNotAllowed(location.to_string()));
+        };
+        if self.redirects.len() as u32 >= max_redirects.min(MAX_FOLLOWED_REDIRECTS) {
+            return Err(HttpFailureReason::TooManyRedirects);
+        }
+
+        let next_request = redirect_request(&self.hop_request, status, location)
+            .map_err(HttpFailureReason::InvalidRedirect)?;
+        if let Some(redirect_validator) = redirect_validator {
+            redirect_validator(self.id, &next_request)
+                .map_err(HttpFailureReason::InvalidRedirect)?;
+        }
+
+        log!(
+            { request_id: self.id },
+            "http_over_ws: HTTP connection with id {} redirected with status {} to {}",
+            self.id,
+            status,
+            next_request.url
+        );
+
+        self.redirects.push(HttpRedirect {
+            status,
+            url: next_request.url.clone(),
+        });
+        self.hop_request = next_request.clone();
+        self.proxy_stats = None;
+
+        Ok(Some(next_request))
+    }
+
     pub(crate) fn get_response(&self) -> GetHttpResponseResult {
         match self.state {
             HttpConnectionState::WaitingForResponse(_) => Err(HttpOverWsError::NotYetReceived),
@@ -132,59 +467,93 @@ impl HttpConnection {
         }
     }
 
+    pub(crate) fn get_response_attestation(&self) -> Option<ResponseAttestation> {
+        self.response_attestation.clone()
+    }
+
+    /// Returns [None] until the response is received from the given proxy.
+    pub(crate) fn get_response_metadata(
+        &self,
+        proxy_principal: Principal,
+    ) -> Option<HttpResponseMetadata> {
+        matches!(self.state, HttpConnectionState::Success(_)).then(|| HttpResponseMetadata {
+            proxy_principal,
+            final_url: self.hop_request.url.clone(),
+            redirects: self.redirects.clone(),
+            timing: self.proxy_stats.as_ref().map(|stats| stats.timing.clone()),
+            body_size_bytes: self.proxy_stats.as_ref().map(|stats| stats.body_size_bytes),
+            proxy_attempts: self.proxy_attempts,
+        })
+    }
+
+    pub(crate) fn get_response_envelope(
+        &self,
+        proxy_principal: Principal,
+    ) -> GetHttpResponseEnvelopeResult {
+        match self.get_response()? {
+            HttpResult::Success(response) => Ok(HttpResponseEnvelope {
+                response,
+                metadata: self
+                    .get_response_metadata(proxy_principal)
+                    .expect("the metadata of a received response is available"),
+            }),
+            HttpResult::Failure(reason) => Err(HttpOverWsError::RequestFailed(reason)),
+        }
+    }
+
+    /// Stores the attestation along with the response, if the result is a response.
     pub(crate) fn update_state(
         &mut self,
         http_result: HttpResult,
+        response_attestation: Option<ResponseAttestation>,
     ) -> Option<HttpCallbackWithResult> {
         match &mut self.state {
-            HttpConnectionState::WaitingForResponse((timer_id, callback)) => {
-                match http_result {
-                    HttpResult::Success(response) => {
-                        // response has been received, clear the timer if it was set
-                        if let Some(timer_id) = timer_id.take() {
-                            ic_cdk_timers::clear_timer(timer_id);
-                        }
-
-                        log!(
-                            "http_over_ws: HTTP connection with id {} received response",
-                            self.id
-                        );
-
-                        let mut res = None;
-                        if let Some(callback) = callback.take() {
-                            res = Some((callback, HttpResult::Success(response.clone())));
-                        }
-
-                        self.state = HttpConnectionState::Success(response);
-
-                        return res;
+            HttpConnectionState::WaitingForResponse(callback) => match http_result {
+                HttpResult::Success(response) => {
+                    log!(
+                        { request_id: self.id },
+                        "http_over_ws: HTTP connection with id {} received response",
+                        self.id
+                    );
+
+                    let mut res = None;
+                    if let Some(callback) = callback.take() {
+                        res = Some((callback, HttpResult::Success(response.clone())));
                     }
-                    HttpResult::Failure(reason) => {
-                        log!(
-                            "http_over_ws: HTTP connection with id {} failed with reason {:?}",
-                            self.id,
-                            reason
-                        );
 
-                        let mut res = None;
-                        if let Some(callback) = callback.take() {
-                            res = Some((callback, HttpResult::Failure(reason.clone())));
-                        }
+                    self.state = HttpConnectionState::Success(response);
+                    self.response_attestation = response_attestation;
 
-                        self.state = HttpConnectionState::Failed(reason);
+                    return res;
+                }
+                HttpResult::Failure(reason) => {
+                    warn!(
+                        { request_id: self.id },
+                        "http_over_ws: HTTP connection with id {} failed with reason {:?}",
+                        self.id,
+                        reason
+                    );
 
-                        return res;
+                    let mut res = None;
+                    if let Some(callback) = callback.take() {
+                        res = Some((callback, HttpResult::Failure(reason.clone())));
                     }
+
+                    self.state = HttpConnectionState::Failed(reason);
+
+                    return res;
                 }
-            }
+            },
             HttpConnectionState::Failed(_) => {
-                log!(
+                warn!(
+                    { request_id: self.id },
                     "http_over_ws: HTTP connection with id {} has already failed",
                     self.id
                 );
             }
             HttpConnectionState::Success(_) => {
-                log!(
+                warn!(
+                    { request_id: self.id },
                     "http_over_ws: HTTP connection with id {} has already succeeded",
                     self.id
                 );
@@ -196,13 +565,13 @@ impl HttpConnection {
 
 #[derive(Clone)]
 pub(crate) enum HttpConnectionState {
-    WaitingForResponse((Option<TimerId>, Option<HttpCallback>)),
+    WaitingForResponse(Option<HttpCallback>),
     Failed(HttpFailureReason),
     Success(HttpResponse),
 }
 
 impl HttpConnectionState {
-    pub(crate) fn new(timer_id: Option<TimerId>, callback: Option<HttpCallback>) -> Self {
-        HttpConnectionState::WaitingForResponse((timer_id, callback))
+    pub(crate) fn new(callback: Option<HttpCallback>) -> Self {
+        HttpConnectionState::WaitingForResponse(callback)
     }
 }
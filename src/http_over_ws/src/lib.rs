@@ -1,8 +1,38 @@
 mod state;
+mod attestation;
+mod encryption;
 mod handlers;
 mod http_connection;
 mod client_proxy;
+mod metrics;
+mod protocol;
+mod redirects;
+mod secrets;
 
 // re-exports
+pub use attestation::{
+    request_hash, response_attestation_digest, ResponseAttestation, ResponseAttestationConfig,
+    ResponseSignature, MAX_RESPONSE_ATTESTATION_CLOCK_SKEW_NS,
+    RESPONSE_ATTESTATION_DOMAIN_SEPARATOR,
+};
+pub use encryption::{
+    decrypt_http_request, encrypt_http_response, encryption_public_key,
+    proxy_encryption_key_digest, EncryptedHttpRequest, ProxyEncryptionKey,
+    PAYLOAD_ENCRYPTION_DOMAIN_SEPARATOR, PROXY_ENCRYPTION_KEY_DOMAIN_SEPARATOR,
+};
+pub use client_proxy::{ProxyHealth, ProxyInfo, UNHEALTHY_PROXY_CONSECUTIVE_FAILURES};
 pub use handlers::*;
 pub use http_connection::*;
+pub use metrics::{
+    Histogram, HttpOverWsMetrics, ProxyErrorCount, ProxyMetrics, LATENCY_MS_BUCKETS,
+    SIZE_BYTES_BUCKETS,
+};
+pub use protocol::{
+    HttpOverWsEnvelope, HttpOverWsProtocolVersion, ProxyCapabilities, ProxyClientSetup,
+    HTTP_OVER_WS_PROTOCOL_V1, HTTP_OVER_WS_PROTOCOL_V2, SUPPORTED_PROTOCOL_VERSIONS,
+};
+pub use redirects::{HttpRedirect, RedirectPolicy, RedirectValidator, MAX_FOLLOWED_REDIRECTS};
+pub use secrets::{
+    is_valid_secret_name, secret_placeholder, secret_placeholder_names, SecretName,
+    MAX_SECRET_NAME_LENGTH, SECRET_PLACEHOLDER_PREFIX, SECRET_PLACEHOLDER_SUFFIX,
+};
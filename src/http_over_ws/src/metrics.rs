@@ -0,0 +1,261 @@
+use std::collections::BTreeMap;
+
+use candid::{CandidType, Deserialize, Principal};
+
+use crate::{HttpFailureReason, HttpRequest, HttpResponse, HttpResult, ProxyError};
+
+/// The upper bounds of the buckets of the latency histograms, in milliseconds.
+pub const LATENCY_MS_BUCKETS: [u64; 11] = [
+    10, 50, 100, 250, 500, 1_000, 2_500, 5_000, 10_000, 30_000, 60_000,
+];
+
+/// The upper bounds of the buckets of the request and response size histograms, in bytes.
+pub const SIZE_BYTES_BUCKETS: [u64; 9] = [
+    256, 1_024, 4_096, 16_384, 65_536, 262_144, 1_048_576, 2_097_152, 4_194_304,
+];
+
+/// A histogram with cumulative buckets, as in Prometheus.
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Histogram {
+    /// The upper bound of each bucket, with the amount of observations lower than or equal to it.
+    pub buckets: Vec<(u64, u64)>,
+    pub sum: u64,
+    pub count: u64,
+}
+
+impl Histogram {
+    pub fn new(bounds: &[u64]) -> Self {
+        Self {
+            buckets: bounds.iter().map(|bound| (*bound, 0)).collect(),
+            sum: 0,
+            count: 0,
+        }
+    }
+
+    pub fn observe(&mut self, value: u64) {
+        for (bound, count) in self.buckets.iter_mut() {
+            if value <= *bound {
+                *count += 1;
+            }
+        }
+        self.sum += value;
+        self.count += 1;
+    }
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq)]
+pub struct ProxyMetrics {
+    pub proxy_principal: Principal,
+    pub in_flight_requests: u64,
+    pub succeeded_requests: u64,
+    pub failed_requests: u64,
+    /// The ratio of succeeded over completed requests, [None] if no request has completed yet.
+    pub success_rate: Option<f64>,
+    pub latency_ms: Histogram,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ProxyErrorCount {
+    /// The [ProxyError::kind] of the errors.
+    pub kind: String,
+    pub count: u64,
+}
+
+/// The metrics of the requests executed by the library.
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq)]
+pub struct HttpOverWsMetrics {
+    /// The requests assigned to a proxy.
+    pub requests_started: u64,
+    pub requests_succeeded: u64,
+    pub requests_failed_timeout: u64,
+    pub requests_failed_proxy_error: u64,
+    pub requests_failed_invalid_attestation: u64,
+    pub requests_failed_invalid_encryption: u64,
+    /// The requests failed because a redirect couldn't be followed.
+    pub requests_failed_redirect: u64,
+    /// The requests that couldn't be started because no proxy was connected.
+    pub requests_rejected_no_proxies: u64,
+    /// The requests sent to another proxy after a retryable [ProxyError].
+    pub requests_retried: u64,
+    /// The errors reported by the proxies, including the ones of the retried requests.
+    pub proxy_errors: Vec<ProxyErrorCount>,
+    pub latency_ms: Histogram,
+    pub request_bytes: Histogram,
+    pub response_bytes: Histogram,
+    /// The metrics of the currently connected proxies,
+    /// which are reset when a proxy disconnects.
+    pub proxies: Vec<ProxyMetrics>,
+}
+
+pub(crate) struct Metrics {
+    requests_started: u64,
+    requests_succeeded: u64,
+    requests_failed_timeout: u64,
+    requests_failed_proxy_error: u64,
+    requests_failed_invalid_attestation: u64,
+    requests_failed_invalid_encryption: u64,
+    requests_failed_redirect: u64,
+    requests_rejected_no_proxies: u64,
+    requests_retried: u64,
+    proxy_errors: BTreeMap<&'static str, u64>,
+    latency_ms: Histogram,
+    request_bytes: Histogram,
+    response_bytes: Histogram,
+}
+
+impl Metrics {
+    pub(crate) fn new() -> Self {
+        Self {
+            requests_started: 0,
+            requests_succeeded: 0,
+            requests_failed_timeout: 0,
+            requests_failed_proxy_error: 0,
+            requests_failed_invalid_attestation: 0,
+            requests_failed_invalid_encryption: 0,
+            requests_failed_redirect: 0,
+            requests_rejected_no_proxies: 0,
+            requests_retried: 0,
+            proxy_errors: BTreeMap::new(),
+            latency_ms: Histogram::new(&LATENCY_MS_BUCKETS),
+            request_bytes: Histogram::new(&SIZE_BYTES_BUCKETS),
+            response_bytes: Histogram::new(&SIZE_BYTES_BUCKETS),
+        }
+    }
+
+    pub(crate) fn record_request_started(&mut self, request: &HttpRequest) {
+        self.requests_started += 1;
+        self.request_bytes.observe(request_size_bytes(request));
+    }
+
+    pub(crate) fn record_request_rejected(&mut self) {
+        self.requests_rejected_no_proxies += 1;
+    }
+
+    pub(crate) fn record_request_retried(&mut self, proxy_error: &ProxyError) {
+        self.requests_retried += 1;
+        self.record_proxy_error(proxy_error);
+    }
+
+    pub(crate) fn record_request_completed(&mut self, http_result: &HttpResult, latency_ms: u64) {
+        match http_result {
+            HttpResult::Success(response) => {
+                self.requests_succeeded += 1;
+                self.response_bytes.observe(response_size_bytes(response));
+            }
+            HttpResult::Failure(HttpFailureReason::RequestTimeout) => {
+                self.requests_failed_timeout += 1;
+            }
+            HttpResult::Failure(HttpFailureReason::ProxyError(proxy_error)) => {
+                self.requests_failed_proxy_error += 1;
+                self.record_proxy_error(proxy_error);
+            }
+            HttpResult::Failure(HttpFailureReason::InvalidResponseAttestation(_)) => {
+                self.requests_failed_invalid_attestation += 1;
+            }
+            HttpResult::Failure(HttpFailureReason::InvalidEncryptedResponse(_)) => {
+                self.requests_failed_invalid_encryption += 1;
+            }
+            HttpResult::Failure(
+                HttpFailureReason::RedirectNotAllowed(_)
+                | HttpFailureReason::TooManyRedirects
+                | HttpFailureReason::InvalidRedirect(_),
+            ) => {
+                self.requests_failed_redirect += 1;
+            }
+        }
+        self.latency_ms.observe(latency_ms);
+    }
+
+    fn record_proxy_error(&mut self, proxy_error: &ProxyError) {
+        *self.proxy_errors.entry(proxy_error.kind()).or_insert(0) += 1;
+    }
+
+    pub(crate) fn to_http_over_ws_metrics(&self, proxies: Vec<ProxyMetrics>) -> HttpOverWsMetrics {
+        HttpOverWsMetrics {
+            requests_started: self.requests_started,
+            requests_succeeded: self.requests_succeeded,
+            requests_failed_timeout: self.requests_failed_timeout,
+            requests_failed_proxy_error: self.requests_failed_proxy_error,
+            requests_failed_invalid_attestation: self.requests_failed_invalid_attestation,
+            requests_failed_invalid_encryption: self.requests_failed_invalid_encryption,
+            requests_failed_redirect: self.requests_failed_redirect,
+            requests_rejected_no_proxies: self.requests_rejected_no_proxies,
+            requests_retried: self.requests_retried,
+            proxy_errors: self
+                .proxy_errors
+                .iter()
+                .map(|(kind, count)| ProxyErrorCount {
+                    kind: kind.to_string(),
+                    count: *count,
+                })
+                .collect(),
+            latency_ms: self.latency_ms.clone(),
+            request_bytes: self.request_bytes.clone(),
+            response_bytes: self.response_bytes.clone(),
+            proxies,
+        }
+    }
+}
+
+/// The metrics of the requests completed by a single proxy.
+pub(crate) struct ClientProxyMetrics {
+    succeeded_requests: u64,
+    failed_requests: u64,
+    latency_ms: Histogram,
+}
+
+impl ClientProxyMetrics {
+    pub(crate) fn new() -> Self {
+        Self {
+            succeeded_requests: 0,
+            failed_requests: 0,
+            latency_ms: Histogram::new(&LATENCY_MS_BUCKETS),
+        }
+    }
+
+    pub(crate) fn record_request_completed(&mut self, http_result: &HttpResult, latency_ms: u64) {
+        match http_result {
+            HttpResult::Success(_) => self.succeeded_requests += 1,
+            HttpResult::Failure(_) => self.failed_requests += 1,
+        }
+        self.latency_ms.observe(latency_ms);
+    }
+
+    pub(crate) fn to_proxy_metrics(
+        &self,
+        proxy_principal: Principal,
+        in_flight_requests: u64,
+    ) -> ProxyMetrics {
+        let completed_requests = self.succeeded_requests + self.failed_requests;
+
+        ProxyMetrics {
+            proxy_principal,
+            in_flight_requests,
+            succeeded_requests: self.succeeded_requests,
+            failed_requests: self.failed_requests,
+            success_rate: (completed_requests > 0)
+                .then(|| self.succeeded_requests as f64 / completed_requests as f64),
+            latency_ms: self.latency_ms.clone(),
+        }
+    }
+}
+
+fn request_size_bytes(request: &HttpRequest) -> u64 {
+    let headers_size: usize = request
+        .headers
+        .iter()
+        .map(|header| header.name.len() + header.value.len())
+        .sum();
+
+    (request.url.len() + headers_size + request.body.as_ref().map_or(0, |body| body.len())) as u64
+}
+
+fn response_size_bytes(response: &HttpResponse) -> u64 {
+    let headers_size: usize = response
+        .headers
+        .iter()
+        .map(|header| header.name.len() + header.value.len())
+        .sum();
+
+    (headers_size + response.body.len()) as u64
+}
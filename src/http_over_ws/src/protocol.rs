@@ -0,0 +1,100 @@
+use candid::{decode_one, encode_one, CandidType, Deserialize};
+
+use crate::{encryption::ProxyEncryptionKey, secrets::SecretName, HttpMethod, HttpOverWsMessage};
+
+pub type HttpOverWsProtocolVersion = u32;
+
+/// The messages are sent as a bare [HttpOverWsMessage].
+/// Spoken by the proxies that set up with [HttpOverWsMessage::SetupProxyClient].
+pub const HTTP_OVER_WS_PROTOCOL_V1: HttpOverWsProtocolVersion = 1;
+/// The messages are wrapped in an [HttpOverWsEnvelope]
+/// and the version is negotiated with [HttpOverWsMessage::SetupVersionedProxyClient].
+pub const HTTP_OVER_WS_PROTOCOL_V2: HttpOverWsProtocolVersion = 2;
+
+/// The protocol versions the canister can speak, from the oldest to the newest.
+pub const SUPPORTED_PROTOCOL_VERSIONS: [HttpOverWsProtocolVersion; 2] =
+    [HTTP_OVER_WS_PROTOCOL_V1, HTTP_OVER_WS_PROTOCOL_V2];
+
+/// Wraps the messages of the protocol versions greater than [HTTP_OVER_WS_PROTOCOL_V1],
+/// so that the receiver knows how to interpret them.
+#[derive(CandidType, Debug, Deserialize, PartialEq, Eq)]
+pub struct HttpOverWsEnvelope {
+    pub version: HttpOverWsProtocolVersion,
+    pub message: HttpOverWsMessage,
+}
+
+#[derive(CandidType, Clone, Debug, Deserialize, PartialEq, Eq)]
+pub struct ProxyClientSetup {
+    /// The protocol versions the proxy can speak.
+    pub supported_protocol_versions: Vec<HttpOverWsProtocolVersion>,
+    /// Defaults to [ProxyCapabilities::default] if not advertised.
+    pub capabilities: Option<ProxyCapabilities>,
+    /// The key with which the payloads exchanged with the proxy are encrypted, if any.
+    /// Accepted only if signed with the attestation key registered for the proxy.
+    pub encryption_key: Option<ProxyEncryptionKey>,
+}
+
+/// What a proxy can do, advertised during the setup.
+/// Requests are only assigned to the proxies capable of executing them.
+#[derive(CandidType, Clone, Debug, Deserialize, PartialEq, Eq)]
+pub struct ProxyCapabilities {
+    pub http_methods: Vec<HttpMethod>,
+    /// The names of the secrets configured on the proxy, which it substitutes for their placeholders,
+    /// see [secret_placeholder](crate::secret_placeholder).
+    pub secret_names: Option<Vec<SecretName>>,
+}
+
+impl Default for ProxyCapabilities {
+    /// The capabilities of the proxies that don't advertise them,
+    /// including the ones speaking [HTTP_OVER_WS_PROTOCOL_V1].
+    fn default() -> Self {
+        ProxyCapabilities {
+            http_methods: vec![
+                HttpMethod::GET,
+                HttpMethod::POST,
+                HttpMethod::PUT,
+                HttpMethod::HEAD,
+                HttpMethod::DELETE,
+            ],
+            secret_names: None,
+        }
+    }
+}
+
+/// Returns the newest version that both the proxy and the canister can speak,
+/// not older than the given minimum version.
+pub(crate) fn negotiate_protocol_version(
+    proxy_supported_versions: &[HttpOverWsProtocolVersion],
+    min_version: HttpOverWsProtocolVersion,
+) -> Option<HttpOverWsProtocolVersion> {
+    SUPPORTED_PROTOCOL_VERSIONS
+        .iter()
+        .rev()
+        .find(|version| **version >= min_version && proxy_supported_versions.contains(version))
+        .copied()
+}
+
+/// Encodes the message in the format of the given protocol version.
+pub(crate) fn encode_message(
+    message: HttpOverWsMessage,
+    version: HttpOverWsProtocolVersion,
+) -> Vec<u8> {
+    if version == HTTP_OVER_WS_PROTOCOL_V1 {
+        encode_one(message).unwrap()
+    } else {
+        encode_one(HttpOverWsEnvelope { version, message }).unwrap()
+    }
+}
+
+/// Decodes a message sent in the format of any protocol version,
+/// returning it along with the version of the format.
+pub(crate) fn decode_message(
+    bytes: &[u8],
+) -> Result<(HttpOverWsProtocolVersion, HttpOverWsMessage), String> {
+    match decode_one::<HttpOverWsEnvelope>(bytes) {
+        Ok(envelope) => Ok((envelope.version, envelope.message)),
+        Err(_) => decode_one::<HttpOverWsMessage>(bytes)
+            .map(|message| (HTTP_OVER_WS_PROTOCOL_V1, message))
+            .map_err(|e| e.to_string()),
+    }
+}
@@ -0,0 +1,108 @@
+use candid::{CandidType, Deserialize};
+use url::Url;
+
+use crate::{
+    secrets::SECRET_PLACEHOLDER_PREFIX, HttpMethod, HttpRequest, HttpRequestId, HttpResponse,
+};
+
+/// The maximum number of redirects followed for a request, whatever its [RedirectPolicy].
+pub const MAX_FOLLOWED_REDIRECTS: u32 = 20;
+
+/// Validates each request about to be sent to follow a redirect, see [set_redirect_validator](crate::set_redirect_validator).
+/// Takes the id of the redirected request and the request for the next hop.
+pub type RedirectValidator = fn(HttpRequestId, &HttpRequest) -> Result<(), String>;
+
+/// What to do when the response to a request is a redirect.
+///
+/// The redirects are followed by the canister: the proxies receive the requests with [RedirectPolicy::Manual]
+/// and the canister sends them the request for the next hop, so that every hop can be validated.
+#[derive(CandidType, Clone, Debug, Deserialize, PartialEq, Eq)]
+pub enum RedirectPolicy {
+    /// Follows at most the given number of redirects, failing the request if there are more.
+    Follow(u32),
+    /// Returns the redirect response as it is.
+    Manual,
+    /// Fails the request if the response is a redirect.
+    Error,
+}
+
+/// A redirect followed for a request.
+#[derive(CandidType, Clone, Debug, Deserialize, PartialEq, Eq)]
+pub struct HttpRedirect {
+    /// The status of the redirect response.
+    pub status: u16,
+    /// The absolute URL the request has been redirected to.
+    pub url: String,
+}
+
+/// Returns the request sent to the proxies, which never follow the redirects on their own
+/// if the canister has to follow them.
+pub(crate) fn proxy_request(request: &HttpRequest) -> HttpRequest {
+    let mut request = request.clone();
+    if matches!(
+        request.redirect,
+        Some(RedirectPolicy::Follow(_) | RedirectPolicy::Error)
+    ) {
+        request.redirect = Some(RedirectPolicy::Manual);
+    }
+    request
+}
+
+/// Returns the status and the `Location` header of the response, if it is a redirect.
+pub(crate) fn redirect_location(response: &HttpResponse) -> Option<(u16, &str)> {
+    let status = u16::try_from(&response.status.0).ok()?;
+    if !matches!(status, 301 | 302 | 303 | 307 | 308) {
+        return None;
+    }
+
+    response
+        .headers
+        .iter()
+        .find(|header| header.name.eq_ignore_ascii_case("location"))
+        .map(|header| (status, header.value.as_str()))
+}
+
+/// Returns the request that follows the redirect, as a browser would (RFC 9110, section 15.4).
+///
+/// The method becomes `GET` without a body after a `303` and after a `301` or `302` to a `POST`.
+/// The credentials and the headers referencing secrets are not sent to a different origin.
+pub(crate) fn redirect_request(
+    request: &HttpRequest,
+    status: u16,
+    location: &str,
+) -> Result<HttpRequest, String> {
+    // the proxies would substitute the secrets chosen by the server
+    if location.contains(SECRET_PLACEHOLDER_PREFIX) {
+        return Err("the location references a secret".to_string());
+    }
+
+    let current_url = Url::parse(&request.url).map_err(|e| e.to_string())?;
+    let url = current_url
+        .join(location)
+        .map_err(|e| format!("invalid location {}: {}", location, e))?;
+
+    let mut next_request = request.clone();
+    next_request.url = url.to_string();
+
+    if status == 303 && request.method != HttpMethod::HEAD
+        || matches!(status, 301 | 302) && request.method == HttpMethod::POST
+    {
+        next_request.method = HttpMethod::GET;
+        next_request.body = None;
+        next_request.headers.retain(|header| {
+            !header.name.eq_ignore_ascii_case("content-type")
+                && !header.name.eq_ignore_ascii_case("content-length")
+        });
+    }
+
+    if url.origin() != current_url.origin() {
+        next_request.headers.retain(|header| {
+            !["authorization", "proxy-authorization", "cookie"]
+                .iter()
+                .any(|name| header.name.eq_ignore_ascii_case(name))
+                && !header.value.contains(SECRET_PLACEHOLDER_PREFIX)
+        });
+    }
+
+    Ok(next_request)
+}
@@ -0,0 +1,61 @@
+use std::collections::BTreeSet;
+
+use crate::HttpRequest;
+
+/// The name of a secret configured locally on the proxies.
+pub type SecretName = String;
+
+/// Starts a placeholder, followed by the name of the secret and [SECRET_PLACEHOLDER_SUFFIX].
+pub const SECRET_PLACEHOLDER_PREFIX: &str = "{{secret:";
+
+pub const SECRET_PLACEHOLDER_SUFFIX: &str = "}}";
+
+/// The maximum length of the name of a secret.
+pub const MAX_SECRET_NAME_LENGTH: usize = 64;
+
+/// Returns the placeholder that the proxies replace with the value of the secret,
+/// in the url or in the header values of a request.
+///
+/// The placeholders are sent to the proxies as they are: the canister never sees the values of the secrets.
+pub fn secret_placeholder(name: &str) -> String {
+    format!(
+        "{}{}{}",
+        SECRET_PLACEHOLDER_PREFIX, name, SECRET_PLACEHOLDER_SUFFIX
+    )
+}
+
+/// Whether the name can be used in a placeholder: ASCII alphanumeric characters, `_`, `-` and `.`,
+/// at most [MAX_SECRET_NAME_LENGTH] characters.
+pub fn is_valid_secret_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= MAX_SECRET_NAME_LENGTH
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "_-.".contains(c))
+}
+
+/// Returns the names of the secrets referenced by the placeholders in the url and in the header values of the request.
+///
+/// Fails if a placeholder is not terminated or if the name of a secret is not valid.
+pub fn secret_placeholder_names(request: &HttpRequest) -> Result<BTreeSet<SecretName>, String> {
+    let mut names = BTreeSet::new();
+
+    for value in std::iter::once(&request.url).chain(request.headers.iter().map(|h| &h.value)) {
+        let mut rest = value.as_str();
+        while let Some(start) = rest.find(SECRET_PLACEHOLDER_PREFIX) {
+            rest = &rest[start + SECRET_PLACEHOLDER_PREFIX.len()..];
+            let end = rest
+                .find(SECRET_PLACEHOLDER_SUFFIX)
+                .ok_or_else(|| "unterminated secret placeholder".to_string())?;
+            let name = &rest[..end];
+            if !is_valid_secret_name(name) {
+                return Err(format!("invalid secret name: {}", name));
+            }
+
+            names.insert(name.to_string());
+            rest = &rest[end + SECRET_PLACEHOLDER_SUFFIX.len()..];
+        }
+    }
+
+    Ok(names)
+}
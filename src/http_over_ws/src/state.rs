@@ -1,13 +1,32 @@
 use crate::{
-    client_proxy::ClientProxy,
+    attestation::{
+        ResponseAttestation, ResponseAttestationConfig, ResponseSignature,
+        MAX_RESPONSE_ATTESTATION_CLOCK_SKEW_NS,
+    },
+    client_proxy::{ClientProxy, ProxyInfo},
+    encryption::{decrypt_http_response, encrypt_http_request, ProxyEncryptionKey},
     http_connection::{
-        GetHttpResponseResult, HttpCallback, HttpConnection, HttpFailureReason, HttpRequest,
-        HttpRequestId, HttpRequestTimeoutMs,
+        GetHttpResponseEnvelopeResult, GetHttpResponseResult, HttpCallback, HttpConnection,
+        HttpFailureReason, HttpRequest, HttpRequestDeadline, HttpRequestId, HttpRequestTimeoutMs,
+        HttpResponse, HttpResponseMetadata, ProxyError, ProxyResponseStats, WebhookHandler,
+        DEADLINES_SWEEP_INTERVAL_MS, DEFAULT_HTTP_REQUEST_TIMEOUT_MS,
     },
-    trigger_callback_with_result, HttpCallbackWithResult, HttpOverWsError, HttpResult,
+    metrics::{HttpOverWsMetrics, Metrics},
+    protocol::{HttpOverWsProtocolVersion, ProxyCapabilities, HTTP_OVER_WS_PROTOCOL_V1},
+    redirects::RedirectValidator,
+    secrets::secret_placeholder_names,
+    trigger_callback_with_result, HttpCallbackWithResult, HttpOverWsError, HttpOverWsMessage,
+    HttpResult,
 };
 use candid::Principal;
-use std::{cell::RefCell, collections::HashMap, time::Duration};
+use ic_cdk::api::time;
+use ic_cdk_timers::TimerId;
+use logger::warn;
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, HashMap},
+    time::Duration,
+};
 
 // local state
 thread_local! {
@@ -17,6 +36,22 @@ thread_local! {
 pub(crate) struct State {
     connected_proxies: ConnectedProxies,
     next_request_id: HttpRequestId,
+    webhook_handlers: HashMap<String, WebhookHandler>,
+    default_webhook_handler: Option<WebhookHandler>,
+    metrics: Metrics,
+    default_timeout_ms: HttpRequestTimeoutMs,
+    /// The proxies of the requests waiting for a response, by deadline.
+    deadlines: BTreeMap<(u64, HttpRequestId), Principal>,
+    /// The timer that periodically fails the overdue requests, set only while there are requests waiting for a response.
+    deadlines_sweeper: Option<TimerId>,
+    min_protocol_version: HttpOverWsProtocolVersion,
+    /// The keys with which the proxies attest their responses, kept across reconnections.
+    proxy_attestation_keys: BTreeMap<Principal, Vec<u8>>,
+    response_attestations_required: bool,
+    /// The secret from which the keys encrypting the payloads are derived, never persisted.
+    payload_encryption_seed: Option<[u8; 32]>,
+    payload_encryption_required: bool,
+    redirect_validator: Option<RedirectValidator>,
 }
 
 impl State {
@@ -24,85 +59,611 @@ impl State {
         State {
             connected_proxies: ConnectedProxies::new(),
             next_request_id: 0,
+            webhook_handlers: HashMap::new(),
+            default_webhook_handler: None,
+            metrics: Metrics::new(),
+            default_timeout_ms: DEFAULT_HTTP_REQUEST_TIMEOUT_MS,
+            deadlines: BTreeMap::new(),
+            deadlines_sweeper: None,
+            min_protocol_version: HTTP_OVER_WS_PROTOCOL_V1,
+            proxy_attestation_keys: BTreeMap::new(),
+            response_attestations_required: false,
+            payload_encryption_seed: None,
+            payload_encryption_required: false,
+            redirect_validator: None,
+        }
+    }
+
+    pub(crate) fn set_default_timeout_ms(&mut self, timeout_ms: HttpRequestTimeoutMs) {
+        self.default_timeout_ms = timeout_ms;
+    }
+
+    pub(crate) fn min_protocol_version(&self) -> HttpOverWsProtocolVersion {
+        self.min_protocol_version
+    }
+
+    pub(crate) fn set_min_protocol_version(&mut self, version: HttpOverWsProtocolVersion) {
+        self.min_protocol_version = version;
+    }
+
+    pub(crate) fn set_proxy_attestation_key(
+        &mut self,
+        proxy_principal: Principal,
+        public_key: Option<Vec<u8>>,
+    ) {
+        match public_key {
+            Some(public_key) => {
+                self.proxy_attestation_keys
+                    .insert(proxy_principal, public_key);
+            }
+            None => {
+                self.proxy_attestation_keys.remove(&proxy_principal);
+            }
         }
     }
 
-    pub(crate) fn add_proxy(&mut self, proxy_principal: Principal) {
-        self.connected_proxies.add_proxy(proxy_principal);
+    pub(crate) fn set_response_attestations_required(&mut self, required: bool) {
+        self.response_attestations_required = required;
+    }
+
+    pub(crate) fn get_response_attestation_config(&self) -> ResponseAttestationConfig {
+        ResponseAttestationConfig {
+            required: self.response_attestations_required,
+            proxy_public_keys: self
+                .proxy_attestation_keys
+                .iter()
+                .map(|(proxy_principal, public_key)| (*proxy_principal, public_key.clone()))
+                .collect(),
+        }
+    }
+
+    /// Verifies the signature of a response with the key registered for the proxy that sent it,
+    /// returning the attestation to store along with the response.
+    ///
+    /// The signature is ignored if no key is registered for the proxy,
+    /// in which case the response is accepted only if attestations are not required.
+    pub(crate) fn attest_response(
+        &self,
+        proxy_principal: Principal,
+        request_id: HttpRequestId,
+        response: &HttpResponse,
+        signature: Option<ResponseSignature>,
+        now_ns: u64,
+    ) -> Result<Option<ResponseAttestation>, String> {
+        let Some(public_key) = self.proxy_attestation_keys.get(&proxy_principal) else {
+            if self.response_attestations_required {
+                return Err(format!(
+                    "no attestation key is registered for client proxy {}",
+                    proxy_principal
+                ));
+            }
+            return Ok(None);
+        };
+        let Some(signature) = signature else {
+            return Err("the response is not attested".to_string());
+        };
+        // the responses for the connections not assigned to the proxy are discarded when updating the state
+        let Some(connection) = self
+            .connected_proxies
+            .proxies
+            .get(&proxy_principal)
+            .and_then(|proxy| proxy.get_connection(request_id))
+        else {
+            return Ok(None);
+        };
+
+        if signature
+            .timestamp_ns
+            .saturating_add(MAX_RESPONSE_ATTESTATION_CLOCK_SKEW_NS)
+            < connection.started_at_ns()
+            || signature.timestamp_ns
+                > now_ns.saturating_add(MAX_RESPONSE_ATTESTATION_CLOCK_SKEW_NS)
+        {
+            return Err(format!(
+                "the attestation timestamp {} is out of range",
+                signature.timestamp_ns
+            ));
+        }
+
+        let attestation = ResponseAttestation {
+            proxy_principal,
+            public_key: public_key.clone(),
+            timestamp_ns: signature.timestamp_ns,
+            signature: signature.signature,
+        };
+        attestation
+            .verify(request_id, &connection.get_hop_request(), response)
+            .map_err(|e| format!("invalid attestation signature: {}", e))?;
+
+        Ok(Some(attestation))
     }
 
+    pub(crate) fn set_payload_encryption_seed(&mut self, seed: [u8; 32]) {
+        self.payload_encryption_seed = Some(seed);
+    }
+
+    pub(crate) fn set_payload_encryption_required(&mut self, required: bool) {
+        self.payload_encryption_required = required;
+    }
+
+    pub(crate) fn is_payload_encryption_required(&self) -> bool {
+        self.payload_encryption_required
+    }
+
+    /// Verifies the encryption key announced by the proxy with the attestation key registered for it.
+    pub(crate) fn verify_proxy_encryption_key(
+        &self,
+        proxy_principal: Principal,
+        encryption_key: &ProxyEncryptionKey,
+    ) -> Result<[u8; 32], String> {
+        let attestation_key = self
+            .proxy_attestation_keys
+            .get(&proxy_principal)
+            .ok_or_else(|| {
+                format!(
+                    "no attestation key is registered for client proxy {}",
+                    proxy_principal
+                )
+            })?;
+
+        encryption_key.verify(&proxy_principal, attestation_key)
+    }
+
+    /// Returns the public key with which the payloads exchanged with the proxy are encrypted,
+    /// if the encryption is initialized and the proxy announced a key.
+    fn get_payload_encryption_key(
+        &self,
+        proxy_principal: &Principal,
+    ) -> Option<(&[u8; 32], &[u8; 32])> {
+        let seed = self.payload_encryption_seed.as_ref()?;
+        let proxy_public_key = self
+            .connected_proxies
+            .proxies
+            .get(proxy_principal)?
+            .encryption_public_key()?;
+
+        Some((seed, proxy_public_key))
+    }
+
+    /// Whether the request for the current hop of the connection has been sent encrypted.
+    pub(crate) fn is_request_encrypted(&self, request_id: HttpRequestId) -> bool {
+        self.connected_proxies
+            .get_connection(request_id)
+            .is_some_and(|connection| connection.is_encrypted())
+    }
+
+    /// Returns the message with which the request is sent to the proxy, encrypted if possible,
+    /// and records on the connection whether it has been encrypted.
+    pub(crate) fn http_request_message(
+        &mut self,
+        proxy_principal: &Principal,
+        request_id: HttpRequestId,
+        request: HttpRequest,
+    ) -> HttpOverWsMessage {
+        let hop = self
+            .connected_proxies
+            .get_connection(request_id)
+            .map_or(0, |connection| connection.hop());
+        let message = match self.get_payload_encryption_key(proxy_principal) {
+            Some((seed, proxy_public_key)) => HttpOverWsMessage::EncryptedHttpRequest(
+                request_id,
+                encrypt_http_request(seed, request_id, hop, proxy_public_key, &request),
+            ),
+            None => HttpOverWsMessage::HttpRequest(request_id, request),
+        };
+
+        if let Some(connection) = self.connected_proxies.get_connection_mut(request_id) {
+            connection.set_encrypted(matches!(
+                message,
+                HttpOverWsMessage::EncryptedHttpRequest(..)
+            ));
+        }
+
+        message
+    }
+
+    pub(crate) fn decrypt_http_response(
+        &self,
+        proxy_principal: &Principal,
+        request_id: HttpRequestId,
+        ciphertext: &[u8],
+    ) -> Result<HttpResponse, String> {
+        let connection = self
+            .connected_proxies
+            .get_connection(request_id)
+            .filter(|connection| connection.is_encrypted())
+            .ok_or_else(|| "the request has not been encrypted".to_string())?;
+        let (seed, proxy_public_key) = self
+            .get_payload_encryption_key(proxy_principal)
+            .ok_or_else(|| "the request has not been encrypted".to_string())?;
+
+        decrypt_http_response(
+            seed,
+            request_id,
+            connection.hop(),
+            proxy_public_key,
+            ciphertext,
+        )
+    }
+
+    pub(crate) fn add_proxy(
+        &mut self,
+        proxy_principal: Principal,
+        protocol_version: HttpOverWsProtocolVersion,
+        capabilities: ProxyCapabilities,
+        encryption_public_key: Option<[u8; 32]>,
+    ) {
+        self.connected_proxies.add_proxy(
+            proxy_principal,
+            protocol_version,
+            capabilities,
+            encryption_public_key,
+        );
+    }
+
+    /// Returns the protocol version negotiated with the proxy,
+    /// or [HTTP_OVER_WS_PROTOCOL_V1] if the proxy is not set up.
+    pub(crate) fn get_proxy_protocol_version(
+        &self,
+        proxy_principal: &Principal,
+    ) -> HttpOverWsProtocolVersion {
+        self.connected_proxies
+            .proxies
+            .get(proxy_principal)
+            .map_or(HTTP_OVER_WS_PROTOCOL_V1, |proxy| proxy.protocol_version())
+    }
+
+    /// Removes the proxy, failing the requests still waiting for its responses.
+    /// Returns the callbacks of these requests with their results.
     pub(crate) fn remove_proxy(
         &mut self,
         proxy_principal: &Principal,
-    ) -> Result<(), HttpOverWsError> {
-        self.connected_proxies.remove_proxy(proxy_principal)
+    ) -> Result<Vec<(HttpRequestId, Option<HttpCallbackWithResult>)>, HttpOverWsError> {
+        let proxy = self.connected_proxies.remove_proxy(proxy_principal)?;
+
+        Ok(self.fail_pending_connections(proxy))
+    }
+
+    pub(crate) fn is_proxy_connected(&self, proxy_principal: &Principal) -> bool {
+        self.connected_proxies.proxies.contains_key(proxy_principal)
     }
 
     pub(crate) fn get_connected_proxies(&self) -> Vec<Principal> {
         self.connected_proxies.get_all_proxies_principals()
     }
 
-    pub(crate) fn remove_all_proxies(&mut self) {
-        self.connected_proxies.remove_all_proxies();
+    /// Like [State::remove_proxy], for all the connected proxies.
+    pub(crate) fn remove_all_proxies(
+        &mut self,
+    ) -> Vec<(HttpRequestId, Option<HttpCallbackWithResult>)> {
+        self.connected_proxies
+            .remove_all_proxies()
+            .into_iter()
+            .flat_map(|proxy| self.fail_pending_connections(proxy))
+            .collect()
+    }
+
+    /// Fails the connections of a removed proxy that are still waiting for a response,
+    /// so that their requests don't wait until their deadline.
+    fn fail_pending_connections(
+        &mut self,
+        proxy: ClientProxy,
+    ) -> Vec<(HttpRequestId, Option<HttpCallbackWithResult>)> {
+        let http_result = HttpResult::Failure(HttpFailureReason::ProxyError(ProxyError::Unknown(
+            Some("proxy disconnected".to_string()),
+        )));
+
+        proxy
+            .into_connections()
+            .into_iter()
+            .filter(|(_, connection)| connection.is_waiting_for_response())
+            .map(|(request_id, mut connection)| {
+                self.deadlines
+                    .remove(&(connection.deadline_ns(), request_id));
+                self.metrics
+                    .record_request_completed(&http_result, connection.elapsed_ms());
+
+                (
+                    request_id,
+                    connection.update_state(http_result.clone(), None),
+                )
+            })
+            .collect()
     }
 
     pub(crate) fn assign_connection(
         &mut self,
         request: HttpRequest,
         callback: Option<HttpCallback>,
-        timeout_ms: Option<HttpRequestTimeoutMs>,
+        deadline: Option<HttpRequestDeadline>,
     ) -> Result<(Principal, HttpRequestId), HttpOverWsError> {
         let request_id = self.next_request_id();
 
-        let proxy_principal = self
-            .get_proxy_for_connection(request_id)
-            .ok_or(HttpOverWsError::NoProxiesConnected)?
-            .clone();
-
-        let timer_id = timeout_ms.and_then(|millis| {
-            Some(ic_cdk_timers::set_timer(
-                Duration::from_millis(millis),
-                move || {
-                    let callback_with_result = http_connection_timeout(proxy_principal, request_id);
-                    trigger_callback_with_result(request_id, callback_with_result);
-                },
-            ))
-        });
-
-        let connection = HttpConnection::new(request_id, request, callback, timer_id);
+        let proxy_principal = match self.get_proxy_for_connection(request_id, &request, None) {
+            Ok(proxy_principal) => proxy_principal,
+            Err(e) => {
+                self.metrics.record_request_rejected();
+                return Err(e);
+            }
+        };
+
+        let timeout_to_ns = |timeout_ms: HttpRequestTimeoutMs| {
+            time().saturating_add(timeout_ms.saturating_mul(1_000_000))
+        };
+        let deadline_ns = match deadline {
+            Some(HttpRequestDeadline::TimeoutMs(timeout_ms)) => timeout_to_ns(timeout_ms),
+            Some(HttpRequestDeadline::AtNs(deadline_ns)) => deadline_ns,
+            None => timeout_to_ns(self.default_timeout_ms),
+        };
+
+        self.metrics.record_request_started(&request);
+        let connection = HttpConnection::new(request_id, request, callback, deadline_ns);
 
         self.connected_proxies.assign_connection_to_proxy(
             &proxy_principal,
             request_id,
             connection,
         )?;
+
+        self.deadlines
+            .insert((deadline_ns, request_id), proxy_principal);
+        if self.deadlines_sweeper.is_none() {
+            self.deadlines_sweeper = Some(ic_cdk_timers::set_timer_interval(
+                Duration::from_millis(DEADLINES_SWEEP_INTERVAL_MS),
+                sweep_overdue_connections,
+            ));
+        }
+
         Ok((proxy_principal, request_id))
     }
 
-    fn next_request_id(&mut self) -> HttpRequestId {
+    /// Fails the requests whose deadline has passed, returning their callbacks with the results.
+    /// The sweeper timer is cleared once no request is waiting for a response.
+    fn fail_overdue_connections(
+        &mut self,
+        now_ns: u64,
+    ) -> Vec<(HttpRequestId, Option<HttpCallbackWithResult>)> {
+        let mut callbacks_with_results = Vec::new();
+
+        while let Some(entry) = self.deadlines.first_entry() {
+            if entry.key().0 > now_ns {
+                break;
+            }
+            let ((_, request_id), proxy_principal) = entry.remove_entry();
+
+            match self.update_connection_state(
+                proxy_principal,
+                request_id,
+                HttpResult::Failure(HttpFailureReason::RequestTimeout),
+                None,
+            ) {
+                Ok(callback_with_result) => {
+                    callbacks_with_results.push((request_id, callback_with_result))
+                }
+                Err(e) => {
+                    warn!(
+                        { request_id: request_id, proxy_principal: proxy_principal },
+                        "http_over_ws: error {:?} while failing overdue request with id: {}",
+                        e,
+                        request_id
+                    );
+                }
+            }
+        }
+
+        if self.deadlines.is_empty() {
+            if let Some(timer_id) = self.deadlines_sweeper.take() {
+                ic_cdk_timers::clear_timer(timer_id);
+            }
+        }
+
+        callbacks_with_results
+    }
+
+    pub(crate) fn set_webhook_handler(&mut self, path: String, handler: WebhookHandler) {
+        self.webhook_handlers.insert(path, handler);
+    }
+
+    pub(crate) fn remove_webhook_handler(&mut self, path: &str) {
+        self.webhook_handlers.remove(path);
+    }
+
+    pub(crate) fn set_default_webhook_handler(&mut self, handler: Option<WebhookHandler>) {
+        self.default_webhook_handler = handler;
+    }
+
+    pub(crate) fn set_redirect_validator(&mut self, validator: Option<RedirectValidator>) {
+        self.redirect_validator = validator;
+    }
+
+    /// Returns the handler registered for the path, or the default handler if there is none.
+    pub(crate) fn get_webhook_handler(&self, path: &str) -> Option<WebhookHandler> {
+        self.webhook_handlers
+            .get(path)
+            .copied()
+            .or(self.default_webhook_handler)
+    }
+
+    pub(crate) fn next_request_id(&mut self) -> HttpRequestId {
         self.next_request_id += 1;
         self.next_request_id
     }
 
-    fn get_proxy_for_connection(&self, request_id: HttpRequestId) -> Option<Principal> {
-        let connected_proxies_count = self.connected_proxies.0.len();
-        if connected_proxies_count == 0 {
+    pub(crate) fn last_request_id(&self) -> HttpRequestId {
+        self.next_request_id
+    }
+
+    pub(crate) fn restore_last_request_id(&mut self, last_request_id: HttpRequestId) {
+        self.next_request_id = self.next_request_id.max(last_request_id);
+    }
+
+    fn get_proxy_for_connection(
+        &self,
+        request_id: HttpRequestId,
+        request: &HttpRequest,
+        excluded_proxy_principal: Option<&Principal>,
+    ) -> Result<Principal, HttpOverWsError> {
+        let secret_names =
+            secret_placeholder_names(request).map_err(HttpOverWsError::InvalidSecretPlaceholder)?;
+
+        // draining proxies only complete the requests already assigned to them
+        let accepting_proxies: Vec<(&Principal, &ClientProxy)> = self
+            .connected_proxies
+            .proxies
+            .iter()
+            .filter(|(proxy_principal, proxy)| {
+                !proxy.is_draining() && Some(*proxy_principal) != excluded_proxy_principal
+            })
+            .collect();
+        if accepting_proxies.is_empty() {
+            return Err(HttpOverWsError::NoProxiesConnected);
+        }
+
+        let capable_proxies: Vec<(&Principal, &ClientProxy)> = accepting_proxies
+            .into_iter()
+            .filter(|(_, proxy)| proxy.supports_method(&request.method))
+            .collect();
+        if capable_proxies.is_empty() {
+            return Err(HttpOverWsError::UnsupportedHttpMethod(
+                request.method.clone(),
+            ));
+        }
+
+        let proxies_holding_secrets: Vec<(&Principal, &ClientProxy)> = capable_proxies
+            .into_iter()
+            .filter(|(_, proxy)| proxy.holds_secrets(&secret_names))
+            .collect();
+        if proxies_holding_secrets.is_empty() {
+            return Err(HttpOverWsError::SecretsNotAvailable(
+                secret_names.into_iter().collect(),
+            ));
+        }
+
+        let available_proxies: Vec<&Principal> = proxies_holding_secrets
+            .into_iter()
+            .filter(|(_, proxy)| {
+                !self.payload_encryption_required
+                    || (self.payload_encryption_seed.is_some()
+                        && proxy.encryption_public_key().is_some())
+            })
+            .map(|(proxy_principal, _)| proxy_principal)
+            .collect();
+        let available_proxies_count = available_proxies.len();
+        if available_proxies_count == 0 {
+            return Err(HttpOverWsError::PayloadEncryptionNotAvailable);
+        }
+        let chosen_proxy_index = request_id as usize % available_proxies_count;
+        // chosen_proxy_index is in [0, available_proxies_count)
+        // where available_proxies_count is the number of proxies currently accepting requests,
+        // therefore the entry at 'chosen_proxy_index' is guaranteed to exist
+        Ok(*available_proxies[chosen_proxy_index])
+    }
+
+    /// Moves the connection from the proxy that failed to execute it to another proxy,
+    /// returning the new proxy along with its protocol version and the message to send to it.
+    ///
+    /// Returns `None` if the request has already been sent to too many proxies
+    /// or no other proxy can execute it.
+    pub(crate) fn retry_connection(
+        &mut self,
+        failed_proxy_principal: Principal,
+        request_id: HttpRequestId,
+        proxy_error: &ProxyError,
+    ) -> Option<(Principal, HttpOverWsProtocolVersion, HttpOverWsMessage)> {
+        let connection = self
+            .connected_proxies
+            .proxies
+            .get(&failed_proxy_principal)?
+            .get_connection(request_id)?;
+        if !connection.can_retry() {
             return None;
         }
-        let chosen_proxy_index = request_id as usize % connected_proxies_count;
-        // chosen_proxy_index is in [0, connected_proxies_count)
-        // where connected_proxies_count is the number of proxies currently connected.
-        // as no proxy is removed while executing this method,
-        // the entry at 'chosen_proxy_index' is guaranteed to exist
-        Some(
-            self.connected_proxies
-                .0
-                .iter()
-                .nth(chosen_proxy_index)
-                .expect("proxy is not connected")
-                .0
-                .clone(),
-        )
+        let request = connection.get_hop_request();
+        let latency_ms = connection.elapsed_ms();
+        let deadline_ns = connection.deadline_ns();
+
+        let proxy_principal = self
+            .get_proxy_for_connection(request_id, &request, Some(&failed_proxy_principal))
+            .ok()?;
+
+        let failed_proxy = self
+            .connected_proxies
+            .proxies
+            .get_mut(&failed_proxy_principal)?;
+        failed_proxy.record_request_completed(
+            &HttpResult::Failure(HttpFailureReason::ProxyError(proxy_error.clone())),
+            latency_ms,
+        );
+        let mut connection = failed_proxy.remove_connection(request_id).ok()?;
+        connection.record_retry();
+
+        self.connected_proxies
+            .assign_connection_to_proxy(&proxy_principal, request_id, connection)
+            .ok()?;
+        self.deadlines
+            .insert((deadline_ns, request_id), proxy_principal);
+        self.metrics.record_request_retried(proxy_error);
+
+        Some((
+            proxy_principal,
+            self.get_proxy_protocol_version(&proxy_principal),
+            self.http_request_message(&proxy_principal, request_id, request),
+        ))
+    }
+
+    /// If the response is a redirect that the canister has to follow,
+    /// returns the protocol version of the proxy and the message to send to it for the next hop.
+    ///
+    /// The next hop is executed by the same proxy, within the deadline of the request.
+    pub(crate) fn follow_redirect(
+        &mut self,
+        proxy_principal: Principal,
+        request_id: HttpRequestId,
+        response: &HttpResponse,
+    ) -> Result<Option<(HttpOverWsProtocolVersion, HttpOverWsMessage)>, HttpFailureReason> {
+        let redirect_validator = self.redirect_validator;
+        let Some(connection) = self
+            .connected_proxies
+            .proxies
+            .get_mut(&proxy_principal)
+            .and_then(|proxy| proxy.get_connection_mut(request_id).ok())
+            .filter(|connection| connection.is_waiting_for_response())
+        else {
+            return Ok(None);
+        };
+
+        let Some(next_request) = connection.follow_redirect(response, redirect_validator)? else {
+            return Ok(None);
+        };
+
+        Ok(Some((
+            self.get_proxy_protocol_version(&proxy_principal),
+            self.http_request_message(&proxy_principal, request_id, next_request),
+        )))
+    }
+
+    pub(crate) fn drain_proxy(
+        &mut self,
+        proxy_principal: &Principal,
+    ) -> Result<(), HttpOverWsError> {
+        self.connected_proxies
+            .proxies
+            .get_mut(proxy_principal)
+            .ok_or(HttpOverWsError::ProxyNotFound)?
+            .start_draining();
+        Ok(())
+    }
+
+    pub(crate) fn get_proxies(&self) -> Vec<ProxyInfo> {
+        let mut proxies: Vec<_> = self
+            .connected_proxies
+            .proxies
+            .iter()
+            .map(|(proxy_principal, proxy)| proxy.get_info(*proxy_principal))
+            .collect();
+        proxies.sort_by_key(|info| info.proxy_principal);
+
+        proxies
     }
 
     pub(crate) fn update_connection_state(
@@ -110,68 +671,184 @@ impl State {
         proxy_principal: Principal,
         request_id: HttpRequestId,
         http_result: HttpResult,
+        response_attestation: Option<ResponseAttestation>,
     ) -> Result<Option<HttpCallbackWithResult>, HttpOverWsError> {
         let proxy = self
             .connected_proxies
-            .0
+            .proxies
             .get_mut(&proxy_principal)
             .ok_or(HttpOverWsError::ProxyNotFound)?;
         let connection = proxy.get_connection_mut(request_id)?;
 
-        let callback_with_result = connection.update_state(http_result);
+        if connection.is_waiting_for_response() {
+            let latency_ms = connection.elapsed_ms();
+            self.deadlines
+                .remove(&(connection.deadline_ns(), request_id));
+
+            self.metrics
+                .record_request_completed(&http_result, latency_ms);
+            proxy.record_request_completed(&http_result, latency_ms);
+        }
+
+        // the connection has to be borrowed again after recording the completion on the proxy
+        let callback_with_result = proxy
+            .get_connection_mut(request_id)?
+            .update_state(http_result, response_attestation);
 
         Ok(callback_with_result)
     }
 
+    pub(crate) fn get_metrics(&self) -> HttpOverWsMetrics {
+        let mut proxies: Vec<_> = self
+            .connected_proxies
+            .proxies
+            .iter()
+            .map(|(proxy_principal, proxy)| proxy.get_metrics(*proxy_principal))
+            .collect();
+        proxies.sort_by_key(|metrics| metrics.proxy_principal);
+
+        self.metrics.to_http_over_ws_metrics(proxies)
+    }
+
     pub(crate) fn get_http_connection(&self, request_id: HttpRequestId) -> Option<HttpRequest> {
-        for (_, proxy) in self.connected_proxies.0.iter() {
-            for (id, connection) in proxy.get_connections() {
-                if id.to_owned() == request_id {
-                    return Some(connection.get_request());
-                }
-            }
-        }
-        None
+        self.connected_proxies
+            .get_connection(request_id)
+            .map(|connection| connection.get_request())
+    }
+
+    pub(crate) fn get_http_connection_proxy(&self, request_id: HttpRequestId) -> Option<Principal> {
+        self.connected_proxies
+            .request_proxies
+            .get(&request_id)
+            .copied()
     }
 
     pub(crate) fn get_http_response(&self, request_id: HttpRequestId) -> GetHttpResponseResult {
-        for (_, proxy) in self.connected_proxies.0.iter() {
-            for (id, connection) in proxy.get_connections() {
-                if id.to_owned() == request_id {
-                    return connection.get_response();
-                }
-            }
-        }
-        Err(HttpOverWsError::RequestIdNotFound)
+        self.connected_proxies
+            .get_connection(request_id)
+            .ok_or(HttpOverWsError::RequestIdNotFound)?
+            .get_response()
+    }
+
+    pub(crate) fn get_http_response_attestation(
+        &self,
+        request_id: HttpRequestId,
+    ) -> Result<Option<ResponseAttestation>, HttpOverWsError> {
+        Ok(self
+            .connected_proxies
+            .get_connection(request_id)
+            .ok_or(HttpOverWsError::RequestIdNotFound)?
+            .get_response_attestation())
+    }
+
+    pub(crate) fn get_http_response_metadata(
+        &self,
+        request_id: HttpRequestId,
+    ) -> Result<Option<HttpResponseMetadata>, HttpOverWsError> {
+        let (proxy_principal, connection) = self
+            .connected_proxies
+            .get_connection_with_proxy(request_id)
+            .ok_or(HttpOverWsError::RequestIdNotFound)?;
+
+        Ok(connection.get_response_metadata(proxy_principal))
+    }
+
+    pub(crate) fn get_http_response_envelope(
+        &self,
+        request_id: HttpRequestId,
+    ) -> GetHttpResponseEnvelopeResult {
+        let (proxy_principal, connection) = self
+            .connected_proxies
+            .get_connection_with_proxy(request_id)
+            .ok_or(HttpOverWsError::RequestIdNotFound)?;
+
+        connection.get_response_envelope(proxy_principal)
     }
-}
 
-fn http_connection_timeout(
-    proxy_principal: Principal,
-    request_id: HttpRequestId,
-) -> Option<HttpCallbackWithResult> {
-    STATE.with(|state| {
-        state
-            .borrow_mut()
+    /// Stores the stats reported by the proxy, until its response for the request is received.
+    pub(crate) fn set_proxy_response_stats(
+        &mut self,
+        proxy_principal: Principal,
+        request_id: HttpRequestId,
+        stats: ProxyResponseStats,
+    ) -> Result<(), HttpOverWsError> {
+        let connection = self
             .connected_proxies
-            .0
+            .proxies
             .get_mut(&proxy_principal)
-            .and_then(|proxy| proxy.get_connection_mut(request_id).ok())
-            .and_then(|connection| {
-                connection.update_state(HttpResult::Failure(HttpFailureReason::RequestTimeout))
-            })
-    })
+            .ok_or(HttpOverWsError::ProxyNotFound)?
+            .get_connection_mut(request_id)?;
+        if !connection.is_waiting_for_response() {
+            return Err(HttpOverWsError::InvalidHttpMessage);
+        }
+
+        connection.set_proxy_stats(stats);
+        Ok(())
+    }
 }
 
-pub(crate) struct ConnectedProxies(HashMap<Principal, ClientProxy>);
+fn sweep_overdue_connections() {
+    let callbacks_with_results =
+        STATE.with(|state| state.borrow_mut().fail_overdue_connections(time()));
+
+    for (request_id, callback_with_result) in callbacks_with_results {
+        trigger_callback_with_result(request_id, callback_with_result);
+    }
+}
+
+pub(crate) struct ConnectedProxies {
+    proxies: HashMap<Principal, ClientProxy>,
+    /// The proxy each connection is assigned to, so that connections can be looked up by id
+    /// without scanning all the proxies.
+    request_proxies: HashMap<HttpRequestId, Principal>,
+}
 
 impl ConnectedProxies {
     fn new() -> Self {
-        ConnectedProxies(HashMap::new())
+        ConnectedProxies {
+            proxies: HashMap::new(),
+            request_proxies: HashMap::new(),
+        }
+    }
+
+    fn add_proxy(
+        &mut self,
+        proxy_principal: Principal,
+        protocol_version: HttpOverWsProtocolVersion,
+        capabilities: ProxyCapabilities,
+        encryption_public_key: Option<[u8; 32]>,
+    ) {
+        self.proxies.insert(
+            proxy_principal,
+            ClientProxy::new(protocol_version, capabilities, encryption_public_key),
+        );
+    }
+
+    fn get_connection(&self, request_id: HttpRequestId) -> Option<&HttpConnection> {
+        self.get_connection_with_proxy(request_id)
+            .map(|(_, connection)| connection)
+    }
+
+    fn get_connection_mut(&mut self, request_id: HttpRequestId) -> Option<&mut HttpConnection> {
+        let proxy_principal = self.request_proxies.get(&request_id)?;
+        self.proxies
+            .get_mut(proxy_principal)?
+            .get_connection_mut(request_id)
+            .ok()
     }
 
-    fn add_proxy(&mut self, proxy_principal: Principal) {
-        self.0.insert(proxy_principal, ClientProxy::new());
+    /// Returns the connection along with the proxy it is assigned to.
+    fn get_connection_with_proxy(
+        &self,
+        request_id: HttpRequestId,
+    ) -> Option<(Principal, &HttpConnection)> {
+        let proxy_principal = self.request_proxies.get(&request_id)?;
+        let connection = self
+            .proxies
+            .get(proxy_principal)?
+            .get_connection(request_id)?;
+
+        Some((*proxy_principal, connection))
     }
 
     fn assign_connection_to_proxy(
@@ -181,10 +858,11 @@ impl ConnectedProxies {
         connection: HttpConnection,
     ) -> Result<(), HttpOverWsError> {
         let proxy: &mut ClientProxy = self
-            .0
+            .proxies
             .get_mut(proxy_principal)
             .ok_or(HttpOverWsError::ProxyNotFound)?;
         proxy.assign_connection(request_id, connection);
+        self.request_proxies.insert(request_id, *proxy_principal);
         Ok(())
     }
 
@@ -197,25 +875,34 @@ impl ConnectedProxies {
         request_id: HttpRequestId,
     ) -> Result<(), HttpOverWsError> {
         let proxy = self
-            .0
+            .proxies
             .get_mut(proxy_principal)
             .ok_or(HttpOverWsError::ProxyNotFound)?;
         proxy.remove_connection(request_id)?;
+        self.request_proxies.remove(&request_id);
         Ok(())
     }
 
-    fn remove_proxy(&mut self, proxy_principal: &Principal) -> Result<(), HttpOverWsError> {
-        self.0
+    fn remove_proxy(
+        &mut self,
+        proxy_principal: &Principal,
+    ) -> Result<ClientProxy, HttpOverWsError> {
+        let proxy = self
+            .proxies
             .remove(proxy_principal)
             .ok_or(HttpOverWsError::ProxyNotFound)?;
-        Ok(())
+        for request_id in proxy.get_connections().keys() {
+            self.request_proxies.remove(request_id);
+        }
+        Ok(proxy)
     }
 
     fn get_all_proxies_principals(&self) -> Vec<Principal> {
-        self.0.keys().map(|key| key.clone()).collect()
+        self.proxies.keys().map(|key| key.clone()).collect()
     }
 
-    fn remove_all_proxies(&mut self) {
-        self.0.clear();
+    fn remove_all_proxies(&mut self) -> Vec<ClientProxy> {
+        self.request_proxies.clear();
+        self.proxies.drain().map(|(_, proxy)| proxy).collect()
     }
 }
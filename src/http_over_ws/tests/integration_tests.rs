@@ -4,13 +4,19 @@ use std::{path::PathBuf, sync::Once};
 
 use candid::{Nat, Principal};
 use http_over_ws::{
-    HttpFailureReason, HttpMethod, HttpOverWsError, HttpOverWsMessage, HttpRequest, HttpResponse,
-    HttpResult,
+    decrypt_http_request, encrypt_http_response, encryption_public_key,
+    proxy_encryption_key_digest, secret_placeholder, EncryptedHttpRequest, HttpFailureReason,
+    HttpHeader, HttpMethod, HttpOverWsError, HttpOverWsMessage, HttpRedirect, HttpRequest,
+    HttpRequestDeadline, HttpRequestId, HttpResponse, HttpResponseEnvelope, HttpResponseMetadata,
+    HttpResponseTiming, HttpResult, ProxyCapabilities, ProxyClientSetup, ProxyEncryptionKey,
+    ProxyError, ProxyResponseStats, RedirectPolicy, ResponseAttestation, ResponseSignature,
+    DEFAULT_HTTP_REQUEST_TIMEOUT_MS, HTTP_OVER_WS_PROTOCOL_V1, HTTP_OVER_WS_PROTOCOL_V2,
 };
 use ic_websocket_cdk::types::{
     CanisterCloseMessageContent, CloseMessageReason, WebsocketServiceMessageContent,
 };
 use lazy_static::lazy_static;
+use ring::signature::{Ed25519KeyPair, KeyPair};
 use test_utils::{
     ic_env::{get_test_env, load_canister_wasm_from_path, CanisterData, TestEnv},
     proxy_client::ProxyClient,
@@ -51,6 +57,47 @@ fn get_test_canister_id(test_env: &TestEnv) -> Principal {
     test_env.get_canisters().into_keys().next().unwrap()
 }
 
+fn redirect_response(status: u16, location: &str) -> HttpResponse {
+    HttpResponse {
+        status: Nat::from(status),
+        headers: vec![HttpHeader {
+            name: "Location".to_string(),
+            value: location.to_string(),
+        }],
+        body: vec![],
+    }
+}
+
+fn encryption_key(
+    key_pair: &Ed25519KeyPair,
+    proxy_principal: Principal,
+    secret_key: &[u8; 32],
+) -> ProxyEncryptionKey {
+    let public_key = encryption_public_key(secret_key);
+    let digest = proxy_encryption_key_digest(&proxy_principal, &public_key);
+
+    ProxyEncryptionKey {
+        public_key: public_key.to_vec(),
+        signature: key_pair.sign(&digest).as_ref().to_vec(),
+    }
+}
+
+fn sign_response(
+    key_pair: &Ed25519KeyPair,
+    request_id: HttpRequestId,
+    request: &HttpRequest,
+    response: &HttpResponse,
+    timestamp_ns: u64,
+) -> ResponseSignature {
+    let digest =
+        http_over_ws::response_attestation_digest(request_id, request, response, timestamp_ns);
+
+    ResponseSignature {
+        timestamp_ns,
+        signature: key_pair.sign(&digest).as_ref().to_vec(),
+    }
+}
+
 #[test]
 fn test_execute_http_request_no_clients_connected() {
     setup();
@@ -225,17 +272,207 @@ fn test_execute_http_request_with_proxy_error() {
     proxy_client.send_http_over_ws_message(HttpOverWsMessage::Error(
         Some(request_id),
         error_message.clone(),
+        None,
+    ));
+
+    // errors without a structured reason are reported as unknown
+    let res = canister_actor.query_get_http_response(request_id);
+    assert_eq!(
+        res,
+        Err(HttpOverWsError::RequestFailed(
+            HttpFailureReason::ProxyError(ProxyError::Unknown(Some(error_message)))
+        ))
+    );
+}
+
+#[test]
+fn test_execute_http_request_with_structured_proxy_error() {
+    setup();
+    reset_canister();
+    let test_env = get_test_env();
+    let mut proxy_client1 = ProxyClient::new(&test_env, get_test_canister_id(&test_env));
+    let mut proxy_client2 = ProxyClient::new(&test_env, get_test_canister_id(&test_env));
+    let canister_actor = CanisterActor::new(&test_env);
+
+    proxy_client1.setup_proxy();
+    proxy_client2.setup_proxy();
+
+    let request = HttpRequest::new(TEST_URL, HttpMethod::GET, vec![], None);
+    let request_id = canister_actor
+        .call_execute_http_request(request, None, false)
+        .unwrap();
+
+    let (mut assigned_proxy, mut idle_proxy) =
+        if !proxy_client1.get_http_over_ws_messages().is_empty() {
+            (proxy_client1, proxy_client2)
+        } else {
+            (proxy_client2, proxy_client1)
+        };
+
+    let proxy_error = ProxyError::TlsError(Some("invalid certificate".to_string()));
+    assigned_proxy.send_http_over_ws_message(HttpOverWsMessage::Error(
+        Some(request_id),
+        "invalid certificate".to_string(),
+        Some(proxy_error.clone()),
+    ));
+
+    // non retryable errors fail the request even if other proxies are connected
+    idle_proxy.expect_received_http_requests_count(0);
+    let res = canister_actor.query_get_http_response(request_id);
+    assert_eq!(
+        res,
+        Err(HttpOverWsError::RequestFailed(
+            HttpFailureReason::ProxyError(proxy_error)
+        ))
+    );
+}
+
+#[test]
+fn test_execute_http_request_retried_on_another_proxy() {
+    setup();
+    reset_canister();
+    let test_env = get_test_env();
+    let mut proxy_client1 = ProxyClient::new(&test_env, get_test_canister_id(&test_env));
+    let mut proxy_client2 = ProxyClient::new(&test_env, get_test_canister_id(&test_env));
+    let canister_actor = CanisterActor::new(&test_env);
+
+    proxy_client1.setup_proxy();
+    proxy_client2.setup_proxy();
+
+    let request = HttpRequest::new(TEST_URL, HttpMethod::GET, vec![], None);
+    let request_id = canister_actor
+        .call_execute_http_request(request.clone(), None, false)
+        .unwrap();
+
+    let (mut first_proxy, mut second_proxy) =
+        if !proxy_client1.get_http_over_ws_messages().is_empty() {
+            (proxy_client1, proxy_client2)
+        } else {
+            (proxy_client2, proxy_client1)
+        };
+
+    first_proxy.send_http_over_ws_message(HttpOverWsMessage::Error(
+        Some(request_id),
+        "too many requests".to_string(),
+        Some(ProxyError::ProxyOverloaded(None)),
+    ));
+
+    // the request is sent again, with the same id, to the other proxy
+    assert_eq!(
+        second_proxy.get_http_over_ws_messages(),
+        vec![HttpOverWsMessage::HttpRequest(request_id, request)]
+    );
+    let res = canister_actor.query_get_http_response(request_id);
+    assert_eq!(res, Err(HttpOverWsError::NotYetReceived));
+
+    // the first proxy isn't assigned to the request anymore
+    let http_response = HttpResponse {
+        status: Nat::from(200),
+        headers: vec![],
+        body: vec![1, 2, 3],
+    };
+    first_proxy.send_http_over_ws_message(HttpOverWsMessage::HttpResponse(
+        request_id,
+        http_response.clone(),
     ));
+    let res = canister_actor.query_get_http_response(request_id);
+    assert_eq!(res, Err(HttpOverWsError::NotYetReceived));
 
+    // the request fails once it has been sent to the maximum amount of proxies
+    let proxy_error = ProxyError::ConnectionRefused(Some("connection refused".to_string()));
+    second_proxy.send_http_over_ws_message(HttpOverWsMessage::Error(
+        Some(request_id),
+        "connection refused".to_string(),
+        Some(proxy_error.clone()),
+    ));
+    first_proxy.expect_received_http_requests_count(0);
     let res = canister_actor.query_get_http_response(request_id);
     assert_eq!(
         res,
         Err(HttpOverWsError::RequestFailed(
-            HttpFailureReason::ProxyError(error_message)
+            HttpFailureReason::ProxyError(proxy_error)
         ))
     );
 }
 
+#[test]
+fn test_execute_http_request_response_envelope() {
+    setup();
+    reset_canister();
+    let test_env = get_test_env();
+    let mut proxy_client1 = ProxyClient::new(&test_env, get_test_canister_id(&test_env));
+    let mut proxy_client2 = ProxyClient::new(&test_env, get_test_canister_id(&test_env));
+    let canister_actor = CanisterActor::new(&test_env);
+
+    proxy_client1.setup_proxy();
+    proxy_client2.setup_proxy();
+
+    let request = HttpRequest::new(TEST_URL, HttpMethod::GET, vec![], None);
+    let request_id = canister_actor
+        .call_execute_http_request(request, None, false)
+        .unwrap();
+
+    let (mut first_proxy, mut second_proxy) =
+        if !proxy_client1.get_http_over_ws_messages().is_empty() {
+            (proxy_client1, proxy_client2)
+        } else {
+            (proxy_client2, proxy_client1)
+        };
+
+    let stats = |total_ms: u64| ProxyResponseStats {
+        timing: HttpResponseTiming {
+            dns_lookup_ms: Some(5),
+            connect_ms: Some(20),
+            time_to_first_byte_ms: Some(100),
+            total_ms,
+        },
+        body_size_bytes: 4096,
+    };
+
+    // the stats reported for an attempt that failed are discarded
+    first_proxy.send_http_over_ws_message(HttpOverWsMessage::HttpResponseStats(
+        request_id,
+        stats(1000),
+    ));
+    first_proxy.send_http_over_ws_message(HttpOverWsMessage::Error(
+        Some(request_id),
+        "too many requests".to_string(),
+        Some(ProxyError::ProxyOverloaded(None)),
+    ));
+    second_proxy.expect_received_http_requests_count(1);
+    assert_eq!(
+        canister_actor.query_get_http_response_envelope(request_id),
+        Err(HttpOverWsError::NotYetReceived)
+    );
+
+    let http_response = HttpResponse {
+        status: Nat::from(200),
+        headers: vec![TEST_HTTP_RESPONSE_HEADER.clone()],
+        body: vec![1, 2, 3],
+    };
+    second_proxy
+        .send_http_over_ws_message(HttpOverWsMessage::HttpResponseStats(request_id, stats(150)));
+    second_proxy.send_http_over_ws_message(HttpOverWsMessage::HttpResponse(
+        request_id,
+        http_response.clone(),
+    ));
+
+    assert_eq!(
+        canister_actor.query_get_http_response_envelope(request_id),
+        Ok(HttpResponseEnvelope {
+            response: http_response,
+            metadata: HttpResponseMetadata {
+                proxy_principal: second_proxy.client_principal(),
+                final_url: TEST_URL.to_string(),
+                redirects: vec![],
+                timing: Some(stats(150).timing),
+                body_size_bytes: Some(4096),
+                proxy_attempts: 2,
+            },
+        })
+    );
+}
+
 #[test]
 fn test_execute_http_request_only_assigned_proxy() {
     setup();
@@ -378,7 +615,11 @@ fn test_execute_http_request_before_timeout() {
     );
 
     let request_id = canister_actor
-        .call_execute_http_request(request.clone(), Some(10_000), false)
+        .call_execute_http_request(
+            request.clone(),
+            Some(HttpRequestDeadline::TimeoutMs(10_000)),
+            false,
+        )
         .unwrap();
 
     proxy_client.expect_received_http_requests_count(1);
@@ -418,7 +659,11 @@ fn test_execute_http_request_timeout_expired() {
     );
 
     let request_id = canister_actor
-        .call_execute_http_request(request.clone(), Some(10_000), false)
+        .call_execute_http_request(
+            request.clone(),
+            Some(HttpRequestDeadline::TimeoutMs(10_000)),
+            false,
+        )
         .unwrap();
 
     proxy_client.expect_received_http_requests_count(1);
@@ -455,6 +700,129 @@ fn test_execute_http_request_timeout_expired() {
     );
 }
 
+#[test]
+fn test_execute_http_request_default_timeout_expired() {
+    setup();
+    reset_canister();
+    let test_env = get_test_env();
+    let mut proxy_client = ProxyClient::new(&test_env, get_test_canister_id(&test_env));
+    let canister_actor = CanisterActor::new(&test_env);
+
+    proxy_client.setup_proxy();
+
+    let request = HttpRequest::new(
+        TEST_URL,
+        HttpMethod::GET,
+        vec![TEST_HTTP_REQUEST_HEADER.clone()],
+        None,
+    );
+
+    let request_id = canister_actor
+        .call_execute_http_request(request.clone(), None, false)
+        .unwrap();
+
+    proxy_client.expect_received_http_requests_count(1);
+
+    test_env.advance_canister_time_ms(DEFAULT_HTTP_REQUEST_TIMEOUT_MS - 1_000);
+
+    let res = canister_actor.query_get_http_response(request_id);
+    assert_eq!(res, Err(HttpOverWsError::NotYetReceived));
+
+    // advance time so that the default timeout expires
+    test_env.advance_canister_time_ms(1_000);
+
+    let res = canister_actor.query_get_http_response(request_id);
+    assert_eq!(
+        res,
+        Err(HttpOverWsError::RequestFailed(
+            HttpFailureReason::RequestTimeout
+        ))
+    );
+}
+
+#[test]
+fn test_execute_http_request_updated_default_timeout_expired() {
+    setup();
+    reset_canister();
+    let test_env = get_test_env();
+    let mut proxy_client = ProxyClient::new(&test_env, get_test_canister_id(&test_env));
+    let canister_actor = CanisterActor::new(&test_env);
+
+    proxy_client.setup_proxy();
+
+    canister_actor.call_set_default_http_request_timeout_ms(5_000);
+
+    let request = HttpRequest::new(
+        TEST_URL,
+        HttpMethod::GET,
+        vec![TEST_HTTP_REQUEST_HEADER.clone()],
+        None,
+    );
+
+    let request_id = canister_actor
+        .call_execute_http_request(request.clone(), None, false)
+        .unwrap();
+
+    proxy_client.expect_received_http_requests_count(1);
+
+    // advance time so that the updated default timeout expires
+    test_env.advance_canister_time_ms(5_000);
+
+    let res = canister_actor.query_get_http_response(request_id);
+    assert_eq!(
+        res,
+        Err(HttpOverWsError::RequestFailed(
+            HttpFailureReason::RequestTimeout
+        ))
+    );
+}
+
+#[test]
+fn test_execute_http_request_absolute_deadline_expired() {
+    setup();
+    reset_canister();
+    let test_env = get_test_env();
+    let mut proxy_client = ProxyClient::new(&test_env, get_test_canister_id(&test_env));
+    let canister_actor = CanisterActor::new(&test_env);
+
+    proxy_client.setup_proxy();
+
+    let request = HttpRequest::new(
+        TEST_URL,
+        HttpMethod::GET,
+        vec![TEST_HTTP_REQUEST_HEADER.clone()],
+        None,
+    );
+
+    let deadline_ns = test_env.get_canister_time_ns() + 10_000_000_000;
+    let request_ids: Vec<_> = (0..2)
+        .map(|_| {
+            canister_actor
+                .call_execute_http_request(
+                    request.clone(),
+                    Some(HttpRequestDeadline::AtNs(deadline_ns)),
+                    false,
+                )
+                .unwrap()
+        })
+        .collect();
+
+    proxy_client.expect_received_http_requests_count(2);
+
+    // advance time so that the deadline of both requests passes
+    test_env.advance_canister_time_ms(10_000);
+
+    for request_id in request_ids {
+        let res = canister_actor.query_get_http_response(request_id);
+        assert_eq!(
+            res,
+            Err(HttpOverWsError::RequestFailed(
+                HttpFailureReason::RequestTimeout
+            ))
+        );
+    }
+}
+
 #[test]
 fn test_execute_http_request_with_callback() {
     setup();
@@ -615,3 +983,1053 @@ fn test_disconnect_all_proxies() {
     );
     assert_eq!(res, Err(HttpOverWsError::NoProxiesConnected));
 }
+
+#[test]
+fn test_proxy_disconnected_with_request_in_flight() {
+    setup();
+    reset_canister();
+    let test_env = get_test_env();
+    let mut proxy_client = ProxyClient::new(&test_env, get_test_canister_id(&test_env));
+    let canister_actor = CanisterActor::new(&test_env);
+
+    proxy_client.setup_proxy();
+
+    let request_id = canister_actor
+        .call_execute_http_request(
+            HttpRequest::new(
+                TEST_URL,
+                HttpMethod::GET,
+                vec![TEST_HTTP_REQUEST_HEADER.clone()],
+                None,
+            ),
+            None,
+            true,
+        )
+        .unwrap();
+
+    proxy_client.expect_received_http_requests_count(1);
+
+    proxy_client.close_ws_connection();
+
+    let expected_result = HttpResult::Failure(HttpFailureReason::ProxyError(ProxyError::Unknown(
+        Some("proxy disconnected".to_string()),
+    )));
+    let callback_res = canister_actor.query_get_callback_results();
+    assert_eq!(callback_res, vec![expected_result]);
+
+    // the connections are removed together with the proxy
+    let res = canister_actor.query_get_http_response(request_id);
+    assert_eq!(res, Err(HttpOverWsError::RequestIdNotFound));
+}
+
+#[test]
+fn test_webhook_request() {
+    setup();
+    reset_canister();
+    let test_env = get_test_env();
+    let mut proxy_client = ProxyClient::new(&test_env, get_test_canister_id(&test_env));
+
+    proxy_client.setup_proxy();
+
+    let request = HttpRequest::new(
+        "https://proxy.example.com/echo?source=test",
+        HttpMethod::POST,
+        vec![],
+        Some(vec![1, 2, 3]),
+    );
+    proxy_client.send_http_over_ws_message(HttpOverWsMessage::HttpRequest(1, request));
+
+    assert_eq!(
+        proxy_client.get_http_over_ws_messages(),
+        vec![HttpOverWsMessage::HttpResponse(
+            1,
+            HttpResponse {
+                status: Nat::from(200),
+                headers: vec![],
+                body: vec![1, 2, 3],
+            }
+        )]
+    );
+}
+
+#[test]
+fn test_webhook_request_not_found() {
+    setup();
+    reset_canister();
+    let test_env = get_test_env();
+    let mut proxy_client = ProxyClient::new(&test_env, get_test_canister_id(&test_env));
+
+    proxy_client.setup_proxy();
+
+    let request = HttpRequest::new("/not-registered", HttpMethod::POST, vec![], None);
+    proxy_client.send_http_over_ws_message(HttpOverWsMessage::HttpRequest(1, request));
+
+    assert_eq!(
+        proxy_client.get_http_over_ws_messages(),
+        vec![HttpOverWsMessage::HttpResponse(
+            1,
+            HttpResponse {
+                status: Nat::from(404),
+                headers: vec![],
+                body: b"Not Found".to_vec(),
+            }
+        )]
+    );
+}
+
+#[test]
+fn test_webhook_request_proxy_not_set_up() {
+    setup();
+    reset_canister();
+    let test_env = get_test_env();
+    let mut proxy_client = ProxyClient::new(&test_env, get_test_canister_id(&test_env));
+
+    // the ws connection is open, but the client didn't declare itself as a proxy
+    proxy_client.open_ws_connection();
+
+    let request = HttpRequest::new("/echo", HttpMethod::POST, vec![], Some(vec![1, 2, 3]));
+    proxy_client.send_http_over_ws_message(HttpOverWsMessage::HttpRequest(1, request));
+
+    assert_eq!(proxy_client.get_http_over_ws_messages(), vec![]);
+}
+
+/// The instruction counts are reported by the `get_http_response` benchmark (`./scripts/bench.sh`).
+#[test]
+fn test_get_http_response_instructions_with_many_connections() {
+    setup();
+    reset_canister();
+    let test_env = get_test_env();
+    let mut proxy_client = ProxyClient::new(&test_env, get_test_canister_id(&test_env));
+    let canister_actor = CanisterActor::new(&test_env);
+
+    proxy_client.setup_proxy();
+
+    let request = HttpRequest::new(
+        TEST_URL,
+        HttpMethod::GET,
+        vec![TEST_HTTP_REQUEST_HEADER.clone()],
+        None,
+    );
+
+    let first_request_id = canister_actor.call_store_http_connections(request.clone(), 1);
+    let (res, instructions_with_one_connection) =
+        canister_actor.query_get_http_response_with_instructions(first_request_id);
+    assert_eq!(res, Err(HttpOverWsError::NotYetReceived));
+
+    // store the connections in batches to stay within the instructions limit of a single call
+    let mut last_request_id = first_request_id;
+    for _ in 0..10 {
+        last_request_id = canister_actor.call_store_http_connections(request.clone(), 10_000);
+    }
+
+    let (res, instructions_with_many_connections) =
+        canister_actor.query_get_http_response_with_instructions(last_request_id);
+    assert_eq!(res, Err(HttpOverWsError::NotYetReceived));
+
+    // the lookup must not depend on the amount of stored connections
+    assert!(
+        instructions_with_many_connections < instructions_with_one_connection * 2,
+        "get_http_response instructions: {} with 1 connection, {} with 100001 connections",
+        instructions_with_one_connection,
+        instructions_with_many_connections
+    );
+}
+
+#[test]
+fn test_versioned_proxy_execute_http_request() {
+    setup();
+    reset_canister();
+    let test_env = get_test_env();
+    let mut proxy_client = ProxyClient::new(&test_env, get_test_canister_id(&test_env));
+    let canister_actor = CanisterActor::new(&test_env);
+
+    let reply = proxy_client.setup_versioned_proxy(ProxyClientSetup {
+        supported_protocol_versions: vec![HTTP_OVER_WS_PROTOCOL_V1, HTTP_OVER_WS_PROTOCOL_V2],
+        capabilities: None,
+        encryption_key: None,
+    });
+    assert_eq!(
+        reply,
+        HttpOverWsMessage::ProtocolVersionNegotiated(HTTP_OVER_WS_PROTOCOL_V2)
+    );
+
+    let request = HttpRequest::new(
+        TEST_URL,
+        HttpMethod::GET,
+        vec![TEST_HTTP_REQUEST_HEADER.clone()],
+        None,
+    );
+
+    let request_id = canister_actor
+        .call_execute_http_request(request.clone(), None, false)
+        .unwrap();
+
+    // the request is received in an envelope of the negotiated version
+    assert_eq!(
+        proxy_client.get_http_over_ws_messages(),
+        vec![HttpOverWsMessage::HttpRequest(request_id, request)]
+    );
+
+    let http_response = HttpResponse {
+        status: Nat::from(200),
+        headers: vec![TEST_HTTP_RESPONSE_HEADER.clone()],
+        body: vec![1, 2, 3],
+    };
+    proxy_client.send_http_over_ws_message(HttpOverWsMessage::HttpResponse(
+        request_id,
+        http_response.clone(),
+    ));
+
+    let res = canister_actor.query_get_http_response(request_id);
+    assert_eq!(res, Ok(HttpResult::Success(http_response)));
+}
+
+#[test]
+fn test_versioned_and_legacy_proxies() {
+    setup();
+    reset_canister();
+    let test_env = get_test_env();
+    let test_canister_id = get_test_canister_id(&test_env);
+    let mut legacy_proxy_client = ProxyClient::new(&test_env, test_canister_id);
+    let mut versioned_proxy_client = ProxyClient::new(&test_env, test_canister_id);
+    let canister_actor = CanisterActor::new(&test_env);
+
+    legacy_proxy_client.setup_proxy();
+    versioned_proxy_client.setup_versioned_proxy(ProxyClientSetup {
+        supported_protocol_versions: vec![HTTP_OVER_WS_PROTOCOL_V1, HTTP_OVER_WS_PROTOCOL_V2],
+        capabilities: None,
+        encryption_key: None,
+    });
+
+    let request = HttpRequest::new(
+        TEST_URL,
+        HttpMethod::GET,
+        vec![TEST_HTTP_REQUEST_HEADER.clone()],
+        None,
+    );
+
+    // the requests are distributed among both proxies,
+    // each receiving them in the format of its own version
+    for _ in 0..2 {
+        canister_actor
+            .call_execute_http_request(request.clone(), None, false)
+            .unwrap();
+    }
+
+    legacy_proxy_client.expect_received_http_requests_count(1);
+    versioned_proxy_client.expect_received_http_requests_count(1);
+}
+
+#[test]
+fn test_versioned_proxy_incompatible() {
+    setup();
+    reset_canister();
+    let test_env = get_test_env();
+    let mut proxy_client = ProxyClient::new(&test_env, get_test_canister_id(&test_env));
+    let canister_actor = CanisterActor::new(&test_env);
+
+    let reply = proxy_client.setup_versioned_proxy(ProxyClientSetup {
+        supported_protocol_versions: vec![HTTP_OVER_WS_PROTOCOL_V2 + 1],
+        capabilities: None,
+        encryption_key: None,
+    });
+    assert!(matches!(
+        reply,
+        HttpOverWsMessage::Error(None, err, _) if err.starts_with("incompatible protocol")
+    ));
+
+    let request = HttpRequest::new(TEST_URL, HttpMethod::GET, vec![], None);
+    let res = canister_actor.call_execute_http_request(request, None, false);
+    assert_eq!(res, Err(HttpOverWsError::NoProxiesConnected));
+}
+
+#[test]
+fn test_legacy_proxy_below_min_protocol_version() {
+    setup();
+    reset_canister();
+    let test_env = get_test_env();
+    let mut proxy_client = ProxyClient::new(&test_env, get_test_canister_id(&test_env));
+    let canister_actor = CanisterActor::new(&test_env);
+
+    assert_eq!(
+        canister_actor.call_set_min_protocol_version(HTTP_OVER_WS_PROTOCOL_V2 + 1),
+        Err(HttpOverWsError::UnsupportedProtocolVersion(
+            HTTP_OVER_WS_PROTOCOL_V2 + 1
+        ))
+    );
+    canister_actor
+        .call_set_min_protocol_version(HTTP_OVER_WS_PROTOCOL_V2)
+        .unwrap();
+
+    proxy_client.setup_proxy();
+
+    // the legacy proxy gets the error in the format it understands
+    let messages = proxy_client.get_http_over_ws_messages();
+    assert_eq!(messages.len(), 1);
+    assert!(matches!(
+        &messages[0],
+        HttpOverWsMessage::Error(None, err, _) if err.starts_with("incompatible protocol")
+    ));
+
+    let request = HttpRequest::new(TEST_URL, HttpMethod::GET, vec![], None);
+    let res = canister_actor.call_execute_http_request(request, None, false);
+    assert_eq!(res, Err(HttpOverWsError::NoProxiesConnected));
+}
+
+#[test]
+fn test_execute_http_request_method_supported_by_proxy() {
+    setup();
+    reset_canister();
+    let test_env = get_test_env();
+    let test_canister_id = get_test_canister_id(&test_env);
+    let mut legacy_proxy_client = ProxyClient::new(&test_env, test_canister_id);
+    let mut versioned_proxy_client = ProxyClient::new(&test_env, test_canister_id);
+    let canister_actor = CanisterActor::new(&test_env);
+
+    legacy_proxy_client.setup_proxy();
+
+    // legacy proxies only get the methods they know
+    let request = HttpRequest::new(TEST_URL, HttpMethod::PATCH, vec![], Some(vec![1, 2, 3]));
+    let res = canister_actor.call_execute_http_request(request.clone(), None, false);
+    assert_eq!(
+        res,
+        Err(HttpOverWsError::UnsupportedHttpMethod(HttpMethod::PATCH))
+    );
+
+    versioned_proxy_client.setup_versioned_proxy(ProxyClientSetup {
+        supported_protocol_versions: vec![HTTP_OVER_WS_PROTOCOL_V2],
+        capabilities: Some(ProxyCapabilities {
+            http_methods: vec![
+                HttpMethod::GET,
+                HttpMethod::PATCH,
+                HttpMethod::Other("PURGE".to_string()),
+            ],
+            secret_names: None,
+        }),
+        encryption_key: None,
+    });
+
+    // the requests are only assigned to the proxy that advertised their methods
+    let custom_request = HttpRequest::new(
+        TEST_URL,
+        HttpMethod::Other("PURGE".to_string()),
+        vec![],
+        None,
+    );
+    let request_id = canister_actor
+        .call_execute_http_request(request.clone(), None, false)
+        .unwrap();
+    let custom_request_id = canister_actor
+        .call_execute_http_request(custom_request.clone(), None, false)
+        .unwrap();
+
+    assert_eq!(
+        versioned_proxy_client.get_http_over_ws_messages(),
+        vec![
+            HttpOverWsMessage::HttpRequest(request_id, request),
+            HttpOverWsMessage::HttpRequest(custom_request_id, custom_request),
+        ]
+    );
+    legacy_proxy_client.expect_received_http_requests_count(0);
+
+    let request = HttpRequest::new(TEST_URL, HttpMethod::OPTIONS, vec![], None);
+    let res = canister_actor.call_execute_http_request(request, None, false);
+    assert_eq!(
+        res,
+        Err(HttpOverWsError::UnsupportedHttpMethod(HttpMethod::OPTIONS))
+    );
+}
+
+#[test]
+fn test_execute_http_request_secrets_held_by_proxy() {
+    setup();
+    reset_canister();
+    let test_env = get_test_env();
+    let test_canister_id = get_test_canister_id(&test_env);
+    let mut legacy_proxy_client = ProxyClient::new(&test_env, test_canister_id);
+    let mut versioned_proxy_client = ProxyClient::new(&test_env, test_canister_id);
+    let canister_actor = CanisterActor::new(&test_env);
+
+    legacy_proxy_client.setup_proxy();
+    versioned_proxy_client.setup_versioned_proxy(ProxyClientSetup {
+        supported_protocol_versions: vec![HTTP_OVER_WS_PROTOCOL_V2],
+        capabilities: Some(ProxyCapabilities {
+            secret_names: Some(vec!["API_KEY".to_string()]),
+            ..Default::default()
+        }),
+        encryption_key: None,
+    });
+
+    // the placeholders are sent as they are, only to the proxy holding the secret
+    let request = HttpRequest::new(
+        &format!("{}?key={}", TEST_URL, secret_placeholder("API_KEY")),
+        HttpMethod::GET,
+        vec![HttpHeader {
+            name: "Authorization".to_string(),
+            value: format!("Bearer {}", secret_placeholder("API_KEY")),
+        }],
+        None,
+    );
+    let request_id = canister_actor
+        .call_execute_http_request(request.clone(), None, false)
+        .unwrap();
+
+    assert_eq!(
+        versioned_proxy_client.get_http_over_ws_messages(),
+        vec![HttpOverWsMessage::HttpRequest(request_id, request)]
+    );
+    legacy_proxy_client.expect_received_http_requests_count(0);
+
+    let request = HttpRequest::new(
+        &format!("{}?key={}", TEST_URL, secret_placeholder("OTHER_KEY")),
+        HttpMethod::GET,
+        vec![],
+        None,
+    );
+    let res = canister_actor.call_execute_http_request(request, None, false);
+    assert_eq!(
+        res,
+        Err(HttpOverWsError::SecretsNotAvailable(vec![
+            "OTHER_KEY".to_string()
+        ]))
+    );
+
+    let request = HttpRequest::new(
+        &format!("{}?key={{{{secret:API_KEY", TEST_URL),
+        HttpMethod::GET,
+        vec![],
+        None,
+    );
+    let res = canister_actor.call_execute_http_request(request, None, false);
+    assert!(matches!(
+        res,
+        Err(HttpOverWsError::InvalidSecretPlaceholder(_))
+    ));
+}
+
+#[test]
+fn test_attested_http_response() {
+    setup();
+    reset_canister();
+    let test_env = get_test_env();
+    let mut proxy_client = ProxyClient::new(&test_env, get_test_canister_id(&test_env));
+    let canister_actor = CanisterActor::new(&test_env);
+    let key_pair = Ed25519KeyPair::from_seed_unchecked(&[1; 32]).unwrap();
+
+    proxy_client.setup_proxy();
+    canister_actor
+        .call_set_proxy_attestation_key(
+            proxy_client.client_principal(),
+            Some(key_pair.public_key().as_ref().to_vec()),
+        )
+        .unwrap();
+
+    let request = HttpRequest::new(TEST_URL, HttpMethod::GET, vec![], None);
+    let request_id = canister_actor
+        .call_execute_http_request(request.clone(), None, false)
+        .unwrap();
+    proxy_client.expect_received_http_requests_count(1);
+
+    let http_response = HttpResponse {
+        status: Nat::from(200),
+        headers: vec![TEST_HTTP_RESPONSE_HEADER.clone()],
+        body: vec![1, 2, 3],
+    };
+    let signature = sign_response(
+        &key_pair,
+        request_id,
+        &request,
+        &http_response,
+        test_env.get_canister_time_ns(),
+    );
+    proxy_client.send_http_over_ws_message(HttpOverWsMessage::AttestedHttpResponse(
+        request_id,
+        http_response.clone(),
+        signature.clone(),
+    ));
+
+    let res = canister_actor.query_get_http_response(request_id);
+    assert_eq!(res, Ok(HttpResult::Success(http_response.clone())));
+
+    // the attestation is stored along with the response and can be verified again
+    let attestation = canister_actor
+        .query_get_http_response_attestation(request_id)
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        attestation,
+        ResponseAttestation {
+            proxy_principal: proxy_client.client_principal(),
+            public_key: key_pair.public_key().as_ref().to_vec(),
+            timestamp_ns: signature.timestamp_ns,
+            signature: signature.signature,
+        }
+    );
+    assert_eq!(
+        attestation.verify(request_id, &request, &http_response),
+        Ok(())
+    );
+    let mut tampered_response = http_response;
+    tampered_response.body = vec![4, 5, 6];
+    assert!(attestation
+        .verify(request_id, &request, &tampered_response)
+        .is_err());
+}
+
+#[test]
+fn test_attested_http_response_rejected() {
+    setup();
+    reset_canister();
+    let test_env = get_test_env();
+    let mut proxy_client = ProxyClient::new(&test_env, get_test_canister_id(&test_env));
+    let canister_actor = CanisterActor::new(&test_env);
+    let key_pair = Ed25519KeyPair::from_seed_unchecked(&[1; 32]).unwrap();
+    let other_key_pair = Ed25519KeyPair::from_seed_unchecked(&[2; 32]).unwrap();
+
+    proxy_client.setup_proxy();
+
+    assert_eq!(
+        canister_actor
+            .call_set_proxy_attestation_key(proxy_client.client_principal(), Some(vec![1, 2, 3])),
+        Err(HttpOverWsError::InvalidAttestationKey)
+    );
+
+    // unattested responses are accepted until attestations are required
+    canister_actor.call_set_response_attestations_required(true);
+
+    let request = HttpRequest::new(TEST_URL, HttpMethod::GET, vec![], None);
+    let http_response = HttpResponse {
+        status: Nat::from(200),
+        headers: vec![],
+        body: vec![1, 2, 3],
+    };
+
+    let request_id = canister_actor
+        .call_execute_http_request(request.clone(), None, false)
+        .unwrap();
+    proxy_client.send_http_over_ws_message(HttpOverWsMessage::HttpResponse(
+        request_id,
+        http_response.clone(),
+    ));
+    let res = canister_actor.query_get_http_response(request_id);
+    assert!(matches!(
+        res,
+        Err(HttpOverWsError::RequestFailed(
+            HttpFailureReason::InvalidResponseAttestation(_)
+        ))
+    ));
+
+    canister_actor
+        .call_set_proxy_attestation_key(
+            proxy_client.client_principal(),
+            Some(key_pair.public_key().as_ref().to_vec()),
+        )
+        .unwrap();
+
+    // signed with a key other than the registered one
+    let request_id = canister_actor
+        .call_execute_http_request(request.clone(), None, false)
+        .unwrap();
+    let signature = sign_response(
+        &other_key_pair,
+        request_id,
+        &request,
+        &http_response,
+        test_env.get_canister_time_ns(),
+    );
+    proxy_client.send_http_over_ws_message(HttpOverWsMessage::AttestedHttpResponse(
+        request_id,
+        http_response.clone(),
+        signature,
+    ));
+    let res = canister_actor.query_get_http_response(request_id);
+    assert!(matches!(
+        res,
+        Err(HttpOverWsError::RequestFailed(
+            HttpFailureReason::InvalidResponseAttestation(_)
+        ))
+    ));
+
+    // signed for a different response
+    let request_id = canister_actor
+        .call_execute_http_request(request.clone(), None, false)
+        .unwrap();
+    let signature = sign_response(
+        &key_pair,
+        request_id,
+        &request,
+        &http_response,
+        test_env.get_canister_time_ns(),
+    );
+    let mut tampered_response = http_response.clone();
+    tampered_response.status = Nat::from(500);
+    proxy_client.send_http_over_ws_message(HttpOverWsMessage::AttestedHttpResponse(
+        request_id,
+        tampered_response,
+        signature,
+    ));
+    let res = canister_actor.query_get_http_response(request_id);
+    assert!(matches!(
+        res,
+        Err(HttpOverWsError::RequestFailed(
+            HttpFailureReason::InvalidResponseAttestation(_)
+        ))
+    ));
+}
+
+#[test]
+fn test_encrypted_http_request() {
+    setup();
+    reset_canister();
+    let test_env = get_test_env();
+    let mut proxy_client = ProxyClient::new(&test_env, get_test_canister_id(&test_env));
+    let canister_actor = CanisterActor::new(&test_env);
+    let key_pair = Ed25519KeyPair::from_seed_unchecked(&[1; 32]).unwrap();
+    let secret_key = [3; 32];
+
+    canister_actor.call_init_payload_encryption([4; 32]);
+    canister_actor
+        .call_set_proxy_attestation_key(
+            proxy_client.client_principal(),
+            Some(key_pair.public_key().as_ref().to_vec()),
+        )
+        .unwrap();
+    proxy_client.setup_versioned_proxy(ProxyClientSetup {
+        supported_protocol_versions: vec![HTTP_OVER_WS_PROTOCOL_V2],
+        capabilities: None,
+        encryption_key: Some(encryption_key(
+            &key_pair,
+            proxy_client.client_principal(),
+            &secret_key,
+        )),
+    });
+
+    let request = HttpRequest::new(
+        TEST_URL,
+        HttpMethod::POST,
+        vec![TEST_HTTP_REQUEST_HEADER.clone()],
+        Some(vec![1, 2, 3]),
+    );
+    let request_id = canister_actor
+        .call_execute_http_request(request.clone(), None, false)
+        .unwrap();
+
+    // only the proxy can decrypt the request
+    let mut messages = proxy_client.get_http_over_ws_messages();
+    assert_eq!(messages.len(), 1);
+    let encrypted_request = match messages.remove(0) {
+        HttpOverWsMessage::EncryptedHttpRequest(id, encrypted_request) => {
+            assert_eq!(id, request_id);
+            encrypted_request
+        }
+        message => panic!("unexpected message: {:?}", message),
+    };
+    assert_eq!(
+        decrypt_http_request(&secret_key, request_id, &encrypted_request),
+        Ok(request)
+    );
+    assert!(decrypt_http_request(&[5; 32], request_id, &encrypted_request).is_err());
+    assert!(decrypt_http_request(&secret_key, request_id + 1, &encrypted_request).is_err());
+
+    let http_response = HttpResponse {
+        status: Nat::from(200),
+        headers: vec![TEST_HTTP_RESPONSE_HEADER.clone()],
+        body: vec![4, 5, 6],
+    };
+    let ciphertext =
+        encrypt_http_response(&secret_key, request_id, &encrypted_request, &http_response).unwrap();
+    proxy_client.send_http_over_ws_message(HttpOverWsMessage::EncryptedHttpResponse(
+        request_id, ciphertext, None,
+    ));
+
+    let res = canister_actor.query_get_http_response(request_id);
+    assert_eq!(res, Ok(HttpResult::Success(http_response)));
+}
+
+#[test]
+fn test_encrypted_http_request_follow_redirect() {
+    setup();
+    reset_canister();
+    let test_env = get_test_env();
+    let mut proxy_client = ProxyClient::new(&test_env, get_test_canister_id(&test_env));
+    let canister_actor = CanisterActor::new(&test_env);
+    let key_pair = Ed25519KeyPair::from_seed_unchecked(&[1; 32]).unwrap();
+    let secret_key = [3; 32];
+
+    canister_actor.call_init_payload_encryption([4; 32]);
+    canister_actor
+        .call_set_proxy_attestation_key(
+            proxy_client.client_principal(),
+            Some(key_pair.public_key().as_ref().to_vec()),
+        )
+        .unwrap();
+    proxy_client.setup_versioned_proxy(ProxyClientSetup {
+        supported_protocol_versions: vec![HTTP_OVER_WS_PROTOCOL_V2],
+        capabilities: None,
+        encryption_key: Some(encryption_key(
+            &key_pair,
+            proxy_client.client_principal(),
+            &secret_key,
+        )),
+    });
+
+    let mut request = HttpRequest::new(TEST_URL, HttpMethod::GET, vec![], None);
+    request.redirect = Some(RedirectPolicy::Follow(1));
+    let request_id = canister_actor
+        .call_execute_http_request(request, None, false)
+        .unwrap();
+
+    let next_encrypted_request = |proxy_client: &mut ProxyClient| {
+        let mut messages = proxy_client.get_http_over_ws_messages();
+        assert_eq!(messages.len(), 1);
+        match messages.remove(0) {
+            HttpOverWsMessage::EncryptedHttpRequest(id, encrypted_request) => {
+                assert_eq!(id, request_id);
+                encrypted_request
+            }
+            message => panic!("unexpected message: {:?}", message),
+        }
+    };
+
+    let first_hop = next_encrypted_request(&mut proxy_client);
+    assert_eq!(first_hop.hop, 0);
+    let ciphertext = encrypt_http_response(
+        &secret_key,
+        request_id,
+        &first_hop,
+        &redirect_response(302, "/moved"),
+    )
+    .unwrap();
+    proxy_client.send_http_over_ws_message(HttpOverWsMessage::EncryptedHttpResponse(
+        request_id, ciphertext, None,
+    ));
+
+    let second_hop = next_encrypted_request(&mut proxy_client);
+    assert_eq!(second_hop.hop, 1);
+    assert_eq!(
+        decrypt_http_request(&secret_key, request_id, &second_hop).map(|request| request.url),
+        Ok("https://example.com/moved".to_string())
+    );
+
+    // each hop is encrypted with its own key
+    assert_ne!(
+        first_hop.ephemeral_public_key,
+        second_hop.ephemeral_public_key
+    );
+    let second_hop_with_first_key = EncryptedHttpRequest {
+        ephemeral_public_key: first_hop.ephemeral_public_key.clone(),
+        ..second_hop.clone()
+    };
+    assert!(decrypt_http_request(&secret_key, request_id, &second_hop_with_first_key).is_err());
+    let second_hop_as_first = EncryptedHttpRequest {
+        hop: 0,
+        ..second_hop.clone()
+    };
+    assert!(decrypt_http_request(&secret_key, request_id, &second_hop_as_first).is_err());
+
+    // a response encrypted for the first hop is rejected
+    let http_response = HttpResponse {
+        status: Nat::from(200),
+        headers: vec![TEST_HTTP_RESPONSE_HEADER.clone()],
+        body: vec![4, 5, 6],
+    };
+    let replayed_ciphertext =
+        encrypt_http_response(&secret_key, request_id, &first_hop, &http_response).unwrap();
+    let ciphertext =
+        encrypt_http_response(&secret_key, request_id, &second_hop, &http_response).unwrap();
+    assert_ne!(replayed_ciphertext, ciphertext);
+    proxy_client.send_http_over_ws_message(HttpOverWsMessage::EncryptedHttpResponse(
+        request_id, ciphertext, None,
+    ));
+
+    let res = canister_actor.query_get_http_response(request_id);
+    assert_eq!(res, Ok(HttpResult::Success(http_response)));
+}
+
+#[test]
+fn test_encrypted_http_response_rejected() {
+    setup();
+    reset_canister();
+    let test_env = get_test_env();
+    let mut proxy_client = ProxyClient::new(&test_env, get_test_canister_id(&test_env));
+    let mut legacy_proxy_client = ProxyClient::new(&test_env, get_test_canister_id(&test_env));
+    let mut forged_proxy_client = ProxyClient::new(&test_env, get_test_canister_id(&test_env));
+    let canister_actor = CanisterActor::new(&test_env);
+    let key_pair = Ed25519KeyPair::from_seed_unchecked(&[1; 32]).unwrap();
+    let other_key_pair = Ed25519KeyPair::from_seed_unchecked(&[2; 32]).unwrap();
+    let secret_key = [3; 32];
+
+    canister_actor.call_init_payload_encryption([4; 32]);
+    for proxy_principal in [
+        proxy_client.client_principal(),
+        forged_proxy_client.client_principal(),
+    ] {
+        canister_actor
+            .call_set_proxy_attestation_key(
+                proxy_principal,
+                Some(key_pair.public_key().as_ref().to_vec()),
+            )
+            .unwrap();
+    }
+
+    // a key not signed with the registered attestation key could have been substituted on the way
+    let reply = forged_proxy_client.setup_versioned_proxy(ProxyClientSetup {
+        supported_protocol_versions: vec![HTTP_OVER_WS_PROTOCOL_V2],
+        capabilities: None,
+        encryption_key: Some(encryption_key(
+            &other_key_pair,
+            forged_proxy_client.client_principal(),
+            &secret_key,
+        )),
+    });
+    assert!(matches!(
+        reply,
+        HttpOverWsMessage::Error(None, err, _) if err.starts_with("invalid encryption key")
+    ));
+
+    let request = HttpRequest::new(TEST_URL, HttpMethod::GET, vec![], None);
+    let res = canister_actor.call_execute_http_request(request.clone(), None, false);
+    assert_eq!(res, Err(HttpOverWsError::NoProxiesConnected));
+
+    // no connected proxy encrypts the payloads
+    legacy_proxy_client.setup_proxy();
+    canister_actor.call_set_payload_encryption_required(true);
+    let res = canister_actor.call_execute_http_request(request.clone(), None, false);
+    assert_eq!(res, Err(HttpOverWsError::PayloadEncryptionNotAvailable));
+    legacy_proxy_client.expect_received_http_requests_count(0);
+
+    proxy_client.setup_versioned_proxy(ProxyClientSetup {
+        supported_protocol_versions: vec![HTTP_OVER_WS_PROTOCOL_V2],
+        capabilities: None,
+        encryption_key: Some(encryption_key(
+            &key_pair,
+            proxy_client.client_principal(),
+            &secret_key,
+        )),
+    });
+
+    // the proxy must not reply in plaintext to an encrypted request
+    let request_id = canister_actor
+        .call_execute_http_request(request, None, false)
+        .unwrap();
+    legacy_proxy_client.expect_received_http_requests_count(0);
+    proxy_client.send_http_over_ws_message(HttpOverWsMessage::HttpResponse(
+        request_id,
+        HttpResponse {
+            status: Nat::from(200),
+            headers: vec![],
+            body: vec![1, 2, 3],
+        },
+    ));
+    let res = canister_actor.query_get_http_response(request_id);
+    assert!(matches!(
+        res,
+        Err(HttpOverWsError::RequestFailed(
+            HttpFailureReason::InvalidEncryptedResponse(_)
+        ))
+    ));
+}
+
+#[test]
+fn test_execute_http_request_follow_redirects() {
+    setup();
+    reset_canister();
+    let test_env = get_test_env();
+    let mut proxy_client = ProxyClient::new(&test_env, get_test_canister_id(&test_env));
+    let canister_actor = CanisterActor::new(&test_env);
+
+    proxy_client.setup_proxy();
+
+    let mut request = HttpRequest::new(
+        TEST_URL,
+        HttpMethod::POST,
+        vec![
+            TEST_HTTP_REQUEST_HEADER.clone(),
+            HttpHeader {
+                name: "Authorization".to_string(),
+                value: "Bearer token".to_string(),
+            },
+        ],
+        Some(vec![1, 2, 3]),
+    );
+    request.redirect = Some(RedirectPolicy::Follow(2));
+    let request_id = canister_actor
+        .call_execute_http_request(request.clone(), None, false)
+        .unwrap();
+
+    // the proxy doesn't follow the redirects on its own
+    let mut proxy_request = request.clone();
+    proxy_request.redirect = Some(RedirectPolicy::Manual);
+    assert_eq!(
+        proxy_client.get_http_over_ws_messages(),
+        vec![HttpOverWsMessage::HttpRequest(
+            request_id,
+            proxy_request.clone()
+        )]
+    );
+    assert_eq!(
+        canister_actor.query_get_http_response_metadata(request_id),
+        Ok(None)
+    );
+
+    // the method and the body are kept
+    proxy_client.send_http_over_ws_message(HttpOverWsMessage::HttpResponse(
+        request_id,
+        redirect_response(307, "/moved?a=1"),
+    ));
+    proxy_request.url = "https://example.com/moved?a=1".to_string();
+    assert_eq!(
+        proxy_client.get_http_over_ws_messages(),
+        vec![HttpOverWsMessage::HttpRequest(request_id, proxy_request)]
+    );
+
+    // the request becomes a GET, and the credentials are not sent to another origin
+    proxy_client.send_http_over_ws_message(HttpOverWsMessage::HttpResponse(
+        request_id,
+        redirect_response(303, "https://other.example.com/result"),
+    ));
+    let expected_request = HttpRequest {
+        url: "https://other.example.com/result".to_string(),
+        method: HttpMethod::GET,
+        headers: vec![TEST_HTTP_REQUEST_HEADER.clone()],
+        body: None,
+        redirect: Some(RedirectPolicy::Manual),
+    };
+    assert_eq!(
+        proxy_client.get_http_over_ws_messages(),
+        vec![HttpOverWsMessage::HttpRequest(request_id, expected_request)]
+    );
+
+    let http_response = HttpResponse {
+        status: Nat::from(200),
+        headers: vec![TEST_HTTP_RESPONSE_HEADER.clone()],
+        body: vec![4, 5, 6],
+    };
+    proxy_client.send_http_over_ws_message(HttpOverWsMessage::HttpResponse(
+        request_id,
+        http_response.clone(),
+    ));
+
+    let res = canister_actor.query_get_http_response(request_id);
+    assert_eq!(res, Ok(HttpResult::Success(http_response)));
+    assert_eq!(
+        canister_actor.query_get_http_response_metadata(request_id),
+        Ok(Some(HttpResponseMetadata {
+            proxy_principal: proxy_client.client_principal(),
+            final_url: "https://other.example.com/result".to_string(),
+            redirects: vec![
+                HttpRedirect {
+                    status: 307,
+                    url: "https://example.com/moved?a=1".to_string(),
+                },
+                HttpRedirect {
+                    status: 303,
+                    url: "https://other.example.com/result".to_string(),
+                },
+            ],
+            timing: None,
+            body_size_bytes: None,
+            proxy_attempts: 1,
+        }))
+    );
+}
+
+#[test]
+fn test_execute_http_request_redirect_policies() {
+    setup();
+    reset_canister();
+    let test_env = get_test_env();
+    let mut proxy_client = ProxyClient::new(&test_env, get_test_canister_id(&test_env));
+    let canister_actor = CanisterActor::new(&test_env);
+
+    proxy_client.setup_proxy();
+
+    let execute_with_redirect = |redirect: Option<RedirectPolicy>| {
+        let mut request = HttpRequest::new(TEST_URL, HttpMethod::GET, vec![], None);
+        request.redirect = redirect;
+        canister_actor
+            .call_execute_http_request(request, None, false)
+            .unwrap()
+    };
+
+    // the redirect response is returned as it is
+    for redirect in [None, Some(RedirectPolicy::Manual)] {
+        let request_id = execute_with_redirect(redirect);
+        proxy_client.expect_received_http_requests_count(1);
+        proxy_client.send_http_over_ws_message(HttpOverWsMessage::HttpResponse(
+            request_id,
+            redirect_response(302, "/moved"),
+        ));
+        let res = canister_actor.query_get_http_response(request_id);
+        assert_eq!(
+            res,
+            Ok(HttpResult::Success(redirect_response(302, "/moved")))
+        );
+        assert_eq!(
+            canister_actor.query_get_http_response_metadata(request_id),
+            Ok(Some(HttpResponseMetadata {
+                proxy_principal: proxy_client.client_principal(),
+                final_url: TEST_URL.to_string(),
+                redirects: vec![],
+                timing: None,
+                body_size_bytes: None,
+                proxy_attempts: 1,
+            }))
+        );
+    }
+
+    let request_id = execute_with_redirect(Some(RedirectPolicy::Error));
+    proxy_client.expect_received_http_requests_count(1);
+    proxy_client.send_http_over_ws_message(HttpOverWsMessage::HttpResponse(
+        request_id,
+        redirect_response(301, "/moved"),
+    ));
+    let res = canister_actor.query_get_http_response(request_id);
+    assert_eq!(
+        res,
+        Err(HttpOverWsError::RequestFailed(
+            HttpFailureReason::RedirectNotAllowed("/moved".to_string())
+        ))
+    );
+
+    let request_id = execute_with_redirect(Some(RedirectPolicy::Follow(1)));
+    proxy_client.expect_received_http_requests_count(1);
+    proxy_client.send_http_over_ws_message(HttpOverWsMessage::HttpResponse(
+        request_id,
+        redirect_response(302, "/moved"),
+    ));
+    proxy_client.expect_received_http_requests_count(1);
+    proxy_client.send_http_over_ws_message(HttpOverWsMessage::HttpResponse(
+        request_id,
+        redirect_response(302, "/moved-again"),
+    ));
+    let res = canister_actor.query_get_http_response(request_id);
+    assert_eq!(
+        res,
+        Err(HttpOverWsError::RequestFailed(
+            HttpFailureReason::TooManyRedirects
+        ))
+    );
+
+    // the proxy would substitute the secret chosen by the server
+    let request_id = execute_with_redirect(Some(RedirectPolicy::Follow(1)));
+    proxy_client.expect_received_http_requests_count(1);
+    proxy_client.send_http_over_ws_message(HttpOverWsMessage::HttpResponse(
+        request_id,
+        redirect_response(
+            302,
+            &format!(
+                "https://evil.example.com/?key={}",
+                secret_placeholder("API_KEY")
+            ),
+        ),
+    ));
+    proxy_client.expect_received_http_requests_count(0);
+    let res = canister_actor.query_get_http_response(request_id);
+    assert!(matches!(
+        res,
+        Err(HttpOverWsError::RequestFailed(
+            HttpFailureReason::InvalidRedirect(_)
+        ))
+    ));
+}
@@ -1,9 +1,12 @@
 use std::cell::RefCell;
 
+use candid::{Nat, Principal};
 use http_over_ws::{
-    ExecuteHttpRequestResult, GetHttpResponseResult, HttpOverWsError, HttpRequest, HttpRequestId,
-    HttpRequestTimeoutMs, HttpResult,
+    ExecuteHttpRequestResult, GetHttpResponseEnvelopeResult, GetHttpResponseResult,
+    HttpOverWsError, HttpOverWsProtocolVersion, HttpRequest, HttpRequestDeadline, HttpRequestId,
+    HttpRequestTimeoutMs, HttpResponse, HttpResponseMetadata, HttpResult, ResponseAttestation,
 };
+use ic_cdk::api::performance_counter;
 use ic_cdk_macros::{query, update};
 use ic_websocket_cdk::{OnCloseCallbackArgs, OnMessageCallbackArgs, OnOpenCallbackArgs};
 use logger::log;
@@ -18,7 +21,11 @@ pub fn on_open(args: OnOpenCallbackArgs) {
 
 pub fn on_message(args: OnMessageCallbackArgs) {
     if let Err(HttpOverWsError::NotHttpOverWsType(_)) =
-        http_over_ws::try_handle_http_over_ws_message(args.client_principal, args.message.clone())
+        http_over_ws::try_handle_http_over_ws_message(
+            args.client_principal,
+            args.message.clone(),
+            ic_websocket_cdk::send,
+        )
     {
         log!(
             "Received WS proxy message: {:?} from {}",
@@ -39,21 +46,105 @@ pub fn on_close(args: OnCloseCallbackArgs) {
 #[update]
 fn execute_http_request(
     req: HttpRequest,
-    timeout_ms: Option<HttpRequestTimeoutMs>,
+    deadline: Option<HttpRequestDeadline>,
     with_callback: bool,
 ) -> ExecuteHttpRequestResult {
     http_over_ws::execute_http_request(
         req,
         with_callback.then_some(|_, res| Box::pin(callback(res))),
-        timeout_ms,
+        deadline,
         ic_websocket_cdk::send,
     )
 }
 
+/// Stores `count` connections without sending their requests to the proxies,
+/// returning the id of the last one.
+#[update]
+fn store_http_connections(req: HttpRequest, count: u32) -> HttpRequestId {
+    let mut last_request_id = 0;
+    for _ in 0..count {
+        last_request_id =
+            http_over_ws::execute_http_request(req.clone(), None, None, |_, _| Ok(())).unwrap();
+    }
+    last_request_id
+}
+
+/// Returns the response of the request along with the instructions it took to look it up.
+#[query]
+fn get_http_response_with_instructions(id: HttpRequestId) -> (GetHttpResponseResult, u64) {
+    let instructions_before = performance_counter(0);
+    let res = http_over_ws::get_http_response(id);
+    (res, performance_counter(0) - instructions_before)
+}
+
+#[update]
+fn set_min_protocol_version(version: HttpOverWsProtocolVersion) -> Result<(), HttpOverWsError> {
+    http_over_ws::set_min_protocol_version(version)
+}
+
+#[update]
+fn set_proxy_attestation_key(
+    proxy_principal: Principal,
+    public_key: Option<Vec<u8>>,
+) -> Result<(), HttpOverWsError> {
+    http_over_ws::set_proxy_attestation_key(proxy_principal, public_key)
+}
+
+#[update]
+fn set_response_attestations_required(required: bool) {
+    http_over_ws::set_response_attestations_required(required);
+}
+
+#[query]
+fn get_http_response_attestation(
+    id: HttpRequestId,
+) -> Result<Option<ResponseAttestation>, HttpOverWsError> {
+    http_over_ws::get_http_response_attestation(id)
+}
+
+#[query]
+fn get_http_response_metadata(
+    id: HttpRequestId,
+) -> Result<Option<HttpResponseMetadata>, HttpOverWsError> {
+    http_over_ws::get_http_response_metadata(id)
+}
+
+#[query]
+fn get_http_response_envelope(id: HttpRequestId) -> GetHttpResponseEnvelopeResult {
+    http_over_ws::get_http_response_envelope(id)
+}
+
+#[update]
+fn init_payload_encryption(seed: Vec<u8>) {
+    http_over_ws::init_payload_encryption(seed.try_into().expect("the seed must be 32 bytes long"));
+}
+
+#[update]
+fn set_payload_encryption_required(required: bool) {
+    http_over_ws::set_payload_encryption_required(required);
+}
+
+#[update]
+fn set_default_http_request_timeout_ms(timeout_ms: HttpRequestTimeoutMs) {
+    http_over_ws::set_default_http_request_timeout_ms(timeout_ms);
+}
+
 async fn callback(http_result: HttpResult) {
     CALLBACK_RESPONSES.with(|http_results| http_results.borrow_mut().push(http_result));
 }
 
+pub fn register_webhook_handlers() {
+    http_over_ws::register_webhook_handler("/echo", |req| Box::pin(echo_webhook(req)));
+}
+
+async fn echo_webhook(req: HttpRequest) -> HttpResponse {
+    HttpResponse {
+        status: Nat::from(200),
+        headers: vec![],
+        body: req.body.unwrap_or_default(),
+    }
+}
+
 #[update]
 fn disconnect_all_proxies() {
     http_over_ws::disconnect_all_connected_proxies(ic_websocket_cdk::close);
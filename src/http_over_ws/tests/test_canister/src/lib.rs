@@ -1,5 +1,5 @@
 use candid::CandidType;
-use canister::{on_close, on_message, on_open};
+use canister::{on_close, on_message, on_open, register_webhook_handlers};
 use ic_cdk_macros::*;
 use ic_websocket_cdk::{
     CanisterWsCloseArguments, CanisterWsCloseResult, CanisterWsGetMessagesArguments,
@@ -13,6 +13,7 @@ mod canister;
 #[init]
 fn init() {
     init_ws();
+    register_webhook_handlers();
 }
 
 #[post_upgrade]
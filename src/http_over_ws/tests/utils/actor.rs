@@ -1,7 +1,8 @@
 use candid::Principal;
 use http_over_ws::{
-    ExecuteHttpRequestResult, GetHttpResponseResult, HttpRequest, HttpRequestId,
-    HttpRequestTimeoutMs, HttpResult,
+    ExecuteHttpRequestResult, GetHttpResponseEnvelopeResult, GetHttpResponseResult,
+    HttpOverWsError, HttpOverWsProtocolVersion, HttpRequest, HttpRequestDeadline, HttpRequestId,
+    HttpRequestTimeoutMs, HttpResponseMetadata, HttpResult, ResponseAttestation,
 };
 use test_utils::{ic_env::TestEnv, identity::generate_random_principal};
 
@@ -23,14 +24,132 @@ impl<'a> CanisterActor<'a> {
     pub fn call_execute_http_request(
         &self,
         req: HttpRequest,
-        timeout_ms: Option<HttpRequestTimeoutMs>,
+        deadline: Option<HttpRequestDeadline>,
         with_callback: bool,
     ) -> ExecuteHttpRequestResult {
         self.test_env.call_canister_method_with_panic(
             self.test_canister_id,
             self.principal,
             "execute_http_request",
-            (req, timeout_ms, with_callback),
+            (req, deadline, with_callback),
+        )
+    }
+
+    pub fn call_store_http_connections(&self, req: HttpRequest, count: u32) -> HttpRequestId {
+        self.test_env.call_canister_method_with_panic(
+            self.test_canister_id,
+            self.principal,
+            "store_http_connections",
+            (req, count),
+        )
+    }
+
+    pub fn query_get_http_response_with_instructions(
+        &self,
+        request_id: HttpRequestId,
+    ) -> (GetHttpResponseResult, u64) {
+        self.test_env.query_canister_method_with_panic(
+            self.test_canister_id,
+            self.principal,
+            "get_http_response_with_instructions",
+            (request_id,),
+        )
+    }
+
+    pub fn call_set_min_protocol_version(
+        &self,
+        version: HttpOverWsProtocolVersion,
+    ) -> Result<(), HttpOverWsError> {
+        self.test_env.call_canister_method_with_panic(
+            self.test_canister_id,
+            self.principal,
+            "set_min_protocol_version",
+            (version,),
+        )
+    }
+
+    pub fn call_set_proxy_attestation_key(
+        &self,
+        proxy_principal: Principal,
+        public_key: Option<Vec<u8>>,
+    ) -> Result<(), HttpOverWsError> {
+        self.test_env.call_canister_method_with_panic(
+            self.test_canister_id,
+            self.principal,
+            "set_proxy_attestation_key",
+            (proxy_principal, public_key),
+        )
+    }
+
+    pub fn call_set_response_attestations_required(&self, required: bool) {
+        self.test_env.call_canister_method_with_panic(
+            self.test_canister_id,
+            self.principal,
+            "set_response_attestations_required",
+            (required,),
+        )
+    }
+
+    pub fn query_get_http_response_attestation(
+        &self,
+        request_id: HttpRequestId,
+    ) -> Result<Option<ResponseAttestation>, HttpOverWsError> {
+        self.test_env.query_canister_method_with_panic(
+            self.test_canister_id,
+            self.principal,
+            "get_http_response_attestation",
+            (request_id,),
+        )
+    }
+
+    pub fn query_get_http_response_metadata(
+        &self,
+        request_id: HttpRequestId,
+    ) -> Result<Option<HttpResponseMetadata>, HttpOverWsError> {
+        self.test_env.query_canister_method_with_panic(
+            self.test_canister_id,
+            self.principal,
+            "get_http_response_metadata",
+            (request_id,),
+        )
+    }
+
+    pub fn query_get_http_response_envelope(
+        &self,
+        request_id: HttpRequestId,
+    ) -> GetHttpResponseEnvelopeResult {
+        self.test_env.query_canister_method_with_panic(
+            self.test_canister_id,
+            self.principal,
+            "get_http_response_envelope",
+            (request_id,),
+        )
+    }
+
+    pub fn call_init_payload_encryption(&self, seed: [u8; 32]) {
+        self.test_env.call_canister_method_with_panic(
+            self.test_canister_id,
+            self.principal,
+            "init_payload_encryption",
+            (seed.to_vec(),),
+        )
+    }
+
+    pub fn call_set_payload_encryption_required(&self, required: bool) {
+        self.test_env.call_canister_method_with_panic(
+            self.test_canister_id,
+            self.principal,
+            "set_payload_encryption_required",
+            (required,),
+        )
+    }
+
+    pub fn call_set_default_http_request_timeout_ms(&self, timeout_ms: HttpRequestTimeoutMs) {
+        self.test_env.call_canister_method_with_panic(
+            self.test_canister_id,
+            self.principal,
+            "set_default_http_request_timeout_ms",
+            (timeout_ms,),
         )
     }
 
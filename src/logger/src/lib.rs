@@ -1,47 +1,344 @@
-use std::{cell::RefCell, time::Duration};
+use std::cell::RefCell;
 
+use candid::{CandidType, Deserialize, Principal};
 use ic_cdk::{api::time, print};
 
-type LogDateTime = String;
-type LogMessage = String;
+mod stable_log;
 
-pub type Logs = Vec<(LogDateTime, LogMessage)>;
+pub use stable_log::STABLE_MEMORY_LOGS_REGION_BYTES;
+use stable_log::{LogMemory, StableLog};
+
+/// The default maximum size of the log entries kept in memory.
+/// When exceeded, the oldest entries are evicted.
+pub const DEFAULT_LOGS_MAX_SIZE_BYTES: u64 = 16 * 1024 * 1024;
+
+/// The maximum amount of log entries returned by a single [get_logs] or [export_logs] call.
+pub const MAX_GET_LOGS_LIMIT: u32 = 500;
+
+/// The maximum amount of entries examined by a single [get_logs] call while filtering.
+const MAX_SCANNED_LOGS: usize = 10_000;
+
+/// The maximum size, in memory, of the entries returned by a single [export_logs] call.
+const MAX_EXPORT_LOGS_BYTES: u64 = 1024 * 1024;
+
+pub type LogEntryId = u64;
 
 thread_local! {
-  /* flexible */ static LOGGER: RefCell<Logger> = RefCell::new(Logger::new());
+  /* flexible */ static LOGGER: RefCell<StableLog> = RefCell::new(StableLog::load(
+    LogMemory::Heap(vec![]),
+    STABLE_MEMORY_LOGS_REGION_BYTES,
+  ));
 }
 
-struct Logger {
-    logs: Logs,
+#[derive(CandidType, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    Debug,
+    Info,
+    Warn,
+    Error,
 }
 
-impl Logger {
-    fn new() -> Self {
-        Logger { logs: Logs::new() }
-    }
+/// Optional structured fields attached to a log entry, which can be used to filter the logs.
+#[derive(CandidType, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct LogFields {
+    pub request_id: Option<u64>,
+    pub canister_id: Option<Principal>,
+    pub proxy_principal: Option<Principal>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct LogEntry {
+    /// Increases monotonically, also across evictions and upgrades.
+    pub id: LogEntryId,
+    pub timestamp_ns: u64,
+    pub level: LogLevel,
+    pub message: String,
+    pub fields: LogFields,
+}
 
-    fn log(&mut self, message: &str) {
-        let time_ns = Duration::from_nanos(time());
-        let utc_datetime = utc_dt::UTCDatetime::from(time_ns);
-        self.logs
-            .push((utc_datetime.as_iso_datetime(Some(3)), message.to_string()))
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct LoggerConfig {
+    /// The maximum size of the log entries kept in memory,
+    /// capped at the size of the region reserved to the logs.
+    pub max_size_bytes: u64,
+    /// The entries with a lower level are discarded.
+    pub min_level: LogLevel,
+}
+
+impl Default for LoggerConfig {
+    fn default() -> Self {
+        Self {
+            max_size_bytes: DEFAULT_LOGS_MAX_SIZE_BYTES,
+            min_level: LogLevel::Info,
+        }
     }
 }
 
-pub fn get_logs() -> Logs {
-    LOGGER.with(|logger| logger.borrow().logs.clone())
+#[derive(CandidType, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct GetLogsArgs {
+    /// Only the entries with an id greater than this one are returned.
+    pub after_id: Option<LogEntryId>,
+    /// Defaults to and is capped at [MAX_GET_LOGS_LIMIT], with a minimum of 1.
+    pub limit: Option<u32>,
+    pub min_level: Option<LogLevel>,
+    pub request_id: Option<u64>,
+    pub canister_id: Option<Principal>,
+    pub proxy_principal: Option<Principal>,
 }
 
-pub fn log(message: &str) {
-    print(message);
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct GetLogsResult {
+    pub logs: Vec<LogEntry>,
+    /// The value to pass as `after_id` to get the next page,
+    /// [None] if there are no more entries.
+    pub next_after_id: Option<LogEntryId>,
+}
+
+/// The position of an entry in the logs, ordered by timestamp.
+/// Entries logged in the same execution share the timestamp, so the id breaks the ties.
+#[derive(CandidType, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct LogsCursor {
+    pub timestamp_ns: u64,
+    pub id: LogEntryId,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ExportLogsArgs {
+    /// Only the entries following the cursor are returned.
+    /// Pass the `next_cursor` of the previous page to continue the export.
+    pub cursor: Option<LogsCursor>,
+    /// When there is no cursor, only the entries logged at or after this timestamp are returned.
+    pub from_timestamp_ns: Option<u64>,
+    /// Defaults to and is capped at [MAX_GET_LOGS_LIMIT], with a minimum of 1.
+    pub limit: Option<u32>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ExportLogsResult {
+    pub logs: Vec<LogEntry>,
+    /// The cursor to pass to get the next page, which is also returned when there are
+    /// no new entries so that the export can be resumed later.
+    pub next_cursor: Option<LogsCursor>,
+    /// Whether there are more entries after this page.
+    pub has_more: bool,
+    /// The amount of entries following the given cursor that have been evicted
+    /// before being exported.
+    pub missed_count: u64,
+}
+
+fn matches_filter(entry: &LogEntry, args: &GetLogsArgs) -> bool {
+    // [None] is lower than any level
+    args.min_level <= Some(entry.level)
+        && (args.request_id.is_none() || args.request_id == entry.fields.request_id)
+        && (args.canister_id.is_none() || args.canister_id == entry.fields.canister_id)
+        && (args.proxy_principal.is_none() || args.proxy_principal == entry.fields.proxy_principal)
+}
+
+fn page_limit(limit: Option<u32>) -> usize {
+    limit
+        .unwrap_or(MAX_GET_LOGS_LIMIT)
+        .clamp(1, MAX_GET_LOGS_LIMIT) as usize
+}
+
+/// Returns the entries matching the filters, ordered by id.
+pub fn get_logs(args: &GetLogsArgs) -> GetLogsResult {
+    LOGGER.with(|logger| {
+        let logger = logger.borrow();
+        let index = logger.index();
+
+        let limit = page_limit(args.limit);
+        let start = match args.after_id {
+            Some(after_id) => index.partition_point(|entry| entry.id <= after_id),
+            None => 0,
+        };
+
+        let mut logs = vec![];
+        let mut next_after_id = None;
+        for (scanned, index_entry) in index.range(start..).enumerate() {
+            // stop at the limits, letting the caller continue from the last examined entry
+            if logs.len() == limit || scanned == MAX_SCANNED_LOGS {
+                next_after_id = index.get(start + scanned - 1).map(|entry| entry.id);
+                break;
+            }
+
+            if let Some(entry) = logger.read(index_entry) {
+                if matches_filter(&entry, args) {
+                    logs.push(entry);
+                }
+            }
+        }
+
+        GetLogsResult {
+            logs,
+            next_after_id,
+        }
+    })
+}
+
+/// Returns a page of the entries ordered by timestamp, to copy the logs off-chain.
+/// Following the returned cursors, every entry is returned exactly once,
+/// unless it is evicted before being exported, in which case it is counted in `missed_count`.
+pub fn export_logs(args: &ExportLogsArgs) -> ExportLogsResult {
+    LOGGER.with(|logger| {
+        let logger = logger.borrow();
+        let index = logger.index();
+
+        let (start, missed_count) = match args.cursor {
+            Some(cursor) => {
+                let start = index.partition_point(|entry| {
+                    LogsCursor {
+                        timestamp_ns: entry.timestamp_ns,
+                        id: entry.id,
+                    } <= cursor
+                });
+                let first_available_id = index.front().map_or(logger.next_id(), |oldest| oldest.id);
+
+                (start, first_available_id.saturating_sub(cursor.id + 1))
+            }
+            None => {
+                let from_timestamp_ns = args.from_timestamp_ns.unwrap_or_default();
+                let start = index.partition_point(|entry| entry.timestamp_ns < from_timestamp_ns);
+
+                (start, 0)
+            }
+        };
+
+        let limit = page_limit(args.limit);
+        let mut logs = vec![];
+        let mut next_cursor = args.cursor;
+        let mut size = 0;
+        let mut end = start;
+        for index_entry in index.range(start..) {
+            // always return at least one entry, so that the export makes progress
+            if logs.len() == limit
+                || (!logs.is_empty() && size + index_entry.size() > MAX_EXPORT_LOGS_BYTES)
+            {
+                break;
+            }
+            size += index_entry.size();
+            end += 1;
+
+            next_cursor = Some(LogsCursor {
+                timestamp_ns: index_entry.timestamp_ns,
+                id: index_entry.id,
+            });
+            if let Some(entry) = logger.read(index_entry) {
+                logs.push(entry);
+            }
+        }
+
+        ExportLogsResult {
+            logs,
+            next_cursor,
+            has_more: end < index.len(),
+            missed_count,
+        }
+    })
+}
+
+/// Keeps the logs in the region of [STABLE_MEMORY_LOGS_REGION_BYTES] bytes of the stable memory
+/// starting at the given offset, so that they survive upgrades. Otherwise, the logs are kept in the heap.
+///
+/// The logs previously kept in the region are loaded, followed by the entries already logged in the heap.
+/// It should therefore be called at the beginning of both the `init` and the `post_upgrade` hooks,
+/// always with the same offset.
+pub fn init_stable_logs(offset: u64) {
+    LOGGER.with(|logger| {
+        let mut logger = logger.borrow_mut();
+        let heap_logger = std::mem::replace(
+            &mut *logger,
+            StableLog::load(LogMemory::Stable(offset), STABLE_MEMORY_LOGS_REGION_BYTES),
+        );
+
+        for index_entry in heap_logger.index() {
+            if let Some(entry) = heap_logger.read(index_entry) {
+                let id = logger.next_id();
+                logger.append(&LogEntry { id, ..entry });
+            }
+        }
+    });
+}
+
+pub fn get_logger_config() -> LoggerConfig {
+    LOGGER.with(|logger| logger.borrow().config().clone())
+}
+
+pub fn set_logger_config(config: LoggerConfig) {
+    LOGGER.with(|logger| logger.borrow_mut().set_config(config));
+}
+
+/// Logs the message with the given level and fields.
+/// Messages below the configured minimum level are neither printed nor stored.
+pub fn log_entry(level: LogLevel, fields: LogFields, message: &str) {
+    if level < LOGGER.with(|logger| logger.borrow().config().min_level) {
+        return;
+    }
+
+    print(format!("[{:?}] {}", level, message));
     LOGGER.with(|logger| {
-        logger.borrow_mut().log(message);
+        let mut logger = logger.borrow_mut();
+        let entry = LogEntry {
+            id: logger.next_id(),
+            timestamp_ns: time(),
+            level,
+            message: message.to_string(),
+            fields,
+        };
+        logger.append(&entry);
     });
 }
 
+/// Logs the message with the [LogLevel::Info] level and no fields.
+pub fn log(message: &str) {
+    log_entry(LogLevel::Info, LogFields::default(), message);
+}
+
+/// Logs with the given level, optionally preceded by the structured fields in braces:
+///
+/// ```ignore
+/// log_with_level!(LogLevel::Info, { request_id: 1, canister_id: caller }, "request {} started", 1);
+/// ```
+#[macro_export]
+macro_rules! log_with_level {
+    ($level:expr, { $($field:ident : $value:expr),* $(,)? }, $($arg:tt)*) => {
+        $crate::log_entry(
+            $level,
+            $crate::LogFields {
+                $($field: Some($value),)*
+                ..Default::default()
+            },
+            &format!($($arg)*),
+        )
+    };
+    ($level:expr, $($arg:tt)*) => {
+        $crate::log_entry($level, $crate::LogFields::default(), &format!($($arg)*))
+    };
+}
+
 #[macro_export]
 macro_rules! log {
     ($($arg:tt)*) => {
-        log(&format!($($arg)*));
+        $crate::log_with_level!($crate::LogLevel::Info, $($arg)*)
+    }
+}
+
+#[macro_export]
+macro_rules! debug {
+    ($($arg:tt)*) => {
+        $crate::log_with_level!($crate::LogLevel::Debug, $($arg)*)
+    }
+}
+
+#[macro_export]
+macro_rules! warn {
+    ($($arg:tt)*) => {
+        $crate::log_with_level!($crate::LogLevel::Warn, $($arg)*)
+    }
+}
+
+#[macro_export]
+macro_rules! error {
+    ($($arg:tt)*) => {
+        $crate::log_with_level!($crate::LogLevel::Error, $($arg)*)
     }
 }
@@ -0,0 +1,483 @@
+use std::collections::VecDeque;
+
+use candid::{decode_one, encode_one};
+use ic_cdk::{
+    api::stable::{stable64_read, stable64_size, CanisterStableMemory, StableIO},
+    print,
+};
+
+use crate::{LogEntry, LogEntryId, LogLevel, LoggerConfig};
+
+/// The size of the stable memory region reserved to the logs once [init_stable_logs](crate::init_stable_logs) is called.
+/// Canisters using the logger must not store their own data in this region.
+pub const STABLE_MEMORY_LOGS_REGION_BYTES: u64 = 64 * 1024 * 1024;
+
+const WASM_PAGE_SIZE_BYTES: u64 = 65_536;
+
+const MAGIC: &[u8; 4] = b"LOGS";
+const LAYOUT_VERSION: u8 = 1;
+const HEADER_SIZE: u64 = 64;
+
+/// Every record starts with the length of the rest of the record.
+const RECORD_LENGTH_SIZE: u64 = 4;
+/// The id and the timestamp of the entry are stored before its Candid encoding,
+/// so that the index can be rebuilt without decoding the entries.
+const RECORD_METADATA_SIZE: u64 = 16;
+/// Written in place of a record length when the next record starts at the beginning of the data area.
+const WRAP_MARKER: u32 = u32::MAX;
+
+/// Locates a record in the data area.
+pub(crate) struct IndexEntry {
+    pub id: LogEntryId,
+    pub timestamp_ns: u64,
+    offset: u64,
+    size: u64,
+}
+
+impl IndexEntry {
+    /// The amount of bytes taken by the record in stable memory.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+}
+
+/// Where the region of a [StableLog] is kept.
+pub(crate) enum LogMemory {
+    /// Lost when the canister is upgraded.
+    Heap(Vec<u8>),
+    /// The region of the stable memory starting at the given offset.
+    Stable(u64),
+}
+
+impl LogMemory {
+    /// The amount of bytes of the region that have been allocated.
+    fn size(&self) -> u64 {
+        match self {
+            LogMemory::Heap(bytes) => bytes.len() as u64,
+            LogMemory::Stable(offset) => {
+                (stable64_size() * WASM_PAGE_SIZE_BYTES).saturating_sub(*offset)
+            }
+        }
+    }
+
+    /// Reads from the region, which must be allocated up to the end of the buffer.
+    fn read(&self, address: u64, buf: &mut [u8]) {
+        match self {
+            LogMemory::Heap(bytes) => {
+                let start = address as usize;
+                buf.copy_from_slice(&bytes[start..start + buf.len()]);
+            }
+            LogMemory::Stable(offset) => stable64_read(offset + address, buf),
+        }
+    }
+
+    /// Writes to the region, growing it if needed.
+    fn write(&mut self, address: u64, buf: &[u8]) -> Result<(), String> {
+        match self {
+            LogMemory::Heap(bytes) => {
+                let start = address as usize;
+                if bytes.len() < start + buf.len() {
+                    bytes.resize(start + buf.len(), 0);
+                }
+                bytes[start..start + buf.len()].copy_from_slice(buf);
+                Ok(())
+            }
+            LogMemory::Stable(offset) => StableIO::<CanisterStableMemory, u64>::with_memory(
+                CanisterStableMemory::default(),
+                *offset + address,
+            )
+            .write(buf)
+            .map(|_| ())
+            .map_err(|e| e.to_string()),
+        }
+    }
+}
+
+/// A ring buffer of log entries, which survives upgrades when kept in the stable memory.
+///
+/// The header at the beginning of the region keeps the positions of the oldest and newest records
+/// and the logger configuration, while the records are kept in the data area following it.
+/// When the configured maximum size is exceeded, the oldest records are evicted.
+///
+/// The in-memory index of the records is rebuilt from the region after an upgrade.
+pub(crate) struct StableLog {
+    memory: LogMemory,
+    /// The size of the data area following the header, in which the records wrap around.
+    data_size: u64,
+    /// The offset in the data area of the oldest record.
+    head: u64,
+    /// The offset in the data area at which the next record is written.
+    tail: u64,
+    used_bytes: u64,
+    next_id: LogEntryId,
+    config: LoggerConfig,
+    /// The records ordered by id, oldest first.
+    index: VecDeque<IndexEntry>,
+}
+
+impl StableLog {
+    /// Loads the log from the region of the given size,
+    /// or creates an empty one if the region is not initialized.
+    pub fn load(memory: LogMemory, region_size: u64) -> Self {
+        let mut log = StableLog {
+            memory,
+            data_size: region_size - HEADER_SIZE,
+            head: 0,
+            tail: 0,
+            used_bytes: 0,
+            next_id: 0,
+            config: LoggerConfig::default(),
+            index: VecDeque::new(),
+        };
+
+        if log.memory.size() < HEADER_SIZE {
+            return log;
+        }
+
+        let mut header = [0; HEADER_SIZE as usize];
+        log.memory.read(0, &mut header);
+        if &header[0..4] != MAGIC || header[4] != LAYOUT_VERSION {
+            return log;
+        }
+
+        let read_u64 =
+            |start: usize| u64::from_le_bytes(header[start..start + 8].try_into().unwrap());
+        log.config = LoggerConfig {
+            max_size_bytes: read_u64(8).min(log.data_size),
+            min_level: level_from_byte(header[5]),
+        };
+        log.head = read_u64(16);
+        log.tail = read_u64(24);
+        log.used_bytes = read_u64(32);
+        let count = read_u64(40);
+        log.next_id = read_u64(48);
+
+        let mut offset = log.head;
+        for _ in 0..count {
+            let (record_offset, length) = log.read_record_start(offset);
+
+            let mut metadata = [0; RECORD_METADATA_SIZE as usize];
+            log.memory.read(
+                data_address(record_offset + RECORD_LENGTH_SIZE),
+                &mut metadata,
+            );
+
+            let size = RECORD_LENGTH_SIZE + length as u64;
+            log.index.push_back(IndexEntry {
+                id: u64::from_le_bytes(metadata[0..8].try_into().unwrap()),
+                timestamp_ns: u64::from_le_bytes(metadata[8..16].try_into().unwrap()),
+                offset: record_offset,
+                size,
+            });
+            offset = record_offset + size;
+        }
+
+        log
+    }
+
+    pub fn next_id(&self) -> LogEntryId {
+        self.next_id
+    }
+
+    pub fn config(&self) -> &LoggerConfig {
+        &self.config
+    }
+
+    pub fn index(&self) -> &VecDeque<IndexEntry> {
+        &self.index
+    }
+
+    /// Updates the configuration, evicting the oldest records if the new maximum size is exceeded.
+    /// The maximum size is capped at the size of the data area.
+    pub fn set_config(&mut self, config: LoggerConfig) {
+        self.config = LoggerConfig {
+            max_size_bytes: config.max_size_bytes.min(self.data_size),
+            ..config
+        };
+
+        while self.used_bytes > self.config.max_size_bytes {
+            self.evict_oldest();
+        }
+
+        self.write_header();
+    }
+
+    /// Appends the entry, which must have the id returned by [StableLog::next_id].
+    pub fn append(&mut self, entry: &LogEntry) {
+        let payload = match encode_one(entry) {
+            Ok(payload) => payload,
+            Err(e) => {
+                print(format!("logger: failed to encode log entry: {}", e));
+                return;
+            }
+        };
+
+        let length = RECORD_METADATA_SIZE + payload.len() as u64;
+        let size = RECORD_LENGTH_SIZE + length;
+        if size > self.config.max_size_bytes {
+            print(format!(
+                "logger: log entry of {} bytes exceeds the maximum size of the logs",
+                size
+            ));
+            return;
+        }
+
+        let offset = loop {
+            if self.used_bytes + size <= self.config.max_size_bytes {
+                if let Some(offset) = self.write_offset(size) {
+                    break offset;
+                }
+            }
+
+            self.evict_oldest();
+        };
+
+        let mut record = Vec::with_capacity(size as usize);
+        record.extend_from_slice(&(length as u32).to_le_bytes());
+        record.extend_from_slice(&entry.id.to_le_bytes());
+        record.extend_from_slice(&entry.timestamp_ns.to_le_bytes());
+        record.extend_from_slice(&payload);
+
+        if offset < self.tail && self.data_size - self.tail >= RECORD_LENGTH_SIZE {
+            if let Err(e) = self
+                .memory
+                .write(data_address(self.tail), &WRAP_MARKER.to_le_bytes())
+            {
+                print(format!("logger: failed to write log entry: {}", e));
+                return;
+            }
+        }
+        if let Err(e) = self.memory.write(data_address(offset), &record) {
+            print(format!("logger: failed to write log entry: {}", e));
+            return;
+        }
+
+        if self.index.is_empty() {
+            self.head = offset;
+        }
+        self.index.push_back(IndexEntry {
+            id: entry.id,
+            timestamp_ns: entry.timestamp_ns,
+            offset,
+            size,
+        });
+        self.tail = offset + size;
+        self.used_bytes += size;
+        self.next_id = entry.id + 1;
+
+        self.write_header();
+    }
+
+    /// Reads the entry of the record from the region.
+    pub fn read(&self, index_entry: &IndexEntry) -> Option<LogEntry> {
+        let mut payload =
+            vec![0; (index_entry.size - RECORD_LENGTH_SIZE - RECORD_METADATA_SIZE) as usize];
+        self.memory.read(
+            data_address(index_entry.offset + RECORD_LENGTH_SIZE + RECORD_METADATA_SIZE),
+            &mut payload,
+        );
+
+        decode_one(&payload).ok()
+    }
+
+    /// Returns the offset in the data area at which a record of the given size fits, if any.
+    fn write_offset(&self, size: u64) -> Option<u64> {
+        if self.index.is_empty() {
+            return Some(0);
+        }
+
+        if self.tail > self.head {
+            if self.tail + size <= self.data_size {
+                return Some(self.tail);
+            }
+            // the records must not reach the head, otherwise a full buffer would look empty
+            if size < self.head {
+                return Some(0);
+            }
+            return None;
+        }
+
+        (self.tail + size < self.head).then_some(self.tail)
+    }
+
+    fn evict_oldest(&mut self) {
+        if let Some(evicted) = self.index.pop_front() {
+            self.used_bytes -= evicted.size;
+        }
+
+        match self.index.front() {
+            Some(oldest) => self.head = oldest.offset,
+            None => {
+                self.head = 0;
+                self.tail = 0;
+            }
+        }
+    }
+
+    fn write_header(&mut self) {
+        let mut header = [0; HEADER_SIZE as usize];
+        header[0..4].copy_from_slice(MAGIC);
+        header[4] = LAYOUT_VERSION;
+        header[5] = self.config.min_level as u8;
+        header[8..16].copy_from_slice(&self.config.max_size_bytes.to_le_bytes());
+        header[16..24].copy_from_slice(&self.head.to_le_bytes());
+        header[24..32].copy_from_slice(&self.tail.to_le_bytes());
+        header[32..40].copy_from_slice(&self.used_bytes.to_le_bytes());
+        header[40..48].copy_from_slice(&(self.index.len() as u64).to_le_bytes());
+        header[48..56].copy_from_slice(&self.next_id.to_le_bytes());
+
+        if let Err(e) = self.memory.write(0, &header) {
+            print(format!("logger: failed to write logs header: {}", e));
+        }
+    }
+
+    /// Returns the offset and the length of the record that is read at the given offset,
+    /// following the wrap around of the data area.
+    fn read_record_start(&self, offset: u64) -> (u64, u32) {
+        let read_length = |offset: u64| {
+            let mut length = [0; RECORD_LENGTH_SIZE as usize];
+            self.memory.read(data_address(offset), &mut length);
+            u32::from_le_bytes(length)
+        };
+
+        if self.data_size - offset < RECORD_LENGTH_SIZE {
+            return (0, read_length(0));
+        }
+
+        match read_length(offset) {
+            WRAP_MARKER => (0, read_length(0)),
+            length => (offset, length),
+        }
+    }
+}
+
+fn data_address(offset: u64) -> u64 {
+    HEADER_SIZE + offset
+}
+
+fn level_from_byte(byte: u8) -> LogLevel {
+    match byte {
+        0 => LogLevel::Debug,
+        1 => LogLevel::Info,
+        2 => LogLevel::Warn,
+        _ => LogLevel::Error,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LogFields;
+
+    const TEST_REGION_SIZE: u64 = HEADER_SIZE + 1024;
+
+    fn entry(id: LogEntryId) -> LogEntry {
+        LogEntry {
+            id,
+            timestamp_ns: 1_000 + id,
+            level: LogLevel::Info,
+            message: format!("log entry {}", id),
+            fields: LogFields {
+                request_id: Some(id),
+                ..Default::default()
+            },
+        }
+    }
+
+    fn entries(log: &StableLog) -> Vec<LogEntry> {
+        log.index()
+            .iter()
+            .map(|index_entry| log.read(index_entry).unwrap())
+            .collect()
+    }
+
+    fn new_log() -> StableLog {
+        let mut log = StableLog::load(LogMemory::Heap(vec![]), TEST_REGION_SIZE);
+        log.set_config(LoggerConfig {
+            max_size_bytes: u64::MAX,
+            min_level: LogLevel::Debug,
+        });
+        log
+    }
+
+    #[test]
+    fn test_append_wraps_around() {
+        let mut log = new_log();
+        assert_eq!(log.config().max_size_bytes, TEST_REGION_SIZE - HEADER_SIZE);
+
+        let mut wraps = 0;
+        for id in 0..100 {
+            let tail = log.tail;
+            log.append(&entry(id));
+            if log.tail < tail {
+                wraps += 1;
+            }
+
+            assert!(log.tail <= log.data_size);
+            assert!(log.used_bytes <= log.config().max_size_bytes);
+            assert_eq!(
+                log.used_bytes,
+                log.index().iter().map(IndexEntry::size).sum::<u64>()
+            );
+        }
+        assert!(wraps > 1);
+        assert_eq!(log.next_id(), 100);
+
+        // only the newest entries are kept, in order
+        let kept = entries(&log);
+        assert!(!kept.is_empty() && kept.len() < 100);
+        let first_kept_id = 100 - kept.len() as u64;
+        assert_eq!(kept, (first_kept_id..100).map(entry).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_load_reads_back_header() {
+        let mut log = new_log();
+        log.set_config(LoggerConfig {
+            max_size_bytes: 700,
+            min_level: LogLevel::Warn,
+        });
+        for id in 0..37 {
+            log.append(&entry(id));
+        }
+
+        let (head, tail, used_bytes) = (log.head, log.tail, log.used_bytes);
+        // the records wrap around the end of the data area
+        assert!(tail < head);
+        let logged = entries(&log);
+
+        let loaded = StableLog::load(log.memory, TEST_REGION_SIZE);
+        assert_eq!(
+            loaded.config(),
+            &LoggerConfig {
+                max_size_bytes: 700,
+                min_level: LogLevel::Warn,
+            }
+        );
+        assert_eq!(
+            (loaded.head, loaded.tail, loaded.used_bytes),
+            (head, tail, used_bytes)
+        );
+        assert_eq!(loaded.next_id(), 37);
+        assert_eq!(entries(&loaded), logged);
+
+        // the loaded log keeps appending after the newest entry
+        let mut loaded = loaded;
+        loaded.append(&entry(37));
+        assert_eq!(entries(&loaded).last(), Some(&entry(37)));
+    }
+
+    #[test]
+    fn test_load_uninitialized_region() {
+        for memory in [
+            LogMemory::Heap(vec![]),
+            LogMemory::Heap(vec![0; HEADER_SIZE as usize * 2]),
+        ] {
+            let log = StableLog::load(memory, TEST_REGION_SIZE);
+
+            assert_eq!(log.config(), &LoggerConfig::default());
+            assert_eq!(log.next_id(), 0);
+            assert!(log.index().is_empty());
+        }
+    }
+}
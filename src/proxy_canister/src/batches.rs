@@ -0,0 +1,114 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use http_over_ws::{HttpRequestId, HttpResult};
+use proxy_canister_types::{CanisterCallbackMethodName, CanisterId};
+
+type BatchId = u64;
+
+/// A batch whose requests have all reached a terminal state.
+pub struct CompletedBatch {
+    pub canister_id: CanisterId,
+    pub callback_method_name: CanisterCallbackMethodName,
+    pub results: Vec<(HttpRequestId, HttpResult)>,
+}
+
+struct Batch {
+    canister_id: CanisterId,
+    callback_method_name: CanisterCallbackMethodName,
+    pending: BTreeSet<HttpRequestId>,
+    results: BTreeMap<HttpRequestId, HttpResult>,
+}
+
+impl Batch {
+    fn complete(self) -> CompletedBatch {
+        CompletedBatch {
+            canister_id: self.canister_id,
+            callback_method_name: self.callback_method_name,
+            results: self.results.into_iter().collect(),
+        }
+    }
+}
+
+/// Collects the results of the requests of the batches that have an aggregated callback.
+pub struct Batches {
+    batches: HashMap<BatchId, Batch>,
+    /// The batches waiting for the result of each request.
+    /// A request can be part of more than one batch when an idempotency key is reused.
+    request_batches: HashMap<HttpRequestId, Vec<BatchId>>,
+    next_batch_id: BatchId,
+}
+
+impl Batches {
+    pub fn new() -> Self {
+        Self {
+            batches: HashMap::new(),
+            request_batches: HashMap::new(),
+            next_batch_id: 0,
+        }
+    }
+
+    /// Starts waiting for the results of the given requests.
+    ///
+    /// `completed` contains the results of the requests that already reached
+    /// a terminal state. If all the requests did, the batch is returned as completed immediately.
+    pub fn start(
+        &mut self,
+        canister_id: CanisterId,
+        callback_method_name: CanisterCallbackMethodName,
+        pending: BTreeSet<HttpRequestId>,
+        completed: BTreeMap<HttpRequestId, HttpResult>,
+    ) -> Option<CompletedBatch> {
+        let batch = Batch {
+            canister_id,
+            callback_method_name,
+            pending,
+            results: completed,
+        };
+
+        if batch.pending.is_empty() {
+            return Some(batch.complete());
+        }
+
+        let batch_id = self.next_batch_id;
+        self.next_batch_id += 1;
+
+        for request_id in &batch.pending {
+            self.request_batches
+                .entry(*request_id)
+                .or_default()
+                .push(batch_id);
+        }
+        self.batches.insert(batch_id, batch);
+
+        None
+    }
+
+    /// Records the result of the request and returns the batches it completed.
+    pub fn complete_request(
+        &mut self,
+        request_id: HttpRequestId,
+        http_result: &HttpResult,
+    ) -> Vec<CompletedBatch> {
+        let Some(batch_ids) = self.request_batches.remove(&request_id) else {
+            return vec![];
+        };
+
+        let mut completed_batches = vec![];
+        for batch_id in batch_ids {
+            let Some(batch) = self.batches.get_mut(&batch_id) else {
+                continue;
+            };
+
+            batch.pending.remove(&request_id);
+            batch.results.insert(request_id, http_result.clone());
+
+            if batch.pending.is_empty() {
+                if let Some(batch) = self.batches.remove(&batch_id) {
+                    completed_batches.push(batch.complete());
+                }
+            }
+        }
+
+        completed_batches
+    }
+}
@@ -0,0 +1,302 @@
+use std::collections::{BTreeMap, HashMap};
+
+use http_over_ws::{
+    HttpHeader, HttpRequest, HttpRequestId, HttpResponse, HttpResponseMetadata, HttpResult,
+};
+use proxy_canister_types::{CacheStats, CacheTtlMs, CanisterId};
+
+use crate::{
+    constants::{MAX_CACHE_TTL_MS, MAX_RESPONSE_CACHE_SIZE_BYTES},
+    utils::days_from_civil,
+};
+
+/// Identifies a cached response by the method and URL of the request
+/// and by the values of the headers selected by the caller.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct CacheKey(String);
+
+impl CacheKey {
+    /// The responses are only cached for the canister that requested them,
+    /// as they may have been obtained with its credentials, secrets or signing profiles.
+    pub fn new(canister_id: CanisterId, request: &HttpRequest, key_headers: &[String]) -> Self {
+        CacheKey(format!(
+            "{} {}",
+            canister_id,
+            request_key(request, key_headers)
+        ))
+    }
+
+    /// Builds a key that takes all the headers of the request into account,
+    /// whichever canister sends it.
+    pub fn from_request(request: &HttpRequest) -> Self {
+        let header_names: Vec<String> = request
+            .headers
+            .iter()
+            .map(|header| header.name.clone())
+            .collect();
+
+        CacheKey(request_key(request, &header_names))
+    }
+}
+
+fn request_key(request: &HttpRequest, key_headers: &[String]) -> String {
+    let mut key_headers: Vec<String> = key_headers
+        .iter()
+        .map(|name| name.to_ascii_lowercase())
+        .collect();
+    key_headers.sort();
+    key_headers.dedup();
+
+    let mut key = format!("{:?} {}", request.method, request.url);
+    // a redirect may be followed or not depending on the policy
+    if let Some(redirect) = &request.redirect {
+        key.push_str(&format!(" {:?}", redirect));
+    }
+    for name in key_headers {
+        let values: Vec<&str> = request
+            .headers
+            .iter()
+            .filter(|header| header.name.eq_ignore_ascii_case(&name))
+            .map(|header| header.value.as_str())
+            .collect();
+        key.push_str(&format!("\n{}:{}", name, values.join(",")));
+    }
+
+    key
+}
+
+struct CacheEntry {
+    response: HttpResponse,
+    /// How the response has been obtained by the request that filled the entry.
+    metadata: Option<HttpResponseMetadata>,
+    expires_at_ms: u64,
+    last_access: u64,
+    size_bytes: usize,
+}
+
+/// A request that has been sent to a proxy and whose response will be cached once received.
+struct PendingCacheEntry {
+    key: CacheKey,
+    ttl_ms: Option<CacheTtlMs>,
+}
+
+pub struct ResponseCache {
+    entries: HashMap<CacheKey, CacheEntry>,
+    /// Cache keys ordered by last access, least recently used first.
+    lru: BTreeMap<u64, CacheKey>,
+    access_counter: u64,
+    size_bytes: usize,
+    pending: HashMap<HttpRequestId, PendingCacheEntry>,
+    hits: u64,
+    misses: u64,
+}
+
+impl ResponseCache {
+    pub fn new() -> Self {
+        Self {
+            entries: HashMap::new(),
+            lru: BTreeMap::new(),
+            access_counter: 0,
+            size_bytes: 0,
+            pending: HashMap::new(),
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    pub fn get(
+        &mut self,
+        key: &CacheKey,
+        now_ms: u64,
+    ) -> Option<(HttpResponse, Option<HttpResponseMetadata>)> {
+        if self
+            .entries
+            .get(key)
+            .is_some_and(|entry| entry.expires_at_ms <= now_ms)
+        {
+            self.remove(key);
+        }
+
+        self.access_counter += 1;
+        match self.entries.get_mut(key) {
+            Some(entry) => {
+                self.lru.remove(&entry.last_access);
+                entry.last_access = self.access_counter;
+                self.lru.insert(entry.last_access, key.clone());
+                self.hits += 1;
+
+                Some((entry.response.clone(), entry.metadata.clone()))
+            }
+            None => {
+                self.misses += 1;
+
+                None
+            }
+        }
+    }
+
+    /// Remembers that the response of the request must be cached once received.
+    pub fn track_request(
+        &mut self,
+        request_id: HttpRequestId,
+        key: CacheKey,
+        ttl_ms: Option<CacheTtlMs>,
+    ) {
+        self.pending
+            .insert(request_id, PendingCacheEntry { key, ttl_ms });
+    }
+
+    /// Caches the response of a tracked request, if the request succeeded
+    /// and the response is cacheable.
+    pub fn complete_request(
+        &mut self,
+        request_id: HttpRequestId,
+        http_result: &HttpResult,
+        metadata: Option<HttpResponseMetadata>,
+        now_ms: u64,
+    ) {
+        let Some(pending) = self.pending.remove(&request_id) else {
+            return;
+        };
+        let HttpResult::Success(response) = http_result else {
+            return;
+        };
+        if response.status < 200_u64 || response.status >= 300_u64 {
+            return;
+        }
+
+        let Some(ttl_ms) = pending
+            .ttl_ms
+            .or_else(|| ttl_from_headers(&response.headers, now_ms))
+            .map(|ttl_ms| ttl_ms.min(MAX_CACHE_TTL_MS))
+        else {
+            return;
+        };
+        if ttl_ms == 0 {
+            return;
+        }
+
+        self.insert(pending.key, response.clone(), metadata, now_ms + ttl_ms);
+    }
+
+    pub fn get_stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits,
+            misses: self.misses,
+            entries: self.entries.len() as u64,
+            size_bytes: self.size_bytes as u64,
+        }
+    }
+
+    fn insert(
+        &mut self,
+        key: CacheKey,
+        response: HttpResponse,
+        metadata: Option<HttpResponseMetadata>,
+        expires_at_ms: u64,
+    ) {
+        let size_bytes = response_size_bytes(&response);
+        if size_bytes > MAX_RESPONSE_CACHE_SIZE_BYTES {
+            return;
+        }
+
+        self.remove(&key);
+        while self.size_bytes + size_bytes > MAX_RESPONSE_CACHE_SIZE_BYTES {
+            let Some((_, lru_key)) = self.lru.pop_first() else {
+                break;
+            };
+            self.remove(&lru_key);
+        }
+
+        self.access_counter += 1;
+        self.lru.insert(self.access_counter, key.clone());
+        self.size_bytes += size_bytes;
+        self.entries.insert(
+            key,
+            CacheEntry {
+                response,
+                metadata,
+                expires_at_ms,
+                last_access: self.access_counter,
+                size_bytes,
+            },
+        );
+    }
+
+    fn remove(&mut self, key: &CacheKey) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.lru.remove(&entry.last_access);
+            self.size_bytes -= entry.size_bytes;
+        }
+    }
+}
+
+fn response_size_bytes(response: &HttpResponse) -> usize {
+    response.body.len()
+        + response
+            .headers
+            .iter()
+            .map(|header| header.name.len() + header.value.len())
+            .sum::<usize>()
+}
+
+/// Computes the TTL of a response from its `Cache-Control` and `Expires` headers.
+/// As in RFC 9111, `s-maxage` and `max-age` take precedence over `Expires`.
+///
+/// Returns [None] if the response must not be cached.
+fn ttl_from_headers(headers: &[HttpHeader], now_ms: u64) -> Option<CacheTtlMs> {
+    let header_value = |name: &str| {
+        headers
+            .iter()
+            .find(|header| header.name.eq_ignore_ascii_case(name))
+            .map(|header| header.value.as_str())
+    };
+
+    if let Some(cache_control) = header_value("cache-control") {
+        let mut max_age_secs = None;
+        let mut s_maxage_secs = None;
+
+        for directive in cache_control
+            .split(',')
+            .map(|directive| directive.trim().to_ascii_lowercase())
+        {
+            match directive.split_once('=') {
+                Some(("max-age", secs)) => max_age_secs = secs.trim_matches('"').parse().ok(),
+                Some(("s-maxage", secs)) => s_maxage_secs = secs.trim_matches('"').parse().ok(),
+                None if matches!(directive.as_str(), "no-store" | "no-cache" | "private") => {
+                    return None;
+                }
+                _ => {}
+            }
+        }
+
+        if let Some(secs) = s_maxage_secs.or(max_age_secs) {
+            return Some(u64::saturating_mul(secs, 1_000));
+        }
+    }
+
+    let expires_at_ms = parse_http_date(header_value("expires")?)?;
+    expires_at_ms.checked_sub(now_ms)
+}
+
+/// Parses an IMF-fixdate (e.g. `Sun, 06 Nov 1994 08:49:37 GMT`)
+/// into milliseconds since the UNIX epoch.
+fn parse_http_date(value: &str) -> Option<u64> {
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+
+    let parts: Vec<&str> = value.split_whitespace().collect();
+    let [_, day, month, year, time, "GMT"] = parts.as_slice() else {
+        return None;
+    };
+
+    let day: i64 = day.parse().ok()?;
+    let month = MONTHS.iter().position(|m| m == month)? as i64 + 1;
+    let year: i64 = year.parse().ok()?;
+    let mut hms = time.split(':').map(|v| v.parse::<i64>().ok());
+    let (hours, minutes, seconds) = (hms.next()??, hms.next()??, hms.next()??);
+
+    let secs = ((days_from_civil(year, month, day) * 24 + hours) * 60 + minutes) * 60 + seconds;
+    u64::try_from(secs).ok().map(|secs| secs * 1_000)
+}
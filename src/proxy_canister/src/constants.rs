@@ -1,10 +1,77 @@
 use http_over_ws::HttpRequestTimeoutMs;
+use proxy_canister_types::CacheTtlMs;
 
-/// The minimum timeout a request can take before timing out.
-pub const MIN_HTTP_REQUEST_TIMEOUT_MS: HttpRequestTimeoutMs = 5_000;
+/// Where the region of the stable memory keeping the logs starts.
+/// The rest of the state is saved after this region across upgrades.
+pub const STABLE_MEMORY_LOGS_OFFSET: u64 = 0;
 
-/// The maximum amount of time a request can take before timing out.
-pub const MAX_HTTP_REQUEST_TIMEOUT_MS: HttpRequestTimeoutMs = 60_000;
+/// The highest maximum timeout of the requests that can be configured.
+pub const MAX_CONFIGURABLE_HTTP_REQUEST_TIMEOUT_MS: HttpRequestTimeoutMs = 10 * 60 * 1_000;
 
-/// The maximum amount of headers a request can have.
-pub const MAX_HTTP_HEADERS_COUNT: usize = 50;
+/// The highest maximum amount of headers of a request that can be configured.
+pub const MAX_CONFIGURABLE_HTTP_HEADERS_COUNT: u32 = 1_000;
+
+/// The maximum amount of time a response can be kept in the cache.
+pub const MAX_CACHE_TTL_MS: CacheTtlMs = 24 * 60 * 60 * 1_000;
+
+/// The maximum total size of the responses kept in the cache.
+/// When exceeded, the least recently used responses are evicted.
+pub const MAX_RESPONSE_CACHE_SIZE_BYTES: usize = 16 * 1024 * 1024;
+
+/// How long an idempotency key is remembered after the request it refers to has started.
+pub const IDEMPOTENCY_KEY_WINDOW_MS: u64 = 10 * 60 * 1_000;
+
+/// The maximum length of an idempotency key.
+pub const MAX_IDEMPOTENCY_KEY_LENGTH: usize = 128;
+
+/// How long a request and its result are retained after the request has been executed.
+pub const REQUEST_RETENTION_MS: u64 = 60 * 60 * 1_000;
+
+/// The default and maximum amount of requests returned by a single list query.
+pub const MAX_LIST_REQUESTS_LIMIT: u32 = 100;
+
+/// The maximum amount of attempts to deliver the result of a request to the callback method
+/// that can be configured. The delay between the attempts doubles at each attempt.
+pub const MAX_CONFIGURABLE_CALLBACK_DELIVERY_ATTEMPTS: u32 = 10;
+
+/// The delay before the first redelivery of a result to the callback method,
+/// doubled at each following attempt.
+pub const CALLBACK_REDELIVERY_BASE_DELAY_MS: u64 = 2_000;
+
+/// The maximum amount of events recorded in the trace of a request.
+/// When exceeded, the following events are not recorded.
+pub const MAX_REQUEST_TRACE_EVENTS: usize = 100;
+
+/// The maximum amount of dead letters kept. When exceeded, the oldest dead letters are dropped.
+pub const MAX_DEAD_LETTERS_COUNT: usize = 10_000;
+
+/// The maximum amount of requests in a single batch.
+pub const MAX_BATCH_REQUESTS_COUNT: usize = 100;
+
+/// The minimum interval between two executions of a scheduled request.
+pub const MIN_SCHEDULE_INTERVAL_MS: u64 = 60_000;
+
+/// The maximum amount of scheduled jobs a canister can have.
+pub const MAX_SCHEDULED_JOBS_PER_CANISTER: usize = 100;
+
+/// The amount of most recent executions kept in the history of a scheduled job.
+pub const MAX_SCHEDULED_JOB_EXECUTIONS_HISTORY: usize = 100;
+
+/// The prefix of the paths on which the proxies receive the webhook requests,
+/// followed by the id of the canister and the path it registered.
+pub const WEBHOOKS_PATH_PREFIX: &str = "/webhooks/";
+
+/// The maximum length of the path of a webhook.
+pub const MAX_WEBHOOK_PATH_LENGTH: usize = 256;
+
+/// The maximum amount of webhooks a canister can register.
+pub const MAX_WEBHOOKS_PER_CANISTER: usize = 20;
+
+/// The maximum amount of signing profiles a canister can have.
+pub const MAX_SIGNING_PROFILES_PER_CANISTER: usize = 20;
+
+/// The maximum length of the name of a signing profile.
+pub const MAX_SIGNING_PROFILE_NAME_LENGTH: usize = 64;
+
+/// The maximum length of the secret of a signing profile.
+pub const MAX_SIGNING_SECRET_LENGTH: usize = 1024;
@@ -0,0 +1,196 @@
+use std::ops::RangeInclusive;
+
+use crate::utils::{civil_from_days, days_from_civil};
+
+const NS_PER_MINUTE: u64 = 60 * 1_000_000_000;
+const MINUTES_PER_DAY: u64 = 24 * 60;
+
+/// The maximum amount of steps taken to find the next matching time,
+/// so that expressions that never match (e.g. `0 0 30 2 *`) don't loop forever.
+const MAX_SEARCH_STEPS: usize = 10_000;
+
+/// A cron expression with the five standard fields, evaluated in UTC:
+/// `minute hour day-of-month month day-of-week`.
+///
+/// Each field accepts `*`, values, ranges (`1-5`), steps (`*/15`, `0-30/10`) and lists of them.
+/// Day-of-week goes from `0` (Sunday) to `7` (Sunday again).
+#[derive(Debug, PartialEq, Eq)]
+pub struct CronSchedule {
+    minutes: u64,
+    hours: u64,
+    days_of_month: u64,
+    months: u64,
+    days_of_week: u64,
+    /// As in standard cron, if both day fields are restricted (i.e. don't start with `*`),
+    /// a day matches if it matches either of them.
+    days_of_month_restricted: bool,
+    days_of_week_restricted: bool,
+}
+
+impl CronSchedule {
+    pub fn parse(expression: &str) -> Result<Self, String> {
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        let [minutes, hours, days_of_month, months, days_of_week] = fields.as_slice() else {
+            return Err(format!(
+                "expected 5 fields in cron expression, found {}",
+                fields.len()
+            ));
+        };
+
+        let mut days_of_week_bits = parse_field(days_of_week, 0..=7)?;
+        // 7 is an alias for Sunday
+        if days_of_week_bits & (1 << 7) != 0 {
+            days_of_week_bits |= 1;
+        }
+
+        Ok(Self {
+            minutes: parse_field(minutes, 0..=59)?,
+            hours: parse_field(hours, 0..=23)?,
+            days_of_month: parse_field(days_of_month, 1..=31)?,
+            months: parse_field(months, 1..=12)?,
+            days_of_week: days_of_week_bits,
+            days_of_month_restricted: !days_of_month.starts_with('*'),
+            days_of_week_restricted: !days_of_week.starts_with('*'),
+        })
+    }
+
+    /// Returns the first time matching the expression strictly after the given time,
+    /// with both in nanoseconds since the UNIX epoch.
+    pub fn next_after(&self, time_ns: u64) -> Option<u64> {
+        let mut minutes = time_ns / NS_PER_MINUTE + 1;
+
+        for _ in 0..MAX_SEARCH_STEPS {
+            let days = minutes / MINUTES_PER_DAY;
+            let (year, month, day) = civil_from_days(days as i64);
+
+            if !has_bit(self.months, month) {
+                // skip to the first day of the next month
+                let (year, month) = if month == 12 {
+                    (year + 1, 1)
+                } else {
+                    (year, month + 1)
+                };
+                minutes = days_from_civil(year, month, 1) as u64 * MINUTES_PER_DAY;
+                continue;
+            }
+
+            if !self.matches_day(days, day) {
+                minutes = (days + 1) * MINUTES_PER_DAY;
+                continue;
+            }
+
+            let minute_of_day = minutes % MINUTES_PER_DAY;
+            if !has_bit(self.hours, (minute_of_day / 60) as i64) {
+                minutes = (minutes / 60 + 1) * 60;
+                continue;
+            }
+
+            if !has_bit(self.minutes, (minute_of_day % 60) as i64) {
+                minutes += 1;
+                continue;
+            }
+
+            return minutes.checked_mul(NS_PER_MINUTE);
+        }
+
+        None
+    }
+
+    fn matches_day(&self, days: u64, day_of_month: i64) -> bool {
+        // the UNIX epoch was a Thursday
+        let day_of_week = ((days + 4) % 7) as i64;
+        let matches_day_of_month = has_bit(self.days_of_month, day_of_month);
+        let matches_day_of_week = has_bit(self.days_of_week, day_of_week);
+
+        if self.days_of_month_restricted && self.days_of_week_restricted {
+            matches_day_of_month || matches_day_of_week
+        } else {
+            matches_day_of_month && matches_day_of_week
+        }
+    }
+}
+
+fn has_bit(bits: u64, value: i64) -> bool {
+    bits & (1 << value) != 0
+}
+
+/// Parses a comma-separated list of `*`, values, ranges and steps into a bit set.
+fn parse_field(field: &str, bounds: RangeInclusive<u64>) -> Result<u64, String> {
+    let mut bits = 0;
+
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => {
+                let step: u64 = step
+                    .parse()
+                    .map_err(|_| format!("invalid step in cron field: {}", part))?;
+                if step == 0 {
+                    return Err(format!("invalid step in cron field: {}", part));
+                }
+                (range, Some(step))
+            }
+            None => (part, None),
+        };
+
+        let (start, end) = if range == "*" {
+            (*bounds.start(), *bounds.end())
+        } else if let Some((start, end)) = range.split_once('-') {
+            (parse_value(start, &bounds)?, parse_value(end, &bounds)?)
+        } else {
+            let value = parse_value(range, &bounds)?;
+            // `5/10` stands for `5-<max>/10`, whatever the step
+            let end = if step.is_some() { *bounds.end() } else { value };
+            (value, end)
+        };
+
+        if start > end {
+            return Err(format!("invalid range in cron field: {}", part));
+        }
+
+        for value in (start..=end).step_by(step.unwrap_or(1) as usize) {
+            bits |= 1 << value;
+        }
+    }
+
+    Ok(bits)
+}
+
+fn parse_value(value: &str, bounds: &RangeInclusive<u64>) -> Result<u64, String> {
+    value
+        .parse()
+        .ok()
+        .filter(|value| bounds.contains(value))
+        .ok_or_else(|| format!("invalid value in cron field: {}", value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_field_steps() {
+        assert_eq!(parse_field("*/20", 0..=59), Ok(1 | 1 << 20 | 1 << 40));
+        assert_eq!(parse_field("0-30/15", 0..=59), Ok(1 | 1 << 15 | 1 << 30));
+        assert_eq!(parse_field("50/5", 0..=59), Ok(1 << 50 | 1 << 55));
+        assert_eq!(parse_field("57/1", 0..=59), Ok(1 << 57 | 1 << 58 | 1 << 59));
+        assert_eq!(parse_field("57", 0..=59), Ok(1 << 57));
+        assert!(parse_field("*/0", 0..=59).is_err());
+    }
+
+    #[test]
+    fn test_next_after_day_fields() {
+        let day_ns = |year, month, day| {
+            days_from_civil(year, month, day) as u64 * MINUTES_PER_DAY * NS_PER_MINUTE
+        };
+        // 2024-01-01 is a Monday
+        let now_ns = day_ns(2024, 1, 1);
+
+        // both day fields are restricted: the 3rd or a Monday
+        let schedule = CronSchedule::parse("0 0 3 * 1").unwrap();
+        assert_eq!(schedule.next_after(now_ns), Some(day_ns(2024, 1, 3)));
+
+        // a day field starting with `*` is not restricted: an odd day that is a Monday
+        let schedule = CronSchedule::parse("0 0 */2 * 1").unwrap();
+        assert_eq!(schedule.next_after(now_ns), Some(day_ns(2024, 1, 15)));
+    }
+}
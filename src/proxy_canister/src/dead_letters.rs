@@ -0,0 +1,94 @@
+use std::{collections::BTreeMap, ops::Bound};
+
+use candid::{CandidType, Deserialize};
+use http_over_ws::HttpRequestId;
+use proxy_canister_types::{CanisterId, DeadLetter, ListDeadLettersResult};
+
+use crate::constants::MAX_DEAD_LETTERS_COUNT;
+
+/// The dead letters, as saved to the stable memory across upgrades.
+#[derive(CandidType, Deserialize, Default)]
+pub struct DeadLettersSnapshot {
+    dead_letters: Vec<DeadLetter>,
+    /// The results that were waiting to be delivered again to the callback methods.
+    /// The redelivery timers don't survive upgrades, so they are set again after the upgrade.
+    pending_redeliveries: Vec<DeadLetter>,
+}
+
+pub struct DeadLetters(BTreeMap<HttpRequestId, DeadLetter>);
+
+impl DeadLetters {
+    pub fn new() -> Self {
+        Self(BTreeMap::new())
+    }
+
+    /// Restores the dead letters, returning the results that were waiting to be delivered again.
+    pub fn from_snapshot(snapshot: DeadLettersSnapshot) -> (Self, Vec<DeadLetter>) {
+        let mut dead_letters = Self::new();
+        for dead_letter in snapshot.dead_letters {
+            dead_letters.insert(dead_letter);
+        }
+        (dead_letters, snapshot.pending_redeliveries)
+    }
+
+    pub fn to_snapshot(&self, pending_redeliveries: Vec<DeadLetter>) -> DeadLettersSnapshot {
+        DeadLettersSnapshot {
+            dead_letters: self.0.values().cloned().collect(),
+            pending_redeliveries,
+        }
+    }
+
+    pub fn insert(&mut self, dead_letter: DeadLetter) {
+        self.0.insert(dead_letter.request_id, dead_letter);
+
+        while self.0.len() > MAX_DEAD_LETTERS_COUNT {
+            self.0.pop_first();
+        }
+    }
+
+    pub fn get(&self, request_id: HttpRequestId) -> Option<&DeadLetter> {
+        self.0.get(&request_id)
+    }
+
+    pub fn remove(&mut self, request_id: HttpRequestId) -> Option<DeadLetter> {
+        self.0.remove(&request_id)
+    }
+
+    /// Lists the dead letters of the given canister, or of all canisters if [None].
+    pub fn list(
+        &self,
+        canister_id: Option<CanisterId>,
+        after_request_id: Option<HttpRequestId>,
+        limit: usize,
+    ) -> ListDeadLettersResult {
+        let lower_bound = match after_request_id {
+            Some(request_id) => Bound::Excluded(request_id),
+            None => Bound::Unbounded,
+        };
+
+        let mut dead_letters: Vec<DeadLetter> = self
+            .0
+            .range((lower_bound, Bound::Unbounded))
+            .map(|(_, dead_letter)| dead_letter)
+            .filter(|dead_letter| {
+                canister_id.is_none() || canister_id == Some(dead_letter.canister_id)
+            })
+            .take(limit + 1)
+            .cloned()
+            .collect();
+
+        let next_after_request_id = if dead_letters.len() > limit {
+            dead_letters.truncate(limit);
+            dead_letters
+                .last()
+                .map(|dead_letter| dead_letter.request_id)
+        } else {
+            None
+        };
+
+        ListDeadLettersResult {
+            dead_letters,
+            next_after_request_id,
+        }
+    }
+}
@@ -0,0 +1,108 @@
+use std::collections::{HashMap, VecDeque};
+
+use http_over_ws::HttpRequestId;
+use proxy_canister_types::{CanisterId, IdempotencyKey};
+
+use crate::{cache::CacheKey, constants::IDEMPOTENCY_KEY_WINDOW_MS};
+
+/// Remembers the request started for each (caller, idempotency key) pair
+/// for [IDEMPOTENCY_KEY_WINDOW_MS].
+pub struct IdempotencyKeys {
+    requests: HashMap<(CanisterId, IdempotencyKey), HttpRequestId>,
+    /// Keys in the order they expire, which is the order they have been inserted in.
+    expirations: VecDeque<(u64, (CanisterId, IdempotencyKey))>,
+}
+
+impl IdempotencyKeys {
+    pub fn new() -> Self {
+        Self {
+            requests: HashMap::new(),
+            expirations: VecDeque::new(),
+        }
+    }
+
+    pub fn get(
+        &mut self,
+        canister_id: CanisterId,
+        key: &IdempotencyKey,
+        now_ms: u64,
+    ) -> Option<HttpRequestId> {
+        self.remove_expired(now_ms);
+
+        self.requests.get(&(canister_id, key.clone())).copied()
+    }
+
+    pub fn insert(
+        &mut self,
+        canister_id: CanisterId,
+        key: IdempotencyKey,
+        request_id: HttpRequestId,
+        now_ms: u64,
+    ) {
+        self.remove_expired(now_ms);
+
+        self.requests.insert((canister_id, key.clone()), request_id);
+        self.expirations
+            .push_back((now_ms + IDEMPOTENCY_KEY_WINDOW_MS, (canister_id, key)));
+    }
+
+    fn remove_expired(&mut self, now_ms: u64) {
+        while let Some((expires_at_ms, _)) = self.expirations.front() {
+            if *expires_at_ms > now_ms {
+                break;
+            }
+
+            if let Some((_, key)) = self.expirations.pop_front() {
+                self.requests.remove(&key);
+            }
+        }
+    }
+}
+
+/// Keeps track of the `GET` requests that are being executed,
+/// so that identical requests can wait for their result instead of reaching the proxies.
+pub struct InFlightRequests {
+    leaders: HashMap<CacheKey, HttpRequestId>,
+    keys: HashMap<HttpRequestId, CacheKey>,
+    followers: HashMap<HttpRequestId, Vec<HttpRequestId>>,
+}
+
+impl InFlightRequests {
+    pub fn new() -> Self {
+        Self {
+            leaders: HashMap::new(),
+            keys: HashMap::new(),
+            followers: HashMap::new(),
+        }
+    }
+
+    pub fn start(&mut self, key: CacheKey, request_id: HttpRequestId) {
+        self.keys.insert(request_id, key.clone());
+        self.leaders.entry(key).or_insert(request_id);
+    }
+
+    /// Returns the id of the in-flight request with the given key, if any.
+    pub fn get(&self, key: &CacheKey) -> Option<HttpRequestId> {
+        self.leaders.get(key).copied()
+    }
+
+    /// Makes the request wait for the result of the in-flight request.
+    pub fn follow(&mut self, leader_id: HttpRequestId, request_id: HttpRequestId) {
+        self.followers
+            .entry(leader_id)
+            .or_default()
+            .push(request_id);
+    }
+
+    /// Removes the request from the in-flight requests
+    /// and returns the requests that were waiting for its result.
+    pub fn complete(&mut self, request_id: HttpRequestId) -> Vec<HttpRequestId> {
+        if let Some(key) = self.keys.remove(&request_id) {
+            if self.leaders.get(&key) == Some(&request_id) {
+                self.leaders.remove(&key);
+            }
+        }
+
+        self.followers.remove(&request_id).unwrap_or_default()
+    }
+}
@@ -1,25 +1,73 @@
+mod batches;
+mod cache;
 mod constants;
+mod cron;
+mod dead_letters;
+mod deduplication;
+mod metrics;
 mod requests;
+mod scheduler;
+mod signing;
 mod state;
 mod utils;
+mod webhooks;
 mod ws;
 
+use cache::CacheKey;
+use candid::Principal;
+use dead_letters::DeadLettersSnapshot;
 use http_over_ws::{
-    disconnect_all_connected_proxies, execute_http_request, HttpRequestId, HttpResult,
+    disconnect_all_connected_proxies, execute_http_request, get_http_connection_proxy,
+    get_http_response_attestation, get_http_response_metadata, get_last_request_id, get_proxies,
+    is_valid_secret_name, reserve_request_id, restore_last_request_id, HttpRequest,
+    HttpRequestDeadline, HttpRequestId, HttpResponse, HttpResponseEnvelope, HttpResult, SecretName,
+};
+use ic_cdk::{
+    api::{canister_balance128, is_controller, management_canister::main::raw_rand, time},
+    caller, trap,
 };
-use ic_cdk::caller;
 use ic_cdk_macros::*;
-use logger::log;
+use logger::{
+    debug, error, log, warn, ExportLogsArgs, ExportLogsResult, GetLogsArgs, GetLogsResult,
+    LoggerConfig,
+};
+use metrics::{encode_prometheus, heap_memory_bytes, stable_memory_bytes};
 use proxy_canister_types::{
-    CanisterRequest, HttpRequestEndpointArgs, HttpRequestEndpointResult, ProxyCanisterError,
-    RequestState,
+    CacheStats, CallbackAttempt, CanisterCallbackMethodName, CanisterId, CanisterRequest,
+    GetMetricsResult, HttpMethod, HttpRequestBatchEndpointResult, HttpRequestEndpointArgs,
+    HttpRequestEndpointResult, InvalidRequest, ListDeadLettersResult, ListRequestsArgs,
+    ListRequestsResult, ManageProxyResult, ManageScheduledJobResult, ProxyCanisterError,
+    ProxyCanisterMetrics, ProxyConfig, ProxyInfo, RegisterSigningProfileArgs,
+    RegisterSigningProfileResult, RegisterWebhookResult, RequestState, RequestTrace,
+    RequestTraceEventKind, ResponseAttestationConfig, ScheduleHttpRequestArgs,
+    ScheduleHttpRequestResult, ScheduledJob, ScheduledJobExecution, ScheduledJobId,
+    ScheduledJobState, SetAllowedSecretsResult, SigningProfileInfo, SigningProfileName,
+    UpdateConfigResult, Webhook, WebhookPath,
+};
+use requests::{
+    validate_config, validate_incoming_request, validate_redirect, validate_schedule,
+    validate_signing_profile, validate_webhook,
+};
+use scheduler::ScheduledJobsSnapshot;
+use signing::SigningProfilesSnapshot;
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, BTreeSet},
+    time::Duration,
 };
-use requests::validate_incoming_request;
-use std::cell::RefCell;
+use webhooks::{parse_webhook_url, text_response, WebhooksSnapshot};
 
 use crate::{
+    batches::CompletedBatch,
+    constants::{
+        CALLBACK_REDELIVERY_BASE_DELAY_MS, MAX_BATCH_REQUESTS_COUNT, MAX_LIST_REQUESTS_LIMIT,
+        STABLE_MEMORY_LOGS_OFFSET,
+    },
     state::ProxyState,
-    utils::{guard_caller_is_controller, guard_caller_is_not_anonymous},
+    utils::{
+        guard_caller_is_controller, guard_caller_is_not_anonymous, stable_restore_after_logs,
+        stable_save_after_logs, time_ms,
+    },
 };
 
 thread_local! {
@@ -27,13 +75,161 @@ thread_local! {
 }
 
 #[init]
-fn init() {
+fn init(config: Option<ProxyConfig>) {
+    logger::init_stable_logs(STABLE_MEMORY_LOGS_OFFSET);
+    init_handlers();
+    schedule_payload_encryption_init();
+    set_config_or_trap(config.unwrap_or_default());
+}
+
+fn init_handlers() {
     ws::init_ws();
+    http_over_ws::set_default_webhook_handler(Some(|request| Box::pin(webhook_handler(request))));
+    http_over_ws::set_redirect_validator(Some(validate_request_redirect));
+}
+
+/// Seeds the keys that encrypt the payloads for the proxies, with randomness fetched right after
+/// the (post-)upgrade since management canister calls can't be made from there.
+fn schedule_payload_encryption_init() {
+    ic_cdk_timers::set_timer(Duration::ZERO, || {
+        ic_cdk::spawn(async {
+            let seed = match raw_rand().await {
+                Ok((bytes,)) => bytes.try_into(),
+                Err((code, msg)) => {
+                    error!(
+                        "[schedule_payload_encryption_init]: failed to fetch randomness: {:?} {}",
+                        code, msg
+                    );
+                    return;
+                }
+            };
+
+            match seed {
+                Ok(seed) => http_over_ws::init_payload_encryption(seed),
+                Err(_) => error!("[schedule_payload_encryption_init]: invalid randomness length"),
+            }
+        })
+    });
+}
+
+fn set_config_or_trap(config: ProxyConfig) {
+    if let Err(e) = validate_config(&config) {
+        trap(&format!("Invalid config: {:?}", e));
+    }
+
+    STATE.with(|state| state.borrow_mut().set_config(config));
+}
+
+#[pre_upgrade]
+fn pre_upgrade() {
+    let (snapshot, config, signing_profiles, allowed_secrets, webhooks, dead_letters) =
+        STATE.with(|state| {
+            let state = state.borrow();
+
+            (
+                state.scheduled_jobs_snapshot(),
+                state.get_config().clone(),
+                state.signing_profiles_snapshot(),
+                state.allowed_secrets_snapshot(),
+                state.webhooks_snapshot(),
+                state.dead_letters_snapshot(),
+            )
+        });
+
+    stable_save_after_logs((
+        snapshot,
+        config,
+        get_last_request_id(),
+        http_over_ws::get_response_attestation_config(),
+        signing_profiles,
+        allowed_secrets,
+        http_over_ws::is_payload_encryption_required(),
+        webhooks,
+        dead_letters,
+    ))
+    .expect("failed to save state to stable memory");
 }
 
 #[post_upgrade]
-fn post_upgrade() {
-    init();
+fn post_upgrade(config: Option<ProxyConfig>) {
+    logger::init_stable_logs(STABLE_MEMORY_LOGS_OFFSET);
+    init_handlers();
+    schedule_payload_encryption_init();
+
+    // the stable memory is empty when upgrading from a version that didn't save the jobs,
+    // and the other values are missing when upgrading from a version that didn't save them
+    if let Ok((
+        snapshot,
+        saved_config,
+        last_request_id,
+        attestation_config,
+        signing_profiles,
+        allowed_secrets,
+        payload_encryption_required,
+        webhooks,
+        dead_letters,
+    )) = stable_restore_after_logs::<(
+        ScheduledJobsSnapshot,
+        Option<ProxyConfig>,
+        Option<HttpRequestId>,
+        Option<ResponseAttestationConfig>,
+        Option<SigningProfilesSnapshot>,
+        Option<BTreeMap<CanisterId, BTreeSet<SecretName>>>,
+        Option<bool>,
+        Option<WebhooksSnapshot>,
+        Option<DeadLettersSnapshot>,
+    )>() {
+        // the ids assigned after the upgrade must not collide with the ones assigned before
+        if let Some(last_request_id) = last_request_id {
+            restore_last_request_id(last_request_id);
+        }
+
+        if let Some(attestation_config) = attestation_config {
+            restore_response_attestation_config(attestation_config);
+        }
+
+        if let Some(required) = payload_encryption_required {
+            http_over_ws::set_payload_encryption_required(required);
+        }
+
+        let pending_redeliveries = STATE.with(|state| {
+            let mut state = state.borrow_mut();
+
+            state.restore_scheduled_jobs(snapshot);
+            if let Some(signing_profiles) = signing_profiles {
+                state.restore_signing_profiles(signing_profiles);
+            }
+            if let Some(allowed_secrets) = allowed_secrets {
+                state.restore_allowed_secrets(allowed_secrets);
+            }
+            if let Some(webhooks) = webhooks {
+                state.restore_webhooks(webhooks);
+            }
+            if let Some(saved_config) = saved_config {
+                state.set_config(saved_config);
+            }
+
+            dead_letters
+                .map(|dead_letters| state.restore_dead_letters(dead_letters))
+                .unwrap_or_default()
+        });
+
+        // the redelivery timers have been lost with the upgrade
+        for (request_id, res, attempt) in pending_redeliveries {
+            ic_cdk_timers::set_timer(Duration::ZERO, move || {
+                ic_cdk::spawn(deliver_http_result(request_id, res, attempt))
+            });
+        }
+    }
+
+    if let Some(config) = config {
+        set_config_or_trap(config);
+    }
+
+    let active_jobs = STATE.with(|state| state.borrow().active_scheduled_jobs());
+    for (job_id, next_execution_ns) in active_jobs {
+        set_scheduled_job_timer(job_id, next_execution_ns);
+    }
 }
 
 #[update]
@@ -42,17 +238,253 @@ fn http_request(args: HttpRequestEndpointArgs) -> HttpRequestEndpointResult {
 
     guard_caller_is_not_anonymous(&canister_id);
 
-    validate_incoming_request(&args).map_err(|e| ProxyCanisterError::InvalidRequest(e))?;
+    handle_http_request(canister_id, args)
+}
+
+#[update]
+fn http_request_batch(
+    requests: Vec<HttpRequestEndpointArgs>,
+    callback_method_name: Option<CanisterCallbackMethodName>,
+) -> HttpRequestBatchEndpointResult {
+    let canister_id = caller();
+
+    guard_caller_is_not_anonymous(&canister_id);
+
+    if requests.len() > MAX_BATCH_REQUESTS_COUNT {
+        return Err(ProxyCanisterError::InvalidRequest(
+            InvalidRequest::TooManyRequests,
+        ));
+    }
 
     log!(
+        { canister_id: canister_id },
+        "[http_request_batch]: canister_id:{}, starting {} requests",
+        canister_id,
+        requests.len()
+    );
+
+    let results: Vec<HttpRequestEndpointResult> = requests
+        .into_iter()
+        .map(|args| handle_http_request(canister_id, args))
+        .collect();
+
+    if let Some(callback_method_name) = callback_method_name {
+        let request_ids = results.iter().filter_map(|res| res.as_ref().ok()).copied();
+        let completed_batch = STATE.with(|state| {
+            state
+                .borrow_mut()
+                .start_batch(canister_id, callback_method_name, request_ids.collect())
+        });
+
+        if let Some(batch) = completed_batch {
+            ic_cdk::spawn(deliver_batch_results(batch));
+        }
+    }
+
+    Ok(results)
+}
+
+fn handle_http_request(
+    canister_id: CanisterId,
+    args: HttpRequestEndpointArgs,
+) -> HttpRequestEndpointResult {
+    validate_canister_request(canister_id, &args)?;
+
+    debug!(
+        { canister_id: canister_id },
         "[http_request]: canister_id:{}, incoming request valid",
         canister_id
     );
 
+    if let Some(idempotency_key) = &args.idempotency_key {
+        let existing_request_id = STATE.with(|state| {
+            state
+                .borrow_mut()
+                .get_idempotent_request(canister_id, idempotency_key, time_ms())
+        });
+
+        if let Some(request_id) = existing_request_id {
+            log!(
+                { request_id: request_id, canister_id: canister_id },
+                "[http_request]: request_id:{}, canister_id:{}, idempotency key already used",
+                request_id,
+                canister_id
+            );
+
+            return Ok(request_id);
+        }
+    }
+
+    let idempotency_key = args.idempotency_key.clone();
+    let request_id = start_http_request(canister_id, args)?;
+
+    if let Some(idempotency_key) = idempotency_key {
+        STATE.with(|state| {
+            state.borrow_mut().set_idempotent_request(
+                canister_id,
+                idempotency_key,
+                request_id,
+                time_ms(),
+            )
+        });
+    }
+
+    Ok(request_id)
+}
+
+/// Used as the [RedirectValidator](http_over_ws::RedirectValidator) of the requests.
+fn validate_request_redirect(
+    request_id: HttpRequestId,
+    request: &HttpRequest,
+) -> Result<(), String> {
+    validate_redirect(request)?;
+
+    STATE.with(|state| {
+        state
+            .borrow()
+            .validate_signed_request_redirect(request_id, request)
+    })
+}
+
+/// Validates the request and checks that the canister is allowed to reference its secrets.
+fn validate_canister_request(
+    canister_id: CanisterId,
+    args: &HttpRequestEndpointArgs,
+) -> Result<(), ProxyCanisterError> {
+    let config = STATE.with(|state| state.borrow().get_config().clone());
+    let secret_names = validate_incoming_request(args, &config)
+        .map_err(|e| ProxyCanisterError::InvalidRequest(e))?;
+
+    let disallowed_secret = STATE.with(|state| {
+        state
+            .borrow()
+            .find_disallowed_secret(canister_id, &secret_names)
+            .cloned()
+    });
+    if let Some(secret_name) = disallowed_secret {
+        return Err(ProxyCanisterError::InvalidRequest(
+            InvalidRequest::SecretNotAllowed(secret_name),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Serves the request from the cache or from an identical in-flight request if possible,
+/// otherwise sends it to a proxy.
+fn start_http_request(
+    canister_id: CanisterId,
+    args: HttpRequestEndpointArgs,
+) -> HttpRequestEndpointResult {
+    let cache_key = args
+        .cache
+        .as_ref()
+        .map(|cache| CacheKey::new(canister_id, &args.request, &cache.key_headers));
+
+    if let Some(cache_key) = &cache_key {
+        let cached_response =
+            STATE.with(|state| state.borrow_mut().get_cached_response(cache_key, time_ms()));
+
+        if let Some((response, response_metadata)) = cached_response {
+            let request_id = reserve_request_id();
+
+            STATE.with(|state| {
+                let mut state = state.borrow_mut();
+
+                state.start_request_for_canister(
+                    canister_id,
+                    request_id,
+                    args.callback_method_name.clone(),
+                    time(),
+                );
+                // the metadata of the request that got the cached response
+                if let Some(response_metadata) = response_metadata {
+                    state.set_request_response_metadata(request_id, response_metadata);
+                }
+                state.add_request_trace_event(
+                    request_id,
+                    RequestTraceEventKind::ServedFromCache,
+                    time(),
+                );
+            });
+
+            log!(
+                { request_id: request_id, canister_id: canister_id },
+                "[http_request]: request_id:{}, canister_id:{}, callback method:{:?}, served from cache",
+                request_id,
+                canister_id,
+                args.callback_method_name
+            );
+
+            ic_cdk::spawn(http_request_callback(
+                request_id,
+                HttpResult::Success(response),
+            ));
+
+            return Ok(request_id);
+        }
+    }
+
+    // the signed requests are unique, so they can't be coalesced
+    let in_flight_key = (args.request.method == HttpMethod::GET && args.signing_profile.is_none())
+        .then(|| CacheKey::from_request(&args.request));
+
+    if let (Some(in_flight_key), Some(true)) = (&in_flight_key, args.coalesce) {
+        let leader_id = STATE.with(|state| state.borrow().get_in_flight_request(in_flight_key));
+
+        if let Some(leader_id) = leader_id {
+            let request_id = reserve_request_id();
+
+            STATE.with(|state| {
+                let mut state = state.borrow_mut();
+
+                state.start_request_for_canister(
+                    canister_id,
+                    request_id,
+                    args.callback_method_name.clone(),
+                    time(),
+                );
+                state.add_request_trace_event(
+                    request_id,
+                    RequestTraceEventKind::CoalescedWith(leader_id),
+                    time(),
+                );
+                state.follow_in_flight_request(leader_id, request_id);
+            });
+
+            log!(
+                { request_id: request_id, canister_id: canister_id },
+                "[http_request]: request_id:{}, canister_id:{}, callback method:{:?}, coalesced with request_id:{}",
+                request_id,
+                canister_id,
+                args.callback_method_name,
+                leader_id
+            );
+
+            return Ok(request_id);
+        }
+    }
+
+    // requests without a timeout would never fail if the proxy doesn't respond
+    let timeout_ms = args.timeout_ms.unwrap_or_else(|| {
+        STATE.with(|state| state.borrow().get_config().default_http_request_timeout_ms)
+    });
+    let mut request = args.request;
+    let request_url = request.url.clone();
+    if let Some(signing_profile) = &args.signing_profile {
+        STATE
+            .with(|state| {
+                state
+                    .borrow()
+                    .sign_request(canister_id, signing_profile, &mut request, time())
+            })
+            .map_err(ProxyCanisterError::InvalidRequest)?;
+    }
+
     let request_id = execute_http_request(
-        args.request,
+        request,
         Some(|id, res| Box::pin(http_request_callback(id, res))),
-        args.timeout_ms,
+        Some(HttpRequestDeadline::TimeoutMs(timeout_ms)),
         ws::send,
     )
     .map_err(|e| ProxyCanisterError::HttpOverWs(e))?;
@@ -64,14 +496,40 @@ fn http_request(args: HttpRequestEndpointArgs) -> HttpRequestEndpointResult {
             canister_id,
             request_id,
             args.callback_method_name.clone(),
+            time(),
         );
+        if let Some(proxy_principal) = get_http_connection_proxy(request_id) {
+            state.add_request_trace_event(
+                request_id,
+                RequestTraceEventKind::AssignedToProxy(proxy_principal),
+                time(),
+            );
+        }
+        state.add_request_trace_event(request_id, RequestTraceEventKind::Sent, time());
+
+        if let Some(cache_key) = cache_key {
+            state.track_cacheable_request(
+                request_id,
+                cache_key,
+                args.cache.and_then(|cache| cache.ttl_ms),
+            );
+        }
+
+        if let Some(in_flight_key) = in_flight_key {
+            state.start_in_flight_request(in_flight_key, request_id);
+        }
+
+        if args.signing_profile.is_some() {
+            state.track_signed_request(request_id, &request_url);
+        }
     });
 
     log!(
-        "[http_request]: request_id:{}, canister_id:{}, timeout_ms:{:?}, callback method:{:?}, started",
+        { request_id: request_id, canister_id: canister_id },
+        "[http_request]: request_id:{}, canister_id:{}, timeout_ms:{}, callback method:{:?}, started",
         request_id,
         canister_id,
-        args.timeout_ms,
+        timeout_ms,
         args.callback_method_name
     );
 
@@ -79,68 +537,207 @@ fn http_request(args: HttpRequestEndpointArgs) -> HttpRequestEndpointResult {
 }
 
 async fn http_request_callback(request_id: HttpRequestId, res: HttpResult) {
-    let request_state = STATE.with(|state| state.borrow().get_request_state(request_id));
+    let (followers, completed_batches) = STATE.with(|state| {
+        let mut state = state.borrow_mut();
 
-    if let Some(r) = request_state {
-        log!(
-            "[http_request]: request_id:{}, canister_id:{}, http completed",
-            request_id,
-            r.canister_id
+        let trace_event = match &res {
+            HttpResult::Success(_) => RequestTraceEventKind::ResponseReceived,
+            HttpResult::Failure(reason) => RequestTraceEventKind::RequestFailed(reason.clone()),
+        };
+        state.add_request_trace_event(request_id, trace_event, time());
+
+        // the results served from the cache or shared with coalesced requests are not attested
+        // and their metadata is set when they are started or when the leader completes
+        let response_metadata = get_http_response_metadata(request_id).ok().flatten();
+
+        state.cache_http_result(request_id, &res, response_metadata.clone(), time_ms());
+        state.set_request_http_result(request_id, res.clone());
+        if let Ok(Some(response_attestation)) = get_http_response_attestation(request_id) {
+            state.set_request_response_attestation(request_id, response_attestation);
+        }
+        if let Some(response_metadata) = &response_metadata {
+            state.set_request_response_metadata(request_id, response_metadata.clone());
+        }
+
+        state.complete_signed_request(request_id);
+        let followers = state.complete_in_flight_request(request_id);
+        if let Some(response_metadata) = &response_metadata {
+            for follower_id in followers.iter() {
+                state.set_request_response_metadata(*follower_id, response_metadata.clone());
+            }
+        }
+
+        (followers, state.complete_batch_request(request_id, &res))
+    });
+
+    for follower_id in followers {
+        debug!(
+            { request_id: follower_id },
+            "[http_request]: request_id:{}, result of coalesced request_id:{} received",
+            follower_id,
+            request_id
         );
 
-        match r.state {
-            RequestState::Executing(method_name) => {
-                let cb_res = if let Some(method_name) = method_name {
-                    log!(
-                        "[http_request]: request_id:{}, canister_id:{}, callback method:{}, starting inter-canister call",
-                        request_id,
-                        r.canister_id,
-                        method_name
-                    );
+        ic_cdk::spawn(http_request_callback(follower_id, res.clone()));
+    }
 
-                    let canister_res: Result<(), _> =
-                        ic_cdk::call(r.canister_id, method_name.as_str(), (request_id, res)).await;
+    for batch in completed_batches {
+        ic_cdk::spawn(deliver_batch_results(batch));
+    }
+
+    log!({ request_id: request_id }, "[http_request]: request_id:{}, http completed", request_id);
+
+    deliver_http_result(request_id, res, 1).await;
+}
 
-                    log!(
-                        "[http_request]: request_id:{}, canister_id:{}, callback method:{}, completed inter-canister call result: {:?}",
+/// Delivers the result to the callback method of the canister that started the request.
+/// If the delivery fails, it is retried with an exponential backoff
+/// until the configured `max_callback_delivery_attempts` is reached, after which the result
+/// is moved to the dead letters.
+async fn deliver_http_result(request_id: HttpRequestId, res: HttpResult, attempt: u32) {
+    let request_state = STATE.with(|state| state.borrow().get_request_state(request_id));
+
+    if let Some(r) = request_state {
+        match r.state {
+            RequestState::Executing(Some(method_name)) => {
+                debug!(
+                    { request_id: request_id, canister_id: r.canister_id },
+                    "[http_request]: request_id:{}, canister_id:{}, callback method:{}, attempt:{}, starting inter-canister call",
+                    request_id,
+                    r.canister_id,
+                    method_name,
+                    attempt
+                );
+
+                STATE.with(|state| {
+                    state.borrow_mut().add_request_trace_event(
                         request_id,
-                        r.canister_id,
-                        method_name,
-                        canister_res
-                    );
+                        RequestTraceEventKind::CallbackStarted { attempt },
+                        time(),
+                    )
+                });
 
+                // the callback methods that only take the first two arguments ignore the metadata
+                let canister_res: Result<(), _> = ic_cdk::call(
+                    r.canister_id,
+                    method_name.as_str(),
+                    (request_id, res.clone(), r.response_metadata.clone()),
+                )
+                .await;
+
+                log!(
+                    { request_id: request_id, canister_id: r.canister_id },
+                    "[http_request]: request_id:{}, canister_id:{}, callback method:{}, attempt:{}, completed inter-canister call result: {:?}",
+                    request_id,
+                    r.canister_id,
+                    method_name,
+                    attempt,
                     canister_res
-                } else {
-                    log!(
-                        "[http_request]: request_id:{}, canister_id:{}, no callback method found",
-                        request_id,
-                        r.canister_id,
-                    );
+                );
 
-                    Ok(())
+                let error = canister_res.err().map(|e| format!("{:?}", e));
+                let callback_attempt = CallbackAttempt {
+                    timestamp_ns: time(),
+                    error: error.clone(),
+                };
+
+                let trace_event = match &error {
+                    None => RequestTraceEventKind::CallbackFinished { attempt },
+                    Some(e) => RequestTraceEventKind::CallbackFailed {
+                        attempt,
+                        error: e.clone(),
+                    },
                 };
 
                 STATE.with(|state| {
                     let mut state = state.borrow_mut();
 
-                    match cb_res {
-                        Ok(_) => {
-                            state.set_request_executed(request_id);
-                        }
-                        Err(e) => {
-                            state.set_request_failed(request_id, format!("{:?}", e));
-                        }
-                    };
+                    state.add_callback_attempt(request_id, callback_attempt);
+                    state.add_request_trace_event(request_id, trace_event, time());
+                });
+
+                let max_attempts =
+                    STATE.with(|state| state.borrow().get_config().max_callback_delivery_attempts);
+
+                match error {
+                    None => {
+                        STATE.with(|state| {
+                            state
+                                .borrow_mut()
+                                .set_request_executed(request_id, time_ms())
+                        });
+
+                        log!(
+                            { request_id: request_id, canister_id: r.canister_id },
+                            "[http_request]: request_id:{}, canister_id:{}, executed",
+                            request_id,
+                            r.canister_id,
+                        );
+                    }
+                    Some(e) if attempt < max_attempts => {
+                        let delay = Duration::from_millis(
+                            CALLBACK_REDELIVERY_BASE_DELAY_MS << (attempt - 1),
+                        );
+                        ic_cdk_timers::set_timer(delay, move || {
+                            ic_cdk::spawn(deliver_http_result(request_id, res, attempt + 1))
+                        });
+
+                        warn!(
+                            { request_id: request_id, canister_id: r.canister_id },
+                            "[http_request]: request_id:{}, canister_id:{}, callback failed with error: {}, retrying in {:?}",
+                            request_id,
+                            r.canister_id,
+                            e,
+                            delay
+                        );
+                    }
+                    Some(e) => {
+                        STATE.with(|state| {
+                            let mut state = state.borrow_mut();
+
+                            state.set_request_failed(request_id, e, time_ms());
+                            state.add_dead_letter(request_id, method_name, res);
+                            state.add_request_trace_event(
+                                request_id,
+                                RequestTraceEventKind::MovedToDeadLetters,
+                                time(),
+                            );
+                        });
+
+                        error!(
+                            { request_id: request_id, canister_id: r.canister_id },
+                            "[http_request]: request_id:{}, canister_id:{}, callback failed after {} attempts, moved to dead letters",
+                            request_id,
+                            r.canister_id,
+                            attempt
+                        );
+                    }
+                }
+            }
+            RequestState::Executing(None) => {
+                log!(
+                    { request_id: request_id, canister_id: r.canister_id },
+                    "[http_request]: request_id:{}, canister_id:{}, no callback method found",
+                    request_id,
+                    r.canister_id,
+                );
+
+                STATE.with(|state| {
+                    state
+                        .borrow_mut()
+                        .set_request_executed(request_id, time_ms())
                 });
 
                 log!(
+                    { request_id: request_id, canister_id: r.canister_id },
                     "[http_request]: request_id:{}, canister_id:{}, executed",
                     request_id,
                     r.canister_id,
                 );
             }
             _ => {
-                log!(
+                warn!(
+                    { request_id: request_id, canister_id: r.canister_id },
                     "[http_request]: request_id:{}, canister_id:{}, already executed",
                     request_id,
                     r.canister_id,
@@ -148,10 +745,35 @@ async fn http_request_callback(request_id: HttpRequestId, res: HttpResult) {
             }
         }
     } else {
-        log!("[http_request]: request_id:{} not found", request_id);
+        warn!({ request_id: request_id }, "[http_request]: request_id:{} not found", request_id);
     }
 }
 
+/// Delivers the results of all the requests of the batch to its aggregated callback.
+async fn deliver_batch_results(batch: CompletedBatch) {
+    debug!(
+        { canister_id: batch.canister_id },
+        "[http_request_batch]: canister_id:{}, callback method:{}, starting inter-canister call",
+        batch.canister_id,
+        batch.callback_method_name
+    );
+
+    let canister_res: Result<(), _> = ic_cdk::call(
+        batch.canister_id,
+        batch.callback_method_name.as_str(),
+        (batch.results,),
+    )
+    .await;
+
+    log!(
+        { canister_id: batch.canister_id },
+        "[http_request_batch]: canister_id:{}, callback method:{}, completed inter-canister call result: {:?}",
+        batch.canister_id,
+        batch.callback_method_name,
+        canister_res
+    );
+}
+
 #[update]
 fn disconnect_all_proxies() {
     let caller = caller();
@@ -160,6 +782,171 @@ fn disconnect_all_proxies() {
     disconnect_all_connected_proxies(ws::close);
 }
 
+#[query]
+fn get_config() -> ProxyConfig {
+    STATE.with(|state| state.borrow().get_config().clone())
+}
+
+#[update]
+fn update_config(config: ProxyConfig) -> UpdateConfigResult {
+    let caller = caller();
+    guard_caller_is_controller(&caller);
+
+    validate_config(&config)?;
+
+    log!("[update_config]: config updated to {:?}", config);
+    STATE.with(|state| state.borrow_mut().set_config(config));
+    Ok(())
+}
+
+#[query]
+fn list_proxies() -> Vec<ProxyInfo> {
+    let caller = caller();
+    guard_caller_is_controller(&caller);
+
+    get_proxies()
+}
+
+#[update]
+fn disconnect_proxy(proxy_principal: Principal) -> ManageProxyResult {
+    let caller = caller();
+    guard_caller_is_controller(&caller);
+
+    http_over_ws::disconnect_proxy(proxy_principal, ws::close)
+}
+
+#[update]
+fn drain_proxy(proxy_principal: Principal) -> ManageProxyResult {
+    let caller = caller();
+    guard_caller_is_controller(&caller);
+
+    http_over_ws::drain_proxy(proxy_principal)
+}
+
+/// Registers the key with which the proxy attests its responses, or removes it if [None].
+#[update]
+fn set_proxy_attestation_key(
+    proxy_principal: Principal,
+    public_key: Option<Vec<u8>>,
+) -> ManageProxyResult {
+    let caller = caller();
+    guard_caller_is_controller(&caller);
+
+    http_over_ws::set_proxy_attestation_key(proxy_principal, public_key)?;
+
+    log!(
+        { proxy_principal: proxy_principal },
+        "[set_proxy_attestation_key]: attestation key of proxy {} updated",
+        proxy_principal
+    );
+    Ok(())
+}
+
+#[update]
+fn set_response_attestations_required(required: bool) {
+    let caller = caller();
+    guard_caller_is_controller(&caller);
+
+    http_over_ws::set_response_attestations_required(required);
+}
+
+#[query]
+fn get_response_attestation_config() -> ResponseAttestationConfig {
+    let caller = caller();
+    guard_caller_is_controller(&caller);
+
+    http_over_ws::get_response_attestation_config()
+}
+
+/// When required, the requests are only executed by the proxies that announced an encryption key,
+/// so that the WS gateway never sees their payloads.
+#[update]
+fn set_payload_encryption_required(required: bool) {
+    let caller = caller();
+    guard_caller_is_controller(&caller);
+
+    http_over_ws::set_payload_encryption_required(required);
+
+    log!(
+        "[set_payload_encryption_required]: payload encryption required: {}",
+        required
+    );
+}
+
+#[query]
+fn is_payload_encryption_required() -> bool {
+    let caller = caller();
+    guard_caller_is_controller(&caller);
+
+    http_over_ws::is_payload_encryption_required()
+}
+
+/// Allows the canister to reference the given proxy secrets in its requests,
+/// replacing the secrets allowed before.
+#[update]
+fn set_allowed_secrets(
+    canister_id: CanisterId,
+    secret_names: Vec<SecretName>,
+) -> SetAllowedSecretsResult {
+    let caller = caller();
+    guard_caller_is_controller(&caller);
+
+    if let Some(secret_name) = secret_names
+        .iter()
+        .find(|secret_name| !is_valid_secret_name(secret_name))
+    {
+        return Err(ProxyCanisterError::InvalidRequest(
+            InvalidRequest::InvalidSecretPlaceholder(format!(
+                "invalid secret name: {}",
+                secret_name
+            )),
+        ));
+    }
+
+    log!(
+        { canister_id: canister_id },
+        "[set_allowed_secrets]: canister_id:{}, allowed secrets: {:?}",
+        canister_id,
+        secret_names
+    );
+
+    STATE.with(|state| {
+        state
+            .borrow_mut()
+            .set_allowed_secrets(canister_id, secret_names.into_iter().collect())
+    });
+    Ok(())
+}
+
+/// Lists the names of the proxy secrets that the caller can reference in its requests.
+#[query]
+fn list_my_allowed_secrets() -> Vec<SecretName> {
+    let canister_id = caller();
+    guard_caller_is_not_anonymous(&canister_id);
+
+    STATE.with(|state| {
+        state
+            .borrow()
+            .get_allowed_secrets(canister_id)
+            .into_iter()
+            .collect()
+    })
+}
+
+fn restore_response_attestation_config(config: ResponseAttestationConfig) {
+    for (proxy_principal, public_key) in config.proxy_public_keys {
+        if let Err(e) = http_over_ws::set_proxy_attestation_key(proxy_principal, Some(public_key)) {
+            error!(
+                { proxy_principal: proxy_principal },
+                "[post_upgrade]: failed to restore the attestation key of proxy {}: {:?}",
+                proxy_principal,
+                e
+            );
+        }
+    }
+    http_over_ws::set_response_attestations_required(config.required);
+}
+
 #[query]
 fn get_request_by_id(request_id: HttpRequestId) -> Option<CanisterRequest> {
     let caller = caller();
@@ -169,9 +956,459 @@ fn get_request_by_id(request_id: HttpRequestId) -> Option<CanisterRequest> {
 }
 
 #[query]
-fn get_logs() -> Vec<(String, String)> {
+fn get_my_request(request_id: HttpRequestId) -> Option<CanisterRequest> {
+    let canister_id = caller();
+    guard_caller_is_not_anonymous(&canister_id);
+
+    STATE.with(|state| state.borrow().get_canister_request(canister_id, request_id))
+}
+
+/// Returns the response to a request started by the caller along with how it has been obtained,
+/// [None] if the request didn't succeed or its response has not been received from a proxy.
+///
+/// The responses served from the cache or shared with coalesced requests come with the metadata
+/// of the request that got them from a proxy.
+#[query]
+fn get_my_http_response(request_id: HttpRequestId) -> Option<HttpResponseEnvelope> {
+    let canister_id = caller();
+    guard_caller_is_not_anonymous(&canister_id);
+
+    let request =
+        STATE.with(|state| state.borrow().get_canister_request(canister_id, request_id))?;
+    match (request.http_result, request.response_metadata) {
+        (Some(HttpResult::Success(response)), Some(metadata)) => {
+            Some(HttpResponseEnvelope { response, metadata })
+        }
+        _ => None,
+    }
+}
+
+/// Returns the trace of a request started by the caller, or of any request if the caller is a controller.
+#[query]
+fn get_request_trace(request_id: HttpRequestId) -> Option<RequestTrace> {
+    let caller = caller();
+    guard_caller_is_not_anonymous(&caller);
+
+    let canister_id = (!is_controller(&caller)).then_some(caller);
+
+    STATE.with(|state| state.borrow().get_request_trace(canister_id, request_id))
+}
+
+#[query]
+fn list_my_requests(args: ListRequestsArgs) -> ListRequestsResult {
+    let canister_id = caller();
+    guard_caller_is_not_anonymous(&canister_id);
+
+    STATE.with(|state| {
+        state.borrow().list_canister_requests(
+            canister_id,
+            args.after_request_id,
+            list_limit(args.limit),
+        )
+    })
+}
+
+#[query]
+fn list_my_dead_letters(args: ListRequestsArgs) -> ListDeadLettersResult {
+    let canister_id = caller();
+    guard_caller_is_not_anonymous(&canister_id);
+
+    STATE.with(|state| {
+        state.borrow().list_dead_letters(
+            Some(canister_id),
+            args.after_request_id,
+            list_limit(args.limit),
+        )
+    })
+}
+
+#[update]
+fn delete_my_dead_letters(request_ids: Vec<HttpRequestId>) {
+    let canister_id = caller();
+    guard_caller_is_not_anonymous(&canister_id);
+
+    STATE.with(|state| {
+        state
+            .borrow_mut()
+            .delete_canister_dead_letters(canister_id, request_ids)
+    });
+}
+
+#[query]
+fn list_dead_letters(args: ListRequestsArgs) -> ListDeadLettersResult {
+    let caller = caller();
+    guard_caller_is_controller(&caller);
+
+    STATE.with(|state| {
+        state
+            .borrow()
+            .list_dead_letters(None, args.after_request_id, list_limit(args.limit))
+    })
+}
+
+#[update]
+fn replay_dead_letters(request_ids: Vec<HttpRequestId>) {
+    let caller = caller();
+    guard_caller_is_controller(&caller);
+
+    for request_id in request_ids {
+        let http_result = STATE.with(|state| state.borrow_mut().restart_dead_letter(request_id));
+
+        if let Some(res) = http_result {
+            log!(
+                { request_id: request_id },
+                "[http_request]: request_id:{}, replaying dead letter",
+                request_id
+            );
+
+            ic_cdk::spawn(deliver_http_result(request_id, res, 1));
+        } else {
+            warn!(
+                { request_id: request_id },
+                "[http_request]: request_id:{}, dead letter not found",
+                request_id
+            );
+        }
+    }
+}
+
+#[update]
+fn schedule_http_request(args: ScheduleHttpRequestArgs) -> ScheduleHttpRequestResult {
+    let canister_id = caller();
+    guard_caller_is_not_anonymous(&canister_id);
+
+    let template = HttpRequestEndpointArgs {
+        request: args.request.clone(),
+        timeout_ms: args.timeout_ms,
+        callback_method_name: Some(args.callback_method_name.clone()),
+        cache: None,
+        idempotency_key: None,
+        coalesce: None,
+        signing_profile: None,
+    };
+    validate_canister_request(canister_id, &template)?;
+    let first_execution_ns =
+        validate_schedule(&args, time()).map_err(ProxyCanisterError::InvalidRequest)?;
+
+    let job_id = STATE
+        .with(|state| {
+            state
+                .borrow_mut()
+                .add_scheduled_job(canister_id, args, first_execution_ns)
+        })
+        .map_err(ProxyCanisterError::InvalidRequest)?;
+
+    log!(
+        { canister_id: canister_id },
+        "[scheduler]: job_id:{}, canister_id:{}, job scheduled",
+        job_id,
+        canister_id
+    );
+
+    set_scheduled_job_timer(job_id, first_execution_ns);
+
+    Ok(job_id)
+}
+
+#[query]
+fn list_my_scheduled_jobs() -> Vec<ScheduledJob> {
+    let canister_id = caller();
+    guard_caller_is_not_anonymous(&canister_id);
+
+    STATE.with(|state| state.borrow().list_canister_scheduled_jobs(canister_id))
+}
+
+#[update]
+fn pause_scheduled_job(job_id: ScheduledJobId) -> ManageScheduledJobResult {
+    let canister_id = caller();
+    guard_caller_is_not_anonymous(&canister_id);
+
+    let timer_id =
+        STATE.with(|state| state.borrow_mut().pause_scheduled_job(canister_id, job_id))?;
+    if let Some(timer_id) = timer_id {
+        ic_cdk_timers::clear_timer(timer_id);
+    }
+
+    log!("[scheduler]: job_id:{}, job paused", job_id);
+
+    Ok(())
+}
+
+#[update]
+fn resume_scheduled_job(job_id: ScheduledJobId) -> ManageScheduledJobResult {
+    let canister_id = caller();
+    guard_caller_is_not_anonymous(&canister_id);
+
+    let next_execution_ns = STATE.with(|state| {
+        state
+            .borrow_mut()
+            .resume_scheduled_job(canister_id, job_id, time())
+    })?;
+    if let Some(next_execution_ns) = next_execution_ns {
+        set_scheduled_job_timer(job_id, next_execution_ns);
+    }
+
+    log!("[scheduler]: job_id:{}, job resumed", job_id);
+
+    Ok(())
+}
+
+#[update]
+fn cancel_scheduled_job(job_id: ScheduledJobId) -> ManageScheduledJobResult {
+    let canister_id = caller();
+    guard_caller_is_not_anonymous(&canister_id);
+
+    let timer_id =
+        STATE.with(|state| state.borrow_mut().cancel_scheduled_job(canister_id, job_id))?;
+    if let Some(timer_id) = timer_id {
+        ic_cdk_timers::clear_timer(timer_id);
+    }
+
+    log!("[scheduler]: job_id:{}, job cancelled", job_id);
+
+    Ok(())
+}
+
+fn set_scheduled_job_timer(job_id: ScheduledJobId, next_execution_ns: u64) {
+    let delay = Duration::from_nanos(next_execution_ns.saturating_sub(time()));
+    let timer_id = ic_cdk_timers::set_timer(delay, move || execute_scheduled_job(job_id));
+
+    STATE.with(|state| state.borrow_mut().set_scheduled_job_timer(job_id, timer_id));
+}
+
+fn execute_scheduled_job(job_id: ScheduledJobId) {
+    let Some(job) = STATE
+        .with(|state| state.borrow().get_scheduled_job(job_id))
+        .filter(|job| job.state == ScheduledJobState::Active)
+    else {
+        return;
+    };
+
+    // the allowed secrets may have changed since the job was scheduled
+    let args = job.http_request_args();
+    let res = validate_canister_request(job.canister_id, &args)
+        .and_then(|_| start_http_request(job.canister_id, args));
+
+    log!(
+        { canister_id: job.canister_id },
+        "[scheduler]: job_id:{}, canister_id:{}, executed with result: {:?}",
+        job_id,
+        job.canister_id,
+        res
+    );
+
+    let execution = ScheduledJobExecution {
+        timestamp_ns: time(),
+        request_id: res.as_ref().ok().copied(),
+        error: res.err().map(|e| format!("{:?}", e)),
+    };
+    let next_execution_ns = STATE.with(|state| {
+        state
+            .borrow_mut()
+            .record_scheduled_job_execution(job_id, execution)
+    });
+
+    if let Some(next_execution_ns) = next_execution_ns {
+        set_scheduled_job_timer(job_id, next_execution_ns);
+    }
+}
+
+#[update]
+fn register_webhook(webhook: Webhook) -> RegisterWebhookResult {
+    let canister_id = caller();
+    guard_caller_is_not_anonymous(&canister_id);
+
+    validate_webhook(&webhook).map_err(ProxyCanisterError::InvalidRequest)?;
+
+    log!(
+        { canister_id: canister_id },
+        "[webhooks]: canister_id:{}, path:{}, registering webhook",
+        canister_id,
+        webhook.path
+    );
+
+    STATE
+        .with(|state| state.borrow_mut().register_webhook(canister_id, webhook))
+        .map_err(ProxyCanisterError::InvalidRequest)
+}
+
+#[update]
+fn unregister_webhook(path: WebhookPath) {
+    let canister_id = caller();
+    guard_caller_is_not_anonymous(&canister_id);
+
+    STATE.with(|state| state.borrow_mut().unregister_webhook(canister_id, &path));
+}
+
+#[query]
+fn list_my_webhooks() -> Vec<Webhook> {
+    let canister_id = caller();
+    guard_caller_is_not_anonymous(&canister_id);
+
+    STATE.with(|state| state.borrow().list_canister_webhooks(canister_id))
+}
+
+#[update]
+fn register_signing_profile(args: RegisterSigningProfileArgs) -> RegisterSigningProfileResult {
+    let caller = caller();
+    guard_caller_is_not_anonymous(&caller);
+
+    let canister_id = args.canister_id.unwrap_or(caller);
+    if canister_id != caller {
+        guard_caller_is_controller(&caller);
+    }
+
+    validate_signing_profile(&args).map_err(ProxyCanisterError::InvalidRequest)?;
+
+    log!(
+        { canister_id: canister_id },
+        "[signing]: canister_id:{}, name:{}, registering signing profile",
+        canister_id,
+        args.name
+    );
+
+    STATE
+        .with(|state| {
+            state
+                .borrow_mut()
+                .register_signing_profile(canister_id, args, time())
+        })
+        .map_err(ProxyCanisterError::InvalidRequest)
+}
+
+#[update]
+fn unregister_signing_profile(canister_id: Option<CanisterId>, name: SigningProfileName) {
+    let caller = caller();
+    guard_caller_is_not_anonymous(&caller);
+
+    let canister_id = canister_id.unwrap_or(caller);
+    if canister_id != caller {
+        guard_caller_is_controller(&caller);
+    }
+
+    STATE.with(|state| {
+        state
+            .borrow_mut()
+            .unregister_signing_profile(canister_id, &name)
+    });
+}
+
+/// Lists the signing profiles of the caller, without their secrets.
+#[query]
+fn list_my_signing_profiles() -> Vec<SigningProfileInfo> {
+    let canister_id = caller();
+    guard_caller_is_not_anonymous(&canister_id);
+
+    STATE.with(|state| state.borrow().list_canister_signing_profiles(canister_id))
+}
+
+/// Forwards the webhook request received by a proxy to the canister that registered its path.
+async fn webhook_handler(request: HttpRequest) -> HttpResponse {
+    let target = parse_webhook_url(&request.url).and_then(|(canister_id, path)| {
+        STATE
+            .with(|state| state.borrow().get_webhook_callback(canister_id, &path))
+            .map(|method_name| (canister_id, method_name))
+    });
+
+    let Some((canister_id, method_name)) = target else {
+        warn!("[webhooks]: url:{}, no webhook registered", request.url);
+
+        return text_response(404, "Not Found");
+    };
+
+    let canister_res: Result<(HttpResponse,), _> =
+        ic_cdk::call(canister_id, method_name.as_str(), (request,)).await;
+
+    log!(
+        { canister_id: canister_id },
+        "[webhooks]: canister_id:{}, callback method:{}, completed inter-canister call result: {:?}",
+        canister_id,
+        method_name,
+        canister_res.as_ref().map(|(response,)| &response.status)
+    );
+
+    match canister_res {
+        Ok((response,)) => response,
+        Err(_) => text_response(502, "Bad Gateway"),
+    }
+}
+
+/// Defaults to and is capped at [MAX_LIST_REQUESTS_LIMIT], with a minimum of 1
+/// so that a page is never empty while there are more items.
+fn list_limit(limit: Option<u32>) -> usize {
+    limit
+        .unwrap_or(MAX_LIST_REQUESTS_LIMIT)
+        .clamp(1, MAX_LIST_REQUESTS_LIMIT) as usize
+}
+
+#[query]
+fn get_logs(args: GetLogsArgs) -> GetLogsResult {
+    let caller = caller();
+    guard_caller_is_controller(&caller);
+
+    logger::get_logs(&args)
+}
+
+#[query]
+fn export_logs(args: ExportLogsArgs) -> ExportLogsResult {
+    let caller = caller();
+    guard_caller_is_controller(&caller);
+
+    logger::export_logs(&args)
+}
+
+#[query]
+fn get_logger_config() -> LoggerConfig {
+    let caller = caller();
+    guard_caller_is_controller(&caller);
+
+    logger::get_logger_config()
+}
+
+#[update]
+fn set_logger_config(config: LoggerConfig) {
+    let caller = caller();
+    guard_caller_is_controller(&caller);
+
+    logger::set_logger_config(config);
+}
+
+#[query]
+fn get_metrics() -> GetMetricsResult {
+    let caller = caller();
+    guard_caller_is_controller(&caller);
+
+    let (callbacks_succeeded, callbacks_failed, cache) = STATE.with(|state| {
+        let state = state.borrow();
+        let (callbacks_succeeded, callbacks_failed) = state.get_callbacks_counts();
+
+        (
+            callbacks_succeeded,
+            callbacks_failed,
+            state.get_cache_stats(),
+        )
+    });
+
+    let metrics = ProxyCanisterMetrics {
+        http_over_ws: http_over_ws::get_metrics(),
+        callbacks_succeeded,
+        callbacks_failed,
+        cache,
+        heap_memory_bytes: heap_memory_bytes(),
+        stable_memory_bytes: stable_memory_bytes(),
+        cycles_balance: canister_balance128(),
+    };
+
+    GetMetricsResult {
+        prometheus: encode_prometheus(&metrics),
+        metrics,
+    }
+}
+
+#[query]
+fn get_cache_stats() -> CacheStats {
     let caller = caller();
     guard_caller_is_controller(&caller);
 
-    logger::get_logs()
+    STATE.with(|state| state.borrow().get_cache_stats())
 }
@@ -0,0 +1,306 @@
+use std::fmt::{Display, Write};
+
+use http_over_ws::Histogram;
+use ic_cdk::api::stable::stable64_size;
+use proxy_canister_types::ProxyCanisterMetrics;
+
+const WASM_PAGE_SIZE_BYTES: u64 = 65_536;
+
+pub fn heap_memory_bytes() -> u64 {
+    #[cfg(target_arch = "wasm32")]
+    {
+        core::arch::wasm32::memory_size(0) as u64 * WASM_PAGE_SIZE_BYTES
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        0
+    }
+}
+
+pub fn stable_memory_bytes() -> u64 {
+    stable64_size() * WASM_PAGE_SIZE_BYTES
+}
+
+/// Encodes the metrics in the Prometheus text exposition format.
+pub fn encode_prometheus(metrics: &ProxyCanisterMetrics) -> String {
+    let mut encoder = PrometheusEncoder::default();
+    let http_over_ws = &metrics.http_over_ws;
+
+    encoder.header(
+        "http_over_ws_requests_started_total",
+        "The requests assigned to a proxy.",
+        "counter",
+    );
+    encoder.sample(
+        "http_over_ws_requests_started_total",
+        &[],
+        http_over_ws.requests_started,
+    );
+
+    encoder.header(
+        "http_over_ws_requests_succeeded_total",
+        "The requests for which a response has been received.",
+        "counter",
+    );
+    encoder.sample(
+        "http_over_ws_requests_succeeded_total",
+        &[],
+        http_over_ws.requests_succeeded,
+    );
+
+    encoder.header(
+        "http_over_ws_requests_failed_total",
+        "The requests that failed, by reason.",
+        "counter",
+    );
+    for (reason, value) in [
+        ("timeout", http_over_ws.requests_failed_timeout),
+        ("proxy_error", http_over_ws.requests_failed_proxy_error),
+        (
+            "invalid_attestation",
+            http_over_ws.requests_failed_invalid_attestation,
+        ),
+        (
+            "invalid_encryption",
+            http_over_ws.requests_failed_invalid_encryption,
+        ),
+        ("redirect", http_over_ws.requests_failed_redirect),
+        ("no_proxies", http_over_ws.requests_rejected_no_proxies),
+    ] {
+        encoder.sample(
+            "http_over_ws_requests_failed_total",
+            &[("reason", reason)],
+            value,
+        );
+    }
+
+    encoder.header(
+        "http_over_ws_requests_retried_total",
+        "The requests sent to another proxy after a retryable proxy error.",
+        "counter",
+    );
+    encoder.sample(
+        "http_over_ws_requests_retried_total",
+        &[],
+        http_over_ws.requests_retried,
+    );
+
+    encoder.header(
+        "http_over_ws_proxy_errors_total",
+        "The errors reported by the proxies, by kind.",
+        "counter",
+    );
+    for proxy_error in http_over_ws.proxy_errors.iter() {
+        encoder.sample(
+            "http_over_ws_proxy_errors_total",
+            &[("kind", &proxy_error.kind)],
+            proxy_error.count,
+        );
+    }
+
+    encoder.header(
+        "http_over_ws_request_latency_ms",
+        "The time between the assignment of a request to a proxy and its result.",
+        "histogram",
+    );
+    encoder.histogram(
+        "http_over_ws_request_latency_ms",
+        &[],
+        &http_over_ws.latency_ms,
+    );
+
+    encoder.header(
+        "http_over_ws_request_bytes",
+        "The size of the requests sent to the proxies.",
+        "histogram",
+    );
+    encoder.histogram(
+        "http_over_ws_request_bytes",
+        &[],
+        &http_over_ws.request_bytes,
+    );
+
+    encoder.header(
+        "http_over_ws_response_bytes",
+        "The size of the responses received from the proxies.",
+        "histogram",
+    );
+    encoder.histogram(
+        "http_over_ws_response_bytes",
+        &[],
+        &http_over_ws.response_bytes,
+    );
+
+    encoder.header(
+        "http_over_ws_proxy_in_flight_requests",
+        "The requests waiting for a response from each connected proxy.",
+        "gauge",
+    );
+    for proxy in &http_over_ws.proxies {
+        encoder.sample(
+            "http_over_ws_proxy_in_flight_requests",
+            &[("proxy", &proxy.proxy_principal.to_text())],
+            proxy.in_flight_requests,
+        );
+    }
+
+    encoder.header(
+        "http_over_ws_proxy_requests_total",
+        "The requests completed by each connected proxy, by result.",
+        "counter",
+    );
+    for proxy in &http_over_ws.proxies {
+        let proxy_principal = proxy.proxy_principal.to_text();
+        encoder.sample(
+            "http_over_ws_proxy_requests_total",
+            &[("proxy", &proxy_principal), ("result", "success")],
+            proxy.succeeded_requests,
+        );
+        encoder.sample(
+            "http_over_ws_proxy_requests_total",
+            &[("proxy", &proxy_principal), ("result", "failure")],
+            proxy.failed_requests,
+        );
+    }
+
+    encoder.header(
+        "http_over_ws_proxy_success_rate",
+        "The ratio of succeeded over completed requests of each connected proxy.",
+        "gauge",
+    );
+    for proxy in &http_over_ws.proxies {
+        if let Some(success_rate) = proxy.success_rate {
+            encoder.sample(
+                "http_over_ws_proxy_success_rate",
+                &[("proxy", &proxy.proxy_principal.to_text())],
+                success_rate,
+            );
+        }
+    }
+
+    encoder.header(
+        "http_over_ws_proxy_request_latency_ms",
+        "The latency of the requests completed by each connected proxy.",
+        "histogram",
+    );
+    for proxy in &http_over_ws.proxies {
+        encoder.histogram(
+            "http_over_ws_proxy_request_latency_ms",
+            &[("proxy", &proxy.proxy_principal.to_text())],
+            &proxy.latency_ms,
+        );
+    }
+
+    encoder.header(
+        "proxy_canister_callbacks_total",
+        "The attempts to deliver a result to a callback method, by result.",
+        "counter",
+    );
+    encoder.sample(
+        "proxy_canister_callbacks_total",
+        &[("result", "success")],
+        metrics.callbacks_succeeded,
+    );
+    encoder.sample(
+        "proxy_canister_callbacks_total",
+        &[("result", "failure")],
+        metrics.callbacks_failed,
+    );
+
+    encoder.header(
+        "proxy_canister_cache_requests_total",
+        "The lookups in the response cache, by result.",
+        "counter",
+    );
+    encoder.sample(
+        "proxy_canister_cache_requests_total",
+        &[("result", "hit")],
+        metrics.cache.hits,
+    );
+    encoder.sample(
+        "proxy_canister_cache_requests_total",
+        &[("result", "miss")],
+        metrics.cache.misses,
+    );
+
+    for (name, help, value) in [
+        (
+            "proxy_canister_cache_entries",
+            "The responses in the cache.",
+            metrics.cache.entries,
+        ),
+        (
+            "proxy_canister_cache_size_bytes",
+            "The size of the responses in the cache.",
+            metrics.cache.size_bytes,
+        ),
+        (
+            "proxy_canister_heap_memory_bytes",
+            "The size of the heap memory.",
+            metrics.heap_memory_bytes,
+        ),
+        (
+            "proxy_canister_stable_memory_bytes",
+            "The size of the stable memory.",
+            metrics.stable_memory_bytes,
+        ),
+    ] {
+        encoder.header(name, help, "gauge");
+        encoder.sample(name, &[], value);
+    }
+
+    encoder.header(
+        "proxy_canister_cycles_balance",
+        "The cycles balance of the canister.",
+        "gauge",
+    );
+    encoder.sample("proxy_canister_cycles_balance", &[], metrics.cycles_balance);
+
+    encoder.output
+}
+
+#[derive(Default)]
+struct PrometheusEncoder {
+    output: String,
+}
+
+impl PrometheusEncoder {
+    fn header(&mut self, name: &str, help: &str, metric_type: &str) {
+        writeln!(self.output, "# HELP {} {}", name, help).unwrap();
+        writeln!(self.output, "# TYPE {} {}", name, metric_type).unwrap();
+    }
+
+    fn sample(&mut self, name: &str, labels: &[(&str, &str)], value: impl Display) {
+        write!(self.output, "{}", name).unwrap();
+
+        if !labels.is_empty() {
+            let labels: Vec<String> = labels
+                .iter()
+                .map(|(key, value)| format!("{}=\"{}\"", key, value))
+                .collect();
+            write!(self.output, "{{{}}}", labels.join(",")).unwrap();
+        }
+
+        writeln!(self.output, " {}", value).unwrap();
+    }
+
+    fn histogram(&mut self, name: &str, labels: &[(&str, &str)], histogram: &Histogram) {
+        let bucket_name = format!("{}_bucket", name);
+
+        for (bound, count) in &histogram.buckets {
+            let bound = bound.to_string();
+            let bucket_labels: Vec<(&str, &str)> = labels
+                .iter()
+                .copied()
+                .chain([("le", bound.as_str())])
+                .collect();
+            self.sample(&bucket_name, &bucket_labels, count);
+        }
+
+        let bucket_labels: Vec<(&str, &str)> =
+            labels.iter().copied().chain([("le", "+Inf")]).collect();
+        self.sample(&bucket_name, &bucket_labels, histogram.count);
+        self.sample(&format!("{}_sum", name), labels, histogram.sum);
+        self.sample(&format!("{}_count", name), labels, histogram.count);
+    }
+}
@@ -1,22 +1,235 @@
-use proxy_canister_types::{HttpRequestEndpointArgs, InvalidRequest};
+use http_over_ws::{
+    secret_placeholder_names, HttpRequest, RedirectPolicy, SecretName, MAX_FOLLOWED_REDIRECTS,
+};
+use proxy_canister_types::{
+    HttpMethod, HttpRequestEndpointArgs, HttpRequestSchedule, InvalidProxyConfig, InvalidRequest,
+    ProxyConfig, RegisterSigningProfileArgs, ScheduleHttpRequestArgs, SigningScheme, Webhook,
+};
+use std::collections::BTreeSet;
 use url::Url;
 
-use crate::constants::{
-    MAX_HTTP_HEADERS_COUNT, MAX_HTTP_REQUEST_TIMEOUT_MS, MIN_HTTP_REQUEST_TIMEOUT_MS,
+use crate::{
+    constants::{
+        MAX_CACHE_TTL_MS, MAX_CONFIGURABLE_CALLBACK_DELIVERY_ATTEMPTS,
+        MAX_CONFIGURABLE_HTTP_HEADERS_COUNT, MAX_CONFIGURABLE_HTTP_REQUEST_TIMEOUT_MS,
+        MAX_IDEMPOTENCY_KEY_LENGTH, MAX_SIGNING_PROFILE_NAME_LENGTH, MAX_SIGNING_SECRET_LENGTH,
+        MAX_WEBHOOK_PATH_LENGTH, MIN_SCHEDULE_INTERVAL_MS,
+    },
+    cron::CronSchedule,
+    scheduler::next_execution_ns,
+    signing::is_valid_secret,
 };
 
-pub fn validate_incoming_request(args: &HttpRequestEndpointArgs) -> Result<(), InvalidRequest> {
-    Url::parse(&args.request.url).map_err(|e| InvalidRequest::InvalidUrl(e.to_string()))?;
+/// The destination policy, applied to the initial URL of the requests and to every redirect they follow:
+/// the URL must be an absolute `http` or `https` URL.
+pub fn validate_destination(url: &str) -> Result<(), InvalidRequest> {
+    let url = Url::parse(url).map_err(|e| InvalidRequest::InvalidUrl(e.to_string()))?;
+
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(InvalidRequest::InvalidUrl(format!(
+            "unsupported scheme: {}",
+            url.scheme()
+        )));
+    }
+
+    Ok(())
+}
+
+/// Validates the destination of each hop of the redirects followed for the requests.
+pub fn validate_redirect(request: &HttpRequest) -> Result<(), String> {
+    validate_destination(&request.url).map_err(|e| format!("{:?}", e))
+}
+
+/// Returns the names of the secrets referenced by the request, see [secret_placeholder_names].
+pub fn validate_incoming_request(
+    args: &HttpRequestEndpointArgs,
+    config: &ProxyConfig,
+) -> Result<BTreeSet<SecretName>, InvalidRequest> {
+    validate_destination(&args.request.url)?;
 
-    if args.request.headers.len() > MAX_HTTP_HEADERS_COUNT {
+    if !args.request.method.is_valid() {
+        return Err(InvalidRequest::InvalidHttpMethod);
+    }
+
+    let max_http_headers_count = config.max_http_headers_count as usize;
+
+    if args.request.headers.len() > max_http_headers_count {
         return Err(InvalidRequest::TooManyHeaders);
     }
 
+    if let Some(RedirectPolicy::Follow(max_redirects)) = args.request.redirect {
+        if max_redirects > MAX_FOLLOWED_REDIRECTS {
+            return Err(InvalidRequest::InvalidRedirectPolicy);
+        }
+    }
+
     if args.timeout_ms.is_some_and(|timeout_ms| {
-        timeout_ms > MAX_HTTP_REQUEST_TIMEOUT_MS || timeout_ms < MIN_HTTP_REQUEST_TIMEOUT_MS
+        timeout_ms > config.max_http_request_timeout_ms
+            || timeout_ms < config.min_http_request_timeout_ms
     }) {
         return Err(InvalidRequest::InvalidTimeout);
     }
 
+    if let Some(cache) = &args.cache {
+        // the signature headers are different for each request
+        if args.request.method != HttpMethod::GET || args.signing_profile.is_some() {
+            return Err(InvalidRequest::NotCacheable);
+        }
+
+        if cache.key_headers.len() > max_http_headers_count {
+            return Err(InvalidRequest::TooManyHeaders);
+        }
+
+        if cache
+            .ttl_ms
+            .is_some_and(|ttl_ms| ttl_ms == 0 || ttl_ms > MAX_CACHE_TTL_MS)
+        {
+            return Err(InvalidRequest::InvalidCacheTtl);
+        }
+    }
+
+    if args
+        .idempotency_key
+        .as_ref()
+        .is_some_and(|key| key.is_empty() || key.len() > MAX_IDEMPOTENCY_KEY_LENGTH)
+    {
+        return Err(InvalidRequest::InvalidIdempotencyKey);
+    }
+
+    let secret_names = secret_placeholder_names(&args.request)
+        .map_err(InvalidRequest::InvalidSecretPlaceholder)?;
+
+    // the proxy substitutes the secrets after the request has been signed
+    if !secret_names.is_empty() && args.signing_profile.is_some() {
+        return Err(InvalidRequest::InvalidSigningProfile(
+            "requests with secret placeholders can't be signed".to_string(),
+        ));
+    }
+
+    Ok(secret_names)
+}
+
+pub fn validate_config(config: &ProxyConfig) -> Result<(), InvalidProxyConfig> {
+    if config.min_http_request_timeout_ms == 0
+        || config.max_http_request_timeout_ms > MAX_CONFIGURABLE_HTTP_REQUEST_TIMEOUT_MS
+        || config.default_http_request_timeout_ms < config.min_http_request_timeout_ms
+        || config.default_http_request_timeout_ms > config.max_http_request_timeout_ms
+    {
+        return Err(InvalidProxyConfig::InvalidTimeouts);
+    }
+
+    if config.max_http_headers_count == 0
+        || config.max_http_headers_count > MAX_CONFIGURABLE_HTTP_HEADERS_COUNT
+    {
+        return Err(InvalidProxyConfig::InvalidMaxHttpHeadersCount);
+    }
+
+    if config.max_callback_delivery_attempts == 0
+        || config.max_callback_delivery_attempts > MAX_CONFIGURABLE_CALLBACK_DELIVERY_ATTEMPTS
+    {
+        return Err(InvalidProxyConfig::InvalidMaxCallbackDeliveryAttempts);
+    }
+
     Ok(())
 }
+
+/// Validates the schedule of the job and returns the time of its first execution.
+pub fn validate_schedule(
+    args: &ScheduleHttpRequestArgs,
+    now_ns: u64,
+) -> Result<u64, InvalidRequest> {
+    match &args.schedule {
+        HttpRequestSchedule::Interval { interval_ms } => {
+            if *interval_ms < MIN_SCHEDULE_INTERVAL_MS {
+                return Err(InvalidRequest::InvalidSchedule(format!(
+                    "interval must be at least {}ms",
+                    MIN_SCHEDULE_INTERVAL_MS
+                )));
+            }
+        }
+        HttpRequestSchedule::Cron(expression) => {
+            CronSchedule::parse(expression).map_err(InvalidRequest::InvalidSchedule)?;
+        }
+    }
+
+    let first_execution_ns = next_execution_ns(&args.schedule, now_ns)
+        .ok_or_else(|| InvalidRequest::InvalidSchedule("schedule never executes".to_string()))?;
+
+    if args
+        .end_time_ns
+        .is_some_and(|end_time_ns| end_time_ns < first_execution_ns)
+    {
+        return Err(InvalidRequest::InvalidSchedule(
+            "end time is before the first execution".to_string(),
+        ));
+    }
+
+    Ok(first_execution_ns)
+}
+
+pub fn validate_webhook(webhook: &Webhook) -> Result<(), InvalidRequest> {
+    if !webhook.path.starts_with('/')
+        || webhook.path.len() > MAX_WEBHOOK_PATH_LENGTH
+        || webhook.path.contains(['?', '#'])
+    {
+        return Err(InvalidRequest::InvalidWebhookPath);
+    }
+
+    Ok(())
+}
+
+pub fn validate_signing_profile(args: &RegisterSigningProfileArgs) -> Result<(), InvalidRequest> {
+    if args.name.is_empty() || args.name.len() > MAX_SIGNING_PROFILE_NAME_LENGTH {
+        return Err(InvalidRequest::InvalidSigningProfile(
+            "invalid name".to_string(),
+        ));
+    }
+
+    match &args.scheme {
+        SigningScheme::HmacSha256 {
+            signature_header,
+            timestamp_header,
+        }
+        | SigningScheme::EcdsaSecp256k1 {
+            signature_header,
+            timestamp_header,
+        } => {
+            if !is_valid_header_name(signature_header)
+                || !is_valid_header_name(timestamp_header)
+                || signature_header.eq_ignore_ascii_case(timestamp_header)
+            {
+                return Err(InvalidRequest::InvalidSigningProfile(
+                    "invalid header names".to_string(),
+                ));
+            }
+        }
+        SigningScheme::AwsSigV4 {
+            access_key_id,
+            region,
+            service,
+        } => {
+            if access_key_id.is_empty() || region.is_empty() || service.is_empty() {
+                return Err(InvalidRequest::InvalidSigningProfile(
+                    "missing access key id, region or service".to_string(),
+                ));
+            }
+        }
+    }
+
+    if args.secret.len() > MAX_SIGNING_SECRET_LENGTH || !is_valid_secret(&args.scheme, &args.secret)
+    {
+        return Err(InvalidRequest::InvalidSigningProfile(
+            "invalid secret".to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Whether the name is a valid HTTP token (RFC 9110).
+fn is_valid_header_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "!#$%&'*+-.^_`|~".contains(c))
+}
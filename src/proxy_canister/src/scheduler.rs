@@ -0,0 +1,235 @@
+use std::collections::{BTreeMap, HashMap};
+
+use candid::{CandidType, Deserialize};
+use ic_cdk_timers::TimerId;
+use proxy_canister_types::{
+    CanisterId, HttpRequestSchedule, ScheduleHttpRequestArgs, ScheduledJob, ScheduledJobError,
+    ScheduledJobExecution, ScheduledJobId, ScheduledJobState,
+};
+
+use crate::{constants::MAX_SCHEDULED_JOB_EXECUTIONS_HISTORY, cron::CronSchedule};
+
+/// The scheduled jobs, as saved to the stable memory across upgrades.
+#[derive(CandidType, Deserialize, Default)]
+pub struct ScheduledJobsSnapshot {
+    jobs: Vec<ScheduledJob>,
+    next_job_id: ScheduledJobId,
+}
+
+pub struct ScheduledJobs {
+    jobs: BTreeMap<ScheduledJobId, ScheduledJob>,
+    next_job_id: ScheduledJobId,
+    /// The timers of the active jobs. Timers don't survive upgrades,
+    /// so they are set again from the jobs' next execution time.
+    timers: HashMap<ScheduledJobId, TimerId>,
+}
+
+impl ScheduledJobs {
+    pub fn new() -> Self {
+        Self {
+            jobs: BTreeMap::new(),
+            next_job_id: 0,
+            timers: HashMap::new(),
+        }
+    }
+
+    pub fn from_snapshot(snapshot: ScheduledJobsSnapshot) -> Self {
+        Self {
+            jobs: snapshot
+                .jobs
+                .into_iter()
+                .map(|job| (job.job_id, job))
+                .collect(),
+            next_job_id: snapshot.next_job_id,
+            timers: HashMap::new(),
+        }
+    }
+
+    pub fn to_snapshot(&self) -> ScheduledJobsSnapshot {
+        ScheduledJobsSnapshot {
+            jobs: self.jobs.values().cloned().collect(),
+            next_job_id: self.next_job_id,
+        }
+    }
+
+    pub fn insert(
+        &mut self,
+        canister_id: CanisterId,
+        args: ScheduleHttpRequestArgs,
+        next_execution_ns: u64,
+    ) -> ScheduledJobId {
+        let job_id = self.next_job_id;
+        self.next_job_id += 1;
+
+        self.jobs.insert(
+            job_id,
+            ScheduledJob {
+                job_id,
+                canister_id,
+                request: args.request,
+                timeout_ms: args.timeout_ms,
+                schedule: args.schedule,
+                end_time_ns: args.end_time_ns,
+                callback_method_name: args.callback_method_name,
+                state: ScheduledJobState::Active,
+                next_execution_ns: Some(next_execution_ns),
+                executions: vec![],
+            },
+        );
+
+        job_id
+    }
+
+    pub fn get(&self, job_id: ScheduledJobId) -> Option<&ScheduledJob> {
+        self.jobs.get(&job_id)
+    }
+
+    pub fn list_canister_jobs(&self, canister_id: CanisterId) -> Vec<ScheduledJob> {
+        self.jobs
+            .values()
+            .filter(|job| job.canister_id == canister_id)
+            .cloned()
+            .collect()
+    }
+
+    pub fn count_canister_jobs(&self, canister_id: CanisterId) -> usize {
+        self.jobs
+            .values()
+            .filter(|job| job.canister_id == canister_id)
+            .count()
+    }
+
+    /// The active jobs with their next execution time.
+    pub fn active_jobs(&self) -> Vec<(ScheduledJobId, u64)> {
+        self.jobs
+            .values()
+            .filter(|job| job.state == ScheduledJobState::Active)
+            .filter_map(|job| job.next_execution_ns.map(|ns| (job.job_id, ns)))
+            .collect()
+    }
+
+    pub fn set_timer(&mut self, job_id: ScheduledJobId, timer_id: TimerId) {
+        self.timers.insert(job_id, timer_id);
+    }
+
+    /// Records the execution and computes the next one.
+    /// Returns the time of the next execution, [None] if the job is not active anymore.
+    pub fn record_execution(
+        &mut self,
+        job_id: ScheduledJobId,
+        execution: ScheduledJobExecution,
+    ) -> Option<u64> {
+        self.timers.remove(&job_id);
+        let job = self.jobs.get_mut(&job_id)?;
+
+        job.executions.push(execution.clone());
+        if job.executions.len() > MAX_SCHEDULED_JOB_EXECUTIONS_HISTORY {
+            job.executions.remove(0);
+        }
+
+        if job.state != ScheduledJobState::Active {
+            return None;
+        }
+
+        job.next_execution_ns = next_execution_ns(&job.schedule, execution.timestamp_ns)
+            .filter(|next_ns| is_before_end_time(job.end_time_ns, *next_ns));
+        if job.next_execution_ns.is_none() {
+            job.state = ScheduledJobState::Finished;
+        }
+
+        job.next_execution_ns
+    }
+
+    /// Returns the timer of the job, which must be cleared.
+    pub fn pause(
+        &mut self,
+        canister_id: CanisterId,
+        job_id: ScheduledJobId,
+    ) -> Result<Option<TimerId>, ScheduledJobError> {
+        let job = get_canister_job(&mut self.jobs, canister_id, job_id)?;
+
+        match job.state {
+            ScheduledJobState::Active => {
+                job.state = ScheduledJobState::Paused;
+                job.next_execution_ns = None;
+
+                Ok(self.timers.remove(&job_id))
+            }
+            ScheduledJobState::Paused => Ok(None),
+            ScheduledJobState::Finished => Err(ScheduledJobError::Finished),
+        }
+    }
+
+    /// Returns the time of the next execution if the job has been resumed,
+    /// [None] if it was already active.
+    pub fn resume(
+        &mut self,
+        canister_id: CanisterId,
+        job_id: ScheduledJobId,
+        now_ns: u64,
+    ) -> Result<Option<u64>, ScheduledJobError> {
+        let job = get_canister_job(&mut self.jobs, canister_id, job_id)?;
+
+        match job.state {
+            ScheduledJobState::Active => Ok(None),
+            ScheduledJobState::Paused => {
+                let next_ns = next_execution_ns(&job.schedule, now_ns)
+                    .filter(|next_ns| is_before_end_time(job.end_time_ns, *next_ns));
+
+                match next_ns {
+                    Some(next_ns) => {
+                        job.state = ScheduledJobState::Active;
+                        job.next_execution_ns = Some(next_ns);
+                    }
+                    None => job.state = ScheduledJobState::Finished,
+                }
+
+                Ok(next_ns)
+            }
+            ScheduledJobState::Finished => Err(ScheduledJobError::Finished),
+        }
+    }
+
+    /// Removes the job and returns its timer, which must be cleared.
+    pub fn cancel(
+        &mut self,
+        canister_id: CanisterId,
+        job_id: ScheduledJobId,
+    ) -> Result<Option<TimerId>, ScheduledJobError> {
+        get_canister_job(&mut self.jobs, canister_id, job_id)?;
+        self.jobs.remove(&job_id);
+
+        Ok(self.timers.remove(&job_id))
+    }
+}
+
+fn get_canister_job(
+    jobs: &mut BTreeMap<ScheduledJobId, ScheduledJob>,
+    canister_id: CanisterId,
+    job_id: ScheduledJobId,
+) -> Result<&mut ScheduledJob, ScheduledJobError> {
+    jobs.get_mut(&job_id)
+        .filter(|job| job.canister_id == canister_id)
+        .ok_or(ScheduledJobError::NotFound)
+}
+
+fn is_before_end_time(end_time_ns: Option<u64>, time_ns: u64) -> bool {
+    match end_time_ns {
+        Some(end_time_ns) => time_ns <= end_time_ns,
+        None => true,
+    }
+}
+
+/// The first execution time of the schedule strictly after the given time.
+///
+/// Returns [None] if the schedule never executes.
+pub fn next_execution_ns(schedule: &HttpRequestSchedule, after_ns: u64) -> Option<u64> {
+    match schedule {
+        HttpRequestSchedule::Interval { interval_ms } => {
+            after_ns.checked_add(interval_ms.checked_mul(1_000_000)?)
+        }
+        HttpRequestSchedule::Cron(expression) => {
+            CronSchedule::parse(expression).ok()?.next_after(after_ns)
+        }
+    }
+}
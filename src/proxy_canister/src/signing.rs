@@ -0,0 +1,621 @@
+use std::collections::{BTreeMap, HashMap};
+
+use candid::{CandidType, Deserialize};
+use hmac::{Hmac, Mac};
+use http_over_ws::{HttpHeader, HttpRequest};
+use k256::ecdsa::{signature::Signer, Signature, SigningKey};
+use proxy_canister_types::{
+    CanisterId, InvalidRequest, RegisterSigningProfileArgs, SigningProfileInfo, SigningProfileName,
+    SigningScheme,
+};
+use sha2::{Digest, Sha256};
+use url::Url;
+
+use crate::{constants::MAX_SIGNING_PROFILES_PER_CANISTER, utils::civil_from_days};
+
+type HmacSha256 = Hmac<Sha256>;
+
+const NS_PER_SECOND: u64 = 1_000_000_000;
+
+#[derive(CandidType, Deserialize, Clone)]
+pub struct SigningProfile {
+    scheme: SigningScheme,
+    secret: Vec<u8>,
+    registered_at_ns: u64,
+}
+
+/// The signing profiles, as saved to the stable memory across upgrades.
+#[derive(CandidType, Deserialize, Default)]
+pub struct SigningProfilesSnapshot {
+    profiles: Vec<(CanisterId, SigningProfileName, SigningProfile)>,
+}
+
+/// The signing profiles registered for each canister.
+/// Their secrets never leave the proxy canister, except as signatures.
+pub struct SigningProfiles(HashMap<CanisterId, BTreeMap<SigningProfileName, SigningProfile>>);
+
+impl SigningProfiles {
+    pub fn new() -> Self {
+        Self(HashMap::new())
+    }
+
+    pub fn from_snapshot(snapshot: SigningProfilesSnapshot) -> Self {
+        let mut profiles = Self::new();
+        for (canister_id, name, profile) in snapshot.profiles {
+            profiles
+                .0
+                .entry(canister_id)
+                .or_default()
+                .insert(name, profile);
+        }
+        profiles
+    }
+
+    pub fn to_snapshot(&self) -> SigningProfilesSnapshot {
+        SigningProfilesSnapshot {
+            profiles: self
+                .0
+                .iter()
+                .flat_map(|(canister_id, profiles)| {
+                    profiles
+                        .iter()
+                        .map(|(name, profile)| (*canister_id, name.clone(), profile.clone()))
+                })
+                .collect(),
+        }
+    }
+
+    /// Registers the profile, replacing the one with the same name if any.
+    pub fn register(
+        &mut self,
+        canister_id: CanisterId,
+        args: RegisterSigningProfileArgs,
+        now_ns: u64,
+    ) -> Result<(), InvalidRequest> {
+        let profiles = self.0.entry(canister_id).or_default();
+
+        if !profiles.contains_key(&args.name) && profiles.len() >= MAX_SIGNING_PROFILES_PER_CANISTER
+        {
+            return Err(InvalidRequest::TooManySigningProfiles);
+        }
+
+        profiles.insert(
+            args.name,
+            SigningProfile {
+                scheme: args.scheme,
+                secret: args.secret,
+                registered_at_ns: now_ns,
+            },
+        );
+
+        Ok(())
+    }
+
+    pub fn unregister(&mut self, canister_id: CanisterId, name: &str) {
+        if let Some(profiles) = self.0.get_mut(&canister_id) {
+            profiles.remove(name);
+
+            if profiles.is_empty() {
+                self.0.remove(&canister_id);
+            }
+        }
+    }
+
+    pub fn list(&self, canister_id: CanisterId) -> Vec<SigningProfileInfo> {
+        self.0
+            .get(&canister_id)
+            .map(|profiles| {
+                profiles
+                    .iter()
+                    .map(|(name, profile)| SigningProfileInfo {
+                        name: name.clone(),
+                        scheme: profile.scheme.clone(),
+                        registered_at_ns: profile.registered_at_ns,
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Adds the signature headers of the profile to the request,
+    /// replacing the headers with the same names.
+    pub fn sign(
+        &self,
+        canister_id: CanisterId,
+        name: &str,
+        request: &mut HttpRequest,
+        now_ns: u64,
+    ) -> Result<(), InvalidRequest> {
+        let profile = self
+            .0
+            .get(&canister_id)
+            .and_then(|profiles| profiles.get(name))
+            .ok_or(InvalidRequest::SigningProfileNotFound)?;
+        let url =
+            Url::parse(&request.url).map_err(|e| InvalidRequest::InvalidUrl(e.to_string()))?;
+
+        match &profile.scheme {
+            SigningScheme::HmacSha256 {
+                signature_header,
+                timestamp_header,
+            } => {
+                let timestamp = (now_ns / NS_PER_SECOND).to_string();
+                let mut mac = new_hmac(&profile.secret);
+                mac.update(&string_to_sign(&timestamp, request, &url));
+                let signature = hex::encode(mac.finalize().into_bytes());
+
+                set_header(request, timestamp_header, timestamp);
+                set_header(request, signature_header, signature);
+            }
+            SigningScheme::EcdsaSecp256k1 {
+                signature_header,
+                timestamp_header,
+            } => {
+                let timestamp = (now_ns / NS_PER_SECOND).to_string();
+                let signing_key = SigningKey::from_slice(&profile.secret)
+                    .map_err(|e| InvalidRequest::InvalidSigningProfile(e.to_string()))?;
+                // the message is hashed with SHA-256 by the signer
+                let signature: Signature =
+                    signing_key.sign(&string_to_sign(&timestamp, request, &url));
+
+                set_header(request, timestamp_header, timestamp);
+                set_header(request, signature_header, hex::encode(signature.to_bytes()));
+            }
+            SigningScheme::AwsSigV4 {
+                access_key_id,
+                region,
+                service,
+            } => {
+                sign_aws_sig_v4(
+                    request,
+                    &url,
+                    access_key_id,
+                    region,
+                    service,
+                    &profile.secret,
+                    now_ns,
+                );
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Whether the secret can be used with the scheme.
+pub fn is_valid_secret(scheme: &SigningScheme, secret: &[u8]) -> bool {
+    match scheme {
+        SigningScheme::EcdsaSecp256k1 { .. } => SigningKey::from_slice(secret).is_ok(),
+        SigningScheme::HmacSha256 { .. } | SigningScheme::AwsSigV4 { .. } => !secret.is_empty(),
+    }
+}
+
+/// The timestamp, the method, the path with the query and the body, concatenated.
+fn string_to_sign(timestamp: &str, request: &HttpRequest, url: &Url) -> Vec<u8> {
+    let mut string_to_sign = format!(
+        "{}{}{}",
+        timestamp,
+        request.method.as_str(),
+        path_and_query(url)
+    )
+    .into_bytes();
+    if let Some(body) = &request.body {
+        string_to_sign.extend_from_slice(body);
+    }
+    string_to_sign
+}
+
+fn path_and_query(url: &Url) -> String {
+    match url.query() {
+        Some(query) => format!("{}?{}", url.path(), query),
+        None => url.path().to_string(),
+    }
+}
+
+fn new_hmac(key: &[u8]) -> HmacSha256 {
+    HmacSha256::new_from_slice(key).expect("HMAC accepts keys of any length")
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = new_hmac(key);
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn set_header(request: &mut HttpRequest, name: &str, value: String) {
+    request
+        .headers
+        .retain(|header| !header.name.eq_ignore_ascii_case(name));
+    request.headers.push(HttpHeader {
+        name: name.to_string(),
+        value,
+    });
+}
+
+/// Signs the request as described in https://docs.aws.amazon.com/IAM/latest/UserGuide/create-signed-request.html.
+///
+/// The signed headers are `host`, `content-type` if present and the `x-amz-*` ones.
+fn sign_aws_sig_v4(
+    request: &mut HttpRequest,
+    url: &Url,
+    access_key_id: &str,
+    region: &str,
+    service: &str,
+    secret_access_key: &[u8],
+    now_ns: u64,
+) {
+    let (_, amz_date) = aws_dates(now_ns);
+    let payload_hash = hex::encode(Sha256::digest(request.body.as_deref().unwrap_or_default()));
+
+    set_header(request, "X-Amz-Date", amz_date);
+    set_header(request, "X-Amz-Content-Sha256", payload_hash);
+    request
+        .headers
+        .retain(|header| !header.name.eq_ignore_ascii_case("Authorization"));
+
+    let authorization = aws_sig_v4_authorization(
+        request,
+        url,
+        access_key_id,
+        region,
+        service,
+        secret_access_key,
+        now_ns,
+    );
+    request.headers.push(HttpHeader {
+        name: "Authorization".to_string(),
+        value: authorization,
+    });
+}
+
+/// The value of the `Authorization` header, signing the request with its current headers.
+fn aws_sig_v4_authorization(
+    request: &HttpRequest,
+    url: &Url,
+    access_key_id: &str,
+    region: &str,
+    service: &str,
+    secret_access_key: &[u8],
+    now_ns: u64,
+) -> String {
+    let (date, amz_date) = aws_dates(now_ns);
+    let payload_hash = hex::encode(Sha256::digest(request.body.as_deref().unwrap_or_default()));
+
+    let host = match url.port() {
+        Some(port) => format!("{}:{}", url.host_str().unwrap_or_default(), port),
+        None => url.host_str().unwrap_or_default().to_string(),
+    };
+    let mut canonical_headers: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    canonical_headers.insert("host".to_string(), vec![host]);
+    for header in request.headers.iter() {
+        let name = header.name.to_ascii_lowercase();
+        if name == "content-type" || name.starts_with("x-amz-") {
+            canonical_headers
+                .entry(name)
+                .or_default()
+                .push(header.value.trim().to_string());
+        }
+    }
+    let signed_headers = canonical_headers
+        .keys()
+        .cloned()
+        .collect::<Vec<_>>()
+        .join(";");
+
+    let canonical_request = format!(
+        "{}\n{}\n{}\n{}\n{}\n{}",
+        request.method.as_str(),
+        aws_canonical_uri(url, service),
+        aws_canonical_query(url),
+        canonical_headers
+            .iter()
+            .map(|(name, values)| format!("{}:{}\n", name, values.join(",")))
+            .collect::<String>(),
+        signed_headers,
+        payload_hash
+    );
+
+    let scope = format!("{}/{}/{}/aws4_request", date, region, service);
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+        amz_date,
+        scope,
+        hex::encode(Sha256::digest(canonical_request.as_bytes()))
+    );
+
+    let mut signing_key = [b"AWS4".as_slice(), secret_access_key].concat();
+    for part in [date.as_str(), region, service, "aws4_request"] {
+        signing_key = hmac_sha256(&signing_key, part.as_bytes());
+    }
+    let signature = hex::encode(hmac_sha256(&signing_key, string_to_sign.as_bytes()));
+
+    format!(
+        "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+        access_key_id, scope, signed_headers, signature
+    )
+}
+
+/// The path, with each segment encoded as required by AWS.
+///
+/// The dot segments are already removed when parsing the URL. Except for S3,
+/// which signs the path as it is, the empty segments are removed too.
+fn aws_canonical_uri(url: &Url, service: &str) -> String {
+    let segments = url.path().split('/').skip(1).collect::<Vec<_>>();
+    let last_segment = segments.len().saturating_sub(1);
+
+    let canonical_uri = segments
+        .iter()
+        .enumerate()
+        .filter(|(i, segment)| service == "s3" || !segment.is_empty() || *i == last_segment)
+        .map(|(_, segment)| format!("/{}", aws_uri_encode(&percent_decode(segment))))
+        .collect::<String>();
+
+    if canonical_uri.is_empty() {
+        "/".to_string()
+    } else {
+        canonical_uri
+    }
+}
+
+/// The query parameters sorted by name and value, encoded as required by AWS.
+///
+/// The raw query is used instead of [Url::query_pairs], which decodes `+` as a space.
+fn aws_canonical_query(url: &Url) -> String {
+    let mut query_pairs: Vec<(String, String)> = url
+        .query()
+        .unwrap_or_default()
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (
+                aws_uri_encode(&percent_decode(key)),
+                aws_uri_encode(&percent_decode(value)),
+            )
+        })
+        .collect();
+    query_pairs.sort();
+
+    query_pairs
+        .iter()
+        .map(|(key, value)| format!("{}={}", key, value))
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+/// The `YYYYMMDD` date and the `YYYYMMDD'T'HHMMSS'Z'` time used by AWS, in UTC.
+fn aws_dates(now_ns: u64) -> (String, String) {
+    let seconds = now_ns / NS_PER_SECOND;
+    let (year, month, day) = civil_from_days((seconds / 86_400) as i64);
+    let seconds_of_day = seconds % 86_400;
+
+    let date = format!("{:04}{:02}{:02}", year, month, day);
+    let amz_date = format!(
+        "{}T{:02}{:02}{:02}Z",
+        date,
+        seconds_of_day / 3_600,
+        seconds_of_day % 3_600 / 60,
+        seconds_of_day % 60
+    );
+
+    (date, amz_date)
+}
+
+/// Decodes the `%XX` sequences, leaving the invalid ones and the `+` as they are.
+fn percent_decode(value: &str) -> Vec<u8> {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = bytes
+            .get(i + 1..i + 3)
+            .filter(|hex| bytes[i] == b'%' && hex.iter().all(u8::is_ascii_hexdigit))
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    decoded
+}
+
+/// Percent-encodes everything but the unreserved characters, as required by AWS.
+fn aws_uri_encode(value: &[u8]) -> String {
+    value
+        .iter()
+        .copied()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use candid::Principal;
+    use http_over_ws::HttpMethod;
+    use k256::ecdsa::{signature::Verifier, VerifyingKey};
+
+    use super::*;
+
+    // from the AWS Signature Version 4 test suite
+    const AWS_ACCESS_KEY_ID: &str = "AKIDEXAMPLE";
+    const AWS_SECRET_ACCESS_KEY: &[u8] = b"wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY";
+    const AWS_REGION: &str = "us-east-1";
+    const AWS_SERVICE: &str = "service";
+    // 2015-08-30T12:36:00Z
+    const AWS_NOW_NS: u64 = 1_440_938_160 * NS_PER_SECOND;
+
+    fn aws_test_suite_authorization(method: HttpMethod, url: &str) -> String {
+        let request = HttpRequest::new(
+            url,
+            method,
+            vec![HttpHeader {
+                name: "X-Amz-Date".to_string(),
+                value: "20150830T123600Z".to_string(),
+            }],
+            None,
+        );
+        aws_sig_v4_authorization(
+            &request,
+            &Url::parse(url).unwrap(),
+            AWS_ACCESS_KEY_ID,
+            AWS_REGION,
+            AWS_SERVICE,
+            AWS_SECRET_ACCESS_KEY,
+            AWS_NOW_NS,
+        )
+    }
+
+    fn aws_test_suite_expected_authorization(signature: &str) -> String {
+        format!(
+            "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/service/aws4_request, SignedHeaders=host;x-amz-date, Signature={}",
+            signature
+        )
+    }
+
+    #[test]
+    fn test_aws_sig_v4_test_suite() {
+        let test_cases = [
+            (
+                // get-vanilla
+                HttpMethod::GET,
+                "https://example.amazonaws.com/",
+                "5fa00fa31553b73ebf1942676e86291e8372ff2a2260956d9b8aae1d763fbf31",
+            ),
+            (
+                // post-vanilla
+                HttpMethod::POST,
+                "https://example.amazonaws.com/",
+                "5da7c1a2acd57cee7505fc6676e4e544621c30862966e37dddb68e92efbe5d6b",
+            ),
+            (
+                // get-vanilla-query-order-key-case
+                HttpMethod::GET,
+                "https://example.amazonaws.com/?Param2=value2&Param1=value1",
+                "b97d918cfa904a5beff61c982a1b6f458b799221646efd99d3219ec94cdf2500",
+            ),
+            (
+                // get-vanilla-utf8-query
+                HttpMethod::GET,
+                "https://example.amazonaws.com/?ሴ=bar",
+                "2cdec8eed098649ff3a119c94853b13c643bcf08f8b0a1d91e12c9027818dd04",
+            ),
+            (
+                // get-space
+                HttpMethod::GET,
+                "https://example.amazonaws.com/example space/",
+                "652487583200325589f1fba4c7e578f72c47cb61beeca81406b39ddec1366741",
+            ),
+            (
+                // get-utf8
+                HttpMethod::GET,
+                "https://example.amazonaws.com/ሴ",
+                "8318018e0b0f223aa2bbf98705b62bb787dc9c0e678f255a891fd03141be5d85",
+            ),
+            (
+                // normalize-path/get-slashes
+                HttpMethod::GET,
+                "https://example.amazonaws.com//example//",
+                "9a624bd73a37c9a373b5312afbebe7a714a789de108f0bdfe846570885f57e84",
+            ),
+            (
+                // normalize-path/get-relative-relative
+                HttpMethod::GET,
+                "https://example.amazonaws.com/example1/example2/../..",
+                "5fa00fa31553b73ebf1942676e86291e8372ff2a2260956d9b8aae1d763fbf31",
+            ),
+        ];
+
+        for (method, url, signature) in test_cases {
+            assert_eq!(
+                aws_test_suite_authorization(method, url),
+                aws_test_suite_expected_authorization(signature),
+                "{}",
+                url
+            );
+        }
+    }
+
+    #[test]
+    fn test_aws_canonical_query() {
+        let url = Url::parse("https://example.com/?b=x+y&a=%2B&a=1&c").unwrap();
+
+        assert_eq!(aws_canonical_query(&url), "a=%2B&a=1&b=x%2By&c=");
+    }
+
+    #[test]
+    fn test_aws_canonical_uri() {
+        let url = Url::parse("https://example.com//a%2fb//c d/").unwrap();
+
+        assert_eq!(aws_canonical_uri(&url, "service"), "/a%2Fb/c%20d/");
+        assert_eq!(aws_canonical_uri(&url, "s3"), "//a%2Fb//c%20d/");
+    }
+
+    #[test]
+    fn test_ecdsa_secp256k1_signature() {
+        let canister_id = Principal::anonymous();
+        let secret = [7; 32];
+        let mut signing_profiles = SigningProfiles::new();
+        signing_profiles
+            .register(
+                canister_id,
+                RegisterSigningProfileArgs {
+                    canister_id: None,
+                    name: "ecdsa".to_string(),
+                    scheme: SigningScheme::EcdsaSecp256k1 {
+                        signature_header: "X-Signature".to_string(),
+                        timestamp_header: "X-Timestamp".to_string(),
+                    },
+                    secret: secret.to_vec(),
+                },
+                0,
+            )
+            .unwrap();
+
+        let mut request = HttpRequest::new(
+            "https://example.com/path?query=1",
+            HttpMethod::POST,
+            vec![],
+            Some(b"body".to_vec()),
+        );
+        signing_profiles
+            .sign(
+                canister_id,
+                "ecdsa",
+                &mut request,
+                1_700_000_000 * NS_PER_SECOND,
+            )
+            .unwrap();
+
+        let header_value = |name: &str| {
+            request
+                .headers
+                .iter()
+                .find(|header| header.name == name)
+                .map(|header| header.value.clone())
+                .unwrap()
+        };
+        assert_eq!(header_value("X-Timestamp"), "1700000000");
+
+        let signature =
+            Signature::from_slice(&hex::decode(header_value("X-Signature")).unwrap()).unwrap();
+        let verifying_key = VerifyingKey::from(&SigningKey::from_slice(&secret).unwrap());
+        assert!(verifying_key
+            .verify(b"1700000000POST/path?query=1body", &signature)
+            .is_ok());
+        assert!(verifying_key
+            .verify(b"1700000000POST/path?query=2body", &signature)
+            .is_err());
+    }
+}
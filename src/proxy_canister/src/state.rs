@@ -1,43 +1,741 @@
-use std::collections::HashMap;
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, VecDeque},
+    ops::Bound,
+};
 
-use http_over_ws::HttpRequestId;
-use proxy_canister_types::{CanisterCallbackMethodName, CanisterId, CanisterRequest};
+use http_over_ws::{
+    HttpRequest, HttpRequestId, HttpResponse, HttpResponseMetadata, HttpResult, SecretName,
+};
+use ic_cdk::trap;
+use ic_cdk_timers::TimerId;
+use proxy_canister_types::{
+    CacheStats, CacheTtlMs, CallbackAttempt, CanisterCallbackMethodName, CanisterId,
+    CanisterRequest, DeadLetter, IdempotencyKey, InvalidRequest, ListDeadLettersResult,
+    ListRequestsResult, ProxyConfig, RegisterSigningProfileArgs, RequestState, RequestTrace,
+    RequestTraceEventKind, ResponseAttestation, ScheduleHttpRequestArgs, ScheduledJob,
+    ScheduledJobError, ScheduledJobExecution, ScheduledJobId, SigningProfileInfo, Webhook,
+};
+
+use url::Url;
+
+use crate::{
+    batches::{Batches, CompletedBatch},
+    cache::{CacheKey, ResponseCache},
+    constants::{MAX_REQUEST_TRACE_EVENTS, MAX_SCHEDULED_JOBS_PER_CANISTER, REQUEST_RETENTION_MS},
+    dead_letters::{DeadLetters, DeadLettersSnapshot},
+    deduplication::{IdempotencyKeys, InFlightRequests},
+    scheduler::{ScheduledJobs, ScheduledJobsSnapshot},
+    signing::{SigningProfiles, SigningProfilesSnapshot},
+    webhooks::{Webhooks, WebhooksSnapshot},
+};
 
 pub struct ProxyState {
     requests: HashMap<HttpRequestId, CanisterRequest>,
+    /// The ids of the requests started by each canister.
+    canister_requests: HashMap<CanisterId, BTreeSet<HttpRequestId>>,
+    /// The executed requests with their execution time, in the order they have been executed.
+    executed_requests: VecDeque<(u64, HttpRequestId)>,
+    /// The lifecycle events of the requests, retained as long as the requests.
+    request_traces: HashMap<HttpRequestId, RequestTrace>,
+    callbacks_succeeded: u64,
+    callbacks_failed: u64,
+    cache: ResponseCache,
+    idempotency_keys: IdempotencyKeys,
+    in_flight_requests: InFlightRequests,
+    dead_letters: DeadLetters,
+    batches: Batches,
+    scheduled_jobs: ScheduledJobs,
+    webhooks: Webhooks,
+    signing_profiles: SigningProfiles,
+    /// The origins of the signed requests being executed, the only ones their redirects can go to.
+    signed_request_origins: HashMap<HttpRequestId, String>,
+    /// The names of the proxy secrets that each canister can reference in its requests.
+    allowed_secrets: BTreeMap<CanisterId, BTreeSet<SecretName>>,
+    config: ProxyConfig,
 }
 
 impl ProxyState {
     pub fn new() -> Self {
         Self {
             requests: HashMap::new(),
+            canister_requests: HashMap::new(),
+            executed_requests: VecDeque::new(),
+            request_traces: HashMap::new(),
+            callbacks_succeeded: 0,
+            callbacks_failed: 0,
+            cache: ResponseCache::new(),
+            idempotency_keys: IdempotencyKeys::new(),
+            in_flight_requests: InFlightRequests::new(),
+            dead_letters: DeadLetters::new(),
+            batches: Batches::new(),
+            scheduled_jobs: ScheduledJobs::new(),
+            webhooks: Webhooks::new(),
+            signing_profiles: SigningProfiles::new(),
+            signed_request_origins: HashMap::new(),
+            allowed_secrets: BTreeMap::new(),
+            config: ProxyConfig::default(),
         }
     }
 
+    pub fn get_config(&self) -> &ProxyConfig {
+        &self.config
+    }
+
+    pub fn set_config(&mut self, config: ProxyConfig) {
+        self.config = config;
+    }
+
+    /// Traps if the request id is already in use, so that the whole call is rolled back.
     pub fn start_request_for_canister(
         &mut self,
         canister_id: CanisterId,
         request_id: HttpRequestId,
         callback_method_name: Option<CanisterCallbackMethodName>,
+        now_ns: u64,
     ) {
-        self.requests
-            .entry(request_id)
-            .or_insert(CanisterRequest::new(canister_id, callback_method_name));
+        // an existing record would otherwise be overwritten or, worse, silently kept
+        // with the caller and callback of another request
+        if self.requests.contains_key(&request_id) {
+            trap(&format!("request id {} is already in use", request_id));
+        }
+
+        self.requests.insert(
+            request_id,
+            CanisterRequest::new(canister_id, callback_method_name),
+        );
+        self.canister_requests
+            .entry(canister_id)
+            .or_default()
+            .insert(request_id);
+        self.request_traces.insert(
+            request_id,
+            RequestTrace::new(request_id, canister_id, now_ns),
+        );
+    }
+
+    pub fn add_request_trace_event(
+        &mut self,
+        request_id: HttpRequestId,
+        kind: RequestTraceEventKind,
+        now_ns: u64,
+    ) {
+        if let Some(trace) = self.request_traces.get_mut(&request_id) {
+            if trace.events.len() < MAX_REQUEST_TRACE_EVENTS {
+                trace.add_event(kind, now_ns);
+            }
+        }
+    }
+
+    /// Returns the trace of the request, only if it has been started by the given canister
+    /// or by any canister if [None].
+    pub fn get_request_trace(
+        &self,
+        canister_id: Option<CanisterId>,
+        request_id: HttpRequestId,
+    ) -> Option<RequestTrace> {
+        self.request_traces
+            .get(&request_id)
+            .filter(|trace| canister_id.is_none() || canister_id == Some(trace.canister_id))
+            .cloned()
     }
 
     pub fn get_request_state(&self, request_id: HttpRequestId) -> Option<CanisterRequest> {
         self.requests.get(&request_id).cloned()
     }
 
-    pub fn set_request_executed(&mut self, request_id: HttpRequestId) {
+    /// Returns the request only if it has been started by the given canister.
+    pub fn get_canister_request(
+        &self,
+        canister_id: CanisterId,
+        request_id: HttpRequestId,
+    ) -> Option<CanisterRequest> {
+        self.requests
+            .get(&request_id)
+            .filter(|r| r.canister_id == canister_id)
+            .cloned()
+    }
+
+    pub fn list_canister_requests(
+        &self,
+        canister_id: CanisterId,
+        after_request_id: Option<HttpRequestId>,
+        limit: usize,
+    ) -> ListRequestsResult {
+        let lower_bound = match after_request_id {
+            Some(request_id) => Bound::Excluded(request_id),
+            None => Bound::Unbounded,
+        };
+
+        let mut request_ids = self
+            .canister_requests
+            .get(&canister_id)
+            .map(|ids| {
+                ids.range((lower_bound, Bound::Unbounded))
+                    .take(limit + 1)
+                    .copied()
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+
+        let next_after_request_id = if request_ids.len() > limit {
+            request_ids.truncate(limit);
+            request_ids.last().copied()
+        } else {
+            None
+        };
+
+        ListRequestsResult {
+            requests: request_ids
+                .into_iter()
+                .filter_map(|id| self.requests.get(&id).map(|r| (id, r.clone())))
+                .collect(),
+            next_after_request_id,
+        }
+    }
+
+    pub fn set_request_http_result(&mut self, request_id: HttpRequestId, http_result: HttpResult) {
+        self.requests
+            .entry(request_id)
+            .and_modify(|r| r.set_http_result(http_result));
+    }
+
+    pub fn set_request_response_attestation(
+        &mut self,
+        request_id: HttpRequestId,
+        response_attestation: ResponseAttestation,
+    ) {
+        self.requests
+            .entry(request_id)
+            .and_modify(|r| r.set_response_attestation(response_attestation));
+    }
+
+    pub fn set_request_response_metadata(
+        &mut self,
+        request_id: HttpRequestId,
+        response_metadata: HttpResponseMetadata,
+    ) {
+        self.requests
+            .entry(request_id)
+            .and_modify(|r| r.set_response_metadata(response_metadata));
+    }
+
+    pub fn set_request_executed(&mut self, request_id: HttpRequestId, now_ms: u64) {
         self.requests
             .entry(request_id)
             .and_modify(|r| r.set_executed());
+        self.complete_request(request_id, now_ms);
     }
 
-    pub fn set_request_failed(&mut self, request_id: HttpRequestId, reason: String) {
+    pub fn set_request_failed(&mut self, request_id: HttpRequestId, reason: String, now_ms: u64) {
         self.requests
             .entry(request_id)
             .and_modify(|r| r.set_failed(reason));
+        self.complete_request(request_id, now_ms);
+    }
+
+    pub fn add_callback_attempt(&mut self, request_id: HttpRequestId, attempt: CallbackAttempt) {
+        match attempt.error {
+            None => self.callbacks_succeeded += 1,
+            Some(_) => self.callbacks_failed += 1,
+        }
+
+        self.requests
+            .entry(request_id)
+            .and_modify(|r| r.add_callback_attempt(attempt));
+    }
+
+    /// Schedules the removal of the executed request after [REQUEST_RETENTION_MS]
+    /// and removes the requests whose retention period is over.
+    fn complete_request(&mut self, request_id: HttpRequestId, now_ms: u64) {
+        self.executed_requests.push_back((now_ms, request_id));
+
+        while let Some((executed_at_ms, _)) = self.executed_requests.front() {
+            if executed_at_ms + REQUEST_RETENTION_MS > now_ms {
+                break;
+            }
+
+            if let Some((_, request_id)) = self.executed_requests.pop_front() {
+                // the request may have been restarted in the meantime
+                let is_executing = self
+                    .requests
+                    .get(&request_id)
+                    .is_some_and(|r| matches!(r.state, RequestState::Executing(_)));
+
+                if !is_executing {
+                    self.remove_request(request_id);
+                }
+            }
+        }
+    }
+
+    fn remove_request(&mut self, request_id: HttpRequestId) {
+        self.request_traces.remove(&request_id);
+
+        let Some(request) = self.requests.remove(&request_id) else {
+            return;
+        };
+
+        if let Some(ids) = self.canister_requests.get_mut(&request.canister_id) {
+            ids.remove(&request_id);
+
+            if ids.is_empty() {
+                self.canister_requests.remove(&request.canister_id);
+            }
+        }
+    }
+
+    pub fn get_cached_response(
+        &mut self,
+        key: &CacheKey,
+        now_ms: u64,
+    ) -> Option<(HttpResponse, Option<HttpResponseMetadata>)> {
+        self.cache.get(key, now_ms)
+    }
+
+    pub fn track_cacheable_request(
+        &mut self,
+        request_id: HttpRequestId,
+        key: CacheKey,
+        ttl_ms: Option<CacheTtlMs>,
+    ) {
+        self.cache.track_request(request_id, key, ttl_ms);
+    }
+
+    pub fn cache_http_result(
+        &mut self,
+        request_id: HttpRequestId,
+        http_result: &HttpResult,
+        metadata: Option<HttpResponseMetadata>,
+        now_ms: u64,
+    ) {
+        self.cache
+            .complete_request(request_id, http_result, metadata, now_ms);
+    }
+
+    pub fn get_cache_stats(&self) -> CacheStats {
+        self.cache.get_stats()
+    }
+
+    /// Returns the amount of succeeded and failed callback delivery attempts.
+    pub fn get_callbacks_counts(&self) -> (u64, u64) {
+        (self.callbacks_succeeded, self.callbacks_failed)
+    }
+
+    pub fn get_idempotent_request(
+        &mut self,
+        canister_id: CanisterId,
+        idempotency_key: &IdempotencyKey,
+        now_ms: u64,
+    ) -> Option<HttpRequestId> {
+        self.idempotency_keys
+            .get(canister_id, idempotency_key, now_ms)
+    }
+
+    pub fn set_idempotent_request(
+        &mut self,
+        canister_id: CanisterId,
+        idempotency_key: IdempotencyKey,
+        request_id: HttpRequestId,
+        now_ms: u64,
+    ) {
+        self.idempotency_keys
+            .insert(canister_id, idempotency_key, request_id, now_ms);
+    }
+
+    pub fn start_in_flight_request(&mut self, key: CacheKey, request_id: HttpRequestId) {
+        self.in_flight_requests.start(key, request_id);
+    }
+
+    pub fn get_in_flight_request(&self, key: &CacheKey) -> Option<HttpRequestId> {
+        self.in_flight_requests.get(key)
+    }
+
+    pub fn follow_in_flight_request(
+        &mut self,
+        leader_id: HttpRequestId,
+        request_id: HttpRequestId,
+    ) {
+        self.in_flight_requests.follow(leader_id, request_id);
+    }
+
+    /// Returns the requests that were waiting for the result of the completed request.
+    pub fn complete_in_flight_request(&mut self, request_id: HttpRequestId) -> Vec<HttpRequestId> {
+        self.in_flight_requests.complete(request_id)
+    }
+
+    pub fn add_dead_letter(
+        &mut self,
+        request_id: HttpRequestId,
+        callback_method_name: CanisterCallbackMethodName,
+        http_result: HttpResult,
+    ) {
+        let Some(request) = self.requests.get(&request_id) else {
+            return;
+        };
+
+        self.dead_letters.insert(DeadLetter {
+            request_id,
+            canister_id: request.canister_id,
+            callback_method_name,
+            http_result,
+            callback_attempts: request.callback_attempts.clone(),
+        });
+    }
+
+    /// Lists the dead letters of the given canister, or of all canisters if [None].
+    pub fn list_dead_letters(
+        &self,
+        canister_id: Option<CanisterId>,
+        after_request_id: Option<HttpRequestId>,
+        limit: usize,
+    ) -> ListDeadLettersResult {
+        self.dead_letters.list(canister_id, after_request_id, limit)
+    }
+
+    /// Removes the dead letters, ignoring the ones that don't belong to the given canister.
+    pub fn delete_canister_dead_letters(
+        &mut self,
+        canister_id: CanisterId,
+        request_ids: Vec<HttpRequestId>,
+    ) {
+        for request_id in request_ids {
+            if self
+                .dead_letters
+                .get(request_id)
+                .is_some_and(|dead_letter| dead_letter.canister_id == canister_id)
+            {
+                self.dead_letters.remove(request_id);
+            }
+        }
+    }
+
+    /// Removes the dead letter and puts its request back in the [RequestState::Executing] state,
+    /// so that its result can be delivered again.
+    pub fn restart_dead_letter(&mut self, request_id: HttpRequestId) -> Option<HttpResult> {
+        let dead_letter = self.dead_letters.remove(request_id)?;
+
+        Some(self.restart_delivery(dead_letter))
+    }
+
+    fn restart_delivery(&mut self, dead_letter: DeadLetter) -> HttpResult {
+        let request_id = dead_letter.request_id;
+
+        // the request may have been removed in the meantime, according to the retention policy
+        let request = self.requests.entry(request_id).or_insert_with(|| {
+            let mut request = CanisterRequest::new(dead_letter.canister_id, None);
+            request.callback_attempts = dead_letter.callback_attempts;
+            request
+        });
+        request.set_executing(Some(dead_letter.callback_method_name));
+        request.set_http_result(dead_letter.http_result.clone());
+
+        self.canister_requests
+            .entry(dead_letter.canister_id)
+            .or_default()
+            .insert(request_id);
+
+        dead_letter.http_result
+    }
+
+    /// Saves the dead letters along with the results of the requests waiting to be delivered again.
+    pub fn dead_letters_snapshot(&self) -> DeadLettersSnapshot {
+        let pending_redeliveries = self
+            .requests
+            .iter()
+            .filter_map(
+                |(request_id, request)| match (&request.state, &request.http_result) {
+                    (RequestState::Executing(Some(callback_method_name)), Some(http_result)) => {
+                        Some(DeadLetter {
+                            request_id: *request_id,
+                            canister_id: request.canister_id,
+                            callback_method_name: callback_method_name.clone(),
+                            http_result: http_result.clone(),
+                            callback_attempts: request.callback_attempts.clone(),
+                        })
+                    }
+                    _ => None,
+                },
+            )
+            .collect();
+
+        self.dead_letters.to_snapshot(pending_redeliveries)
+    }
+
+    /// Restores the dead letters and puts the requests that were waiting to be delivered again
+    /// back in the [RequestState::Executing] state.
+    /// Returns these requests with their results and the number of the next delivery attempt.
+    pub fn restore_dead_letters(
+        &mut self,
+        snapshot: DeadLettersSnapshot,
+    ) -> Vec<(HttpRequestId, HttpResult, u32)> {
+        let (dead_letters, pending_redeliveries) = DeadLetters::from_snapshot(snapshot);
+        self.dead_letters = dead_letters;
+
+        pending_redeliveries
+            .into_iter()
+            .map(|dead_letter| {
+                let request_id = dead_letter.request_id;
+                let attempt = dead_letter.callback_attempts.len() as u32 + 1;
+
+                (request_id, self.restart_delivery(dead_letter), attempt)
+            })
+            .collect()
+    }
+
+    /// Starts collecting the results of the batch's requests for the aggregated callback.
+    /// Returns the batch if all its requests already have a result.
+    pub fn start_batch(
+        &mut self,
+        canister_id: CanisterId,
+        callback_method_name: CanisterCallbackMethodName,
+        request_ids: Vec<HttpRequestId>,
+    ) -> Option<CompletedBatch> {
+        let mut pending = BTreeSet::new();
+        let mut completed = BTreeMap::new();
+
+        for request_id in request_ids {
+            // requests removed according to the retention policy are not waited for
+            let Some(request) = self.requests.get(&request_id) else {
+                continue;
+            };
+
+            match &request.http_result {
+                Some(http_result) => {
+                    completed.insert(request_id, http_result.clone());
+                }
+                None => {
+                    pending.insert(request_id);
+                }
+            }
+        }
+
+        self.batches
+            .start(canister_id, callback_method_name, pending, completed)
+    }
+
+    /// Returns the batches completed by the result of the request.
+    pub fn complete_batch_request(
+        &mut self,
+        request_id: HttpRequestId,
+        http_result: &HttpResult,
+    ) -> Vec<CompletedBatch> {
+        self.batches.complete_request(request_id, http_result)
+    }
+
+    pub fn add_scheduled_job(
+        &mut self,
+        canister_id: CanisterId,
+        args: ScheduleHttpRequestArgs,
+        first_execution_ns: u64,
+    ) -> Result<ScheduledJobId, InvalidRequest> {
+        if self.scheduled_jobs.count_canister_jobs(canister_id) >= MAX_SCHEDULED_JOBS_PER_CANISTER {
+            return Err(InvalidRequest::TooManyScheduledJobs);
+        }
+
+        Ok(self
+            .scheduled_jobs
+            .insert(canister_id, args, first_execution_ns))
+    }
+
+    pub fn get_scheduled_job(&self, job_id: ScheduledJobId) -> Option<ScheduledJob> {
+        self.scheduled_jobs.get(job_id).cloned()
+    }
+
+    pub fn list_canister_scheduled_jobs(&self, canister_id: CanisterId) -> Vec<ScheduledJob> {
+        self.scheduled_jobs.list_canister_jobs(canister_id)
+    }
+
+    pub fn active_scheduled_jobs(&self) -> Vec<(ScheduledJobId, u64)> {
+        self.scheduled_jobs.active_jobs()
+    }
+
+    pub fn set_scheduled_job_timer(&mut self, job_id: ScheduledJobId, timer_id: TimerId) {
+        self.scheduled_jobs.set_timer(job_id, timer_id);
+    }
+
+    /// Returns the time of the next execution of the job, if any.
+    pub fn record_scheduled_job_execution(
+        &mut self,
+        job_id: ScheduledJobId,
+        execution: ScheduledJobExecution,
+    ) -> Option<u64> {
+        self.scheduled_jobs.record_execution(job_id, execution)
+    }
+
+    pub fn pause_scheduled_job(
+        &mut self,
+        canister_id: CanisterId,
+        job_id: ScheduledJobId,
+    ) -> Result<Option<TimerId>, ScheduledJobError> {
+        self.scheduled_jobs.pause(canister_id, job_id)
+    }
+
+    pub fn resume_scheduled_job(
+        &mut self,
+        canister_id: CanisterId,
+        job_id: ScheduledJobId,
+        now_ns: u64,
+    ) -> Result<Option<u64>, ScheduledJobError> {
+        self.scheduled_jobs.resume(canister_id, job_id, now_ns)
+    }
+
+    pub fn cancel_scheduled_job(
+        &mut self,
+        canister_id: CanisterId,
+        job_id: ScheduledJobId,
+    ) -> Result<Option<TimerId>, ScheduledJobError> {
+        self.scheduled_jobs.cancel(canister_id, job_id)
+    }
+
+    pub fn scheduled_jobs_snapshot(&self) -> ScheduledJobsSnapshot {
+        self.scheduled_jobs.to_snapshot()
+    }
+
+    pub fn restore_scheduled_jobs(&mut self, snapshot: ScheduledJobsSnapshot) {
+        self.scheduled_jobs = ScheduledJobs::from_snapshot(snapshot);
+    }
+
+    pub fn register_webhook(
+        &mut self,
+        canister_id: CanisterId,
+        webhook: Webhook,
+    ) -> Result<(), InvalidRequest> {
+        self.webhooks.register(canister_id, webhook)
+    }
+
+    pub fn unregister_webhook(&mut self, canister_id: CanisterId, path: &str) {
+        self.webhooks.unregister(canister_id, path);
+    }
+
+    pub fn get_webhook_callback(
+        &self,
+        canister_id: CanisterId,
+        path: &str,
+    ) -> Option<CanisterCallbackMethodName> {
+        self.webhooks.get(canister_id, path)
+    }
+
+    pub fn list_canister_webhooks(&self, canister_id: CanisterId) -> Vec<Webhook> {
+        self.webhooks.list(canister_id)
+    }
+
+    pub fn webhooks_snapshot(&self) -> WebhooksSnapshot {
+        self.webhooks.to_snapshot()
+    }
+
+    pub fn restore_webhooks(&mut self, snapshot: WebhooksSnapshot) {
+        self.webhooks = Webhooks::from_snapshot(snapshot);
+    }
+
+    pub fn register_signing_profile(
+        &mut self,
+        canister_id: CanisterId,
+        args: RegisterSigningProfileArgs,
+        now_ns: u64,
+    ) -> Result<(), InvalidRequest> {
+        self.signing_profiles.register(canister_id, args, now_ns)
+    }
+
+    pub fn unregister_signing_profile(&mut self, canister_id: CanisterId, name: &str) {
+        self.signing_profiles.unregister(canister_id, name);
+    }
+
+    pub fn list_canister_signing_profiles(
+        &self,
+        canister_id: CanisterId,
+    ) -> Vec<SigningProfileInfo> {
+        self.signing_profiles.list(canister_id)
+    }
+
+    pub fn sign_request(
+        &self,
+        canister_id: CanisterId,
+        signing_profile: &str,
+        request: &mut HttpRequest,
+        now_ns: u64,
+    ) -> Result<(), InvalidRequest> {
+        self.signing_profiles
+            .sign(canister_id, signing_profile, request, now_ns)
+    }
+
+    pub fn track_signed_request(&mut self, request_id: HttpRequestId, url: &str) {
+        if let Ok(url) = Url::parse(url) {
+            self.signed_request_origins
+                .insert(request_id, url.origin().ascii_serialization());
+        }
+    }
+
+    pub fn complete_signed_request(&mut self, request_id: HttpRequestId) {
+        self.signed_request_origins.remove(&request_id);
+    }
+
+    /// Fails the redirects of the signed requests to a different origin,
+    /// which could replay the signature headers.
+    pub fn validate_signed_request_redirect(
+        &self,
+        request_id: HttpRequestId,
+        request: &HttpRequest,
+    ) -> Result<(), String> {
+        let Some(origin) = self.signed_request_origins.get(&request_id) else {
+            return Ok(());
+        };
+
+        let url = Url::parse(&request.url).map_err(|e| e.to_string())?;
+        if url.origin().ascii_serialization() != *origin {
+            return Err("signed requests can't be redirected to a different origin".to_string());
+        }
+
+        Ok(())
+    }
+
+    pub fn signing_profiles_snapshot(&self) -> SigningProfilesSnapshot {
+        self.signing_profiles.to_snapshot()
+    }
+
+    pub fn restore_signing_profiles(&mut self, snapshot: SigningProfilesSnapshot) {
+        self.signing_profiles = SigningProfiles::from_snapshot(snapshot);
+    }
+
+    pub fn set_allowed_secrets(
+        &mut self,
+        canister_id: CanisterId,
+        secret_names: BTreeSet<SecretName>,
+    ) {
+        if secret_names.is_empty() {
+            self.allowed_secrets.remove(&canister_id);
+        } else {
+            self.allowed_secrets.insert(canister_id, secret_names);
+        }
+    }
+
+    pub fn get_allowed_secrets(&self, canister_id: CanisterId) -> BTreeSet<SecretName> {
+        self.allowed_secrets
+            .get(&canister_id)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Returns the first secret that the canister is not allowed to reference, if any.
+    pub fn find_disallowed_secret<'a>(
+        &self,
+        canister_id: CanisterId,
+        secret_names: &'a BTreeSet<SecretName>,
+    ) -> Option<&'a SecretName> {
+        let allowed_secrets = self.allowed_secrets.get(&canister_id);
+
+        secret_names.iter().find(|secret_name| {
+            !allowed_secrets.is_some_and(|allowed_secrets| allowed_secrets.contains(*secret_name))
+        })
+    }
+
+    pub fn allowed_secrets_snapshot(&self) -> BTreeMap<CanisterId, BTreeSet<SecretName>> {
+        self.allowed_secrets.clone()
+    }
+
+    pub fn restore_allowed_secrets(
+        &mut self,
+        snapshot: BTreeMap<CanisterId, BTreeSet<SecretName>>,
+    ) {
+        self.allowed_secrets = snapshot;
     }
 }
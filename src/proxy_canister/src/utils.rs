@@ -1,5 +1,19 @@
-use candid::Principal;
-use ic_cdk::{api::is_controller, trap};
+use candid::{
+    decode_args, encode_args,
+    utils::{ArgumentDecoder, ArgumentEncoder},
+    Principal,
+};
+use ic_cdk::{
+    api::{
+        is_controller,
+        stable::{CanisterStableMemory, StableReader, StableWriter},
+        time,
+    },
+    trap,
+};
+use logger::STABLE_MEMORY_LOGS_REGION_BYTES;
+
+use crate::constants::STABLE_MEMORY_LOGS_OFFSET;
 
 pub fn guard_caller_is_controller(caller: &Principal) {
     if !is_controller(caller) {
@@ -12,3 +26,80 @@ pub fn guard_caller_is_not_anonymous(caller: &Principal) {
         trap("Caller is anonymous");
     }
 }
+
+/// The current IC time in milliseconds.
+pub fn time_ms() -> u64 {
+    time() / 1_000_000
+}
+
+/// Days since the UNIX epoch of the given proleptic Gregorian date.
+///
+/// See http://howardhinnant.github.io/date_algorithms.html#days_from_civil.
+pub fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// The proleptic Gregorian date (year, month, day) of the given days since the UNIX epoch.
+///
+/// See http://howardhinnant.github.io/date_algorithms.html#civil_from_days.
+pub fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    (year, month, day)
+}
+
+/// Saves the arguments in the stable memory after the region reserved to the logs,
+/// prefixed by their length.
+pub fn stable_save_after_logs<T: ArgumentEncoder>(args: T) -> Result<(), String> {
+    let bytes = encode_args(args).map_err(|e| e.to_string())?;
+
+    let mut writer = StableWriter::with_memory(
+        CanisterStableMemory::default(),
+        (STABLE_MEMORY_LOGS_OFFSET + STABLE_MEMORY_LOGS_REGION_BYTES) as usize,
+    );
+    writer
+        .write(&(bytes.len() as u64).to_le_bytes())
+        .and_then(|_| writer.write(&bytes))
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+/// Restores the arguments saved with [stable_save_after_logs].
+pub fn stable_restore_after_logs<T>() -> Result<T, String>
+where
+    T: for<'de> ArgumentDecoder<'de>,
+{
+    let mut reader = StableReader::with_memory(
+        CanisterStableMemory::default(),
+        (STABLE_MEMORY_LOGS_OFFSET + STABLE_MEMORY_LOGS_REGION_BYTES) as usize,
+    );
+
+    let mut length = [0; 8];
+    read_exact(&mut reader, &mut length)?;
+    let mut bytes = vec![0; u64::from_le_bytes(length) as usize];
+    read_exact(&mut reader, &mut bytes)?;
+
+    decode_args(&bytes).map_err(|e| e.to_string())
+}
+
+fn read_exact(reader: &mut StableReader, buf: &mut [u8]) -> Result<(), String> {
+    match reader.read(buf) {
+        Ok(read) if read == buf.len() => Ok(()),
+        Ok(_) => Err("unexpected end of stable memory".to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
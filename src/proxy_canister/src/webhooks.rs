@@ -0,0 +1,116 @@
+use std::collections::{BTreeMap, HashMap};
+
+use candid::{Nat, Principal};
+use http_over_ws::HttpResponse;
+use proxy_canister_types::{
+    CanisterCallbackMethodName, CanisterId, InvalidRequest, Webhook, WebhookPath,
+};
+use url::Url;
+
+use crate::constants::{MAX_WEBHOOKS_PER_CANISTER, WEBHOOKS_PATH_PREFIX};
+
+/// The registered webhooks, saved in the stable memory across upgrades.
+pub type WebhooksSnapshot = Vec<(CanisterId, Webhook)>;
+
+/// The webhooks registered by each canister.
+pub struct Webhooks(HashMap<CanisterId, BTreeMap<WebhookPath, CanisterCallbackMethodName>>);
+
+impl Webhooks {
+    pub fn new() -> Self {
+        Self(HashMap::new())
+    }
+
+    pub fn from_snapshot(snapshot: WebhooksSnapshot) -> Self {
+        let mut webhooks = Self::new();
+        for (canister_id, webhook) in snapshot {
+            webhooks
+                .0
+                .entry(canister_id)
+                .or_default()
+                .insert(webhook.path, webhook.callback_method_name);
+        }
+        webhooks
+    }
+
+    pub fn to_snapshot(&self) -> WebhooksSnapshot {
+        self.0
+            .keys()
+            .flat_map(|canister_id| {
+                self.list(*canister_id)
+                    .into_iter()
+                    .map(move |webhook| (*canister_id, webhook))
+            })
+            .collect()
+    }
+
+    pub fn register(
+        &mut self,
+        canister_id: CanisterId,
+        webhook: Webhook,
+    ) -> Result<(), InvalidRequest> {
+        let webhooks = self.0.entry(canister_id).or_default();
+
+        if !webhooks.contains_key(&webhook.path) && webhooks.len() >= MAX_WEBHOOKS_PER_CANISTER {
+            return Err(InvalidRequest::TooManyWebhooks);
+        }
+
+        webhooks.insert(webhook.path, webhook.callback_method_name);
+
+        Ok(())
+    }
+
+    pub fn unregister(&mut self, canister_id: CanisterId, path: &str) {
+        if let Some(webhooks) = self.0.get_mut(&canister_id) {
+            webhooks.remove(path);
+
+            if webhooks.is_empty() {
+                self.0.remove(&canister_id);
+            }
+        }
+    }
+
+    pub fn get(&self, canister_id: CanisterId, path: &str) -> Option<CanisterCallbackMethodName> {
+        self.0
+            .get(&canister_id)
+            .and_then(|webhooks| webhooks.get(path))
+            .cloned()
+    }
+
+    pub fn list(&self, canister_id: CanisterId) -> Vec<Webhook> {
+        self.0
+            .get(&canister_id)
+            .map(|webhooks| {
+                webhooks
+                    .iter()
+                    .map(|(path, callback_method_name)| Webhook {
+                        path: path.clone(),
+                        callback_method_name: callback_method_name.clone(),
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+/// Extracts the canister id and the registered path from the URL of a webhook request,
+/// which has the `/webhooks/<canister id><path>` path and may be either absolute or just a path.
+pub fn parse_webhook_url(url: &str) -> Option<(CanisterId, WebhookPath)> {
+    let base_url = Url::parse("http://localhost").ok()?;
+    let url = Url::options().base_url(Some(&base_url)).parse(url).ok()?;
+
+    let rest = url.path().strip_prefix(WEBHOOKS_PATH_PREFIX)?;
+    let (canister_id, path) = match rest.find('/') {
+        Some(i) => rest.split_at(i),
+        None => (rest, "/"),
+    };
+
+    Some((Principal::from_text(canister_id).ok()?, path.to_string()))
+}
+
+pub fn text_response(status: u16, text: &str) -> HttpResponse {
+    HttpResponse {
+        status: Nat::from(status),
+        headers: vec![],
+        body: text.as_bytes().to_vec(),
+    }
+}
@@ -9,7 +9,7 @@ use ic_websocket_cdk::{
 
 pub use ic_websocket_cdk::close;
 pub use ic_websocket_cdk::send;
-use logger::log;
+use logger::{debug, log};
 
 pub fn init_ws() {
     let params = WsInitParams::new(WsHandlers {
@@ -27,12 +27,15 @@ pub fn on_open(args: OnOpenCallbackArgs) {
 
 pub fn on_message(args: OnMessageCallbackArgs) {
     if let Err(HttpOverWsError::NotHttpOverWsType(_)) =
-        http_over_ws::try_handle_http_over_ws_message(args.client_principal, args.message.clone())
+        http_over_ws::try_handle_http_over_ws_message(
+            args.client_principal,
+            args.message.clone(),
+            ic_websocket_cdk::send,
+        )
     {
-        log!(
+        debug!(
             "[ws]: Received WS client message: {:?} from {}",
-            args.message,
-            args.client_principal
+            args.message, args.client_principal
         );
     }
 }
@@ -41,7 +44,7 @@ pub fn on_close(args: OnCloseCallbackArgs) {
     if let Err(_) = http_over_ws::try_disconnect_http_proxy(args.client_principal) {
         log!("[ws]: WS client {} disconnected", args.client_principal);
     } else {
-        log!("[ws]: Proxy client {} disconnected", args.client_principal);
+        log!({ proxy_principal: args.client_principal }, "[ws]: Proxy client {} disconnected", args.client_principal);
     }
 }
 
@@ -6,16 +6,26 @@ use std::{
 };
 
 use candid::{encode_args, Nat, Principal};
+use hmac::{Hmac, Mac};
 use http_over_ws::{
-    HttpFailureReason, HttpHeader, HttpMethod, HttpOverWsError, HttpOverWsMessage, HttpRequest,
-    HttpResponse, HttpResult,
+    secret_placeholder, HttpFailureReason, HttpHeader, HttpMethod, HttpOverWsError,
+    HttpOverWsMessage, HttpRedirect, HttpRequest, HttpResponse, HttpResponseEnvelope,
+    HttpResponseMetadata, HttpResponseTiming, HttpResult, ProxyCapabilities, ProxyClientSetup,
+    ProxyError, ProxyResponseStats, RedirectPolicy, ResponseSignature, HTTP_OVER_WS_PROTOCOL_V2,
+    MAX_FOLLOWED_REDIRECTS, UNHEALTHY_PROXY_CONSECUTIVE_FAILURES,
 };
 use lazy_static::lazy_static;
 use pocket_ic::{ErrorCode, UserError};
 use proxy_canister_types::{
-    HttpRequestEndpointArgs, HttpRequestEndpointResult, InvalidRequest, ProxyCanisterError,
-    RequestState,
+    ExportLogsArgs, GetLogsArgs, HttpRequestCacheOptions, HttpRequestEndpointArgs,
+    HttpRequestEndpointResult, HttpRequestSchedule, InvalidProxyConfig, InvalidRequest,
+    ListRequestsArgs, LogLevel, LoggerConfig, LogsCursor, ProxyCanisterError, ProxyConfig,
+    ProxyHealth, RegisterSigningProfileArgs, RequestState, RequestTraceEventKind,
+    ResponseAttestationConfig, ScheduleHttpRequestArgs, ScheduledJobError, ScheduledJobState,
+    SigningProfileInfo, SigningScheme, Webhook,
 };
+use ring::signature::{Ed25519KeyPair, KeyPair};
+use sha2::Sha256;
 use test_utils::{
     ic_env::{get_test_env, load_canister_wasm_from_path, CanisterData},
     identity::generate_random_principal,
@@ -105,9 +115,14 @@ fn test_proxy_canister_http_request_anonymous() {
                 method: HttpMethod::GET,
                 headers: vec![],
                 body: None,
+                redirect: None,
             },
             timeout_ms: None,
             callback_method_name: None,
+            cache: None,
+            idempotency_key: None,
+            coalesce: None,
+            signing_profile: None,
         },
     );
 
@@ -140,9 +155,14 @@ fn test_http_request_invalid() {
             method: HttpMethod::GET,
             headers: vec![],
             body: None,
+            redirect: None,
         },
         timeout_ms: None,
         callback_method_name: None,
+        cache: None,
+        idempotency_key: None,
+        coalesce: None,
+        signing_profile: None,
     });
     assert_eq!(
         res,
@@ -152,6 +172,56 @@ fn test_http_request_invalid() {
     );
     proxy_client.expect_received_http_requests_count(0);
 
+    // invalid custom methods
+    for method in ["", "BAD METHOD", "GET"] {
+        let res = test_canister_actor.call_http_request_via_proxy(HttpRequestEndpointArgs {
+            request: HttpRequest {
+                url: TEST_URL.to_string(),
+                method: HttpMethod::Other(method.to_string()),
+                headers: vec![],
+                body: None,
+                redirect: None,
+            },
+            timeout_ms: None,
+            callback_method_name: None,
+            cache: None,
+            idempotency_key: None,
+            coalesce: None,
+            signing_profile: None,
+        });
+        assert_eq!(
+            res,
+            Err(ProxyCanisterError::InvalidRequest(
+                InvalidRequest::InvalidHttpMethod
+            )),
+        );
+    }
+    proxy_client.expect_received_http_requests_count(0);
+
+    // method not supported by the connected proxy
+    let res = test_canister_actor.call_http_request_via_proxy(HttpRequestEndpointArgs {
+        request: HttpRequest {
+            url: TEST_URL.to_string(),
+            method: HttpMethod::PATCH,
+            headers: vec![],
+            body: None,
+            redirect: None,
+        },
+        timeout_ms: None,
+        callback_method_name: None,
+        cache: None,
+        idempotency_key: None,
+        coalesce: None,
+        signing_profile: None,
+    });
+    assert_eq!(
+        res,
+        Err(ProxyCanisterError::HttpOverWs(
+            HttpOverWsError::UnsupportedHttpMethod(HttpMethod::PATCH)
+        )),
+    );
+    proxy_client.expect_received_http_requests_count(0);
+
     // too many headers
     let res = test_canister_actor.call_http_request_via_proxy(HttpRequestEndpointArgs {
         request: HttpRequest {
@@ -165,9 +235,14 @@ fn test_http_request_invalid() {
                 })
                 .collect(),
             body: None,
+            redirect: None,
         },
         timeout_ms: None,
         callback_method_name: None,
+        cache: None,
+        idempotency_key: None,
+        coalesce: None,
+        signing_profile: None,
     });
     assert_eq!(
         res,
@@ -184,9 +259,14 @@ fn test_http_request_invalid() {
             method: HttpMethod::GET,
             headers: vec![],
             body: None,
+            redirect: None,
         },
         timeout_ms: Some(0), // less than the min
         callback_method_name: None,
+        cache: None,
+        idempotency_key: None,
+        coalesce: None,
+        signing_profile: None,
     });
     assert_eq!(
         res,
@@ -202,9 +282,14 @@ fn test_http_request_invalid() {
             method: HttpMethod::GET,
             headers: vec![],
             body: None,
+            redirect: None,
         },
         timeout_ms: Some(70_000), // more than the max
         callback_method_name: None,
+        cache: None,
+        idempotency_key: None,
+        coalesce: None,
+        signing_profile: None,
     });
     assert_eq!(
         res,
@@ -213,6 +298,91 @@ fn test_http_request_invalid() {
         )),
     );
     proxy_client.expect_received_http_requests_count(0);
+
+    // cache options on a non-GET request
+    let res = test_canister_actor.call_http_request_via_proxy(HttpRequestEndpointArgs {
+        request: HttpRequest {
+            url: TEST_URL.to_string(),
+            method: HttpMethod::POST,
+            headers: vec![],
+            body: None,
+            redirect: None,
+        },
+        timeout_ms: None,
+        callback_method_name: None,
+        cache: Some(HttpRequestCacheOptions {
+            ttl_ms: Some(10_000),
+            key_headers: vec![],
+        }),
+        idempotency_key: None,
+        coalesce: None,
+        signing_profile: None,
+    });
+    assert_eq!(
+        res,
+        Err(ProxyCanisterError::InvalidRequest(
+            InvalidRequest::NotCacheable
+        )),
+    );
+    proxy_client.expect_received_http_requests_count(0);
+
+    // invalid cache ttl
+    let res = test_canister_actor.call_http_request_via_proxy(HttpRequestEndpointArgs {
+        request: HttpRequest {
+            url: TEST_URL.to_string(),
+            method: HttpMethod::GET,
+            headers: vec![],
+            body: None,
+            redirect: None,
+        },
+        timeout_ms: None,
+        callback_method_name: None,
+        cache: Some(HttpRequestCacheOptions {
+            ttl_ms: Some(0),
+            key_headers: vec![],
+        }),
+        idempotency_key: None,
+        coalesce: None,
+        signing_profile: None,
+    });
+    assert_eq!(
+        res,
+        Err(ProxyCanisterError::InvalidRequest(
+            InvalidRequest::InvalidCacheTtl
+        )),
+    );
+    proxy_client.expect_received_http_requests_count(0);
+
+    // empty idempotency key
+    let res = test_canister_actor.call_http_request_via_proxy(HttpRequestEndpointArgs {
+        request: HttpRequest {
+            url: TEST_URL.to_string(),
+            method: HttpMethod::GET,
+            headers: vec![],
+            body: None,
+            redirect: None,
+        },
+        timeout_ms: None,
+        callback_method_name: None,
+        cache: None,
+        idempotency_key: Some(String::new()),
+        coalesce: None,
+        signing_profile: None,
+    });
+    assert_eq!(
+        res,
+        Err(ProxyCanisterError::InvalidRequest(
+            InvalidRequest::InvalidIdempotencyKey
+        )),
+    );
+    proxy_client.expect_received_http_requests_count(0);
+}
+
+/// Advances the time until all the callback delivery attempts have been made.
+fn wait_for_callback_retries() {
+    let test_env = get_test_env();
+    test_env.advance_canister_time_ms(2_000);
+    test_env.advance_canister_time_ms(4_000);
 }
 
 fn test_wrong_callback(
@@ -232,9 +402,14 @@ fn test_wrong_callback(
                 method: HttpMethod::GET,
                 headers: vec![],
                 body: None,
+                redirect: None,
             },
             timeout_ms: None,
             callback_method_name: Some(callback_name.to_string()),
+            cache: None,
+            idempotency_key: None,
+            coalesce: None,
+            signing_profile: None,
         })
         .unwrap();
 
@@ -248,6 +423,7 @@ fn test_wrong_callback(
             body: vec![1, 2, 3],
         },
     ));
+    wait_for_callback_retries();
 
     let req_state1 = proxy_canister_actor
         .query_get_request_by_id_with_panic(get_proxy_canister_controller(), request_id1)
@@ -262,12 +438,17 @@ fn test_wrong_callback(
         method: HttpMethod::GET,
         headers: vec![],
         body: None,
+        redirect: None,
     };
     let request_id2 = test_canister_actor
         .call_http_request_via_proxy(HttpRequestEndpointArgs {
             request: req_2.clone(),
             timeout_ms: None,
             callback_method_name: Some(callback_name.to_string()),
+            cache: None,
+            idempotency_key: None,
+            coalesce: None,
+            signing_profile: None,
         })
         .unwrap();
 
@@ -286,6 +467,7 @@ fn test_wrong_callback(
             body: vec![1, 2, 3],
         },
     ));
+    wait_for_callback_retries();
 
     let req_state2 = proxy_canister_actor
         .query_get_request_by_id_with_panic(get_proxy_canister_controller(), request_id2)
@@ -344,9 +526,14 @@ fn test_http_request() {
                 method: HttpMethod::GET,
                 headers: vec![],
                 body: None,
+                redirect: None,
             },
             timeout_ms: None,
             callback_method_name: None,
+            cache: None,
+            idempotency_key: None,
+            coalesce: None,
+            signing_profile: None,
         })
         .unwrap();
 
@@ -393,9 +580,14 @@ fn test_http_request_with_timeout() {
                 method: HttpMethod::GET,
                 headers: vec![],
                 body: None,
+                redirect: None,
             },
             timeout_ms: Some(timeout_ms),
             callback_method_name: Some("http_response_callback".to_string()),
+            cache: None,
+            idempotency_key: None,
+            coalesce: None,
+            signing_profile: None,
         })
         .unwrap();
     proxy_client.expect_received_http_requests_count(1);
@@ -436,9 +628,14 @@ fn test_http_request_proxy_error() {
                 method: HttpMethod::GET,
                 headers: vec![],
                 body: None,
+                redirect: None,
             },
             timeout_ms: None,
             callback_method_name: Some("http_response_callback".to_string()),
+            cache: None,
+            idempotency_key: None,
+            coalesce: None,
+            signing_profile: None,
         })
         .unwrap();
     proxy_client.expect_received_http_requests_count(1);
@@ -448,6 +645,7 @@ fn test_http_request_proxy_error() {
     proxy_client.send_http_over_ws_message(HttpOverWsMessage::Error(
         Some(request_id),
         proxy_error.clone(),
+        None,
     ));
 
     let req_state = proxy_canister_actor
@@ -460,7 +658,9 @@ fn test_http_request_proxy_error() {
     let cb_responses = test_canister_actor.query_get_callback_results();
     assert_eq!(
         cb_responses.get(&request_id).unwrap(),
-        &HttpResult::Failure(HttpFailureReason::ProxyError(proxy_error))
+        &HttpResult::Failure(HttpFailureReason::ProxyError(ProxyError::Unknown(Some(
+            proxy_error
+        ))))
     );
 }
 
@@ -483,9 +683,14 @@ fn test_http_request_with_callback() {
                 method: HttpMethod::GET,
                 headers: vec![],
                 body: None,
+                redirect: None,
             },
             timeout_ms: None,
             callback_method_name: Some("http_response_callback".to_string()),
+            cache: None,
+            idempotency_key: None,
+            coalesce: None,
+            signing_profile: None,
         })
         .unwrap();
 
@@ -517,129 +722,3087 @@ fn test_http_request_with_callback() {
 }
 
 #[test]
-fn test_get_request_by_id_unauthorized() {
+fn test_http_request_with_metadata_callback() {
     setup();
     reset_canisters();
     let test_env = get_test_env();
-    let proxy_canister_id = get_proxy_canister_id();
-    let proxy_canister_actor = ProxyCanisterActor::new(&test_env, proxy_canister_id);
+    let mut proxy_client = ProxyClient::new(&test_env, get_proxy_canister_id());
+    let test_canister_id = get_test_user_canister_id();
+    let test_canister_actor = TestUserCanisterActor::new(&test_env, test_canister_id);
+    let proxy_canister_actor = ProxyCanisterActor::new(&test_env, get_proxy_canister_id());
 
-    let res = proxy_canister_actor.query_get_request_by_id(generate_random_principal(), 1);
+    proxy_client.setup_proxy();
 
-    assert_eq!(
-        res,
-        Err(UserError {
-            code: ErrorCode::CanisterCalledTrap,
-            description: format!(
-                "Canister {} trapped explicitly: Caller is not a controller",
-                proxy_canister_id
-            ),
+    let request_id = test_canister_actor
+        .call_http_request_via_proxy(HttpRequestEndpointArgs {
+            callback_method_name: Some("http_response_callback_with_metadata".to_string()),
+            ..http_request_args()
         })
-    )
-}
+        .unwrap();
+    proxy_client.expect_received_http_requests_count(1);
 
-#[test]
-fn test_get_logs_unauthorized() {
-    setup();
-    reset_canisters();
-    let test_env = get_test_env();
-    let proxy_canister_id = get_proxy_canister_id();
-    let proxy_canister_actor = ProxyCanisterActor::new(&test_env, proxy_canister_id);
+    let response = HttpResponse {
+        status: Nat::from(200),
+        headers: vec![],
+        body: vec![1, 2, 3],
+    };
+    let timing = HttpResponseTiming {
+        dns_lookup_ms: None,
+        connect_ms: Some(15),
+        time_to_first_byte_ms: Some(80),
+        total_ms: 120,
+    };
+    proxy_client.send_http_over_ws_message(HttpOverWsMessage::HttpResponseStats(
+        request_id,
+        ProxyResponseStats {
+            timing: timing.clone(),
+            body_size_bytes: 2048,
+        },
+    ));
+    proxy_client.send_http_over_ws_message(HttpOverWsMessage::HttpResponse(
+        request_id,
+        response.clone(),
+    ));
 
-    let res = proxy_canister_actor.query_get_logs(generate_random_principal());
+    let metadata = HttpResponseMetadata {
+        proxy_principal: proxy_client.client_principal(),
+        final_url: TEST_URL.to_string(),
+        redirects: vec![],
+        timing: Some(timing),
+        body_size_bytes: Some(2048),
+        proxy_attempts: 1,
+    };
+    assert_eq!(
+        test_canister_actor
+            .query_get_callback_results()
+            .get(&request_id),
+        Some(&HttpResult::Success(response.clone()))
+    );
+    assert_eq!(
+        test_canister_actor
+            .query_get_callback_metadata()
+            .get(&request_id),
+        Some(&Some(metadata.clone()))
+    );
 
+    // the same envelope can be pulled
     assert_eq!(
-        res,
-        Err(UserError {
-            code: ErrorCode::CanisterCalledTrap,
-            description: format!(
-                "Canister {} trapped explicitly: Caller is not a controller",
-                proxy_canister_id
-            ),
-        })
-    )
+        proxy_canister_actor.query_get_my_http_response(test_canister_id, request_id),
+        Some(HttpResponseEnvelope { response, metadata })
+    );
+    assert_eq!(
+        proxy_canister_actor.query_get_my_http_response(generate_random_principal(), request_id),
+        None
+    );
 }
 
 #[test]
-fn test_get_logs() {
+fn test_http_request_cached() {
     setup();
     reset_canisters();
     let test_env = get_test_env();
-    let proxy_canister_id = get_proxy_canister_id();
+    let mut proxy_client = ProxyClient::new(&test_env, get_proxy_canister_id());
     let test_canister_actor = TestUserCanisterActor::new(&test_env, get_test_user_canister_id());
-    let proxy_canister_actor = ProxyCanisterActor::new(&test_env, proxy_canister_id);
+    let proxy_canister_actor = ProxyCanisterActor::new(&test_env, get_proxy_canister_id());
 
-    // execute an http request before to have logs
-    let _ = test_canister_actor.call_http_request_via_proxy(HttpRequestEndpointArgs {
+    proxy_client.setup_proxy();
+
+    let args = HttpRequestEndpointArgs {
         request: HttpRequest {
             url: TEST_URL.to_string(),
             method: HttpMethod::GET,
             headers: vec![],
             body: None,
+            redirect: None,
         },
         timeout_ms: None,
-        callback_method_name: None,
-    });
+        callback_method_name: Some("http_response_callback".to_string()),
+        cache: Some(HttpRequestCacheOptions {
+            ttl_ms: Some(60_000),
+            key_headers: vec![],
+        }),
+        idempotency_key: None,
+        coalesce: None,
+        signing_profile: None,
+    };
+
+    let request_id1 = test_canister_actor
+        .call_http_request_via_proxy(args.clone())
+        .unwrap();
+    proxy_client.expect_received_http_requests_count(1);
+
+    let response = HttpResponse {
+        status: Nat::from(200),
+        headers: vec![],
+        body: vec![1, 2, 3],
+    };
+    proxy_client.send_http_over_ws_message(HttpOverWsMessage::HttpResponse(
+        request_id1,
+        response.clone(),
+    ));
+
+    // the same request is served from the cache, without contacting the proxy
+    let request_id2 = test_canister_actor
+        .call_http_request_via_proxy(args)
+        .unwrap();
+    assert_ne!(request_id1, request_id2);
+    proxy_client.expect_received_http_requests_count(0);
+
+    let req_state = proxy_canister_actor
+        .query_get_request_by_id_with_panic(get_proxy_canister_controller(), request_id2)
+        .unwrap();
+    assert!(matches!(req_state.state, RequestState::Executed));
+
+    let cb_responses = test_canister_actor.query_get_callback_results();
+    assert_eq!(
+        cb_responses.get(&request_id1).unwrap(),
+        &HttpResult::Success(response.clone())
+    );
+    assert_eq!(
+        cb_responses.get(&request_id2).unwrap(),
+        &HttpResult::Success(response.clone())
+    );
+
+    // the cached response comes with the metadata of the request that got it
+    let metadata = HttpResponseMetadata {
+        proxy_principal: proxy_client.client_principal(),
+        final_url: TEST_URL.to_string(),
+        redirects: vec![],
+        timing: None,
+        body_size_bytes: None,
+        proxy_attempts: 1,
+    };
+    assert_eq!(
+        proxy_canister_actor.query_get_my_http_response(get_test_user_canister_id(), request_id2),
+        Some(HttpResponseEnvelope { response, metadata })
+    );
 
-    let res = proxy_canister_actor.query_get_logs(get_proxy_canister_controller());
-    assert!(res.unwrap().len() > 0);
+    let stats = proxy_canister_actor
+        .query_get_cache_stats(get_proxy_canister_controller())
+        .unwrap();
+    assert_eq!(stats.hits, 1);
+    assert_eq!(stats.misses, 1);
+    assert_eq!(stats.entries, 1);
 }
 
 #[test]
-fn test_disconnect_all_proxies_unauthorized() {
+fn test_http_request_cached_per_canister() {
     setup();
     reset_canisters();
     let test_env = get_test_env();
-    let proxy_canister_id = get_proxy_canister_id();
-    let proxy_canister_actor = ProxyCanisterActor::new(&test_env, proxy_canister_id);
+    let mut proxy_client = ProxyClient::new(&test_env, get_proxy_canister_id());
+    let test_canister_actor = TestUserCanisterActor::new(&test_env, get_test_user_canister_id());
+    let proxy_canister_actor = ProxyCanisterActor::new(&test_env, get_proxy_canister_id());
 
-    let res = proxy_canister_actor.call_disconnect_all_proxies(generate_random_principal());
+    proxy_client.setup_proxy();
 
-    assert_eq!(
-        res,
-        Err(UserError {
-            code: ErrorCode::CanisterCalledTrap,
-            description: format!(
-                "Canister {} trapped explicitly: Caller is not a controller",
-                proxy_canister_id
-            ),
-        })
-    )
+    let args = HttpRequestEndpointArgs {
+        request: HttpRequest {
+            url: TEST_URL.to_string(),
+            method: HttpMethod::GET,
+            headers: vec![HttpHeader {
+                name: "Authorization".to_string(),
+                value: "Bearer token".to_string(),
+            }],
+            body: None,
+            redirect: None,
+        },
+        timeout_ms: None,
+        callback_method_name: None,
+        cache: Some(HttpRequestCacheOptions {
+            ttl_ms: Some(60_000),
+            key_headers: vec![],
+        }),
+        idempotency_key: None,
+        coalesce: None,
+        signing_profile: None,
+    };
+
+    let request_id = test_canister_actor
+        .call_http_request_via_proxy(args.clone())
+        .unwrap();
+    proxy_client.expect_received_http_requests_count(1);
+    proxy_client.send_http_over_ws_message(HttpOverWsMessage::HttpResponse(
+        request_id,
+        HttpResponse {
+            status: Nat::from(200),
+            headers: vec![],
+            body: vec![1, 2, 3],
+        },
+    ));
+
+    // another canister doesn't get the response obtained with the credentials of the first one
+    let other_args = HttpRequestEndpointArgs {
+        request: HttpRequest {
+            headers: vec![],
+            ..args.request.clone()
+        },
+        ..args.clone()
+    };
+    proxy_canister_actor
+        .call_http_request(generate_random_principal(), other_args)
+        .unwrap()
+        .unwrap();
+    proxy_client.expect_received_http_requests_count(1);
+
+    // while the canister that requested it does
+    test_canister_actor
+        .call_http_request_via_proxy(args)
+        .unwrap();
+    proxy_client.expect_received_http_requests_count(0);
+
+    let stats = proxy_canister_actor
+        .query_get_cache_stats(get_proxy_canister_controller())
+        .unwrap();
+    assert_eq!(stats.hits, 1);
+    assert_eq!(stats.misses, 2);
 }
 
 #[test]
-fn test_disconnect_all_proxies() {
+fn test_http_request_cache_expired() {
     setup();
     reset_canisters();
     let test_env = get_test_env();
-    let proxy_canister_id = get_proxy_canister_id();
-    let proxy_canister_actor = ProxyCanisterActor::new(&test_env, proxy_canister_id);
+    let mut proxy_client = ProxyClient::new(&test_env, get_proxy_canister_id());
     let test_canister_actor = TestUserCanisterActor::new(&test_env, get_test_user_canister_id());
-    let mut proxy_client1 = ProxyClient::new(&test_env, proxy_canister_id);
-    let mut proxy_client2 = ProxyClient::new(&test_env, proxy_canister_id);
-
-    proxy_client1.setup_proxy();
-    proxy_client2.setup_proxy();
 
-    proxy_canister_actor
-        .call_disconnect_all_proxies(get_proxy_canister_controller())
-        .unwrap();
+    proxy_client.setup_proxy();
 
-    let res = test_canister_actor.call_http_request_via_proxy(HttpRequestEndpointArgs {
+    let ttl_ms = 10_000;
+    let args = HttpRequestEndpointArgs {
         request: HttpRequest {
             url: TEST_URL.to_string(),
             method: HttpMethod::GET,
             headers: vec![],
             body: None,
+            redirect: None,
         },
         timeout_ms: None,
         callback_method_name: None,
-    });
+        cache: Some(HttpRequestCacheOptions {
+            ttl_ms: Some(ttl_ms),
+            key_headers: vec![],
+        }),
+        idempotency_key: None,
+        coalesce: None,
+        signing_profile: None,
+    };
 
-    assert_eq!(
-        res,
-        HttpRequestEndpointResult::Err(ProxyCanisterError::HttpOverWs(
-            HttpOverWsError::NoProxiesConnected
-        )),
+    let request_id = test_canister_actor
+        .call_http_request_via_proxy(args.clone())
+        .unwrap();
+    proxy_client.expect_received_http_requests_count(1);
+    proxy_client.send_http_over_ws_message(HttpOverWsMessage::HttpResponse(
+        request_id,
+        HttpResponse {
+            status: Nat::from(200),
+            headers: vec![],
+            body: vec![1, 2, 3],
+        },
+    ));
+
+    test_env.advance_canister_time_ms(ttl_ms);
+
+    // the cached response has expired, so the request goes to the proxy again
+    test_canister_actor
+        .call_http_request_via_proxy(args)
+        .unwrap();
+    proxy_client.expect_received_http_requests_count(1);
+}
+
+#[test]
+fn test_http_request_cache_ttl_from_headers() {
+    setup();
+    reset_canisters();
+    let test_env = get_test_env();
+    let mut proxy_client = ProxyClient::new(&test_env, get_proxy_canister_id());
+    let test_canister_actor = TestUserCanisterActor::new(&test_env, get_test_user_canister_id());
+
+    proxy_client.setup_proxy();
+
+    for (cache_control, expect_cached) in [("max-age=60", true), ("no-store", false)] {
+        let args = HttpRequestEndpointArgs {
+            request: HttpRequest {
+                url: format!("{}{}", TEST_URL, cache_control),
+                method: HttpMethod::GET,
+                headers: vec![],
+                body: None,
+                redirect: None,
+            },
+            timeout_ms: None,
+            callback_method_name: None,
+            cache: Some(HttpRequestCacheOptions {
+                ttl_ms: None,
+                key_headers: vec![],
+            }),
+            idempotency_key: None,
+            coalesce: None,
+            signing_profile: None,
+        };
+
+        let request_id = test_canister_actor
+            .call_http_request_via_proxy(args.clone())
+            .unwrap();
+        proxy_client.expect_received_http_requests_count(1);
+        proxy_client.send_http_over_ws_message(HttpOverWsMessage::HttpResponse(
+            request_id,
+            HttpResponse {
+                status: Nat::from(200),
+                headers: vec![HttpHeader {
+                    name: "Cache-Control".to_string(),
+                    value: cache_control.to_string(),
+                }],
+                body: vec![1, 2, 3],
+            },
+        ));
+
+        test_canister_actor
+            .call_http_request_via_proxy(args)
+            .unwrap();
+        proxy_client.expect_received_http_requests_count(if expect_cached { 0 } else { 1 });
+    }
+}
+
+#[test]
+fn test_http_request_idempotency_key() {
+    setup();
+    reset_canisters();
+    let test_env = get_test_env();
+    let mut proxy_client = ProxyClient::new(&test_env, get_proxy_canister_id());
+    let test_canister_actor = TestUserCanisterActor::new(&test_env, get_test_user_canister_id());
+    let proxy_canister_actor = ProxyCanisterActor::new(&test_env, get_proxy_canister_id());
+
+    proxy_client.setup_proxy();
+
+    let args = HttpRequestEndpointArgs {
+        request: HttpRequest {
+            url: TEST_URL.to_string(),
+            method: HttpMethod::POST,
+            headers: vec![],
+            body: Some(vec![1, 2, 3]),
+            redirect: None,
+        },
+        timeout_ms: None,
+        callback_method_name: None,
+        cache: None,
+        idempotency_key: Some("key".to_string()),
+        coalesce: None,
+        signing_profile: None,
+    };
+
+    let request_id1 = test_canister_actor
+        .call_http_request_via_proxy(args.clone())
+        .unwrap();
+    proxy_client.expect_received_http_requests_count(1);
+
+    // a retry returns the same request id and doesn't reach the proxy
+    let request_id2 = test_canister_actor
+        .call_http_request_via_proxy(args.clone())
+        .unwrap();
+    assert_eq!(request_id1, request_id2);
+    proxy_client.expect_received_http_requests_count(0);
+
+    // the same key used by another caller refers to another request
+    let request_id3 = proxy_canister_actor
+        .call_http_request(generate_random_principal(), args.clone())
+        .unwrap()
+        .unwrap();
+    assert_ne!(request_id1, request_id3);
+    proxy_client.expect_received_http_requests_count(1);
+
+    // once the window has passed, the key can be used again
+    test_env.advance_canister_time_ms(10 * 60 * 1_000);
+
+    let request_id4 = test_canister_actor
+        .call_http_request_via_proxy(args)
+        .unwrap();
+    assert_ne!(request_id1, request_id4);
+    proxy_client.expect_received_http_requests_count(1);
+}
+
+#[test]
+fn test_http_request_coalesced() {
+    setup();
+    reset_canisters();
+    let test_env = get_test_env();
+    let mut proxy_client = ProxyClient::new(&test_env, get_proxy_canister_id());
+    let test_canister_actor = TestUserCanisterActor::new(&test_env, get_test_user_canister_id());
+    let proxy_canister_actor = ProxyCanisterActor::new(&test_env, get_proxy_canister_id());
+
+    proxy_client.setup_proxy();
+
+    let request = HttpRequest {
+        url: TEST_URL.to_string(),
+        method: HttpMethod::GET,
+        headers: vec![],
+        body: None,
+        redirect: None,
+    };
+
+    let request_id1 = proxy_canister_actor
+        .call_http_request(
+            generate_random_principal(),
+            HttpRequestEndpointArgs {
+                request: request.clone(),
+                timeout_ms: None,
+                callback_method_name: None,
+                cache: None,
+                idempotency_key: None,
+                coalesce: None,
+                signing_profile: None,
+            },
+        )
+        .unwrap()
+        .unwrap();
+    proxy_client.expect_received_http_requests_count(1);
+
+    // an identical request waits for the in-flight one
+    let request_id2 = test_canister_actor
+        .call_http_request_via_proxy(HttpRequestEndpointArgs {
+            request,
+            timeout_ms: None,
+            callback_method_name: Some("http_response_callback".to_string()),
+            cache: None,
+            idempotency_key: None,
+            coalesce: Some(true),
+            signing_profile: None,
+        })
+        .unwrap();
+    assert_ne!(request_id1, request_id2);
+    proxy_client.expect_received_http_requests_count(0);
+
+    let response = HttpResponse {
+        status: Nat::from(200),
+        headers: vec![],
+        body: vec![1, 2, 3],
+    };
+    proxy_client.send_http_over_ws_message(HttpOverWsMessage::HttpResponse(
+        request_id1,
+        response.clone(),
+    ));
+
+    let req_state = proxy_canister_actor
+        .query_get_request_by_id_with_panic(get_proxy_canister_controller(), request_id2)
+        .unwrap();
+    assert!(matches!(req_state.state, RequestState::Executed));
+
+    let cb_responses = test_canister_actor.query_get_callback_results();
+    assert_eq!(
+        cb_responses.get(&request_id2).unwrap(),
+        &HttpResult::Success(response.clone())
+    );
+
+    // the coalesced request gets the metadata of the one that got the response
+    let metadata = HttpResponseMetadata {
+        proxy_principal: proxy_client.client_principal(),
+        final_url: TEST_URL.to_string(),
+        redirects: vec![],
+        timing: None,
+        body_size_bytes: None,
+        proxy_attempts: 1,
+    };
+    assert_eq!(
+        proxy_canister_actor.query_get_my_http_response(get_test_user_canister_id(), request_id2),
+        Some(HttpResponseEnvelope { response, metadata })
+    );
+}
+
+#[test]
+fn test_update_config_unauthorized() {
+    setup();
+    reset_canisters();
+    let test_env = get_test_env();
+    let proxy_canister_id = get_proxy_canister_id();
+    let proxy_canister_actor = ProxyCanisterActor::new(&test_env, proxy_canister_id);
+
+    let res = proxy_canister_actor
+        .call_update_config(generate_random_principal(), ProxyConfig::default());
+
+    assert_eq!(
+        res,
+        Err(UserError {
+            code: ErrorCode::CanisterCalledTrap,
+            description: format!(
+                "Canister {} trapped explicitly: Caller is not a controller",
+                proxy_canister_id
+            ),
+        })
+    );
+}
+
+#[test]
+fn test_update_config() {
+    setup();
+    reset_canisters();
+    let test_env = get_test_env();
+    let mut proxy_client = ProxyClient::new(&test_env, get_proxy_canister_id());
+    let proxy_canister_actor = ProxyCanisterActor::new(&test_env, get_proxy_canister_id());
+    let caller = generate_random_principal();
+
+    proxy_client.setup_proxy();
+
+    assert_eq!(
+        proxy_canister_actor.query_get_config(caller),
+        ProxyConfig::default()
+    );
+
+    // invalid configs are rejected
+    for (config, expected_err) in [
+        (
+            ProxyConfig {
+                min_http_request_timeout_ms: 0,
+                ..ProxyConfig::default()
+            },
+            InvalidProxyConfig::InvalidTimeouts,
+        ),
+        (
+            ProxyConfig {
+                default_http_request_timeout_ms: 70_000,
+                ..ProxyConfig::default()
+            },
+            InvalidProxyConfig::InvalidTimeouts,
+        ),
+        (
+            ProxyConfig {
+                max_http_headers_count: 0,
+                ..ProxyConfig::default()
+            },
+            InvalidProxyConfig::InvalidMaxHttpHeadersCount,
+        ),
+        (
+            ProxyConfig {
+                max_callback_delivery_attempts: 0,
+                ..ProxyConfig::default()
+            },
+            InvalidProxyConfig::InvalidMaxCallbackDeliveryAttempts,
+        ),
+    ] {
+        let res = proxy_canister_actor
+            .call_update_config(get_proxy_canister_controller(), config)
+            .unwrap();
+        assert_eq!(res, Err(expected_err));
+    }
+    assert_eq!(
+        proxy_canister_actor.query_get_config(caller),
+        ProxyConfig::default()
+    );
+
+    let config = ProxyConfig {
+        min_http_request_timeout_ms: 1_000,
+        max_http_request_timeout_ms: 120_000,
+        default_http_request_timeout_ms: 10_000,
+        max_http_headers_count: 2,
+        max_callback_delivery_attempts: 5,
+    };
+    let res = proxy_canister_actor
+        .call_update_config(get_proxy_canister_controller(), config.clone())
+        .unwrap();
+    assert_eq!(res, Ok(()));
+    assert_eq!(proxy_canister_actor.query_get_config(caller), config);
+
+    // the requests are validated against the new config
+    let res = proxy_canister_actor
+        .call_http_request(
+            caller,
+            HttpRequestEndpointArgs {
+                timeout_ms: Some(90_000),
+                ..http_request_args()
+            },
+        )
+        .unwrap();
+    assert!(res.is_ok());
+    proxy_client.expect_received_http_requests_count(1);
+
+    let res = proxy_canister_actor
+        .call_http_request(
+            caller,
+            HttpRequestEndpointArgs {
+                request: HttpRequest {
+                    headers: (0..3)
+                        .map(|i| HttpHeader {
+                            name: format!("name_{}", i),
+                            value: format!("value_{}", i),
+                        })
+                        .collect(),
+                    ..http_request_args().request
+                },
+                ..http_request_args()
+            },
+        )
+        .unwrap();
+    assert_eq!(
+        res,
+        Err(ProxyCanisterError::InvalidRequest(
+            InvalidRequest::TooManyHeaders
+        ))
+    );
+    proxy_client.expect_received_http_requests_count(0);
+
+    // the config is kept across upgrades
+    test_env.upgrade_canister(&get_proxy_canister_id());
+    assert_eq!(proxy_canister_actor.query_get_config(caller), config);
+}
+
+#[test]
+fn test_http_request_ids_unique_across_upgrades() {
+    setup();
+    reset_canisters();
+    let test_env = get_test_env();
+    let mut proxy_client = ProxyClient::new(&test_env, get_proxy_canister_id());
+    let proxy_canister_actor = ProxyCanisterActor::new(&test_env, get_proxy_canister_id());
+    let caller = generate_random_principal();
+
+    proxy_client.setup_proxy();
+
+    let request_id_before_upgrade = proxy_canister_actor
+        .call_http_request(caller, http_request_args())
+        .unwrap()
+        .unwrap();
+    proxy_client.expect_received_http_requests_count(1);
+
+    test_env.upgrade_canister(&get_proxy_canister_id());
+
+    let mut proxy_client = ProxyClient::new(&test_env, get_proxy_canister_id());
+    proxy_client.setup_proxy();
+
+    let request_id_after_upgrade = proxy_canister_actor
+        .call_http_request(caller, http_request_args())
+        .unwrap()
+        .unwrap();
+    proxy_client.expect_received_http_requests_count(1);
+
+    assert!(request_id_after_upgrade > request_id_before_upgrade);
+}
+
+#[test]
+fn test_http_request_attested_response() {
+    setup();
+    reset_canisters();
+    let test_env = get_test_env();
+    let mut proxy_client = ProxyClient::new(&test_env, get_proxy_canister_id());
+    let proxy_canister_actor = ProxyCanisterActor::new(&test_env, get_proxy_canister_id());
+    let controller = get_proxy_canister_controller();
+    let caller = generate_random_principal();
+    let key_pair = Ed25519KeyPair::from_seed_unchecked(&[1; 32]).unwrap();
+    let public_key = key_pair.public_key().as_ref().to_vec();
+
+    proxy_client.setup_proxy();
+
+    // only the controllers can register keys
+    assert!(proxy_canister_actor
+        .call_set_proxy_attestation_key(
+            caller,
+            proxy_client.client_principal(),
+            Some(public_key.clone())
+        )
+        .is_err());
+    proxy_canister_actor
+        .call_set_proxy_attestation_key(
+            controller,
+            proxy_client.client_principal(),
+            Some(public_key.clone()),
+        )
+        .unwrap()
+        .unwrap();
+
+    let request_id = proxy_canister_actor
+        .call_http_request(caller, http_request_args())
+        .unwrap()
+        .unwrap();
+    let request = match proxy_client.get_http_over_ws_messages().pop() {
+        Some(HttpOverWsMessage::HttpRequest(_, request)) => request,
+        message => panic!("unexpected message: {:?}", message),
+    };
+
+    let response = HttpResponse {
+        status: Nat::from(200),
+        headers: vec![],
+        body: vec![1, 2, 3],
+    };
+    let timestamp_ns = test_env.get_canister_time_ns();
+    let digest =
+        http_over_ws::response_attestation_digest(request_id, &request, &response, timestamp_ns);
+    proxy_client.send_http_over_ws_message(HttpOverWsMessage::AttestedHttpResponse(
+        request_id,
+        response.clone(),
+        ResponseSignature {
+            timestamp_ns,
+            signature: key_pair.sign(&digest).as_ref().to_vec(),
+        },
+    ));
+
+    let request_state = proxy_canister_actor
+        .query_get_request_by_id_with_panic(controller, request_id)
+        .unwrap();
+    assert_eq!(
+        request_state.http_result,
+        Some(HttpResult::Success(response.clone()))
+    );
+    let attestation = request_state.response_attestation.unwrap();
+    assert_eq!(attestation.proxy_principal, proxy_client.client_principal());
+    assert_eq!(attestation.verify(request_id, &request, &response), Ok(()));
+
+    // the registered keys are kept across upgrades
+    test_env.upgrade_canister(&get_proxy_canister_id());
+    assert_eq!(
+        proxy_canister_actor
+            .query_get_response_attestation_config(controller)
+            .unwrap(),
+        ResponseAttestationConfig {
+            required: false,
+            proxy_public_keys: vec![(proxy_client.client_principal(), public_key)],
+        }
+    );
+}
+
+#[test]
+fn test_http_request_default_timeout() {
+    setup();
+    reset_canisters();
+    let test_env = get_test_env();
+    let mut proxy_client = ProxyClient::new(&test_env, get_proxy_canister_id());
+    let proxy_canister_actor = ProxyCanisterActor::new(&test_env, get_proxy_canister_id());
+    let caller = generate_random_principal();
+
+    proxy_client.setup_proxy();
+
+    // the request doesn't specify a timeout
+    let request_id = proxy_canister_actor
+        .call_http_request(caller, http_request_args())
+        .unwrap()
+        .unwrap();
+    proxy_client.expect_received_http_requests_count(1);
+
+    test_env.advance_canister_time_ms(ProxyConfig::default().default_http_request_timeout_ms);
+
+    let req_state = proxy_canister_actor
+        .query_get_my_request(caller, request_id)
+        .unwrap();
+    assert!(matches!(req_state.state, RequestState::Executed));
+    let trace = proxy_canister_actor
+        .query_get_request_trace(caller, request_id)
+        .unwrap();
+    assert!(trace.events.iter().any(|event| event.kind
+        == RequestTraceEventKind::RequestFailed(HttpFailureReason::RequestTimeout)));
+}
+
+fn http_request_args() -> HttpRequestEndpointArgs {
+    HttpRequestEndpointArgs {
+        request: HttpRequest {
+            url: TEST_URL.to_string(),
+            method: HttpMethod::GET,
+            headers: vec![],
+            body: None,
+            redirect: None,
+        },
+        timeout_ms: None,
+        callback_method_name: None,
+        cache: None,
+        idempotency_key: None,
+        coalesce: None,
+        signing_profile: None,
+    }
+}
+
+#[test]
+fn test_get_my_request() {
+    setup();
+    reset_canisters();
+    let test_env = get_test_env();
+    let mut proxy_client = ProxyClient::new(&test_env, get_proxy_canister_id());
+    let proxy_canister_actor = ProxyCanisterActor::new(&test_env, get_proxy_canister_id());
+    let caller = generate_random_principal();
+
+    proxy_client.setup_proxy();
+
+    let request_id = proxy_canister_actor
+        .call_http_request(caller, http_request_args())
+        .unwrap()
+        .unwrap();
+    proxy_client.expect_received_http_requests_count(1);
+
+    let req = proxy_canister_actor
+        .query_get_my_request(caller, request_id)
+        .unwrap();
+    assert!(matches!(req.state, RequestState::Executing(None)));
+    assert_eq!(req.http_result, None);
+
+    let response = HttpResponse {
+        status: Nat::from(200),
+        headers: vec![],
+        body: vec![1, 2, 3],
+    };
+    proxy_client.send_http_over_ws_message(HttpOverWsMessage::HttpResponse(
+        request_id,
+        response.clone(),
+    ));
+
+    let req = proxy_canister_actor
+        .query_get_my_request(caller, request_id)
+        .unwrap();
+    assert_eq!(req.canister_id, caller);
+    assert!(matches!(req.state, RequestState::Executed));
+    assert_eq!(req.http_result, Some(HttpResult::Success(response)));
+
+    // other callers can't see the request
+    let req = proxy_canister_actor.query_get_my_request(generate_random_principal(), request_id);
+    assert_eq!(req, None);
+}
+
+#[test]
+fn test_get_request_trace() {
+    setup();
+    reset_canisters();
+    let test_env = get_test_env();
+    let mut proxy_client = ProxyClient::new(&test_env, get_proxy_canister_id());
+    let proxy_canister_actor = ProxyCanisterActor::new(&test_env, get_proxy_canister_id());
+    let caller = generate_random_principal();
+
+    proxy_client.setup_proxy();
+
+    let request_id = proxy_canister_actor
+        .call_http_request(caller, http_request_args())
+        .unwrap()
+        .unwrap();
+    proxy_client.expect_received_http_requests_count(1);
+
+    test_env.advance_canister_time_ms(1_000);
+    proxy_client.send_http_over_ws_message(HttpOverWsMessage::HttpResponse(
+        request_id,
+        HttpResponse {
+            status: Nat::from(200),
+            headers: vec![],
+            body: vec![1, 2, 3],
+        },
+    ));
+
+    let trace = proxy_canister_actor
+        .query_get_request_trace(caller, request_id)
+        .unwrap();
+    assert_eq!(trace.request_id, request_id);
+    assert_eq!(trace.canister_id, caller);
+    assert_eq!(trace.proxy_principal, Some(proxy_client.client_principal()));
+    assert_eq!(
+        trace
+            .events
+            .iter()
+            .map(|event| event.kind.clone())
+            .collect::<Vec<_>>(),
+        vec![
+            RequestTraceEventKind::Validated,
+            RequestTraceEventKind::AssignedToProxy(proxy_client.client_principal()),
+            RequestTraceEventKind::Sent,
+            RequestTraceEventKind::ResponseReceived,
+        ]
+    );
+    assert!(trace.events[3].duration_ns >= 1_000_000_000);
+    assert_eq!(
+        trace.duration_ns,
+        trace.events[3].timestamp_ns - trace.events[0].timestamp_ns
+    );
+
+    // other callers can't see the trace, but controllers can
+    let trace =
+        proxy_canister_actor.query_get_request_trace(generate_random_principal(), request_id);
+    assert_eq!(trace, None);
+    let trace =
+        proxy_canister_actor.query_get_request_trace(get_proxy_canister_controller(), request_id);
+    assert!(trace.is_some());
+}
+
+#[test]
+fn test_get_metrics() {
+    setup();
+    reset_canisters();
+    let test_env = get_test_env();
+    let mut proxy_client = ProxyClient::new(&test_env, get_proxy_canister_id());
+    let proxy_canister_actor = ProxyCanisterActor::new(&test_env, get_proxy_canister_id());
+    let caller = generate_random_principal();
+
+    proxy_client.setup_proxy();
+
+    let request_id = proxy_canister_actor
+        .call_http_request(caller, http_request_args())
+        .unwrap()
+        .unwrap();
+    proxy_client.expect_received_http_requests_count(1);
+
+    test_env.advance_canister_time_ms(1_000);
+    proxy_client.send_http_over_ws_message(HttpOverWsMessage::HttpResponse(
+        request_id,
+        HttpResponse {
+            status: Nat::from(200),
+            headers: vec![],
+            body: vec![1, 2, 3],
+        },
+    ));
+
+    let res = proxy_canister_actor
+        .query_get_metrics(get_proxy_canister_controller())
+        .unwrap();
+
+    let http_over_ws_metrics = res.metrics.http_over_ws;
+    assert_eq!(http_over_ws_metrics.requests_started, 1);
+    assert_eq!(http_over_ws_metrics.requests_succeeded, 1);
+    assert_eq!(http_over_ws_metrics.requests_failed_timeout, 0);
+    assert_eq!(http_over_ws_metrics.requests_failed_proxy_error, 0);
+    assert_eq!(http_over_ws_metrics.requests_rejected_no_proxies, 0);
+    assert_eq!(http_over_ws_metrics.latency_ms.count, 1);
+    assert!(http_over_ws_metrics.latency_ms.sum >= 1_000);
+    assert_eq!(http_over_ws_metrics.response_bytes.count, 1);
+    assert_eq!(http_over_ws_metrics.response_bytes.sum, 3);
+    assert_eq!(http_over_ws_metrics.proxies.len(), 1);
+    let proxy_metrics = &http_over_ws_metrics.proxies[0];
+    assert_eq!(
+        proxy_metrics.proxy_principal,
+        proxy_client.client_principal()
+    );
+    assert_eq!(proxy_metrics.in_flight_requests, 0);
+    assert_eq!(proxy_metrics.succeeded_requests, 1);
+    assert_eq!(proxy_metrics.failed_requests, 0);
+    assert_eq!(proxy_metrics.success_rate, Some(1.0));
+    assert!(res.metrics.stable_memory_bytes > 0);
+    assert!(res.metrics.cycles_balance > 0);
+
+    assert!(res
+        .prometheus
+        .contains("# TYPE http_over_ws_requests_started_total counter\n"));
+    assert!(res
+        .prometheus
+        .contains("http_over_ws_requests_started_total 1\n"));
+    assert!(res
+        .prometheus
+        .contains("http_over_ws_requests_failed_total{reason=\"timeout\"} 0\n"));
+    assert!(res
+        .prometheus
+        .contains("http_over_ws_request_latency_ms_bucket{le=\"+Inf\"} 1\n"));
+    assert!(res.prometheus.contains(&format!(
+        "http_over_ws_proxy_success_rate{{proxy=\"{}\"}} 1\n",
+        proxy_client.client_principal()
+    )));
+}
+
+#[test]
+fn test_get_metrics_unauthorized() {
+    setup();
+    reset_canisters();
+    let test_env = get_test_env();
+    let proxy_canister_id = get_proxy_canister_id();
+    let proxy_canister_actor = ProxyCanisterActor::new(&test_env, proxy_canister_id);
+
+    let res = proxy_canister_actor.query_get_metrics(generate_random_principal());
+
+    assert_eq!(
+        res,
+        Err(UserError {
+            code: ErrorCode::CanisterCalledTrap,
+            description: format!(
+                "Canister {} trapped explicitly: Caller is not a controller",
+                proxy_canister_id
+            ),
+        })
+    );
+}
+
+#[test]
+fn test_get_my_request_retention() {
+    setup();
+    reset_canisters();
+    let test_env = get_test_env();
+    let mut proxy_client = ProxyClient::new(&test_env, get_proxy_canister_id());
+    let proxy_canister_actor = ProxyCanisterActor::new(&test_env, get_proxy_canister_id());
+    let caller = generate_random_principal();
+
+    proxy_client.setup_proxy();
+
+    let response = HttpResponse {
+        status: Nat::from(200),
+        headers: vec![],
+        body: vec![1, 2, 3],
+    };
+
+    let request_id1 = proxy_canister_actor
+        .call_http_request(caller, http_request_args())
+        .unwrap()
+        .unwrap();
+    proxy_client.expect_received_http_requests_count(1);
+    proxy_client.send_http_over_ws_message(HttpOverWsMessage::HttpResponse(
+        request_id1,
+        response.clone(),
+    ));
+
+    test_env.advance_canister_time_ms(60 * 60 * 1_000);
+
+    // executing another request removes the requests whose retention period is over
+    let request_id2 = proxy_canister_actor
+        .call_http_request(caller, http_request_args())
+        .unwrap()
+        .unwrap();
+    proxy_client.expect_received_http_requests_count(1);
+    proxy_client.send_http_over_ws_message(HttpOverWsMessage::HttpResponse(request_id2, response));
+
+    assert_eq!(
+        proxy_canister_actor.query_get_my_request(caller, request_id1),
+        None
+    );
+    assert!(proxy_canister_actor
+        .query_get_my_request(caller, request_id2)
+        .is_some());
+}
+
+#[test]
+fn test_list_my_requests() {
+    setup();
+    reset_canisters();
+    let test_env = get_test_env();
+    let mut proxy_client = ProxyClient::new(&test_env, get_proxy_canister_id());
+    let proxy_canister_actor = ProxyCanisterActor::new(&test_env, get_proxy_canister_id());
+    let caller = generate_random_principal();
+
+    proxy_client.setup_proxy();
+
+    let request_ids: Vec<_> = (0..3)
+        .map(|_| {
+            proxy_canister_actor
+                .call_http_request(caller, http_request_args())
+                .unwrap()
+                .unwrap()
+        })
+        .collect();
+    proxy_client.expect_received_http_requests_count(3);
+
+    let page1 = proxy_canister_actor.query_list_my_requests(
+        caller,
+        ListRequestsArgs {
+            after_request_id: None,
+            limit: Some(2),
+        },
+    );
+    assert_eq!(
+        page1.requests.iter().map(|(id, _)| *id).collect::<Vec<_>>(),
+        request_ids[..2]
+    );
+    assert_eq!(page1.next_after_request_id, Some(request_ids[1]));
+
+    let page2 = proxy_canister_actor.query_list_my_requests(
+        caller,
+        ListRequestsArgs {
+            after_request_id: page1.next_after_request_id,
+            limit: Some(2),
+        },
+    );
+    assert_eq!(
+        page2.requests.iter().map(|(id, _)| *id).collect::<Vec<_>>(),
+        request_ids[2..]
+    );
+    assert_eq!(page2.next_after_request_id, None);
+
+    // a zero limit still returns a page, so that the end of the list can't be mistaken
+    let page = proxy_canister_actor.query_list_my_requests(
+        caller,
+        ListRequestsArgs {
+            after_request_id: None,
+            limit: Some(0),
+        },
+    );
+    assert_eq!(
+        page.requests.iter().map(|(id, _)| *id).collect::<Vec<_>>(),
+        request_ids[..1]
+    );
+    assert_eq!(page.next_after_request_id, Some(request_ids[0]));
+
+    // other callers don't see the requests
+    let res = proxy_canister_actor.query_list_my_requests(
+        generate_random_principal(),
+        ListRequestsArgs {
+            after_request_id: None,
+            limit: None,
+        },
+    );
+    assert!(res.requests.is_empty());
+}
+
+#[test]
+fn test_get_cache_stats_unauthorized() {
+    setup();
+    reset_canisters();
+    let test_env = get_test_env();
+    let proxy_canister_id = get_proxy_canister_id();
+    let proxy_canister_actor = ProxyCanisterActor::new(&test_env, proxy_canister_id);
+
+    let res = proxy_canister_actor.query_get_cache_stats(generate_random_principal());
+
+    assert_eq!(
+        res,
+        Err(UserError {
+            code: ErrorCode::CanisterCalledTrap,
+            description: format!(
+                "Canister {} trapped explicitly: Caller is not a controller",
+                proxy_canister_id
+            ),
+        })
+    )
+}
+
+#[test]
+fn test_get_request_by_id_unauthorized() {
+    setup();
+    reset_canisters();
+    let test_env = get_test_env();
+    let proxy_canister_id = get_proxy_canister_id();
+    let proxy_canister_actor = ProxyCanisterActor::new(&test_env, proxy_canister_id);
+
+    let res = proxy_canister_actor.query_get_request_by_id(generate_random_principal(), 1);
+
+    assert_eq!(
+        res,
+        Err(UserError {
+            code: ErrorCode::CanisterCalledTrap,
+            description: format!(
+                "Canister {} trapped explicitly: Caller is not a controller",
+                proxy_canister_id
+            ),
+        })
+    )
+}
+
+#[test]
+fn test_get_logs_unauthorized() {
+    setup();
+    reset_canisters();
+    let test_env = get_test_env();
+    let proxy_canister_id = get_proxy_canister_id();
+    let proxy_canister_actor = ProxyCanisterActor::new(&test_env, proxy_canister_id);
+
+    let res =
+        proxy_canister_actor.query_get_logs(generate_random_principal(), GetLogsArgs::default());
+
+    assert_eq!(
+        res,
+        Err(UserError {
+            code: ErrorCode::CanisterCalledTrap,
+            description: format!(
+                "Canister {} trapped explicitly: Caller is not a controller",
+                proxy_canister_id
+            ),
+        })
+    )
+}
+
+#[test]
+fn test_get_logs() {
+    setup();
+    reset_canisters();
+    let test_env = get_test_env();
+    let proxy_canister_id = get_proxy_canister_id();
+    let test_canister_actor = TestUserCanisterActor::new(&test_env, get_test_user_canister_id());
+    let proxy_canister_actor = ProxyCanisterActor::new(&test_env, proxy_canister_id);
+
+    // execute an http request before to have logs
+    let _ = test_canister_actor.call_http_request_via_proxy(HttpRequestEndpointArgs {
+        request: HttpRequest {
+            url: TEST_URL.to_string(),
+            method: HttpMethod::GET,
+            headers: vec![],
+            body: None,
+            redirect: None,
+        },
+        timeout_ms: None,
+        callback_method_name: None,
+        cache: None,
+        idempotency_key: None,
+        coalesce: None,
+        signing_profile: None,
+    });
+
+    let res = proxy_canister_actor
+        .query_get_logs(get_proxy_canister_controller(), GetLogsArgs::default());
+    assert!(res.unwrap().logs.len() > 0);
+}
+
+#[test]
+fn test_get_logs_filters_and_pagination() {
+    setup();
+    reset_canisters();
+    let test_env = get_test_env();
+    let proxy_canister_id = get_proxy_canister_id();
+    let test_canister_actor = TestUserCanisterActor::new(&test_env, get_test_user_canister_id());
+    let proxy_canister_actor = ProxyCanisterActor::new(&test_env, proxy_canister_id);
+
+    let request_id = test_canister_actor
+        .call_http_request_via_proxy(HttpRequestEndpointArgs {
+            request: HttpRequest {
+                url: TEST_URL.to_string(),
+                method: HttpMethod::GET,
+                headers: vec![],
+                body: None,
+                redirect: None,
+            },
+            timeout_ms: None,
+            callback_method_name: None,
+            cache: None,
+            idempotency_key: None,
+            coalesce: None,
+            signing_profile: None,
+        })
+        .unwrap();
+
+    let request_logs = proxy_canister_actor
+        .query_get_logs(
+            get_proxy_canister_controller(),
+            GetLogsArgs {
+                request_id: Some(request_id),
+                ..Default::default()
+            },
+        )
+        .unwrap()
+        .logs;
+    assert!(!request_logs.is_empty());
+    assert!(request_logs
+        .iter()
+        .all(|entry| entry.fields.request_id == Some(request_id)
+            && entry.fields.canister_id == Some(get_test_user_canister_id())));
+
+    let error_logs = proxy_canister_actor
+        .query_get_logs(
+            get_proxy_canister_controller(),
+            GetLogsArgs {
+                min_level: Some(LogLevel::Error),
+                ..Default::default()
+            },
+        )
+        .unwrap()
+        .logs;
+    assert!(error_logs
+        .iter()
+        .all(|entry| entry.level == LogLevel::Error));
+
+    let all_logs = proxy_canister_actor
+        .query_get_logs(get_proxy_canister_controller(), GetLogsArgs::default())
+        .unwrap();
+    assert_eq!(all_logs.next_after_id, None);
+
+    // fetch the same logs one page at a time
+    let mut paged_logs = vec![];
+    let mut after_id = None;
+    loop {
+        let page = proxy_canister_actor
+            .query_get_logs(
+                get_proxy_canister_controller(),
+                GetLogsArgs {
+                    after_id,
+                    limit: Some(2),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        assert!(page.logs.len() <= 2);
+        paged_logs.extend(page.logs);
+
+        match page.next_after_id {
+            Some(id) => after_id = Some(id),
+            None => break,
+        }
+    }
+    assert_eq!(paged_logs, all_logs.logs);
+}
+
+#[test]
+fn test_set_logger_config() {
+    setup();
+    reset_canisters();
+    let test_env = get_test_env();
+    let proxy_canister_id = get_proxy_canister_id();
+    let test_canister_actor = TestUserCanisterActor::new(&test_env, get_test_user_canister_id());
+    let proxy_canister_actor = ProxyCanisterActor::new(&test_env, proxy_canister_id);
+
+    let config = LoggerConfig {
+        max_size_bytes: 1_000,
+        min_level: LogLevel::Debug,
+    };
+
+    let res =
+        proxy_canister_actor.call_set_logger_config(generate_random_principal(), config.clone());
+    assert_eq!(
+        res,
+        Err(UserError {
+            code: ErrorCode::CanisterCalledTrap,
+            description: format!(
+                "Canister {} trapped explicitly: Caller is not a controller",
+                proxy_canister_id
+            ),
+        })
+    );
+
+    proxy_canister_actor
+        .call_set_logger_config(get_proxy_canister_controller(), config)
+        .unwrap();
+
+    let _ = test_canister_actor.call_http_request_via_proxy(HttpRequestEndpointArgs {
+        request: HttpRequest {
+            url: TEST_URL.to_string(),
+            method: HttpMethod::GET,
+            headers: vec![],
+            body: None,
+            redirect: None,
+        },
+        timeout_ms: None,
+        callback_method_name: None,
+        cache: None,
+        idempotency_key: None,
+        coalesce: None,
+        signing_profile: None,
+    });
+
+    let logs = proxy_canister_actor
+        .query_get_logs(get_proxy_canister_controller(), GetLogsArgs::default())
+        .unwrap()
+        .logs;
+    assert!(!logs.is_empty());
+    assert!(logs.iter().map(|entry| entry.message.len()).sum::<usize>() < 1_000);
+    // the oldest entries have been evicted, but the ids keep increasing
+    assert!(logs[0].id > 0);
+    assert!(logs
+        .windows(2)
+        .all(|entries| entries[1].id == entries[0].id + 1));
+}
+
+#[test]
+fn test_logs_survive_upgrade() {
+    setup();
+    reset_canisters();
+    let test_env = get_test_env();
+    let proxy_canister_id = get_proxy_canister_id();
+    let test_canister_actor = TestUserCanisterActor::new(&test_env, get_test_user_canister_id());
+    let proxy_canister_actor = ProxyCanisterActor::new(&test_env, proxy_canister_id);
+
+    proxy_canister_actor
+        .call_set_logger_config(
+            get_proxy_canister_controller(),
+            LoggerConfig {
+                max_size_bytes: 100_000,
+                min_level: LogLevel::Debug,
+            },
+        )
+        .unwrap();
+
+    let _ = test_canister_actor.call_http_request_via_proxy(HttpRequestEndpointArgs {
+        request: HttpRequest {
+            url: TEST_URL.to_string(),
+            method: HttpMethod::GET,
+            headers: vec![],
+            body: None,
+            redirect: None,
+        },
+        timeout_ms: None,
+        callback_method_name: None,
+        cache: None,
+        idempotency_key: None,
+        coalesce: None,
+        signing_profile: None,
+    });
+
+    let logs_before_upgrade = proxy_canister_actor
+        .query_get_logs(get_proxy_canister_controller(), GetLogsArgs::default())
+        .unwrap()
+        .logs;
+    assert!(!logs_before_upgrade.is_empty());
+
+    test_env.upgrade_canister(&proxy_canister_id);
+
+    let _ = test_canister_actor.call_http_request_via_proxy(HttpRequestEndpointArgs {
+        request: HttpRequest {
+            url: TEST_URL.to_string(),
+            method: HttpMethod::GET,
+            headers: vec![],
+            body: None,
+            redirect: None,
+        },
+        timeout_ms: None,
+        callback_method_name: None,
+        cache: None,
+        idempotency_key: None,
+        coalesce: None,
+        signing_profile: None,
+    });
+
+    let logs_after_upgrade = proxy_canister_actor
+        .query_get_logs(get_proxy_canister_controller(), GetLogsArgs::default())
+        .unwrap()
+        .logs;
+    assert!(logs_after_upgrade.starts_with(&logs_before_upgrade));
+    assert!(logs_after_upgrade.len() > logs_before_upgrade.len());
+    // the ids continue from the ones logged before the upgrade
+    assert_eq!(
+        logs_after_upgrade[logs_before_upgrade.len()].id,
+        logs_before_upgrade.last().unwrap().id + 1
+    );
+    assert!(logs_after_upgrade
+        .iter()
+        .any(|entry| entry.level == LogLevel::Debug));
+}
+
+#[test]
+fn test_export_logs() {
+    setup();
+    reset_canisters();
+    let test_env = get_test_env();
+    let proxy_canister_id = get_proxy_canister_id();
+    let test_canister_actor = TestUserCanisterActor::new(&test_env, get_test_user_canister_id());
+    let proxy_canister_actor = ProxyCanisterActor::new(&test_env, proxy_canister_id);
+
+    let res = proxy_canister_actor
+        .query_export_logs(generate_random_principal(), ExportLogsArgs::default());
+    assert_eq!(
+        res,
+        Err(UserError {
+            code: ErrorCode::CanisterCalledTrap,
+            description: format!(
+                "Canister {} trapped explicitly: Caller is not a controller",
+                proxy_canister_id
+            ),
+        })
+    );
+
+    let http_request_args = HttpRequestEndpointArgs {
+        request: HttpRequest {
+            url: TEST_URL.to_string(),
+            method: HttpMethod::GET,
+            headers: vec![],
+            body: None,
+            redirect: None,
+        },
+        timeout_ms: None,
+        callback_method_name: None,
+        cache: None,
+        idempotency_key: None,
+        coalesce: None,
+        signing_profile: None,
+    };
+    let _ = test_canister_actor.call_http_request_via_proxy(http_request_args.clone());
+
+    let export_all = |cursor: Option<LogsCursor>| {
+        let mut exported_logs = vec![];
+        let mut cursor = cursor;
+        loop {
+            let page = proxy_canister_actor
+                .query_export_logs(
+                    get_proxy_canister_controller(),
+                    ExportLogsArgs {
+                        cursor,
+                        from_timestamp_ns: None,
+                        limit: Some(2),
+                    },
+                )
+                .unwrap();
+            assert!(page.logs.len() <= 2);
+            assert_eq!(page.missed_count, 0);
+            exported_logs.extend(page.logs);
+            cursor = page.next_cursor;
+
+            if !page.has_more {
+                return (exported_logs, cursor);
+            }
+        }
+    };
+
+    let (exported_logs, cursor) = export_all(None);
+    let all_logs = proxy_canister_actor
+        .query_get_logs(get_proxy_canister_controller(), GetLogsArgs::default())
+        .unwrap()
+        .logs;
+    assert_eq!(exported_logs, all_logs);
+    assert!(exported_logs
+        .windows(2)
+        .all(|entries| entries[0].timestamp_ns <= entries[1].timestamp_ns));
+
+    // resuming from the last cursor only returns the new entries
+    let _ = test_canister_actor.call_http_request_via_proxy(http_request_args);
+    let (new_logs, _) = export_all(cursor);
+    assert!(!new_logs.is_empty());
+    assert_eq!(new_logs[0].id, exported_logs.last().unwrap().id + 1);
+
+    // starting from a timestamp skips the older entries
+    let from_timestamp_ns = new_logs[0].timestamp_ns;
+    let page = proxy_canister_actor
+        .query_export_logs(
+            get_proxy_canister_controller(),
+            ExportLogsArgs {
+                cursor: None,
+                from_timestamp_ns: Some(from_timestamp_ns),
+                limit: None,
+            },
+        )
+        .unwrap();
+    assert!(!page.has_more);
+    assert!(page
+        .logs
+        .iter()
+        .all(|entry| entry.timestamp_ns >= from_timestamp_ns));
+    assert_eq!(page.logs.last(), new_logs.last());
+}
+
+#[test]
+fn test_manage_proxies_unauthorized() {
+    setup();
+    reset_canisters();
+    let test_env = get_test_env();
+    let proxy_canister_id = get_proxy_canister_id();
+    let proxy_canister_actor = ProxyCanisterActor::new(&test_env, proxy_canister_id);
+    let caller = generate_random_principal();
+    let expected_err = UserError {
+        code: ErrorCode::CanisterCalledTrap,
+        description: format!(
+            "Canister {} trapped explicitly: Caller is not a controller",
+            proxy_canister_id
+        ),
+    };
+
+    assert_eq!(
+        proxy_canister_actor.query_list_proxies(caller),
+        Err(expected_err.clone())
+    );
+    assert_eq!(
+        proxy_canister_actor.call_disconnect_proxy(caller, generate_random_principal()),
+        Err(expected_err.clone())
+    );
+    assert_eq!(
+        proxy_canister_actor.call_drain_proxy(caller, generate_random_principal()),
+        Err(expected_err)
+    );
+}
+
+#[test]
+fn test_list_proxies() {
+    setup();
+    reset_canisters();
+    let test_env = get_test_env();
+    let mut proxy_client = ProxyClient::new(&test_env, get_proxy_canister_id());
+    let proxy_canister_actor = ProxyCanisterActor::new(&test_env, get_proxy_canister_id());
+    let caller = generate_random_principal();
+
+    let proxies = proxy_canister_actor
+        .query_list_proxies(get_proxy_canister_controller())
+        .unwrap();
+    assert!(proxies.is_empty());
+
+    proxy_client.setup_proxy();
+
+    let request_id = proxy_canister_actor
+        .call_http_request(caller, http_request_args())
+        .unwrap()
+        .unwrap();
+    proxy_client.expect_received_http_requests_count(1);
+
+    let proxies = proxy_canister_actor
+        .query_list_proxies(get_proxy_canister_controller())
+        .unwrap();
+    assert_eq!(proxies.len(), 1);
+    assert_eq!(proxies[0].proxy_principal, proxy_client.client_principal());
+    assert!(proxies[0].connected_at_ns > 0);
+    assert!(!proxies[0].draining);
+    assert_eq!(proxies[0].in_flight_requests, 1);
+    assert_eq!(proxies[0].completed_requests, 0);
+    assert_eq!(proxies[0].health, ProxyHealth::Unknown);
+
+    proxy_client.send_http_over_ws_message(HttpOverWsMessage::HttpResponse(
+        request_id,
+        HttpResponse {
+            status: Nat::from(200),
+            headers: vec![],
+            body: vec![],
+        },
+    ));
+
+    let proxies = proxy_canister_actor
+        .query_list_proxies(get_proxy_canister_controller())
+        .unwrap();
+    assert_eq!(proxies[0].in_flight_requests, 0);
+    assert_eq!(proxies[0].completed_requests, 1);
+    assert_eq!(proxies[0].health, ProxyHealth::Healthy);
+
+    // the proxy becomes unhealthy after consecutive failures
+    for _ in 0..UNHEALTHY_PROXY_CONSECUTIVE_FAILURES {
+        let request_id = proxy_canister_actor
+            .call_http_request(caller, http_request_args())
+            .unwrap()
+            .unwrap();
+        proxy_client.expect_received_http_requests_count(1);
+        proxy_client.send_http_over_ws_message(HttpOverWsMessage::Error(
+            Some(request_id),
+            "proxy error".to_string(),
+            None,
+        ));
+    }
+
+    let proxies = proxy_canister_actor
+        .query_list_proxies(get_proxy_canister_controller())
+        .unwrap();
+    assert_eq!(
+        proxies[0].completed_requests,
+        1 + UNHEALTHY_PROXY_CONSECUTIVE_FAILURES
+    );
+    assert_eq!(proxies[0].health, ProxyHealth::Unhealthy);
+}
+
+#[test]
+fn test_drain_proxy() {
+    setup();
+    reset_canisters();
+    let test_env = get_test_env();
+    let proxy_canister_actor = ProxyCanisterActor::new(&test_env, get_proxy_canister_id());
+    let mut proxy_client1 = ProxyClient::new(&test_env, get_proxy_canister_id());
+    let mut proxy_client2 = ProxyClient::new(&test_env, get_proxy_canister_id());
+    let caller = generate_random_principal();
+
+    proxy_client1.setup_proxy();
+
+    let in_flight_request_id = proxy_canister_actor
+        .call_http_request(caller, http_request_args())
+        .unwrap()
+        .unwrap();
+    proxy_client1.expect_received_http_requests_count(1);
+
+    proxy_client2.setup_proxy();
+
+    let res = proxy_canister_actor
+        .call_drain_proxy(
+            get_proxy_canister_controller(),
+            proxy_client1.client_principal(),
+        )
+        .unwrap();
+    assert_eq!(res, Ok(()));
+
+    let proxies = proxy_canister_actor
+        .query_list_proxies(get_proxy_canister_controller())
+        .unwrap();
+    let drained_proxy = proxies
+        .iter()
+        .find(|proxy| proxy.proxy_principal == proxy_client1.client_principal())
+        .unwrap();
+    assert!(drained_proxy.draining);
+
+    // new requests are only assigned to the proxy that is not draining
+    for _ in 0..3 {
+        proxy_canister_actor
+            .call_http_request(caller, http_request_args())
+            .unwrap()
+            .unwrap();
+    }
+    proxy_client1.expect_received_http_requests_count(0);
+    proxy_client2.expect_received_http_requests_count(3);
+
+    // the in-flight request of the draining proxy can still complete
+    proxy_client1.send_http_over_ws_message(HttpOverWsMessage::HttpResponse(
+        in_flight_request_id,
+        HttpResponse {
+            status: Nat::from(200),
+            headers: vec![],
+            body: vec![],
+        },
+    ));
+    let req_state = proxy_canister_actor
+        .query_get_my_request(caller, in_flight_request_id)
+        .unwrap();
+    assert!(matches!(req_state.state, RequestState::Executed));
+
+    let res = proxy_canister_actor
+        .call_drain_proxy(get_proxy_canister_controller(), generate_random_principal())
+        .unwrap();
+    assert_eq!(res, Err(HttpOverWsError::ProxyNotFound));
+}
+
+#[test]
+fn test_disconnect_proxy() {
+    setup();
+    reset_canisters();
+    let test_env = get_test_env();
+    let proxy_canister_actor = ProxyCanisterActor::new(&test_env, get_proxy_canister_id());
+    let mut proxy_client1 = ProxyClient::new(&test_env, get_proxy_canister_id());
+    let mut proxy_client2 = ProxyClient::new(&test_env, get_proxy_canister_id());
+    let caller = generate_random_principal();
+
+    proxy_client1.setup_proxy();
+    proxy_client2.setup_proxy();
+
+    let res = proxy_canister_actor
+        .call_disconnect_proxy(
+            get_proxy_canister_controller(),
+            proxy_client1.client_principal(),
+        )
+        .unwrap();
+    assert_eq!(res, Ok(()));
+
+    let proxies = proxy_canister_actor
+        .query_list_proxies(get_proxy_canister_controller())
+        .unwrap();
+    assert_eq!(proxies.len(), 1);
+    assert_eq!(proxies[0].proxy_principal, proxy_client2.client_principal());
+
+    for _ in 0..2 {
+        proxy_canister_actor
+            .call_http_request(caller, http_request_args())
+            .unwrap()
+            .unwrap();
+    }
+    proxy_client2.expect_received_http_requests_count(2);
+
+    let res = proxy_canister_actor
+        .call_disconnect_proxy(
+            get_proxy_canister_controller(),
+            proxy_client1.client_principal(),
+        )
+        .unwrap();
+    assert_eq!(res, Err(HttpOverWsError::ProxyNotFound));
+}
+
+#[test]
+fn test_disconnect_all_proxies_unauthorized() {
+    setup();
+    reset_canisters();
+    let test_env = get_test_env();
+    let proxy_canister_id = get_proxy_canister_id();
+    let proxy_canister_actor = ProxyCanisterActor::new(&test_env, proxy_canister_id);
+
+    let res = proxy_canister_actor.call_disconnect_all_proxies(generate_random_principal());
+
+    assert_eq!(
+        res,
+        Err(UserError {
+            code: ErrorCode::CanisterCalledTrap,
+            description: format!(
+                "Canister {} trapped explicitly: Caller is not a controller",
+                proxy_canister_id
+            ),
+        })
+    )
+}
+
+#[test]
+fn test_disconnect_all_proxies() {
+    setup();
+    reset_canisters();
+    let test_env = get_test_env();
+    let proxy_canister_id = get_proxy_canister_id();
+    let proxy_canister_actor = ProxyCanisterActor::new(&test_env, proxy_canister_id);
+    let test_canister_actor = TestUserCanisterActor::new(&test_env, get_test_user_canister_id());
+    let mut proxy_client1 = ProxyClient::new(&test_env, proxy_canister_id);
+    let mut proxy_client2 = ProxyClient::new(&test_env, proxy_canister_id);
+
+    proxy_client1.setup_proxy();
+    proxy_client2.setup_proxy();
+
+    proxy_canister_actor
+        .call_disconnect_all_proxies(get_proxy_canister_controller())
+        .unwrap();
+
+    let res = test_canister_actor.call_http_request_via_proxy(HttpRequestEndpointArgs {
+        request: HttpRequest {
+            url: TEST_URL.to_string(),
+            method: HttpMethod::GET,
+            headers: vec![],
+            body: None,
+            redirect: None,
+        },
+        timeout_ms: None,
+        callback_method_name: None,
+        cache: None,
+        idempotency_key: None,
+        coalesce: None,
+        signing_profile: None,
+    });
+
+    assert_eq!(
+        res,
+        HttpRequestEndpointResult::Err(ProxyCanisterError::HttpOverWs(
+            HttpOverWsError::NoProxiesConnected
+        )),
+    );
+}
+
+#[test]
+fn test_http_request_callback_dead_letter() {
+    setup();
+    reset_canisters();
+    let test_env = get_test_env();
+    let mut proxy_client = ProxyClient::new(&test_env, get_proxy_canister_id());
+    let proxy_canister_actor = ProxyCanisterActor::new(&test_env, get_proxy_canister_id());
+    // the caller isn't a canister, so the callback can't be delivered
+    let caller = generate_random_principal();
+
+    proxy_client.setup_proxy();
+
+    let request_id = proxy_canister_actor
+        .call_http_request(
+            caller,
+            HttpRequestEndpointArgs {
+                callback_method_name: Some("http_response_callback".to_string()),
+                ..http_request_args()
+            },
+        )
+        .unwrap()
+        .unwrap();
+    proxy_client.expect_received_http_requests_count(1);
+    let response = HttpResponse {
+        status: Nat::from(200),
+        headers: vec![],
+        body: vec![1, 2, 3],
+    };
+    proxy_client.send_http_over_ws_message(HttpOverWsMessage::HttpResponse(
+        request_id,
+        response.clone(),
+    ));
+
+    // the first attempt failed, the callback is retried later
+    let req_state = proxy_canister_actor
+        .query_get_my_request(caller, request_id)
+        .unwrap();
+    assert!(matches!(req_state.state, RequestState::Executing(_)));
+    assert_eq!(req_state.callback_attempts.len(), 1);
+
+    wait_for_callback_retries();
+
+    let req_state = proxy_canister_actor
+        .query_get_my_request(caller, request_id)
+        .unwrap();
+    assert!(matches!(req_state.state, RequestState::CallbackFailed(_)));
+    assert_eq!(req_state.callback_attempts.len(), 3);
+    assert!(req_state
+        .callback_attempts
+        .iter()
+        .all(|attempt| attempt.error.is_some()));
+
+    let res = proxy_canister_actor.query_list_my_dead_letters(
+        caller,
+        ListRequestsArgs {
+            after_request_id: None,
+            limit: None,
+        },
+    );
+    assert_eq!(res.dead_letters.len(), 1);
+    let dead_letter = &res.dead_letters[0];
+    assert_eq!(dead_letter.request_id, request_id);
+    assert_eq!(dead_letter.canister_id, caller);
+    assert_eq!(dead_letter.http_result, HttpResult::Success(response));
+    assert_eq!(dead_letter.callback_attempts.len(), 3);
+
+    let trace = proxy_canister_actor
+        .query_get_request_trace(caller, request_id)
+        .unwrap();
+    let callback_events: Vec<RequestTraceEventKind> = trace
+        .events
+        .into_iter()
+        .map(|event| event.kind)
+        .skip_while(|kind| !matches!(kind, RequestTraceEventKind::CallbackStarted { .. }))
+        .collect();
+    assert_eq!(callback_events.len(), 7);
+    assert!(matches!(
+        callback_events[4],
+        RequestTraceEventKind::CallbackStarted { attempt: 3 }
+    ));
+    assert!(matches!(
+        callback_events[5],
+        RequestTraceEventKind::CallbackFailed { attempt: 3, .. }
+    ));
+    assert_eq!(
+        callback_events[6],
+        RequestTraceEventKind::MovedToDeadLetters
+    );
+
+    // other callers don't see the dead letter
+    let res = proxy_canister_actor.query_list_my_dead_letters(
+        generate_random_principal(),
+        ListRequestsArgs {
+            after_request_id: None,
+            limit: None,
+        },
+    );
+    assert!(res.dead_letters.is_empty());
+
+    // other callers can't delete the dead letter
+    proxy_canister_actor
+        .call_delete_my_dead_letters(generate_random_principal(), vec![request_id])
+        .unwrap();
+    let res = proxy_canister_actor
+        .query_list_dead_letters(
+            get_proxy_canister_controller(),
+            ListRequestsArgs {
+                after_request_id: None,
+                limit: None,
+            },
+        )
+        .unwrap();
+    assert_eq!(res.dead_letters.len(), 1);
+
+    proxy_canister_actor
+        .call_delete_my_dead_letters(caller, vec![request_id])
+        .unwrap();
+    let res = proxy_canister_actor.query_list_my_dead_letters(
+        caller,
+        ListRequestsArgs {
+            after_request_id: None,
+            limit: None,
+        },
+    );
+    assert!(res.dead_letters.is_empty());
+}
+
+#[test]
+fn test_http_request_callback_delivery_attempts_configured() {
+    setup();
+    reset_canisters();
+    let test_env = get_test_env();
+    let mut proxy_client = ProxyClient::new(&test_env, get_proxy_canister_id());
+    let proxy_canister_actor = ProxyCanisterActor::new(&test_env, get_proxy_canister_id());
+    let caller = generate_random_principal();
+
+    proxy_client.setup_proxy();
+
+    proxy_canister_actor
+        .call_update_config(
+            get_proxy_canister_controller(),
+            ProxyConfig {
+                max_callback_delivery_attempts: 1,
+                ..ProxyConfig::default()
+            },
+        )
+        .unwrap()
+        .unwrap();
+
+    let request_id = proxy_canister_actor
+        .call_http_request(
+            caller,
+            HttpRequestEndpointArgs {
+                callback_method_name: Some("http_response_callback".to_string()),
+                ..http_request_args()
+            },
+        )
+        .unwrap()
+        .unwrap();
+    proxy_client.expect_received_http_requests_count(1);
+    proxy_client.send_http_over_ws_message(HttpOverWsMessage::HttpResponse(
+        request_id,
+        HttpResponse {
+            status: Nat::from(200),
+            headers: vec![],
+            body: vec![1, 2, 3],
+        },
+    ));
+
+    // the result is moved to the dead letters without being delivered again
+    let req_state = proxy_canister_actor
+        .query_get_my_request(caller, request_id)
+        .unwrap();
+    assert!(matches!(req_state.state, RequestState::CallbackFailed(_)));
+    assert_eq!(req_state.callback_attempts.len(), 1);
+
+    let res = proxy_canister_actor.query_list_my_dead_letters(
+        caller,
+        ListRequestsArgs {
+            after_request_id: None,
+            limit: None,
+        },
+    );
+    assert_eq!(res.dead_letters.len(), 1);
+    assert_eq!(res.dead_letters[0].callback_attempts.len(), 1);
+}
+
+#[test]
+fn test_replay_dead_letters() {
+    setup();
+    reset_canisters();
+    let test_env = get_test_env();
+    let mut proxy_client = ProxyClient::new(&test_env, get_proxy_canister_id());
+    let proxy_canister_actor = ProxyCanisterActor::new(&test_env, get_proxy_canister_id());
+    let caller = generate_random_principal();
+
+    proxy_client.setup_proxy();
+
+    let request_id = proxy_canister_actor
+        .call_http_request(
+            caller,
+            HttpRequestEndpointArgs {
+                callback_method_name: Some("http_response_callback".to_string()),
+                ..http_request_args()
+            },
+        )
+        .unwrap()
+        .unwrap();
+    proxy_client.expect_received_http_requests_count(1);
+    proxy_client.send_http_over_ws_message(HttpOverWsMessage::HttpResponse(
+        request_id,
+        HttpResponse {
+            status: Nat::from(200),
+            headers: vec![],
+            body: vec![1, 2, 3],
+        },
+    ));
+    wait_for_callback_retries();
+
+    // only the controller can replay the dead letters
+    let res = proxy_canister_actor
+        .call_replay_dead_letters(generate_random_principal(), vec![request_id]);
+    assert_eq!(
+        res,
+        Err(UserError {
+            code: ErrorCode::CanisterCalledTrap,
+            description: format!(
+                "Canister {} trapped explicitly: Caller is not a controller",
+                get_proxy_canister_id()
+            ),
+        })
+    );
+
+    proxy_canister_actor
+        .call_replay_dead_letters(get_proxy_canister_controller(), vec![request_id])
+        .unwrap();
+
+    // the replayed delivery fails again and the dead letter is back with all the attempts
+    let req_state = proxy_canister_actor
+        .query_get_my_request(caller, request_id)
+        .unwrap();
+    assert!(matches!(req_state.state, RequestState::Executing(_)));
+    assert_eq!(req_state.callback_attempts.len(), 4);
+
+    wait_for_callback_retries();
+
+    let res = proxy_canister_actor
+        .query_list_dead_letters(
+            get_proxy_canister_controller(),
+            ListRequestsArgs {
+                after_request_id: None,
+                limit: None,
+            },
+        )
+        .unwrap();
+    assert_eq!(res.dead_letters.len(), 1);
+    assert_eq!(res.dead_letters[0].callback_attempts.len(), 6);
+}
+
+#[test]
+fn test_dead_letters_upgrade() {
+    setup();
+    reset_canisters();
+    let test_env = get_test_env();
+    let mut proxy_client = ProxyClient::new(&test_env, get_proxy_canister_id());
+    let proxy_canister_actor = ProxyCanisterActor::new(&test_env, get_proxy_canister_id());
+    let caller = generate_random_principal();
+
+    proxy_client.setup_proxy();
+
+    let mut execute_request = || {
+        let request_id = proxy_canister_actor
+            .call_http_request(
+                caller,
+                HttpRequestEndpointArgs {
+                    callback_method_name: Some("http_response_callback".to_string()),
+                    ..http_request_args()
+                },
+            )
+            .unwrap()
+            .unwrap();
+        proxy_client.expect_received_http_requests_count(1);
+        proxy_client.send_http_over_ws_message(HttpOverWsMessage::HttpResponse(
+            request_id,
+            HttpResponse {
+                status: Nat::from(200),
+                headers: vec![],
+                body: vec![1, 2, 3],
+            },
+        ));
+        request_id
+    };
+
+    let request_id1 = execute_request();
+    wait_for_callback_retries();
+    let request_id2 = execute_request();
+
+    // the first result is in the dead letters, the second one waits to be delivered again
+    test_env.upgrade_canister(&get_proxy_canister_id());
+
+    let res = proxy_canister_actor.query_list_my_dead_letters(
+        caller,
+        ListRequestsArgs {
+            after_request_id: None,
+            limit: None,
+        },
+    );
+    assert_eq!(res.dead_letters.len(), 1);
+    assert_eq!(res.dead_letters[0].request_id, request_id1);
+    assert_eq!(res.dead_letters[0].callback_attempts.len(), 3);
+
+    // the delivery of the second result is retried after the upgrade
+    wait_for_callback_retries();
+
+    let res = proxy_canister_actor.query_list_my_dead_letters(
+        caller,
+        ListRequestsArgs {
+            after_request_id: None,
+            limit: None,
+        },
+    );
+    assert_eq!(res.dead_letters.len(), 2);
+    assert_eq!(res.dead_letters[1].request_id, request_id2);
+    assert_eq!(res.dead_letters[1].callback_attempts.len(), 3);
+}
+
+#[test]
+fn test_list_dead_letters_unauthorized() {
+    setup();
+    reset_canisters();
+    let test_env = get_test_env();
+    let proxy_canister_id = get_proxy_canister_id();
+    let proxy_canister_actor = ProxyCanisterActor::new(&test_env, proxy_canister_id);
+
+    let res = proxy_canister_actor.query_list_dead_letters(
+        generate_random_principal(),
+        ListRequestsArgs {
+            after_request_id: None,
+            limit: None,
+        },
+    );
+
+    assert_eq!(
+        res,
+        Err(UserError {
+            code: ErrorCode::CanisterCalledTrap,
+            description: format!(
+                "Canister {} trapped explicitly: Caller is not a controller",
+                proxy_canister_id
+            ),
+        })
+    )
+}
+
+#[test]
+fn test_http_request_batch() {
+    setup();
+    reset_canisters();
+    let test_env = get_test_env();
+    let mut proxy_client = ProxyClient::new(&test_env, get_proxy_canister_id());
+    let test_canister_actor = TestUserCanisterActor::new(&test_env, get_test_user_canister_id());
+
+    proxy_client.setup_proxy();
+
+    let invalid_args = HttpRequestEndpointArgs {
+        timeout_ms: Some(1_000),
+        ..http_request_args()
+    };
+    let results = test_canister_actor
+        .call_http_request_batch_via_proxy(
+            vec![http_request_args(), invalid_args, http_request_args()],
+            Some("http_response_batch_callback".to_string()),
+        )
+        .unwrap();
+
+    // the invalid request doesn't prevent the others from being executed
+    assert_eq!(results.len(), 3);
+    assert_eq!(
+        results[1],
+        Err(ProxyCanisterError::InvalidRequest(
+            InvalidRequest::InvalidTimeout
+        ))
+    );
+    let request_id1 = *results[0].as_ref().unwrap();
+    let request_id2 = *results[2].as_ref().unwrap();
+    proxy_client.expect_received_http_requests_count(2);
+
+    let response = HttpResponse {
+        status: Nat::from(200),
+        headers: vec![],
+        body: vec![1, 2, 3],
+    };
+    proxy_client.send_http_over_ws_message(HttpOverWsMessage::HttpResponse(
+        request_id1,
+        response.clone(),
+    ));
+
+    // the aggregated callback waits for all the requests
+    assert!(test_canister_actor
+        .query_get_batch_callback_results()
+        .is_empty());
+
+    proxy_client.send_http_over_ws_message(HttpOverWsMessage::HttpResponse(
+        request_id2,
+        response.clone(),
+    ));
+
+    assert_eq!(
+        test_canister_actor.query_get_batch_callback_results(),
+        vec![vec![
+            (request_id1, HttpResult::Success(response.clone())),
+            (request_id2, HttpResult::Success(response)),
+        ]]
+    );
+}
+
+#[test]
+fn test_http_request_batch_too_many_requests() {
+    setup();
+    reset_canisters();
+    let test_env = get_test_env();
+    let test_canister_actor = TestUserCanisterActor::new(&test_env, get_test_user_canister_id());
+
+    let res =
+        test_canister_actor.call_http_request_batch_via_proxy(vec![http_request_args(); 101], None);
+
+    assert_eq!(
+        res,
+        Err(ProxyCanisterError::InvalidRequest(
+            InvalidRequest::TooManyRequests
+        ))
+    );
+}
+
+fn schedule_http_request_args(schedule: HttpRequestSchedule) -> ScheduleHttpRequestArgs {
+    ScheduleHttpRequestArgs {
+        request: HttpRequest {
+            url: TEST_URL.to_string(),
+            method: HttpMethod::GET,
+            headers: vec![],
+            body: None,
+            redirect: None,
+        },
+        timeout_ms: None,
+        schedule,
+        end_time_ns: None,
+        callback_method_name: "http_response_callback".to_string(),
+    }
+}
+
+#[test]
+fn test_schedule_http_request_invalid() {
+    setup();
+    reset_canisters();
+    let test_env = get_test_env();
+    let proxy_canister_actor = ProxyCanisterActor::new(&test_env, get_proxy_canister_id());
+    let caller = generate_random_principal();
+
+    let res = proxy_canister_actor.call_schedule_http_request(
+        caller,
+        schedule_http_request_args(HttpRequestSchedule::Interval { interval_ms: 1_000 }),
+    );
+    assert!(matches!(
+        res,
+        Err(ProxyCanisterError::InvalidRequest(
+            InvalidRequest::InvalidSchedule(_)
+        ))
+    ));
+
+    let res = proxy_canister_actor.call_schedule_http_request(
+        caller,
+        schedule_http_request_args(HttpRequestSchedule::Cron("61 * * * *".to_string())),
+    );
+    assert_eq!(
+        res,
+        Err(ProxyCanisterError::InvalidRequest(
+            InvalidRequest::InvalidSchedule("invalid value in cron field: 61".to_string())
+        ))
+    );
+
+    // February 30th never happens
+    let res = proxy_canister_actor.call_schedule_http_request(
+        caller,
+        schedule_http_request_args(HttpRequestSchedule::Cron("0 0 30 2 *".to_string())),
+    );
+    assert_eq!(
+        res,
+        Err(ProxyCanisterError::InvalidRequest(
+            InvalidRequest::InvalidSchedule("schedule never executes".to_string())
+        ))
+    );
+
+    assert!(proxy_canister_actor
+        .query_list_my_scheduled_jobs(caller)
+        .is_empty());
+}
+
+#[test]
+fn test_schedule_http_request() {
+    setup();
+    reset_canisters();
+    let test_env = get_test_env();
+    let mut proxy_client = ProxyClient::new(&test_env, get_proxy_canister_id());
+    let proxy_canister_actor = ProxyCanisterActor::new(&test_env, get_proxy_canister_id());
+    let caller = generate_random_principal();
+
+    proxy_client.setup_proxy();
+
+    let job_id = proxy_canister_actor
+        .call_schedule_http_request(
+            caller,
+            schedule_http_request_args(HttpRequestSchedule::Interval {
+                interval_ms: 60_000,
+            }),
+        )
+        .unwrap();
+
+    proxy_client.expect_received_http_requests_count(0);
+    test_env.advance_canister_time_ms(60_000);
+    proxy_client.expect_received_http_requests_count(1);
+    test_env.advance_canister_time_ms(60_000);
+    proxy_client.expect_received_http_requests_count(1);
+
+    let jobs = proxy_canister_actor.query_list_my_scheduled_jobs(caller);
+    assert_eq!(jobs.len(), 1);
+    assert_eq!(jobs[0].job_id, job_id);
+    assert_eq!(jobs[0].state, ScheduledJobState::Active);
+    assert_eq!(jobs[0].executions.len(), 2);
+    assert!(jobs[0]
+        .executions
+        .iter()
+        .all(|execution| execution.request_id.is_some()));
+
+    // other callers can't see nor manage the job
+    assert!(proxy_canister_actor
+        .query_list_my_scheduled_jobs(generate_random_principal())
+        .is_empty());
+    assert_eq!(
+        proxy_canister_actor.call_pause_scheduled_job(generate_random_principal(), job_id),
+        Err(ScheduledJobError::NotFound)
+    );
+
+    proxy_canister_actor
+        .call_pause_scheduled_job(caller, job_id)
+        .unwrap();
+    test_env.advance_canister_time_ms(60_000);
+    proxy_client.expect_received_http_requests_count(0);
+
+    proxy_canister_actor
+        .call_resume_scheduled_job(caller, job_id)
+        .unwrap();
+    test_env.advance_canister_time_ms(60_000);
+    proxy_client.expect_received_http_requests_count(1);
+
+    proxy_canister_actor
+        .call_cancel_scheduled_job(caller, job_id)
+        .unwrap();
+    test_env.advance_canister_time_ms(60_000);
+    proxy_client.expect_received_http_requests_count(0);
+    assert!(proxy_canister_actor
+        .query_list_my_scheduled_jobs(caller)
+        .is_empty());
+}
+
+#[test]
+fn test_schedule_http_request_end_time() {
+    setup();
+    reset_canisters();
+    let test_env = get_test_env();
+    let mut proxy_client = ProxyClient::new(&test_env, get_proxy_canister_id());
+    let proxy_canister_actor = ProxyCanisterActor::new(&test_env, get_proxy_canister_id());
+    let caller = generate_random_principal();
+
+    proxy_client.setup_proxy();
+
+    let now_ns = test_env.get_canister_time_ns();
+    let job_id = proxy_canister_actor
+        .call_schedule_http_request(
+            caller,
+            ScheduleHttpRequestArgs {
+                end_time_ns: Some(now_ns + 90_000_000_000),
+                ..schedule_http_request_args(HttpRequestSchedule::Interval {
+                    interval_ms: 60_000,
+                })
+            },
+        )
+        .unwrap();
+
+    test_env.advance_canister_time_ms(60_000);
+    proxy_client.expect_received_http_requests_count(1);
+    test_env.advance_canister_time_ms(60_000);
+    proxy_client.expect_received_http_requests_count(0);
+
+    let jobs = proxy_canister_actor.query_list_my_scheduled_jobs(caller);
+    assert_eq!(jobs[0].state, ScheduledJobState::Finished);
+    assert_eq!(jobs[0].next_execution_ns, None);
+    assert_eq!(
+        proxy_canister_actor.call_resume_scheduled_job(caller, job_id),
+        Err(ScheduledJobError::Finished)
+    );
+}
+
+#[test]
+fn test_schedule_http_request_upgrade() {
+    setup();
+    reset_canisters();
+    let test_env = get_test_env();
+    let mut proxy_client = ProxyClient::new(&test_env, get_proxy_canister_id());
+    let proxy_canister_actor = ProxyCanisterActor::new(&test_env, get_proxy_canister_id());
+    let caller = generate_random_principal();
+
+    let job_id = proxy_canister_actor
+        .call_schedule_http_request(
+            caller,
+            schedule_http_request_args(HttpRequestSchedule::Cron("* * * * *".to_string())),
+        )
+        .unwrap();
+
+    test_env.upgrade_canister(&get_proxy_canister_id());
+
+    let jobs = proxy_canister_actor.query_list_my_scheduled_jobs(caller);
+    assert_eq!(jobs.len(), 1);
+    assert_eq!(jobs[0].job_id, job_id);
+
+    // the timers of the jobs are set again after the upgrade
+    proxy_client.setup_proxy();
+    test_env.advance_canister_time_ms(60_000);
+    proxy_client.expect_received_http_requests_count(1);
+}
+
+#[test]
+fn test_webhook_request() {
+    setup();
+    reset_canisters();
+    let test_env = get_test_env();
+    let mut proxy_client = ProxyClient::new(&test_env, get_proxy_canister_id());
+    let proxy_canister_actor = ProxyCanisterActor::new(&test_env, get_proxy_canister_id());
+    let test_canister_actor = TestUserCanisterActor::new(&test_env, get_test_user_canister_id());
+
+    proxy_client.setup_proxy();
+
+    test_canister_actor
+        .call_register_webhook_via_proxy(Webhook {
+            path: "/github/push".to_string(),
+            callback_method_name: "webhook_callback".to_string(),
+        })
+        .unwrap();
+
+    let request = HttpRequest {
+        url: format!(
+            "https://proxy.example.com/webhooks/{}/github/push",
+            get_test_user_canister_id()
+        ),
+        method: HttpMethod::POST,
+        headers: vec![],
+        body: Some(vec![1, 2, 3]),
+        redirect: None,
+    };
+    proxy_client.send_http_over_ws_message(HttpOverWsMessage::HttpRequest(1, request));
+    // wait for the inter-canister call to the test canister
+    test_env.tick_n(10);
+
+    assert_eq!(
+        proxy_client.get_http_over_ws_messages(),
+        vec![HttpOverWsMessage::HttpResponse(
+            1,
+            HttpResponse {
+                status: Nat::from(200),
+                headers: vec![],
+                body: vec![1, 2, 3],
+            }
+        )]
+    );
+
+    // a path registered by another canister is not found
+    let request = HttpRequest {
+        url: format!("/webhooks/{}/github/push", generate_random_principal()),
+        method: HttpMethod::POST,
+        headers: vec![],
+        body: None,
+        redirect: None,
+    };
+    proxy_client.send_http_over_ws_message(HttpOverWsMessage::HttpRequest(2, request));
+    test_env.tick_n(10);
+
+    assert_eq!(
+        proxy_client.get_http_over_ws_messages(),
+        vec![HttpOverWsMessage::HttpResponse(
+            2,
+            HttpResponse {
+                status: Nat::from(404),
+                headers: vec![],
+                body: b"Not Found".to_vec(),
+            }
+        )]
+    );
+
+    // the webhooks are kept across upgrades
+    let webhooks = proxy_canister_actor.query_list_my_webhooks(get_test_user_canister_id());
+    test_env.upgrade_canister(&get_proxy_canister_id());
+    assert_eq!(
+        proxy_canister_actor.query_list_my_webhooks(get_test_user_canister_id()),
+        webhooks
+    );
+
+    proxy_client.setup_proxy();
+    let request = HttpRequest {
+        url: format!("/webhooks/{}/github/push", get_test_user_canister_id()),
+        method: HttpMethod::POST,
+        headers: vec![],
+        body: Some(vec![4, 5, 6]),
+        redirect: None,
+    };
+    proxy_client.send_http_over_ws_message(HttpOverWsMessage::HttpRequest(3, request));
+    test_env.tick_n(10);
+
+    assert_eq!(
+        proxy_client.get_http_over_ws_messages(),
+        vec![HttpOverWsMessage::HttpResponse(
+            3,
+            HttpResponse {
+                status: Nat::from(200),
+                headers: vec![],
+                body: vec![4, 5, 6],
+            }
+        )]
+    );
+}
+
+#[test]
+fn test_register_webhook_invalid() {
+    setup();
+    reset_canisters();
+    let test_env = get_test_env();
+    let proxy_canister_actor = ProxyCanisterActor::new(&test_env, get_proxy_canister_id());
+    let caller = generate_random_principal();
+
+    for path in ["no-slash", "/with?query", "/with#fragment"] {
+        let res = proxy_canister_actor.call_register_webhook(
+            caller,
+            Webhook {
+                path: path.to_string(),
+                callback_method_name: "webhook_callback".to_string(),
+            },
+        );
+
+        assert_eq!(
+            res,
+            Err(ProxyCanisterError::InvalidRequest(
+                InvalidRequest::InvalidWebhookPath
+            ))
+        );
+    }
+
+    assert!(proxy_canister_actor
+        .query_list_my_webhooks(caller)
+        .is_empty());
+}
+
+fn hmac_signing_profile_args(name: &str) -> RegisterSigningProfileArgs {
+    RegisterSigningProfileArgs {
+        canister_id: None,
+        name: name.to_string(),
+        scheme: SigningScheme::HmacSha256 {
+            signature_header: "X-Signature".to_string(),
+            timestamp_header: "X-Timestamp".to_string(),
+        },
+        secret: b"secret".to_vec(),
+    }
+}
+
+#[test]
+fn test_http_request_signed() {
+    setup();
+    reset_canisters();
+    let test_env = get_test_env();
+    let mut proxy_client = ProxyClient::new(&test_env, get_proxy_canister_id());
+    let proxy_canister_actor = ProxyCanisterActor::new(&test_env, get_proxy_canister_id());
+    let caller = generate_random_principal();
+
+    proxy_client.setup_proxy();
+
+    proxy_canister_actor
+        .call_register_signing_profile(caller, hmac_signing_profile_args("exchange"))
+        .unwrap()
+        .unwrap();
+
+    // the secret can't be read back
+    let profiles = proxy_canister_actor.query_list_my_signing_profiles(caller);
+    assert_eq!(profiles.len(), 1);
+    assert!(matches!(
+        &profiles[0],
+        SigningProfileInfo { name, scheme: SigningScheme::HmacSha256 { .. }, .. } if name == "exchange"
+    ));
+
+    let mut args = http_request_args();
+    args.request.url = format!("{}orders?symbol=ICP", TEST_URL);
+    args.request.method = HttpMethod::POST;
+    args.request.headers = vec![HttpHeader {
+        name: "x-signature".to_string(),
+        value: "overwritten".to_string(),
+    }];
+    args.request.body = Some(b"{}".to_vec());
+    args.signing_profile = Some("exchange".to_string());
+    proxy_canister_actor
+        .call_http_request(caller, args)
+        .unwrap()
+        .unwrap();
+
+    let request = match proxy_client.get_http_over_ws_messages().pop() {
+        Some(HttpOverWsMessage::HttpRequest(_, request)) => request,
+        message => panic!("unexpected message: {:?}", message),
+    };
+    let header = |name: &str| {
+        let values: Vec<String> = request
+            .headers
+            .iter()
+            .filter(|header| header.name.eq_ignore_ascii_case(name))
+            .map(|header| header.value.clone())
+            .collect();
+        assert_eq!(values.len(), 1);
+        values[0].clone()
+    };
+    let timestamp = header("X-Timestamp");
+    assert_eq!(
+        timestamp.parse::<u64>().unwrap(),
+        test_env.get_canister_time_ns() / 1_000_000_000
+    );
+
+    let mut mac = Hmac::<Sha256>::new_from_slice(b"secret").unwrap();
+    mac.update(format!("{}POST/orders?symbol=ICP{{}}", timestamp).as_bytes());
+    assert_eq!(
+        header("X-Signature"),
+        hex::encode(mac.finalize().into_bytes())
+    );
+
+    // the profiles are registered per canister
+    let mut args = http_request_args();
+    args.signing_profile = Some("exchange".to_string());
+    assert_eq!(
+        proxy_canister_actor
+            .call_http_request(generate_random_principal(), args)
+            .unwrap(),
+        Err(ProxyCanisterError::InvalidRequest(
+            InvalidRequest::SigningProfileNotFound
+        ))
+    );
+
+    // the signed requests are never cached
+    let mut args = http_request_args();
+    args.signing_profile = Some("exchange".to_string());
+    args.cache = Some(HttpRequestCacheOptions {
+        ttl_ms: None,
+        key_headers: vec![],
+    });
+    assert_eq!(
+        proxy_canister_actor
+            .call_http_request(caller, args)
+            .unwrap(),
+        Err(ProxyCanisterError::InvalidRequest(
+            InvalidRequest::NotCacheable
+        ))
+    );
+}
+
+#[test]
+fn test_register_signing_profile_invalid() {
+    setup();
+    reset_canisters();
+    let test_env = get_test_env();
+    let proxy_canister_actor = ProxyCanisterActor::new(&test_env, get_proxy_canister_id());
+    let controller = get_proxy_canister_controller();
+    let caller = generate_random_principal();
+
+    let mut args = hmac_signing_profile_args("exchange");
+    args.secret = vec![];
+    assert!(matches!(
+        proxy_canister_actor.call_register_signing_profile(caller, args),
+        Ok(Err(ProxyCanisterError::InvalidRequest(
+            InvalidRequest::InvalidSigningProfile(_)
+        )))
+    ));
+
+    let mut args = hmac_signing_profile_args("exchange");
+    args.scheme = SigningScheme::EcdsaSecp256k1 {
+        signature_header: "X-Signature".to_string(),
+        timestamp_header: "X-Timestamp".to_string(),
+    };
+    assert!(matches!(
+        proxy_canister_actor.call_register_signing_profile(caller, args),
+        Ok(Err(ProxyCanisterError::InvalidRequest(
+            InvalidRequest::InvalidSigningProfile(_)
+        )))
+    ));
+
+    // only the controllers can register profiles for other canisters
+    let mut args = hmac_signing_profile_args("exchange");
+    args.canister_id = Some(get_test_user_canister_id());
+    assert!(proxy_canister_actor
+        .call_register_signing_profile(caller, args.clone())
+        .is_err());
+    proxy_canister_actor
+        .call_register_signing_profile(controller, args)
+        .unwrap()
+        .unwrap();
+
+    assert!(proxy_canister_actor
+        .query_list_my_signing_profiles(caller)
+        .is_empty());
+    assert_eq!(
+        proxy_canister_actor
+            .query_list_my_signing_profiles(get_test_user_canister_id())
+            .len(),
+        1
+    );
+}
+
+#[test]
+fn test_http_request_with_secret_placeholders() {
+    setup();
+    reset_canisters();
+    let test_env = get_test_env();
+    let mut proxy_client = ProxyClient::new(&test_env, get_proxy_canister_id());
+    let proxy_canister_actor = ProxyCanisterActor::new(&test_env, get_proxy_canister_id());
+    let controller = get_proxy_canister_controller();
+    let caller = generate_random_principal();
+
+    proxy_client.setup_versioned_proxy(ProxyClientSetup {
+        supported_protocol_versions: vec![HTTP_OVER_WS_PROTOCOL_V2],
+        capabilities: Some(ProxyCapabilities {
+            secret_names: Some(vec!["API_KEY".to_string()]),
+            ..Default::default()
+        }),
+        encryption_key: None,
+    });
+
+    let mut args = http_request_args();
+    args.request.headers = vec![HttpHeader {
+        name: "Authorization".to_string(),
+        value: format!("Bearer {}", secret_placeholder("API_KEY")),
+    }];
+
+    // the secrets must be allowed for the caller
+    assert_eq!(
+        proxy_canister_actor
+            .call_http_request(caller, args.clone())
+            .unwrap(),
+        Err(ProxyCanisterError::InvalidRequest(
+            InvalidRequest::SecretNotAllowed("API_KEY".to_string())
+        ))
+    );
+
+    // only the controllers can allow secrets
+    assert!(proxy_canister_actor
+        .call_set_allowed_secrets(caller, caller, vec!["API_KEY".to_string()])
+        .is_err());
+    proxy_canister_actor
+        .call_set_allowed_secrets(
+            controller,
+            caller,
+            vec!["API_KEY".to_string(), "OTHER_KEY".to_string()],
+        )
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        proxy_canister_actor.query_list_my_allowed_secrets(caller),
+        vec!["API_KEY".to_string(), "OTHER_KEY".to_string()]
+    );
+
+    // the placeholders are sent as they are to the proxy
+    let request_id = proxy_canister_actor
+        .call_http_request(caller, args.clone())
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        proxy_client.get_http_over_ws_messages(),
+        vec![HttpOverWsMessage::HttpRequest(request_id, args.request)]
+    );
+
+    // no proxy holds the secret
+    let mut args = http_request_args();
+    args.request.url = format!("{}?key={}", TEST_URL, secret_placeholder("OTHER_KEY"));
+    assert_eq!(
+        proxy_canister_actor
+            .call_http_request(caller, args)
+            .unwrap(),
+        Err(ProxyCanisterError::HttpOverWs(
+            HttpOverWsError::SecretsNotAvailable(vec!["OTHER_KEY".to_string()])
+        ))
+    );
+
+    let mut args = http_request_args();
+    args.request.url = format!("{}?key={{{{secret:API_KEY", TEST_URL);
+    assert!(matches!(
+        proxy_canister_actor
+            .call_http_request(caller, args)
+            .unwrap(),
+        Err(ProxyCanisterError::InvalidRequest(
+            InvalidRequest::InvalidSecretPlaceholder(_)
+        ))
+    ));
+
+    // the allowed secrets are kept across upgrades
+    test_env.upgrade_canister(&get_proxy_canister_id());
+    assert_eq!(
+        proxy_canister_actor.query_list_my_allowed_secrets(caller),
+        vec!["API_KEY".to_string(), "OTHER_KEY".to_string()]
+    );
+}
+
+#[test]
+fn test_schedule_http_request_with_secret_placeholders() {
+    setup();
+    reset_canisters();
+    let test_env = get_test_env();
+    let mut proxy_client = ProxyClient::new(&test_env, get_proxy_canister_id());
+    let proxy_canister_actor = ProxyCanisterActor::new(&test_env, get_proxy_canister_id());
+    let controller = get_proxy_canister_controller();
+    let caller = generate_random_principal();
+
+    proxy_client.setup_versioned_proxy(ProxyClientSetup {
+        supported_protocol_versions: vec![HTTP_OVER_WS_PROTOCOL_V2],
+        capabilities: Some(ProxyCapabilities {
+            secret_names: Some(vec!["API_KEY".to_string()]),
+            ..Default::default()
+        }),
+        encryption_key: None,
+    });
+
+    let mut args = schedule_http_request_args(HttpRequestSchedule::Interval {
+        interval_ms: 60_000,
+    });
+    args.request.headers = vec![HttpHeader {
+        name: "Authorization".to_string(),
+        value: format!("Bearer {}", secret_placeholder("API_KEY")),
+    }];
+
+    // the secrets must be allowed for the caller when the job is scheduled
+    assert_eq!(
+        proxy_canister_actor.call_schedule_http_request(caller, args.clone()),
+        Err(ProxyCanisterError::InvalidRequest(
+            InvalidRequest::SecretNotAllowed("API_KEY".to_string())
+        ))
+    );
+    assert!(proxy_canister_actor
+        .query_list_my_scheduled_jobs(caller)
+        .is_empty());
+
+    proxy_canister_actor
+        .call_set_allowed_secrets(controller, caller, vec!["API_KEY".to_string()])
+        .unwrap()
+        .unwrap();
+    proxy_canister_actor
+        .call_schedule_http_request(caller, args)
+        .unwrap();
+
+    test_env.advance_canister_time_ms(60_000);
+    proxy_client.expect_received_http_requests_count(1);
+
+    // and when it's executed, since the secret may have been disallowed in the meantime
+    proxy_canister_actor
+        .call_set_allowed_secrets(controller, caller, vec![])
+        .unwrap()
+        .unwrap();
+    test_env.advance_canister_time_ms(60_000);
+    proxy_client.expect_received_http_requests_count(0);
+
+    let jobs = proxy_canister_actor.query_list_my_scheduled_jobs(caller);
+    let last_execution = jobs[0].executions.last().unwrap();
+    assert_eq!(last_execution.request_id, None);
+    assert_eq!(
+        last_execution.error,
+        Some(format!(
+            "{:?}",
+            ProxyCanisterError::InvalidRequest(InvalidRequest::SecretNotAllowed(
+                "API_KEY".to_string()
+            ))
+        ))
+    );
+}
+
+#[test]
+fn test_http_request_payload_encryption_required() {
+    setup();
+    reset_canisters();
+    let test_env = get_test_env();
+    let mut proxy_client = ProxyClient::new(&test_env, get_proxy_canister_id());
+    let proxy_canister_actor = ProxyCanisterActor::new(&test_env, get_proxy_canister_id());
+    let controller = get_proxy_canister_controller();
+    let caller = generate_random_principal();
+
+    proxy_client.setup_proxy();
+
+    // only the controllers can require the encryption
+    assert!(proxy_canister_actor
+        .call_set_payload_encryption_required(caller, true)
+        .is_err());
+    proxy_canister_actor
+        .call_set_payload_encryption_required(controller, true)
+        .unwrap();
+    assert!(proxy_canister_actor.query_is_payload_encryption_required(controller));
+
+    // the connected proxy didn't announce an encryption key
+    assert_eq!(
+        proxy_canister_actor
+            .call_http_request(caller, http_request_args())
+            .unwrap(),
+        Err(ProxyCanisterError::HttpOverWs(
+            HttpOverWsError::PayloadEncryptionNotAvailable
+        ))
+    );
+    proxy_client.expect_received_http_requests_count(0);
+
+    // the requirement is kept across upgrades
+    test_env.upgrade_canister(&get_proxy_canister_id());
+    assert!(proxy_canister_actor.query_is_payload_encryption_required(controller));
+}
+
+#[test]
+fn test_http_request_follow_redirects() {
+    setup();
+    reset_canisters();
+    let test_env = get_test_env();
+    let mut proxy_client = ProxyClient::new(&test_env, get_proxy_canister_id());
+    let proxy_canister_actor = ProxyCanisterActor::new(&test_env, get_proxy_canister_id());
+    let caller = generate_random_principal();
+
+    proxy_client.setup_proxy();
+
+    let redirect_response = |location: &str| HttpResponse {
+        status: Nat::from(302),
+        headers: vec![HttpHeader {
+            name: "Location".to_string(),
+            value: location.to_string(),
+        }],
+        body: vec![],
+    };
+
+    let mut args = http_request_args();
+    args.request.redirect = Some(RedirectPolicy::Follow(MAX_FOLLOWED_REDIRECTS + 1));
+    assert_eq!(
+        proxy_canister_actor
+            .call_http_request(caller, args)
+            .unwrap(),
+        Err(ProxyCanisterError::InvalidRequest(
+            InvalidRequest::InvalidRedirectPolicy
+        ))
+    );
+
+    let mut args = http_request_args();
+    args.request.url = "ftp://example.com/".to_string();
+    assert!(matches!(
+        proxy_canister_actor
+            .call_http_request(caller, args)
+            .unwrap(),
+        Err(ProxyCanisterError::InvalidRequest(
+            InvalidRequest::InvalidUrl(_)
+        ))
+    ));
+
+    let mut args = http_request_args();
+    args.request.redirect = Some(RedirectPolicy::Follow(2));
+    let request_id = proxy_canister_actor
+        .call_http_request(caller, args.clone())
+        .unwrap()
+        .unwrap();
+    proxy_client.expect_received_http_requests_count(1);
+    proxy_client.send_http_over_ws_message(HttpOverWsMessage::HttpResponse(
+        request_id,
+        redirect_response("/moved"),
+    ));
+    proxy_client.expect_received_http_requests_count(1);
+    let response = HttpResponse {
+        status: Nat::from(200),
+        headers: vec![],
+        body: vec![1, 2, 3],
+    };
+    proxy_client.send_http_over_ws_message(HttpOverWsMessage::HttpResponse(
+        request_id,
+        response.clone(),
+    ));
+
+    // the redirects are reported along with the response
+    let req = proxy_canister_actor
+        .query_get_my_request(caller, request_id)
+        .unwrap();
+    assert_eq!(req.http_result, Some(HttpResult::Success(response)));
+    assert_eq!(
+        req.response_metadata,
+        Some(HttpResponseMetadata {
+            proxy_principal: proxy_client.client_principal(),
+            final_url: "https://example.com/moved".to_string(),
+            redirects: vec![HttpRedirect {
+                status: 302,
+                url: "https://example.com/moved".to_string(),
+            }],
+            timing: None,
+            body_size_bytes: None,
+            proxy_attempts: 1,
+        })
+    );
+
+    // the destination policy applies to every hop
+    let request_id = proxy_canister_actor
+        .call_http_request(caller, args)
+        .unwrap()
+        .unwrap();
+    proxy_client.expect_received_http_requests_count(1);
+    proxy_client.send_http_over_ws_message(HttpOverWsMessage::HttpResponse(
+        request_id,
+        redirect_response("ftp://example.com/file"),
+    ));
+    proxy_client.expect_received_http_requests_count(0);
+    let req = proxy_canister_actor
+        .query_get_my_request(caller, request_id)
+        .unwrap();
+    assert!(matches!(
+        req.http_result,
+        Some(HttpResult::Failure(HttpFailureReason::InvalidRedirect(_)))
+    ));
+    assert_eq!(req.response_metadata, None);
+}
+
+#[test]
+fn test_http_request_signed_follow_redirects() {
+    setup();
+    reset_canisters();
+    let test_env = get_test_env();
+    let mut proxy_client = ProxyClient::new(&test_env, get_proxy_canister_id());
+    let proxy_canister_actor = ProxyCanisterActor::new(&test_env, get_proxy_canister_id());
+    let caller = generate_random_principal();
+
+    proxy_client.setup_proxy();
+
+    proxy_canister_actor
+        .call_register_signing_profile(caller, hmac_signing_profile_args("exchange"))
+        .unwrap()
+        .unwrap();
+
+    let mut args = http_request_args();
+    args.request.redirect = Some(RedirectPolicy::Follow(2));
+    args.signing_profile = Some("exchange".to_string());
+    let request_id = proxy_canister_actor
+        .call_http_request(caller, args)
+        .unwrap()
+        .unwrap();
+    proxy_client.expect_received_http_requests_count(1);
+
+    // the same origin gets the signature headers
+    proxy_client.send_http_over_ws_message(HttpOverWsMessage::HttpResponse(
+        request_id,
+        HttpResponse {
+            status: Nat::from(302),
+            headers: vec![HttpHeader {
+                name: "Location".to_string(),
+                value: "/moved".to_string(),
+            }],
+            body: vec![],
+        },
+    ));
+    proxy_client.expect_received_http_requests_count(1);
+
+    // while another origin could replay them
+    proxy_client.send_http_over_ws_message(HttpOverWsMessage::HttpResponse(
+        request_id,
+        HttpResponse {
+            status: Nat::from(302),
+            headers: vec![HttpHeader {
+                name: "Location".to_string(),
+                value: "https://other.example.com/".to_string(),
+            }],
+            body: vec![],
+        },
+    ));
+    proxy_client.expect_received_http_requests_count(0);
+
+    let req = proxy_canister_actor
+        .query_get_my_request(caller, request_id)
+        .unwrap();
+    assert_eq!(
+        req.http_result,
+        Some(HttpResult::Failure(HttpFailureReason::InvalidRedirect(
+            "signed requests can't be redirected to a different origin".to_string()
+        )))
     );
 }
@@ -1,15 +1,19 @@
 use std::{cell::RefCell, collections::HashMap};
 
-use candid::Principal;
+use candid::{Nat, Principal};
 use ic_cdk::{print, trap};
 use ic_cdk_macros::{init, query, update};
 use proxy_canister_types::{
-    HttpRequestEndpointArgs, HttpRequestEndpointResult, HttpRequestId, HttpResult,
+    CanisterCallbackMethodName, HttpRequest, HttpRequestBatchCallbackArgs,
+    HttpRequestBatchEndpointResult, HttpRequestEndpointArgs, HttpRequestEndpointResult,
+    HttpRequestId, HttpResponse, HttpResponseMetadata, HttpResult, RegisterWebhookResult, Webhook,
 };
 
 thread_local! {
     /* flexible */ static PROXY_CANISTER_ID: RefCell<Principal> = RefCell::new(Principal::anonymous());
     /* flexible */ static CALLBACK_RESPONSES: RefCell<HashMap<HttpRequestId, HttpResult>> = RefCell::new(HashMap::new());
+    /* flexible */ static CALLBACK_METADATA: RefCell<HashMap<HttpRequestId, Option<HttpResponseMetadata>>> = RefCell::new(HashMap::new());
+    /* flexible */ static BATCH_CALLBACK_RESPONSES: RefCell<Vec<HttpRequestBatchCallbackArgs>> = const { RefCell::new(Vec::new()) };
 }
 
 #[init]
@@ -33,6 +37,50 @@ async fn http_request_via_proxy(args: HttpRequestEndpointArgs) -> HttpRequestEnd
     }
 }
 
+#[update]
+async fn http_request_batch_via_proxy(
+    requests: Vec<HttpRequestEndpointArgs>,
+    callback_method_name: Option<CanisterCallbackMethodName>,
+) -> HttpRequestBatchEndpointResult {
+    let proxy_canister_id = PROXY_CANISTER_ID.with(|id| *id.borrow());
+    let res: Result<(HttpRequestBatchEndpointResult,), _> = ic_cdk::call(
+        proxy_canister_id,
+        "http_request_batch",
+        (requests, callback_method_name),
+    )
+    .await;
+
+    match res {
+        Ok(http_res) => http_res.0,
+        Err(e) => {
+            trap(format!("{:?}", e).as_str());
+        }
+    }
+}
+
+#[update]
+async fn register_webhook_via_proxy(webhook: Webhook) -> RegisterWebhookResult {
+    let proxy_canister_id = PROXY_CANISTER_ID.with(|id| *id.borrow());
+    let res: Result<(RegisterWebhookResult,), _> =
+        ic_cdk::call(proxy_canister_id, "register_webhook", (webhook,)).await;
+
+    match res {
+        Ok(res) => res.0,
+        Err(e) => {
+            trap(format!("{:?}", e).as_str());
+        }
+    }
+}
+
+#[update]
+fn webhook_callback(request: HttpRequest) -> HttpResponse {
+    HttpResponse {
+        status: Nat::from(200),
+        headers: vec![],
+        body: request.body.unwrap_or_default(),
+    }
+}
+
 #[update]
 fn http_response_callback(request_id: HttpRequestId, res: HttpResult) {
     CALLBACK_RESPONSES.with(|callbacks| {
@@ -41,6 +89,25 @@ fn http_response_callback(request_id: HttpRequestId, res: HttpResult) {
     });
 }
 
+#[update]
+fn http_response_callback_with_metadata(
+    request_id: HttpRequestId,
+    res: HttpResult,
+    metadata: Option<HttpResponseMetadata>,
+) {
+    http_response_callback(request_id, res);
+    CALLBACK_METADATA.with(|callbacks| {
+        callbacks.borrow_mut().insert(request_id, metadata);
+    });
+}
+
+#[update]
+fn http_response_batch_callback(results: HttpRequestBatchCallbackArgs) {
+    BATCH_CALLBACK_RESPONSES.with(|callbacks| {
+        callbacks.borrow_mut().push(results);
+    });
+}
+
 #[update]
 fn http_response_callback_traps(request_id: HttpRequestId) {
     trap(format!("request_id: {}", request_id).as_str());
@@ -55,3 +122,13 @@ fn http_response_callback_wrong_args(s: String) {
 fn get_callback_results() -> HashMap<HttpRequestId, HttpResult> {
     CALLBACK_RESPONSES.with(|responses| responses.borrow().clone())
 }
+
+#[query]
+fn get_callback_metadata() -> HashMap<HttpRequestId, Option<HttpResponseMetadata>> {
+    CALLBACK_METADATA.with(|metadata| metadata.borrow().clone())
+}
+
+#[query]
+fn get_batch_callback_results() -> Vec<HttpRequestBatchCallbackArgs> {
+    BATCH_CALLBACK_RESPONSES.with(|responses| responses.borrow().clone())
+}
@@ -1,9 +1,20 @@
 use std::collections::HashMap;
 
 use candid::Principal;
-use http_over_ws::{HttpRequestId, HttpResult};
+use http_over_ws::{
+    HttpRequestId, HttpResponseEnvelope, HttpResponseMetadata, HttpResult, SecretName,
+};
 use pocket_ic::UserError;
-use proxy_canister_types::{CanisterRequest, HttpRequestEndpointArgs, HttpRequestEndpointResult};
+use proxy_canister_types::{
+    CacheStats, CanisterRequest, ExportLogsArgs, ExportLogsResult, GetLogsArgs, GetLogsResult,
+    GetMetricsResult, HttpRequestBatchCallbackArgs, HttpRequestBatchEndpointResult,
+    HttpRequestEndpointArgs, HttpRequestEndpointResult, ListDeadLettersResult, ListRequestsArgs,
+    ListRequestsResult, LoggerConfig, ManageProxyResult, ManageScheduledJobResult, ProxyConfig,
+    ProxyInfo, RegisterSigningProfileArgs, RegisterSigningProfileResult, RegisterWebhookResult,
+    RequestTrace, ResponseAttestationConfig, ScheduleHttpRequestArgs, ScheduleHttpRequestResult,
+    ScheduledJob, ScheduledJobId, SetAllowedSecretsResult, SigningProfileInfo, UpdateConfigResult,
+    Webhook,
+};
 use test_utils::{ic_env::TestEnv, identity::generate_random_principal};
 
 pub struct TestUserCanisterActor<'a> {
@@ -33,6 +44,28 @@ impl<'a> TestUserCanisterActor<'a> {
         )
     }
 
+    pub fn call_http_request_batch_via_proxy(
+        &self,
+        requests: Vec<HttpRequestEndpointArgs>,
+        callback_method_name: Option<String>,
+    ) -> HttpRequestBatchEndpointResult {
+        self.test_env.call_canister_method_with_panic(
+            self.canister_id,
+            self.principal,
+            "http_request_batch_via_proxy",
+            (requests, callback_method_name),
+        )
+    }
+
+    pub fn call_register_webhook_via_proxy(&self, webhook: Webhook) -> RegisterWebhookResult {
+        self.test_env.call_canister_method_with_panic(
+            self.canister_id,
+            self.principal,
+            "register_webhook_via_proxy",
+            (webhook,),
+        )
+    }
+
     pub fn query_get_callback_results(&self) -> HashMap<HttpRequestId, HttpResult> {
         self.test_env.query_canister_method_with_panic(
             self.canister_id,
@@ -41,6 +74,26 @@ impl<'a> TestUserCanisterActor<'a> {
             (),
         )
     }
+
+    pub fn query_get_callback_metadata(
+        &self,
+    ) -> HashMap<HttpRequestId, Option<HttpResponseMetadata>> {
+        self.test_env.query_canister_method_with_panic(
+            self.canister_id,
+            self.principal,
+            "get_callback_metadata",
+            (),
+        )
+    }
+
+    pub fn query_get_batch_callback_results(&self) -> Vec<HttpRequestBatchCallbackArgs> {
+        self.test_env.query_canister_method_with_panic(
+            self.canister_id,
+            self.principal,
+            "get_batch_callback_results",
+            (),
+        )
+    }
 }
 
 pub struct ProxyCanisterActor<'a> {
@@ -70,6 +123,77 @@ impl<'a> ProxyCanisterActor<'a> {
             .call_canister_method(self.canister_id, caller, "disconnect_all_proxies", ())
     }
 
+    pub fn query_get_config(&self, caller: Principal) -> ProxyConfig {
+        self.test_env
+            .query_canister_method_with_panic(self.canister_id, caller, "get_config", ())
+    }
+
+    pub fn call_update_config(
+        &self,
+        caller: Principal,
+        config: ProxyConfig,
+    ) -> Result<UpdateConfigResult, UserError> {
+        self.test_env
+            .call_canister_method(self.canister_id, caller, "update_config", (config,))
+    }
+
+    pub fn query_list_proxies(&self, caller: Principal) -> Result<Vec<ProxyInfo>, UserError> {
+        self.test_env
+            .query_canister_method(self.canister_id, caller, "list_proxies", ())
+    }
+
+    pub fn call_disconnect_proxy(
+        &self,
+        caller: Principal,
+        proxy_principal: Principal,
+    ) -> Result<ManageProxyResult, UserError> {
+        self.test_env.call_canister_method(
+            self.canister_id,
+            caller,
+            "disconnect_proxy",
+            (proxy_principal,),
+        )
+    }
+
+    pub fn call_drain_proxy(
+        &self,
+        caller: Principal,
+        proxy_principal: Principal,
+    ) -> Result<ManageProxyResult, UserError> {
+        self.test_env.call_canister_method(
+            self.canister_id,
+            caller,
+            "drain_proxy",
+            (proxy_principal,),
+        )
+    }
+
+    pub fn call_set_proxy_attestation_key(
+        &self,
+        caller: Principal,
+        proxy_principal: Principal,
+        public_key: Option<Vec<u8>>,
+    ) -> Result<ManageProxyResult, UserError> {
+        self.test_env.call_canister_method(
+            self.canister_id,
+            caller,
+            "set_proxy_attestation_key",
+            (proxy_principal, public_key),
+        )
+    }
+
+    pub fn query_get_response_attestation_config(
+        &self,
+        caller: Principal,
+    ) -> Result<ResponseAttestationConfig, UserError> {
+        self.test_env.query_canister_method(
+            self.canister_id,
+            caller,
+            "get_response_attestation_config",
+            (),
+        )
+    }
+
     pub fn query_get_request_by_id(
         &self,
         caller: Principal,
@@ -92,8 +216,290 @@ impl<'a> ProxyCanisterActor<'a> {
             .expect("query_get_request_by_id should succeed")
     }
 
-    pub fn query_get_logs(&self, caller: Principal) -> Result<Vec<(String, String)>, UserError> {
+    pub fn query_get_logs(
+        &self,
+        caller: Principal,
+        args: GetLogsArgs,
+    ) -> Result<GetLogsResult, UserError> {
+        self.test_env
+            .query_canister_method(self.canister_id, caller, "get_logs", (args,))
+    }
+
+    pub fn query_export_logs(
+        &self,
+        caller: Principal,
+        args: ExportLogsArgs,
+    ) -> Result<ExportLogsResult, UserError> {
+        self.test_env
+            .query_canister_method(self.canister_id, caller, "export_logs", (args,))
+    }
+
+    pub fn call_set_logger_config(
+        &self,
+        caller: Principal,
+        config: LoggerConfig,
+    ) -> Result<(), UserError> {
+        self.test_env
+            .call_canister_method(self.canister_id, caller, "set_logger_config", (config,))
+    }
+
+    pub fn query_get_cache_stats(&self, caller: Principal) -> Result<CacheStats, UserError> {
         self.test_env
-            .query_canister_method(self.canister_id, caller, "get_logs", ())
+            .query_canister_method(self.canister_id, caller, "get_cache_stats", ())
+    }
+
+    pub fn query_get_metrics(&self, caller: Principal) -> Result<GetMetricsResult, UserError> {
+        self.test_env
+            .query_canister_method(self.canister_id, caller, "get_metrics", ())
+    }
+
+    pub fn query_get_my_request(
+        &self,
+        caller: Principal,
+        request_id: HttpRequestId,
+    ) -> Option<CanisterRequest> {
+        self.test_env.query_canister_method_with_panic(
+            self.canister_id,
+            caller,
+            "get_my_request",
+            (request_id,),
+        )
+    }
+
+    pub fn query_get_my_http_response(
+        &self,
+        caller: Principal,
+        request_id: HttpRequestId,
+    ) -> Option<HttpResponseEnvelope> {
+        self.test_env.query_canister_method_with_panic(
+            self.canister_id,
+            caller,
+            "get_my_http_response",
+            (request_id,),
+        )
+    }
+
+    pub fn query_get_request_trace(
+        &self,
+        caller: Principal,
+        request_id: HttpRequestId,
+    ) -> Option<RequestTrace> {
+        self.test_env.query_canister_method_with_panic(
+            self.canister_id,
+            caller,
+            "get_request_trace",
+            (request_id,),
+        )
+    }
+
+    pub fn query_list_my_requests(
+        &self,
+        caller: Principal,
+        args: ListRequestsArgs,
+    ) -> ListRequestsResult {
+        self.test_env.query_canister_method_with_panic(
+            self.canister_id,
+            caller,
+            "list_my_requests",
+            (args,),
+        )
+    }
+
+    pub fn query_list_my_dead_letters(
+        &self,
+        caller: Principal,
+        args: ListRequestsArgs,
+    ) -> ListDeadLettersResult {
+        self.test_env.query_canister_method_with_panic(
+            self.canister_id,
+            caller,
+            "list_my_dead_letters",
+            (args,),
+        )
+    }
+
+    pub fn call_delete_my_dead_letters(
+        &self,
+        caller: Principal,
+        request_ids: Vec<HttpRequestId>,
+    ) -> Result<(), UserError> {
+        self.test_env.call_canister_method(
+            self.canister_id,
+            caller,
+            "delete_my_dead_letters",
+            (request_ids,),
+        )
+    }
+
+    pub fn query_list_dead_letters(
+        &self,
+        caller: Principal,
+        args: ListRequestsArgs,
+    ) -> Result<ListDeadLettersResult, UserError> {
+        self.test_env
+            .query_canister_method(self.canister_id, caller, "list_dead_letters", (args,))
+    }
+
+    pub fn call_replay_dead_letters(
+        &self,
+        caller: Principal,
+        request_ids: Vec<HttpRequestId>,
+    ) -> Result<(), UserError> {
+        self.test_env.call_canister_method(
+            self.canister_id,
+            caller,
+            "replay_dead_letters",
+            (request_ids,),
+        )
+    }
+
+    pub fn call_schedule_http_request(
+        &self,
+        caller: Principal,
+        args: ScheduleHttpRequestArgs,
+    ) -> ScheduleHttpRequestResult {
+        self.test_env.call_canister_method_with_panic(
+            self.canister_id,
+            caller,
+            "schedule_http_request",
+            (args,),
+        )
+    }
+
+    pub fn query_list_my_scheduled_jobs(&self, caller: Principal) -> Vec<ScheduledJob> {
+        self.test_env.query_canister_method_with_panic(
+            self.canister_id,
+            caller,
+            "list_my_scheduled_jobs",
+            (),
+        )
+    }
+
+    pub fn call_pause_scheduled_job(
+        &self,
+        caller: Principal,
+        job_id: ScheduledJobId,
+    ) -> ManageScheduledJobResult {
+        self.test_env.call_canister_method_with_panic(
+            self.canister_id,
+            caller,
+            "pause_scheduled_job",
+            (job_id,),
+        )
+    }
+
+    pub fn call_resume_scheduled_job(
+        &self,
+        caller: Principal,
+        job_id: ScheduledJobId,
+    ) -> ManageScheduledJobResult {
+        self.test_env.call_canister_method_with_panic(
+            self.canister_id,
+            caller,
+            "resume_scheduled_job",
+            (job_id,),
+        )
+    }
+
+    pub fn call_cancel_scheduled_job(
+        &self,
+        caller: Principal,
+        job_id: ScheduledJobId,
+    ) -> ManageScheduledJobResult {
+        self.test_env.call_canister_method_with_panic(
+            self.canister_id,
+            caller,
+            "cancel_scheduled_job",
+            (job_id,),
+        )
+    }
+
+    pub fn call_register_webhook(
+        &self,
+        caller: Principal,
+        webhook: Webhook,
+    ) -> RegisterWebhookResult {
+        self.test_env.call_canister_method_with_panic(
+            self.canister_id,
+            caller,
+            "register_webhook",
+            (webhook,),
+        )
+    }
+
+    pub fn query_list_my_webhooks(&self, caller: Principal) -> Vec<Webhook> {
+        self.test_env.query_canister_method_with_panic(
+            self.canister_id,
+            caller,
+            "list_my_webhooks",
+            (),
+        )
+    }
+
+    pub fn call_register_signing_profile(
+        &self,
+        caller: Principal,
+        args: RegisterSigningProfileArgs,
+    ) -> Result<RegisterSigningProfileResult, UserError> {
+        self.test_env.call_canister_method(
+            self.canister_id,
+            caller,
+            "register_signing_profile",
+            (args,),
+        )
+    }
+
+    pub fn query_list_my_signing_profiles(&self, caller: Principal) -> Vec<SigningProfileInfo> {
+        self.test_env.query_canister_method_with_panic(
+            self.canister_id,
+            caller,
+            "list_my_signing_profiles",
+            (),
+        )
+    }
+
+    pub fn call_set_allowed_secrets(
+        &self,
+        caller: Principal,
+        canister_id: Principal,
+        secret_names: Vec<SecretName>,
+    ) -> Result<SetAllowedSecretsResult, UserError> {
+        self.test_env.call_canister_method(
+            self.canister_id,
+            caller,
+            "set_allowed_secrets",
+            (canister_id, secret_names),
+        )
+    }
+
+    pub fn call_set_payload_encryption_required(
+        &self,
+        caller: Principal,
+        required: bool,
+    ) -> Result<(), UserError> {
+        self.test_env.call_canister_method(
+            self.canister_id,
+            caller,
+            "set_payload_encryption_required",
+            (required,),
+        )
+    }
+
+    pub fn query_is_payload_encryption_required(&self, caller: Principal) -> bool {
+        self.test_env.query_canister_method_with_panic(
+            self.canister_id,
+            caller,
+            "is_payload_encryption_required",
+            (),
+        )
+    }
+
+    pub fn query_list_my_allowed_secrets(&self, caller: Principal) -> Vec<SecretName> {
+        self.test_env.query_canister_method_with_panic(
+            self.canister_id,
+            caller,
+            "list_my_allowed_secrets",
+            (),
+        )
     }
 }
@@ -1,22 +1,106 @@
 use candid::{CandidType, Deserialize, Principal};
 
 pub use http_over_ws::{
-    HttpHeader, HttpMethod, HttpOverWsError, HttpRequest, HttpRequestId, HttpRequestTimeoutMs,
-    HttpResponse, HttpResult,
+    Histogram, HttpFailureReason, HttpHeader, HttpMethod, HttpOverWsError, HttpOverWsMetrics,
+    HttpOverWsProtocolVersion, HttpRedirect, HttpRequest, HttpRequestId, HttpRequestTimeoutMs,
+    HttpResponse, HttpResponseEnvelope, HttpResponseMetadata, HttpResponseTiming, HttpResult,
+    ProxyCapabilities, ProxyError, ProxyErrorCount, ProxyHealth, ProxyInfo, ProxyMetrics,
+    ProxyResponseStats, RedirectPolicy, ResponseAttestation, ResponseAttestationConfig, SecretName,
+};
+pub use logger::{
+    ExportLogsArgs, ExportLogsResult, GetLogsArgs, GetLogsResult, LogEntry, LogEntryId, LogFields,
+    LogLevel, LoggerConfig, LogsCursor,
 };
 
 pub type CanisterId = Principal;
 pub type CanisterCallbackMethodName = String;
 
-#[derive(CandidType, Deserialize, Debug, PartialEq, Eq)]
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
 pub struct HttpRequestEndpointArgs {
     pub request: HttpRequest,
     pub timeout_ms: Option<HttpRequestTimeoutMs>,
     pub callback_method_name: Option<CanisterCallbackMethodName>,
+    /// If set, the response is served from (and stored in) the proxy canister's response cache.
+    /// Only `GET` requests without a signing profile can be cached,
+    /// and the cached responses are only served to the caller that requested them.
+    pub cache: Option<HttpRequestCacheOptions>,
+    /// If set, retries of the same request made by the same caller within the idempotency window
+    /// return the [HttpRequestId] of the first request, which is executed only once.
+    pub idempotency_key: Option<IdempotencyKey>,
+    /// If `true`, a `GET` request identical to one that is already being executed
+    /// doesn't reach any proxy and gets the result of the in-flight request instead.
+    /// Requests with a signing profile are never coalesced.
+    pub coalesce: Option<bool>,
+    /// The name of a signing profile registered for the caller with `register_signing_profile`,
+    /// used to add the signature headers to the request right before it is sent to a proxy.
+    /// The redirects of a signed request can't go to a different origin.
+    pub signing_profile: Option<SigningProfileName>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct HttpRequestCacheOptions {
+    /// How long the response stays in the cache.
+    ///
+    /// If not set, the TTL is taken from the `Cache-Control` or `Expires` headers of the response.
+    /// Responses without such headers are not cached.
+    pub ttl_ms: Option<CacheTtlMs>,
+    /// The names of the request headers that, together with the method and the URL,
+    /// make up the cache key.
+    pub key_headers: Vec<String>,
 }
 
+pub type CacheTtlMs = u64;
+
+pub type IdempotencyKey = String;
+
 pub type HttpRequestEndpointResult = Result<HttpRequestId, ProxyCanisterError>;
 
+/// The settings of the proxy canister, which can be changed without upgrading it.
+///
+/// Passed as the optional argument of the init and upgrade methods and updated by the controllers
+/// with `update_config`. The settings are kept across upgrades that don't pass a new config.
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ProxyConfig {
+    /// The minimum timeout a request can take before timing out.
+    pub min_http_request_timeout_ms: HttpRequestTimeoutMs,
+    /// The maximum amount of time a request can take before timing out.
+    pub max_http_request_timeout_ms: HttpRequestTimeoutMs,
+    /// The timeout applied to the requests that don't specify one.
+    pub default_http_request_timeout_ms: HttpRequestTimeoutMs,
+    /// The maximum amount of headers a request can have.
+    pub max_http_headers_count: u32,
+    /// How many times the result of a request is delivered to the callback method
+    /// before being moved to the dead letters.
+    pub max_callback_delivery_attempts: u32,
+}
+
+impl Default for ProxyConfig {
+    fn default() -> Self {
+        Self {
+            min_http_request_timeout_ms: 5_000,
+            max_http_request_timeout_ms: 60_000,
+            default_http_request_timeout_ms: 30_000,
+            max_http_headers_count: 50,
+            max_callback_delivery_attempts: 3,
+        }
+    }
+}
+
+#[derive(CandidType, Deserialize, Debug, PartialEq, Eq)]
+pub enum InvalidProxyConfig {
+    /// The timeouts must be greater than zero, not exceed the maximum configurable timeout
+    /// and the default timeout must be between the minimum and the maximum ones.
+    InvalidTimeouts,
+    /// The maximum amount of headers must be greater than zero
+    /// and not exceed the maximum configurable amount.
+    InvalidMaxHttpHeadersCount,
+    /// The maximum amount of callback delivery attempts must be greater than zero
+    /// and not exceed the maximum configurable amount.
+    InvalidMaxCallbackDeliveryAttempts,
+}
+
+pub type UpdateConfigResult = Result<(), InvalidProxyConfig>;
+
 #[derive(CandidType, Deserialize, Debug, PartialEq, Eq)]
 pub enum ProxyCanisterError {
     InvalidRequest(InvalidRequest),
@@ -26,10 +110,45 @@ pub enum ProxyCanisterError {
 #[derive(CandidType, Deserialize, Debug, PartialEq, Eq)]
 pub enum InvalidRequest {
     InvalidUrl(String),
+    /// The custom method is not valid, see [HttpMethod::is_valid].
+    InvalidHttpMethod,
     TooManyHeaders,
     InvalidTimeout,
+    /// Only `GET` requests without a signing profile can be cached.
+    NotCacheable,
+    InvalidCacheTtl,
+    InvalidIdempotencyKey,
+    /// The batch contains more requests than allowed.
+    TooManyRequests,
+    InvalidSchedule(String),
+    TooManyScheduledJobs,
+    /// The path must start with `/` and can't contain a query or a fragment.
+    InvalidWebhookPath,
+    TooManyWebhooks,
+    InvalidSigningProfile(String),
+    TooManySigningProfiles,
+    SigningProfileNotFound,
+    /// A secret placeholder is not terminated or names an invalid secret,
+    /// see [http_over_ws::secret_placeholder].
+    InvalidSecretPlaceholder(String),
+    /// The secret has not been allowed for the caller with `set_allowed_secrets`.
+    SecretNotAllowed(SecretName),
+    /// More redirects than [http_over_ws::MAX_FOLLOWED_REDIRECTS] are allowed.
+    InvalidRedirectPolicy,
 }
 
+/// The results of the requests of a batch, in the same order as the requests.
+///
+/// Each request is validated and started independently: an invalid request
+/// gets an error in its position and doesn't prevent the other requests from being executed.
+/// The whole batch is rejected only if it contains too many requests.
+pub type HttpRequestBatchEndpointResult =
+    Result<Vec<HttpRequestEndpointResult>, ProxyCanisterError>;
+
+/// The argument of the aggregated callback of a batch, called once all the started requests
+/// of the batch have a result. Requests that failed to start are not included.
+pub type HttpRequestBatchCallbackArgs = Vec<(HttpRequestId, HttpResult)>;
+
 #[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
 pub enum RequestState {
     /// Used to indicate that the request is being executed.
@@ -40,7 +159,7 @@ pub enum RequestState {
     /// because from the proxy canister perspective, the request has been executed.
     Executed,
     /// Used to indicate that the proxy canister failed to call the callback method
-    /// on the user canister.
+    /// on the user canister, even after retrying. The result has been moved to the dead letters.
     CallbackFailed(String),
 }
 
@@ -48,6 +167,16 @@ pub enum RequestState {
 pub struct CanisterRequest {
     pub canister_id: CanisterId,
     pub state: RequestState,
+    /// The result of the HTTP request, available once the request has been executed.
+    pub http_result: Option<HttpResult>,
+    /// The proof of which proxy vouched for the response, if the proxy attested it.
+    pub response_attestation: Option<ResponseAttestation>,
+    /// How the response has been obtained, if it has been received from a proxy:
+    /// the proxy, the final URL, the timing, the size before truncation and the attempts.
+    /// It is passed as the third argument of the callback method.
+    pub response_metadata: Option<HttpResponseMetadata>,
+    /// The attempts made to deliver the result to the callback method.
+    pub callback_attempts: Vec<CallbackAttempt>,
 }
 
 impl CanisterRequest {
@@ -58,9 +187,33 @@ impl CanisterRequest {
         Self {
             canister_id,
             state: RequestState::Executing(callback_method_name),
+            http_result: None,
+            response_attestation: None,
+            response_metadata: None,
+            callback_attempts: Vec::new(),
         }
     }
 
+    pub fn set_http_result(&mut self, http_result: HttpResult) {
+        self.http_result = Some(http_result);
+    }
+
+    pub fn set_response_attestation(&mut self, response_attestation: ResponseAttestation) {
+        self.response_attestation = Some(response_attestation);
+    }
+
+    pub fn set_response_metadata(&mut self, response_metadata: HttpResponseMetadata) {
+        self.response_metadata = Some(response_metadata);
+    }
+
+    pub fn add_callback_attempt(&mut self, attempt: CallbackAttempt) {
+        self.callback_attempts.push(attempt);
+    }
+
+    pub fn set_executing(&mut self, callback_method_name: Option<CanisterCallbackMethodName>) {
+        self.state = RequestState::Executing(callback_method_name);
+    }
+
     pub fn set_executed(&mut self) {
         self.state = RequestState::Executed;
     }
@@ -69,3 +222,300 @@ impl CanisterRequest {
         self.state = RequestState::CallbackFailed(reason);
     }
 }
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub entries: u64,
+    pub size_bytes: u64,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq)]
+pub struct ProxyCanisterMetrics {
+    pub http_over_ws: HttpOverWsMetrics,
+    /// The results delivered to the callback methods.
+    pub callbacks_succeeded: u64,
+    /// The failed attempts to deliver a result to a callback method, including the ones retried later.
+    pub callbacks_failed: u64,
+    pub cache: CacheStats,
+    pub heap_memory_bytes: u64,
+    pub stable_memory_bytes: u64,
+    pub cycles_balance: u128,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq)]
+pub struct GetMetricsResult {
+    pub metrics: ProxyCanisterMetrics,
+    /// The same metrics in the Prometheus text exposition format.
+    pub prometheus: String,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct CallbackAttempt {
+    pub timestamp_ns: u64,
+    /// [None] if the result has been delivered to the callback method.
+    pub error: Option<String>,
+}
+
+/// The result of a request that couldn't be delivered to the callback method.
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct DeadLetter {
+    pub request_id: HttpRequestId,
+    pub canister_id: CanisterId,
+    pub callback_method_name: CanisterCallbackMethodName,
+    pub http_result: HttpResult,
+    pub callback_attempts: Vec<CallbackAttempt>,
+}
+
+/// A step in the lifecycle of a request.
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum RequestTraceEventKind {
+    /// The request has been validated and accepted by the proxy canister.
+    Validated,
+    ServedFromCache,
+    /// The request follows an identical in-flight request, whose id is given.
+    CoalescedWith(HttpRequestId),
+    AssignedToProxy(Principal),
+    Sent,
+    ResponseReceived,
+    RequestFailed(HttpFailureReason),
+    CallbackStarted {
+        attempt: u32,
+    },
+    CallbackFinished {
+        attempt: u32,
+    },
+    CallbackFailed {
+        attempt: u32,
+        error: String,
+    },
+    MovedToDeadLetters,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct RequestTraceEvent {
+    pub timestamp_ns: u64,
+    /// The time elapsed since the previous event, 0 for the first one.
+    pub duration_ns: u64,
+    pub kind: RequestTraceEventKind,
+}
+
+/// The timeline of the lifecycle events of a request, oldest first.
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct RequestTrace {
+    pub request_id: HttpRequestId,
+    pub canister_id: CanisterId,
+    /// The proxy the request has been assigned to, if any.
+    pub proxy_principal: Option<Principal>,
+    pub events: Vec<RequestTraceEvent>,
+    /// The time elapsed between the first and the last event.
+    pub duration_ns: u64,
+}
+
+impl RequestTrace {
+    pub fn new(request_id: HttpRequestId, canister_id: CanisterId, timestamp_ns: u64) -> Self {
+        Self {
+            request_id,
+            canister_id,
+            proxy_principal: None,
+            events: vec![RequestTraceEvent {
+                timestamp_ns,
+                duration_ns: 0,
+                kind: RequestTraceEventKind::Validated,
+            }],
+            duration_ns: 0,
+        }
+    }
+
+    pub fn add_event(&mut self, kind: RequestTraceEventKind, timestamp_ns: u64) {
+        if let RequestTraceEventKind::AssignedToProxy(proxy_principal) = kind {
+            self.proxy_principal = Some(proxy_principal);
+        }
+
+        let (first, last) = match (self.events.first(), self.events.last()) {
+            (Some(first), Some(last)) => (first.timestamp_ns, last.timestamp_ns),
+            _ => (timestamp_ns, timestamp_ns),
+        };
+
+        self.events.push(RequestTraceEvent {
+            timestamp_ns,
+            duration_ns: timestamp_ns.saturating_sub(last),
+            kind,
+        });
+        self.duration_ns = timestamp_ns.saturating_sub(first);
+    }
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ListRequestsArgs {
+    /// Only the requests with an id greater than this one are returned.
+    pub after_request_id: Option<HttpRequestId>,
+    /// Defaults to and is capped at 100, with a minimum of 1.
+    pub limit: Option<u32>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ListRequestsResult {
+    pub requests: Vec<(HttpRequestId, CanisterRequest)>,
+    /// The value to pass as `after_request_id` to get the next page,
+    /// [None] if there are no more requests.
+    pub next_after_request_id: Option<HttpRequestId>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ListDeadLettersResult {
+    pub dead_letters: Vec<DeadLetter>,
+    /// The value to pass as `after_request_id` to get the next page,
+    /// [None] if there are no more dead letters.
+    pub next_after_request_id: Option<HttpRequestId>,
+}
+
+pub type ScheduledJobId = u64;
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum HttpRequestSchedule {
+    /// The request is executed every `interval_ms`, starting `interval_ms` after the job is scheduled.
+    Interval { interval_ms: u64 },
+    /// A cron expression with the five standard fields, evaluated in UTC,
+    /// e.g. `*/5 * * * *` to execute the request every 5 minutes.
+    Cron(String),
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ScheduleHttpRequestArgs {
+    /// The request executed at each scheduled time.
+    pub request: HttpRequest,
+    pub timeout_ms: Option<HttpRequestTimeoutMs>,
+    pub schedule: HttpRequestSchedule,
+    /// The time, in nanoseconds since the UNIX epoch, after which the request isn't executed anymore.
+    pub end_time_ns: Option<u64>,
+    /// Receives the result of each execution, like the callback of [HttpRequestEndpointArgs].
+    pub callback_method_name: CanisterCallbackMethodName,
+}
+
+pub type ScheduleHttpRequestResult = Result<ScheduledJobId, ProxyCanisterError>;
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum ScheduledJobState {
+    Active,
+    Paused,
+    /// The end time of the job has been reached.
+    Finished,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ScheduledJobExecution {
+    pub timestamp_ns: u64,
+    /// The id of the request started by the execution, [None] if the request failed to start.
+    pub request_id: Option<HttpRequestId>,
+    pub error: Option<String>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ScheduledJob {
+    pub job_id: ScheduledJobId,
+    pub canister_id: CanisterId,
+    pub request: HttpRequest,
+    pub timeout_ms: Option<HttpRequestTimeoutMs>,
+    pub schedule: HttpRequestSchedule,
+    pub end_time_ns: Option<u64>,
+    pub callback_method_name: CanisterCallbackMethodName,
+    pub state: ScheduledJobState,
+    /// The time of the next execution, [None] if the job is not active.
+    pub next_execution_ns: Option<u64>,
+    /// The most recent executions, oldest first.
+    pub executions: Vec<ScheduledJobExecution>,
+}
+
+impl ScheduledJob {
+    /// The arguments of the request started at each execution.
+    pub fn http_request_args(&self) -> HttpRequestEndpointArgs {
+        HttpRequestEndpointArgs {
+            request: self.request.clone(),
+            timeout_ms: self.timeout_ms,
+            callback_method_name: Some(self.callback_method_name.clone()),
+            cache: None,
+            idempotency_key: None,
+            coalesce: None,
+            signing_profile: None,
+        }
+    }
+}
+
+#[derive(CandidType, Deserialize, Debug, PartialEq, Eq)]
+pub enum ScheduledJobError {
+    NotFound,
+    /// Finished jobs can't be resumed.
+    Finished,
+}
+
+pub type ManageScheduledJobResult = Result<(), ScheduledJobError>;
+
+pub type ManageProxyResult = Result<(), HttpOverWsError>;
+
+pub type WebhookPath = String;
+
+/// Proxies forward the webhook requests they receive on `/webhooks/<canister id><path>`
+/// to the callback method of the canister, which must have the signature `(HttpRequest) -> (HttpResponse)`.
+/// The response is sent back to the webhook caller.
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Webhook {
+    pub path: WebhookPath,
+    pub callback_method_name: CanisterCallbackMethodName,
+}
+
+pub type RegisterWebhookResult = Result<(), ProxyCanisterError>;
+
+pub type SigningProfileName = String;
+
+/// How the signature headers of a request are computed from the secret of a signing profile.
+///
+/// The HMAC and ECDSA schemes sign the string made of the timestamp, the method, the path
+/// (including the query) and the body of the request, concatenated without separators,
+/// where the timestamp is the Unix time in seconds, which is also set in `timestamp_header`.
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum SigningScheme {
+    /// The HMAC-SHA256 of the string to sign, hex encoded in `signature_header`.
+    HmacSha256 {
+        signature_header: String,
+        timestamp_header: String,
+    },
+    /// The secp256k1 ECDSA signature (`r || s`) of the SHA-256 hash of the string to sign,
+    /// hex encoded in `signature_header`. The secret is the 32 bytes private key.
+    EcdsaSecp256k1 {
+        signature_header: String,
+        timestamp_header: String,
+    },
+    /// AWS Signature Version 4, with the secret access key as secret.
+    /// Sets the `Authorization`, `X-Amz-Date` and `X-Amz-Content-Sha256` headers.
+    AwsSigV4 {
+        access_key_id: String,
+        region: String,
+        service: String,
+    },
+}
+
+/// Not [Debug], so that the secret can't end up in the logs.
+#[derive(CandidType, Deserialize, Clone, PartialEq, Eq)]
+pub struct RegisterSigningProfileArgs {
+    /// The canister that can use the profile, the caller if not set.
+    /// Only the controllers can register profiles for other canisters.
+    pub canister_id: Option<CanisterId>,
+    pub name: SigningProfileName,
+    pub scheme: SigningScheme,
+    /// The HMAC secret or the private key, which can never be read back.
+    pub secret: Vec<u8>,
+}
+
+pub type RegisterSigningProfileResult = Result<(), ProxyCanisterError>;
+
+/// A signing profile, without its secret.
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct SigningProfileInfo {
+    pub name: SigningProfileName,
+    pub scheme: SigningScheme,
+    pub registered_at_ns: u64,
+}
+
+pub type SetAllowedSecretsResult = Result<(), ProxyCanisterError>;
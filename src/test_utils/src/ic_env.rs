@@ -2,7 +2,7 @@ use std::{
     collections::HashMap,
     path::PathBuf,
     sync::{Mutex, MutexGuard},
-    time::Duration,
+    time::{Duration, UNIX_EPOCH},
 };
 
 use candid::{decode_one, encode_args, utils::ArgumentEncoder, CandidType, Deserialize, Principal};
@@ -91,6 +91,27 @@ impl TestEnv {
             .unwrap();
     }
 
+    pub fn get_canister_time_ns(&self) -> u64 {
+        self.pic
+            .get_time()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos() as u64
+    }
+
+    pub fn upgrade_canister(&self, canister_id: &Principal) {
+        let data = self.canisters.get(canister_id).unwrap();
+
+        self.pic
+            .upgrade_canister(
+                *canister_id,
+                data.wasm_module.clone(),
+                data.args.clone(),
+                data.controller,
+            )
+            .unwrap();
+    }
+
     /// Produce and advance by some blocks to fire eventual timers.
     ///
     /// See https://forum.dfinity.org/t/pocketic-multi-subnet-canister-testing/24901/4.
@@ -1,5 +1,8 @@
 use candid::{decode_one, encode_one, Principal};
-use http_over_ws::HttpOverWsMessage;
+use http_over_ws::{
+    HttpOverWsEnvelope, HttpOverWsMessage, HttpOverWsProtocolVersion, ProxyClientSetup,
+    HTTP_OVER_WS_PROTOCOL_V1,
+};
 
 use crate::ic_env::TestEnv;
 
@@ -18,6 +21,7 @@ pub struct ProxyClient<'a> {
     gateway_principal: Principal,
     outgoing_messages_sequence_num: u64,
     polling_nonce: u64,
+    protocol_version: HttpOverWsProtocolVersion,
 }
 
 impl<'a> ProxyClient<'a> {
@@ -29,9 +33,14 @@ impl<'a> ProxyClient<'a> {
             gateway_principal: generate_random_principal(),
             outgoing_messages_sequence_num: 0,
             polling_nonce: 0,
+            protocol_version: HTTP_OVER_WS_PROTOCOL_V1,
         }
     }
 
+    pub fn client_principal(&self) -> Principal {
+        self.client_key.client_principal
+    }
+
     pub fn open_ws_connection(&self) {
         let res: CanisterWsOpenResult = self.test_env.call_canister_method_with_panic(
             self.canister_id,
@@ -52,6 +61,31 @@ impl<'a> ProxyClient<'a> {
         self.send_http_over_ws_message(HttpOverWsMessage::SetupProxyClient);
     }
 
+    /// Sets up the proxy negotiating the protocol version, with the setup message
+    /// in the format of the newest supported version.
+    /// Returns the message with which the canister answered the setup.
+    pub fn setup_versioned_proxy(&mut self, setup: ProxyClientSetup) -> HttpOverWsMessage {
+        self.open_ws_connection();
+
+        self.protocol_version = setup
+            .supported_protocol_versions
+            .iter()
+            .max()
+            .copied()
+            .unwrap_or(HTTP_OVER_WS_PROTOCOL_V1);
+        self.send_http_over_ws_message(HttpOverWsMessage::SetupVersionedProxyClient(setup));
+
+        let mut messages = self.get_http_over_ws_messages();
+        assert_eq!(messages.len(), 1);
+        let reply = messages.remove(0);
+
+        if let HttpOverWsMessage::ProtocolVersionNegotiated(version) = reply {
+            self.protocol_version = version;
+        }
+
+        reply
+    }
+
     pub fn send_ws_message(&mut self, message: Vec<u8>) {
         self.outgoing_messages_sequence_num += 1;
 
@@ -71,8 +105,19 @@ impl<'a> ProxyClient<'a> {
         assert!(res.is_ok());
     }
 
+    /// Sends the message in the format of the protocol version negotiated with the canister.
     pub fn send_http_over_ws_message(&mut self, message: HttpOverWsMessage) {
-        self.send_ws_message(encode_one(message).unwrap());
+        let bytes = if self.protocol_version == HTTP_OVER_WS_PROTOCOL_V1 {
+            encode_one(message).unwrap()
+        } else {
+            encode_one(HttpOverWsEnvelope {
+                version: self.protocol_version,
+                message,
+            })
+            .unwrap()
+        };
+
+        self.send_ws_message(bytes);
     }
 
     pub fn get_ws_messages(&mut self) -> Vec<WebsocketMessage> {
@@ -102,10 +147,24 @@ impl<'a> ProxyClient<'a> {
         }
     }
 
+    /// Returns the messages received from the canister,
+    /// checking that they are in the format of the protocol version negotiated with it.
     pub fn get_http_over_ws_messages(&mut self) -> Vec<HttpOverWsMessage> {
+        let protocol_version = self.protocol_version;
+
         self.get_ws_messages()
             .iter()
-            .filter_map(|msg| (!msg.is_service_message).then(|| decode_one(&msg.content).unwrap()))
+            .filter_map(|msg| {
+                (!msg.is_service_message).then(|| {
+                    if protocol_version == HTTP_OVER_WS_PROTOCOL_V1 {
+                        decode_one(&msg.content).unwrap()
+                    } else {
+                        let envelope: HttpOverWsEnvelope = decode_one(&msg.content).unwrap();
+                        assert_eq!(envelope.version, protocol_version);
+                        envelope.message
+                    }
+                })
+            })
             .collect()
     }
 